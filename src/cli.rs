@@ -1,4 +1,135 @@
-use clap::{Args, Parser, Subcommand};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use kube::Client;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    secret_manager::SecretManagerRef, secret_ref::SecretKeyRef, zone_accounts::ZoneAccounts, Result,
+};
+
+/// Domain of the CNAME targets of Cloudflare Tunnels
+pub const DEFAULT_TUNNEL_CNAME_DOMAIN: &str = "cfargotunnel.com";
+
+pub fn tunnel_cname(tunnel_id: &str, domain: &str) -> String {
+    format!("{tunnel_id}.{domain}")
+}
+
+/// Exit code of arguments that conflict with each other
+pub const EXIT_CONFLICTING_ARGS: u8 = 64;
+/// Exit code of invalid Cloudflare credentials
+pub const EXIT_INVALID_CREDENTIALS: u8 = 65;
+/// Exit code of an invalid IngressClass or controller name
+pub const EXIT_INVALID_INGRESS_CLASS: u8 = 66;
+/// Exit code of invalid namespace names
+pub const EXIT_INVALID_NAMESPACE: u8 = 67;
+/// Exit code of an invalid hostname template or domain
+pub const EXIT_INVALID_HOSTNAME: u8 = 68;
+/// Exit code of a CA bundle without any readable certificate
+pub const EXIT_INVALID_CA_BUNDLE: u8 = 69;
+/// Exit code of an empty tunnel name prefix
+pub const EXIT_INVALID_TUNNEL_PREFIX: u8 = 70;
+
+/// Arguments rejected before any controller starts, with a hint on how to fix them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidArgs {
+    pub exit_code: u8,
+    pub message: String,
+    pub remediation: String,
+}
+
+impl InvalidArgs {
+    fn new(exit_code: u8, message: impl Into<String>, remediation: impl Into<String>) -> Self {
+        Self {
+            exit_code,
+            message: message.into(),
+            remediation: remediation.into(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {}\n  hint: {}", self.message, self.remediation)
+    }
+}
+
+impl std::error::Error for InvalidArgs {}
+
+/// Whether the name is a valid RFC 1123 label, as namespace names are
+fn is_dns_label(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// Whether the name is a valid RFC 1123 subdomain, such as a DNS domain
+fn is_dns_subdomain(name: &str) -> bool {
+    name.len() <= 253 && name.split('.').all(is_dns_label)
+}
+
+/// Shard of a key among `count` shards, stable across processes and releases
+fn shard_of(key: &str, count: u32) -> u32 {
+    let digest = Sha256::digest(key.as_bytes());
+    let hash = u64::from_be_bytes(digest[..8].try_into().unwrap());
+    (hash % u64::from(count)) as u32
+}
+
+/// TTL of the DNS records, 1 for automatic or the 60 to 86400 seconds Cloudflare accepts
+fn parse_dns_record_ttl(value: &str) -> std::result::Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(x) if x == 1 || (60..=86400).contains(&x) => Ok(x),
+        Ok(_) => Err("must be 1 for automatic or between 60 and 86400".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive_f64(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(x) if x.is_finite() && x > 0.0 => Ok(x),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_http_url(value: &str) -> std::result::Result<url::Url, String> {
+    let url = url::Url::parse(value).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("must be an http or https URL".to_string());
+    }
+    Ok(url)
+}
+
+/// Base URL of the Cloudflare API, with a trailing slash so that the endpoint paths are
+/// appended to it
+fn parse_api_url(value: &str) -> std::result::Result<url::Url, String> {
+    let mut url = parse_http_url(value)?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+fn parse_zone_accounts_file(value: &str) -> std::result::Result<ZoneAccounts, String> {
+    ZoneAccounts::from_file(Path::new(value))
+}
+
+/// What to do with tunnels matching a managed prefix that no CloudflaredTunnel refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OrphanTunnelPolicy {
+    Delete,
+    Ignore,
+    /// Only record metrics and Events
+    Report,
+}
 
 #[derive(Parser, Debug, Clone)]
 pub struct Cli {
@@ -12,6 +143,24 @@ pub enum Commands {
     CreateYaml,
     #[command()]
     Run(ControllerArgs),
+    #[command(about = "Delete all managed tunnels and CloudflaredTunnels before uninstalling")]
+    Uninstall(UninstallArgs),
+    #[command(
+        about = "Print the cloudflared config.yml generated for a CloudflaredTunnel manifest"
+    )]
+    RenderConfig(RenderConfigArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RenderConfigArgs {
+    #[arg(long)]
+    file: PathBuf,
+}
+
+impl RenderConfigArgs {
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
 }
 
 #[derive(Debug, Clone, Args)]
@@ -21,19 +170,160 @@ pub struct ControllerArgs {
     #[arg(
         long,
         env,
+        value_delimiter = ',',
         default_value = "chalharu.top/cloudflared-ingress-controller"
     )]
-    ingress_controller: String,
-    #[arg(long, env)]
-    cloudflare_token: String,
-    #[arg(long, env)]
-    cloudflare_account_id: String,
+    ingress_controller: Vec<String>,
+    #[arg(
+        long,
+        env,
+        required_unless_present_any = ["cloudflare_token_secret_ref", "cloudflare_token_secret_manager"]
+    )]
+    cloudflare_token: Option<String>,
+    #[arg(long, env, conflicts_with = "cloudflare_token")]
+    cloudflare_token_secret_ref: Option<SecretKeyRef>,
+    #[arg(
+        long,
+        env,
+        conflicts_with_all = ["cloudflare_token", "cloudflare_token_secret_ref"]
+    )]
+    cloudflare_token_secret_manager: Option<SecretManagerRef>,
+    #[arg(
+        long,
+        env,
+        required_unless_present = "cloudflare_account_id_secret_ref"
+    )]
+    cloudflare_account_id: Option<String>,
+    #[arg(long, env, conflicts_with = "cloudflare_account_id")]
+    cloudflare_account_id_secret_ref: Option<SecretKeyRef>,
+    #[arg(
+        long = "zone-accounts-file",
+        env = "ZONE_ACCOUNTS_FILE",
+        value_parser = parse_zone_accounts_file
+    )]
+    zone_accounts: Option<ZoneAccounts>,
     #[arg(long, env, default_value = "k8s-ingress-")]
     cloudflare_tunnel_prefix: String,
     #[arg(long, env, default_value = "cloudflared")]
     cloudflare_tunnel_namespace: String,
     #[arg(long, env, default_value = "1")]
     deployment_replicas: usize,
+    #[arg(long, env, default_value = "60")]
+    connection_status_interval_secs: u64,
+    #[arg(long, env, value_delimiter = ',')]
+    allowed_image_registries: Vec<String>,
+    #[arg(long, env, default_value = "300")]
+    tunnel_ready_timeout_secs: u64,
+    #[arg(long, env)]
+    admin_token: Option<String>,
+    #[arg(long, env, default_value = "10000")]
+    slow_reconcile_threshold_ms: u64,
+    #[arg(long, env)]
+    default_hostname_template: Option<String>,
+    #[arg(long, env, default_value = "600")]
+    progress_deadline_seconds: i32,
+    #[arg(long, env, value_parser = clap::value_parser!(u64).range(1..))]
+    max_rules_per_tunnel: Option<u64>,
+    #[arg(long, env, default_value = "8", value_parser = clap::value_parser!(u64).range(1..))]
+    dns_list_concurrency: u64,
+    #[arg(long, env, default_value = DEFAULT_TUNNEL_CNAME_DOMAIN)]
+    tunnel_cname_domain: String,
+    #[arg(long, env, value_delimiter = ',')]
+    watch_namespaces: Vec<String>,
+    #[arg(long, env, value_delimiter = ',')]
+    deny_namespaces: Vec<String>,
+    #[arg(long, env, value_delimiter = ',')]
+    zone_allowlist: Vec<String>,
+    #[arg(long, env, value_delimiter = ',')]
+    zone_denylist: Vec<String>,
+    #[arg(long, env, default_value = "3600")]
+    token_verify_interval_secs: u64,
+    #[arg(long, env, default_value = "14")]
+    token_expiry_warning_days: u64,
+    #[arg(long, env, value_enum, default_value_t = OrphanTunnelPolicy::Delete)]
+    orphan_tunnel_policy: OrphanTunnelPolicy,
+    #[arg(long, env, default_value = "300")]
+    orphan_tunnel_grace_period_secs: u64,
+    #[arg(long, env)]
+    tunnel_delete_cascade: bool,
+    #[arg(long, env)]
+    controller_id: Option<String>,
+    #[arg(long, env, default_value = "0")]
+    shard_index: u32,
+    #[arg(long, env, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    shard_count: u32,
+    #[arg(long, env, default_value = "1", value_parser = parse_dns_record_ttl)]
+    dns_record_ttl: u32,
+    #[arg(long, env)]
+    dns_record_comment: Option<String>,
+    #[arg(long, env, value_delimiter = ',')]
+    dns_record_tags: Vec<String>,
+    #[arg(long, env)]
+    dns_record_marker: Option<String>,
+    #[arg(long, env)]
+    txt_owner_id: Option<String>,
+    #[arg(long, env, default_value = "_cloudflared-ingress.")]
+    txt_prefix: String,
+    #[arg(long, env, value_parser = parse_api_url)]
+    cloudflare_api_url: Option<url::Url>,
+    #[arg(long, env, value_parser = parse_http_url)]
+    cloudflare_proxy: Option<url::Url>,
+    #[arg(long, env)]
+    cloudflare_ca_file: Option<PathBuf>,
+    #[arg(long, env, default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    cloudflare_api_timeout_secs: u64,
+    #[arg(long, env, default_value = "3")]
+    cloudflare_api_max_retries: u32,
+    #[arg(long, env, default_value = "500")]
+    cloudflare_api_retry_base_delay_ms: u64,
+    #[arg(long, env, default_value = "10000")]
+    cloudflare_api_retry_max_delay_ms: u64,
+    #[arg(long, env, default_value = "30000")]
+    cloudflare_api_rate_limit_delay_ms: u64,
+    #[arg(long, env, default_value = "4", value_parser = parse_positive_f64)]
+    cloudflare_api_requests_per_second: f64,
+    #[arg(long, env, default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+    cloudflare_api_burst: u32,
+    #[arg(long, env, default_value = "100", value_parser = clap::value_parser!(u32).range(5..=1000))]
+    cloudflare_api_per_page: u32,
+    #[arg(long, env, default_value = "300")]
+    dns_cache_ttl_secs: u64,
+    #[arg(long, env, default_value = "200", value_parser = clap::value_parser!(u32).range(1..=3500))]
+    dns_batch_size: u32,
+    #[arg(long, env)]
+    create_ingress_class: Option<String>,
+    #[arg(long, env, requires = "create_ingress_class")]
+    default_ingress_class: bool,
+    #[arg(long, env, requires = "webhook_tls_key_file")]
+    webhook_tls_cert_file: Option<PathBuf>,
+    #[arg(long, env, requires = "webhook_tls_cert_file")]
+    webhook_tls_key_file: Option<PathBuf>,
+    #[arg(long, env, default_value = "8443")]
+    webhook_port: u16,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct UninstallArgs {
+    #[command(flatten)]
+    controller_args: ControllerArgs,
+    #[arg(long, env)]
+    controller_deployment: Option<String>,
+    #[arg(long, env, default_value = "600")]
+    uninstall_timeout_secs: u64,
+}
+
+impl UninstallArgs {
+    pub fn controller_args(&self) -> &ControllerArgs {
+        &self.controller_args
+    }
+
+    pub fn controller_deployment(&self) -> Option<&str> {
+        self.controller_deployment.as_deref()
+    }
+
+    pub fn uninstall_timeout(&self) -> Duration {
+        Duration::from_secs(self.uninstall_timeout_secs)
+    }
 }
 
 impl ControllerArgs {
@@ -41,16 +331,59 @@ impl ControllerArgs {
         self.ingress_class.as_ref()
     }
 
-    pub fn ingress_controller(&self) -> &str {
+    /// `spec.controller` of the created IngressClass, the first of `--ingress-controller`
+    pub fn ingress_controller(&self) -> Option<&str> {
+        self.ingress_controller.first().map(String::as_str)
+    }
+
+    /// `spec.controller` values of the IngressClasses served by the controller
+    pub fn ingress_controllers(&self) -> &[String] {
         &self.ingress_controller
     }
 
     pub fn cloudflare_token(&self) -> &str {
-        &self.cloudflare_token
+        self.cloudflare_token.as_deref().unwrap_or_default()
     }
 
     pub fn cloudflare_account_id(&self) -> &str {
-        &self.cloudflare_account_id
+        self.cloudflare_account_id.as_deref().unwrap_or_default()
+    }
+
+    /// Secret holding the Cloudflare token, watched for its rotation
+    pub fn cloudflare_token_secret_ref(&self) -> Option<&SecretKeyRef> {
+        self.cloudflare_token_secret_ref.as_ref()
+    }
+
+    /// Secret manager secret holding the Cloudflare token, re-read to follow its rotation
+    pub fn cloudflare_token_secret_manager(&self) -> Option<&SecretManagerRef> {
+        self.cloudflare_token_secret_manager.as_ref()
+    }
+
+    /// Replace the Secret and secret manager references of the Cloudflare credentials with
+    /// their values. The token Secret and secret manager references are kept to follow the
+    /// rotation of the token
+    pub async fn resolve_secret_refs(mut self) -> Result<Self> {
+        if let Some(ref secret_manager_ref) = self.cloudflare_token_secret_manager {
+            self.cloudflare_token = Some(secret_manager_ref.read().await?);
+        }
+        if self.cloudflare_token_secret_ref.is_none()
+            && self.cloudflare_account_id_secret_ref.is_none()
+        {
+            return Ok(self);
+        }
+        let client = Client::try_default().await?;
+        if let Some(ref secret_ref) = self.cloudflare_token_secret_ref {
+            self.cloudflare_token = Some(secret_ref.read(&client).await?);
+        }
+        if let Some(secret_ref) = self.cloudflare_account_id_secret_ref.take() {
+            self.cloudflare_account_id = Some(secret_ref.read(&client).await?);
+        }
+        Ok(self)
+    }
+
+    /// Zones of other Cloudflare accounts, read from `--zone-accounts-file`
+    pub fn zone_accounts(&self) -> Option<&ZoneAccounts> {
+        self.zone_accounts.as_ref()
     }
 
     pub fn cloudflare_tunnel_prefix(&self) -> &str {
@@ -64,6 +397,414 @@ impl ControllerArgs {
     pub fn deployment_replicas(&self) -> usize {
         self.deployment_replicas
     }
+
+    pub fn connection_status_interval(&self) -> Duration {
+        Duration::from_secs(self.connection_status_interval_secs)
+    }
+
+    pub fn allowed_image_registries(&self) -> &[String] {
+        &self.allowed_image_registries
+    }
+
+    pub fn tunnel_ready_timeout(&self) -> Duration {
+        Duration::from_secs(self.tunnel_ready_timeout_secs)
+    }
+
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token.as_deref()
+    }
+
+    pub fn slow_reconcile_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_reconcile_threshold_ms)
+    }
+
+    pub fn default_hostname_template(&self) -> Option<&str> {
+        self.default_hostname_template.as_deref()
+    }
+
+    pub fn progress_deadline_seconds(&self) -> i32 {
+        self.progress_deadline_seconds
+    }
+
+    pub fn max_rules_per_tunnel(&self) -> Option<usize> {
+        self.max_rules_per_tunnel.map(|x| x as usize)
+    }
+
+    pub fn dns_list_concurrency(&self) -> usize {
+        self.dns_list_concurrency as usize
+    }
+
+    pub fn tunnel_cname_domain(&self) -> &str {
+        &self.tunnel_cname_domain
+    }
+
+    pub fn watch_namespaces(&self) -> &[String] {
+        &self.watch_namespaces
+    }
+
+    pub fn deny_namespaces(&self) -> &[String] {
+        &self.deny_namespaces
+    }
+
+    /// Whether DNS records may be managed in the zone, which must be in `--zone-allowlist` when
+    /// set and must not be in `--zone-denylist`
+    pub fn is_zone_allowed(&self, zone_name: &str) -> bool {
+        let listed = |zones: &[String]| zones.iter().any(|z| z.eq_ignore_ascii_case(zone_name));
+        (self.zone_allowlist.is_empty() || listed(&self.zone_allowlist))
+            && !listed(&self.zone_denylist)
+    }
+
+    /// Whether only the zones of `--zone-allowlist` are allowed
+    pub fn has_zone_allowlist(&self) -> bool {
+        !self.zone_allowlist.is_empty()
+    }
+
+    pub fn token_verify_interval(&self) -> Duration {
+        Duration::from_secs(self.token_verify_interval_secs)
+    }
+
+    /// How long before the expiry of the Cloudflare token to start warning
+    pub fn token_expiry_warning(&self) -> Duration {
+        Duration::from_secs(self.token_expiry_warning_days * 24 * 60 * 60)
+    }
+
+    pub fn orphan_tunnel_policy(&self) -> OrphanTunnelPolicy {
+        self.orphan_tunnel_policy
+    }
+
+    /// How long a tunnel has to stay orphaned, from the reconcile that first found it, before
+    /// it is deleted
+    pub fn orphan_tunnel_grace_period(&self) -> Duration {
+        Duration::from_secs(self.orphan_tunnel_grace_period_secs)
+    }
+
+    /// Whether the tunnels are deleted with cascade, also when connections remain
+    pub fn tunnel_delete_cascade(&self) -> bool {
+        self.tunnel_delete_cascade
+    }
+
+    /// Identity recorded in the metadata of the created tunnels, the namespace of the controller
+    /// and the UID of the `kube-system` namespace unless set
+    pub fn controller_id(&self) -> Option<&str> {
+        self.controller_id.as_deref()
+    }
+
+    /// Whether the object of the key, `{namespace}/{name}` or the name of a cluster scoped one,
+    /// is reconciled by this instance among `--shard-count` instances
+    pub fn is_own_shard(&self, key: &str) -> bool {
+        shard_of(key, self.shard_count) == self.shard_index
+    }
+
+    /// Whether this instance does the work not tied to an object, such as deleting the orphaned
+    /// tunnels
+    pub fn is_first_shard(&self) -> bool {
+        self.shard_index == 0
+    }
+
+    /// TTL of the CNAME records in seconds, 1 for automatic
+    pub fn dns_record_ttl(&self) -> u32 {
+        self.dns_record_ttl
+    }
+
+    /// Comment template of the CNAME records, `{namespace}` and `{name}` are those of the
+    /// CloudflaredTunnel
+    pub fn dns_record_comment(&self) -> Option<&str> {
+        self.dns_record_comment.as_deref()
+    }
+
+    /// Tag templates of the CNAME records in the `name:value` form
+    pub fn dns_record_tags(&self) -> &[String] {
+        &self.dns_record_tags
+    }
+
+    /// Marker added to the comment of the CNAME records, only the records carrying it are
+    /// deleted when set
+    pub fn dns_record_marker(&self) -> Option<&str> {
+        self.dns_record_marker.as_deref()
+    }
+
+    /// Owner id of the TXT registry records, the registry is disabled unless set
+    pub fn txt_owner_id(&self) -> Option<&str> {
+        self.txt_owner_id.as_deref()
+    }
+
+    /// Prefix of the names of the TXT registry records
+    pub fn txt_prefix(&self) -> &str {
+        &self.txt_prefix
+    }
+
+    /// Retries of a Cloudflare API request failed by a rate limit, server or network error
+    pub fn cloudflare_api_max_retries(&self) -> u32 {
+        self.cloudflare_api_max_retries
+    }
+
+    /// Delay of the first retry of a Cloudflare API request, doubled on each retry
+    pub fn cloudflare_api_retry_base_delay(&self) -> Duration {
+        Duration::from_millis(self.cloudflare_api_retry_base_delay_ms)
+    }
+
+    /// Endpoint replacing `https://api.cloudflare.com/client/v4/`, e.g. for the China network,
+    /// an API gateway or a mock server
+    pub fn cloudflare_api_url(&self) -> Option<&url::Url> {
+        self.cloudflare_api_url.as_ref()
+    }
+
+    /// Export `--cloudflare-proxy` as `HTTPS_PROXY` and `--cloudflare-ca-file` as
+    /// `SSL_CERT_FILE`, which the HTTP clients read as cloudflare-rs does not take them.
+    /// `NO_PROXY` still applies. Call it before the tokio runtime starts any thread, as
+    /// setting an environment variable races with the threads reading one
+    pub fn export_http_client_env(&self) {
+        if let Some(ref proxy) = self.cloudflare_proxy {
+            std::env::set_var("HTTPS_PROXY", proxy.as_str());
+        }
+        // OSの証明書ストアの代わりに読まれ、組み込みのroot証明書は引き続き信頼される
+        if let Some(ref ca_file) = self.cloudflare_ca_file {
+            std::env::set_var("SSL_CERT_FILE", ca_file);
+        }
+    }
+
+    /// Timeout of a Cloudflare API request attempt, covering the connection and the response
+    pub fn cloudflare_api_timeout(&self) -> Duration {
+        Duration::from_secs(self.cloudflare_api_timeout_secs)
+    }
+
+    pub fn cloudflare_api_retry_max_delay(&self) -> Duration {
+        Duration::from_millis(self.cloudflare_api_retry_max_delay_ms)
+    }
+
+    /// Least delay of the retry of a Cloudflare API request failed by a rate limit
+    pub fn cloudflare_api_rate_limit_delay(&self) -> Duration {
+        Duration::from_millis(self.cloudflare_api_rate_limit_delay_ms)
+    }
+
+    /// Sustained rate of the Cloudflare API requests, 4 keeps within the limit of 1200
+    /// requests per 5 minutes
+    pub fn cloudflare_api_requests_per_second(&self) -> f64 {
+        self.cloudflare_api_requests_per_second
+    }
+
+    /// Requests sent at once before `--cloudflare-api-requests-per-second` applies
+    pub fn cloudflare_api_burst(&self) -> u32 {
+        self.cloudflare_api_burst
+    }
+
+    /// Items per page when listing tunnels, zones and DNS records, zones are listed at most
+    /// 50 at a time
+    pub fn cloudflare_api_per_page(&self) -> u32 {
+        self.cloudflare_api_per_page
+    }
+
+    /// How long the listed DNS records of a zone are reused, changes made outside of the
+    /// controller are seen after that. `0` lists them on every reconcile
+    pub fn dns_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.dns_cache_ttl_secs)
+    }
+
+    /// DNS record changes of a zone sent per batch request, 200 is the limit of the free plan
+    pub fn dns_batch_size(&self) -> usize {
+        self.dns_batch_size as usize
+    }
+
+    /// IngressClass created and kept pointing at `--ingress-controller`
+    pub fn create_ingress_class(&self) -> Option<&str> {
+        self.create_ingress_class.as_deref()
+    }
+
+    /// Whether the created IngressClass is marked as the default one
+    pub fn default_ingress_class(&self) -> bool {
+        self.default_ingress_class
+    }
+
+    /// Certificate and private key of the admission webhook server, which is disabled
+    /// unless both are given
+    pub fn webhook_tls_files(&self) -> Option<(&Path, &Path)> {
+        self.webhook_tls_cert_file
+            .as_deref()
+            .zip(self.webhook_tls_key_file.as_deref())
+    }
+
+    pub fn webhook_port(&self) -> u16 {
+        self.webhook_port
+    }
+
+    /// Cross-check the arguments that clap cannot validate on its own
+    pub fn validate(&self) -> std::result::Result<(), InvalidArgs> {
+        if let Some(token) = self.cloudflare_token.as_deref() {
+            if token.trim().is_empty() {
+                return Err(InvalidArgs::new(
+                    EXIT_INVALID_CREDENTIALS,
+                    "--cloudflare-token is empty",
+                    "set CLOUDFLARE_TOKEN to an API token, or use --cloudflare-token-secret-ref",
+                ));
+            }
+        }
+        if let Some(account_id) = self.cloudflare_account_id.as_deref() {
+            if account_id.len() != 32 || !account_id.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(InvalidArgs::new(
+                    EXIT_INVALID_CREDENTIALS,
+                    format!(
+                        "--cloudflare-account-id {account_id:?} is not a Cloudflare account id"
+                    ),
+                    "use the 32 hexadecimal digit id shown in the URL of the Cloudflare dashboard",
+                ));
+            }
+        }
+        if self
+            .ingress_class
+            .as_deref()
+            .is_some_and(|c| !is_dns_subdomain(c))
+        {
+            return Err(InvalidArgs::new(
+                EXIT_INVALID_INGRESS_CLASS,
+                format!(
+                    "--ingress-class {:?} is not a valid IngressClass name",
+                    self.ingress_class.as_deref().unwrap_or_default()
+                ),
+                "use the metadata.name of the IngressClass, e.g. `cloudflared`",
+            ));
+        }
+        if let Some(class) = self.create_ingress_class.as_deref() {
+            if !is_dns_subdomain(class) {
+                return Err(InvalidArgs::new(
+                    EXIT_INVALID_INGRESS_CLASS,
+                    format!("--create-ingress-class {class:?} is not a valid IngressClass name"),
+                    "use a lower case name, e.g. `cloudflared`",
+                ));
+            }
+            if self.ingress_class.as_deref().is_some_and(|c| c != class) {
+                return Err(InvalidArgs::new(
+                    EXIT_CONFLICTING_ARGS,
+                    format!(
+                        "--create-ingress-class {class:?} is not served by --ingress-class {:?}",
+                        self.ingress_class.as_deref().unwrap_or_default()
+                    ),
+                    "give both the same name, or leave --ingress-class unset",
+                ));
+            }
+        }
+        if self.ingress_controller.is_empty() {
+            return Err(InvalidArgs::new(
+                EXIT_INVALID_INGRESS_CLASS,
+                "--ingress-controller is empty",
+                "use the spec.controller of the IngressClass, e.g. `chalharu.top/cloudflared-ingress-controller`",
+            ));
+        }
+        // IngressClassのspec.controllerはdomain付きのpathでなければならない
+        if let Some(controller) = self.ingress_controller.iter().find(|c| {
+            let domain = c.split_once('/').map(|(d, _)| d);
+            !domain.is_some_and(|d| d.contains('.') && is_dns_subdomain(d))
+        }) {
+            return Err(InvalidArgs::new(
+                EXIT_INVALID_INGRESS_CLASS,
+                format!("--ingress-controller {controller:?} is not a domain-prefixed path"),
+                "use the spec.controller of the IngressClass, e.g. `chalharu.top/cloudflared-ingress-controller`",
+            ));
+        }
+        // 空のprefixはaccountの全てのtunnelに一致し、全てが管理対象かつorphanの候補となる
+        if self.cloudflare_tunnel_prefix.is_empty() {
+            return Err(InvalidArgs::new(
+                EXIT_INVALID_TUNNEL_PREFIX,
+                "--cloudflare-tunnel-prefix is empty",
+                "use a prefix no other tunnel of the account starts with, e.g. `k8s-ingress-`",
+            ));
+        }
+        for (arg, namespaces) in [
+            (
+                "--cloudflare-tunnel-namespace",
+                std::slice::from_ref(&self.cloudflare_tunnel_namespace),
+            ),
+            ("--watch-namespaces", self.watch_namespaces.as_slice()),
+            ("--deny-namespaces", self.deny_namespaces.as_slice()),
+        ] {
+            if let Some(ns) = namespaces.iter().find(|ns| !is_dns_label(ns)) {
+                return Err(InvalidArgs::new(
+                    EXIT_INVALID_NAMESPACE,
+                    format!("{arg} contains {ns:?}, which is not a valid namespace name"),
+                    "namespace names consist of lower case alphanumeric characters and `-`",
+                ));
+            }
+        }
+        if let Some(ns) = self
+            .watch_namespaces
+            .iter()
+            .find(|ns| self.deny_namespaces.contains(ns))
+        {
+            return Err(InvalidArgs::new(
+                EXIT_CONFLICTING_ARGS,
+                format!("namespace {ns:?} is both watched and denied"),
+                "remove it from either --watch-namespaces or --deny-namespaces",
+            ));
+        }
+        if self.shard_index >= self.shard_count {
+            return Err(InvalidArgs::new(
+                EXIT_CONFLICTING_ARGS,
+                format!(
+                    "--shard-index {} is out of --shard-count {}",
+                    self.shard_index, self.shard_count
+                ),
+                "number the instances from 0 to --shard-count minus 1",
+            ));
+        }
+        if let Some(zone) = self.zone_allowlist.iter().find(|zone| {
+            self.zone_denylist
+                .iter()
+                .any(|z| z.eq_ignore_ascii_case(zone))
+        }) {
+            return Err(InvalidArgs::new(
+                EXIT_CONFLICTING_ARGS,
+                format!("zone {zone:?} is both allowed and denied"),
+                "remove it from either --zone-allowlist or --zone-denylist",
+            ));
+        }
+        if let Some(template) = self.default_hostname_template.as_deref() {
+            if !template.contains("{name}") {
+                return Err(InvalidArgs::new(
+                    EXIT_INVALID_HOSTNAME,
+                    format!("--default-hostname-template {template:?} does not contain {{name}}"),
+                    "include {name} so that each Ingress gets a hostname of its own, e.g. `{name}.{namespace}.apps.example.com`",
+                ));
+            }
+        }
+        if !is_dns_subdomain(&self.tunnel_cname_domain) {
+            return Err(InvalidArgs::new(
+                EXIT_INVALID_HOSTNAME,
+                format!(
+                    "--tunnel-cname-domain {:?} is not a valid domain",
+                    self.tunnel_cname_domain
+                ),
+                format!("leave it unset to use {DEFAULT_TUNNEL_CNAME_DOMAIN}"),
+            ));
+        }
+        if let Some(ref ca_file) = self.cloudflare_ca_file {
+            let certs = std::fs::File::open(ca_file).and_then(|f| {
+                rustls_pemfile::certs(&mut std::io::BufReader::new(f))
+                    .collect::<std::io::Result<Vec<_>>>()
+            });
+            match certs {
+                Ok(certs) if !certs.is_empty() => {}
+                Ok(_) => {
+                    return Err(InvalidArgs::new(
+                        EXIT_INVALID_CA_BUNDLE,
+                        format!("--cloudflare-ca-file {ca_file:?} holds no certificate"),
+                        "give a file of PEM encoded CA certificates",
+                    ));
+                }
+                Err(e) => {
+                    return Err(InvalidArgs::new(
+                        EXIT_INVALID_CA_BUNDLE,
+                        format!("--cloudflare-ca-file {ca_file:?} cannot be read: {e}"),
+                        "mount the CA bundle, e.g. from a Secret or ConfigMap, at that path",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// CNAME target of the tunnel, `{tunnel_id}.cfargotunnel.com` by default
+    pub fn tunnel_cname(&self, tunnel_id: &str) -> String {
+        tunnel_cname(tunnel_id, &self.tunnel_cname_domain)
+    }
 }
 
 impl Cli {
@@ -71,3 +812,299 @@ impl Cli {
         &self.commands
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ACCOUNT_ID: &str = "a0000000000000000000000000000001";
+
+    fn validate(args: &[&str]) -> std::result::Result<(), InvalidArgs> {
+        let cli = Cli::try_parse_from(
+            [
+                "cloudflared-ingress-rs",
+                "run",
+                "--cloudflare-token",
+                "token",
+            ]
+            .iter()
+            .chain(args),
+        )
+        .unwrap();
+        let Commands::Run(args) = cli.commands() else {
+            unreachable!();
+        };
+        args.validate()
+    }
+
+    fn exit_code(args: &[&str]) -> u8 {
+        validate(args).unwrap_err().exit_code
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        validate(&["--cloudflare-account-id", ACCOUNT_ID]).unwrap();
+    }
+
+    #[test]
+    fn credentials_missing_is_rejected_by_clap() {
+        let error = Cli::try_parse_from(["cloudflared-ingress-rs", "run"]).unwrap_err();
+        assert_eq!(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            error.kind()
+        );
+    }
+
+    #[test]
+    fn dns_record_ttl_range() {
+        let parse = |ttl: &str| {
+            Cli::try_parse_from([
+                "cloudflared-ingress-rs",
+                "run",
+                "--cloudflare-token",
+                "token",
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--dns-record-ttl",
+                ttl,
+            ])
+        };
+        assert!(parse("1").is_ok());
+        assert!(parse("60").is_ok());
+        assert!(parse("30").is_err());
+        assert!(parse("86401").is_err());
+    }
+
+    #[test]
+    fn cloudflare_api_url_ends_with_slash() {
+        let parse = |url: &str| {
+            Cli::try_parse_from([
+                "cloudflared-ingress-rs",
+                "run",
+                "--cloudflare-token",
+                "token",
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--cloudflare-api-url",
+                url,
+            ])
+        };
+        let cli = parse("https://api.example.com/client/v4").unwrap();
+        let Commands::Run(args) = cli.commands() else {
+            unreachable!();
+        };
+        assert_eq!(
+            "https://api.example.com/client/v4/",
+            args.cloudflare_api_url().unwrap().as_str()
+        );
+        assert_eq!(
+            clap::error::ErrorKind::ValueValidation,
+            parse("ftp://api.example.com/").unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn missing_ca_file() {
+        assert_eq!(
+            EXIT_INVALID_CA_BUNDLE,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--cloudflare-ca-file",
+                "/nonexistent/ca.crt"
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_account_id() {
+        assert_eq!(
+            EXIT_INVALID_CREDENTIALS,
+            exit_code(&["--cloudflare-account-id", "my-account"])
+        );
+    }
+
+    #[test]
+    fn empty_tunnel_prefix() {
+        assert_eq!(
+            EXIT_INVALID_TUNNEL_PREFIX,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--cloudflare-tunnel-prefix",
+                ""
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_ingress_controller() {
+        assert_eq!(
+            EXIT_INVALID_INGRESS_CLASS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--ingress-controller",
+                "cloudflared"
+            ])
+        );
+        assert_eq!(
+            EXIT_INVALID_INGRESS_CLASS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--ingress-class",
+                "Cloudflared"
+            ])
+        );
+    }
+
+    #[test]
+    fn multiple_ingress_controllers() {
+        let args = [
+            "--cloudflare-account-id",
+            ACCOUNT_ID,
+            "--ingress-controller",
+            "chalharu.top/cloudflared-ingress-controller,example.com/cloudflared",
+        ];
+        assert!(validate(&args).is_ok());
+        assert_eq!(
+            EXIT_INVALID_INGRESS_CLASS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--ingress-controller",
+                "chalharu.top/cloudflared-ingress-controller,cloudflared"
+            ])
+        );
+    }
+
+    #[test]
+    fn created_ingress_class_must_be_served() {
+        assert_eq!(
+            EXIT_CONFLICTING_ARGS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--ingress-class",
+                "cloudflared",
+                "--create-ingress-class",
+                "tunnel"
+            ])
+        );
+        assert!(validate(&[
+            "--cloudflare-account-id",
+            ACCOUNT_ID,
+            "--ingress-class",
+            "cloudflared",
+            "--create-ingress-class",
+            "cloudflared",
+            "--default-ingress-class"
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn namespace_both_watched_and_denied() {
+        let error = validate(&[
+            "--cloudflare-account-id",
+            ACCOUNT_ID,
+            "--watch-namespaces",
+            "default,web",
+            "--deny-namespaces",
+            "web",
+        ])
+        .unwrap_err();
+        assert_eq!(EXIT_CONFLICTING_ARGS, error.exit_code);
+        assert!(error.to_string().contains("hint: remove it"));
+    }
+
+    #[test]
+    fn shard_index_out_of_count() {
+        assert_eq!(
+            EXIT_CONFLICTING_ARGS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--shard-index",
+                "2",
+                "--shard-count",
+                "2",
+            ])
+        );
+    }
+
+    #[test]
+    fn every_key_has_one_shard() {
+        let keys = (0..100).map(|i| format!("default/tunnel-{i}"));
+        let shards = keys.map(|k| shard_of(&k, 3)).collect::<Vec<_>>();
+        assert!(shards.iter().all(|&s| s < 3));
+        // 偏りがあっても全てのshardに割り当てられる
+        assert!((0..3).all(|s| shards.contains(&s)));
+        assert_eq!(0, shard_of("default/tunnel", 1));
+    }
+
+    #[test]
+    fn zone_both_allowed_and_denied() {
+        assert_eq!(
+            EXIT_CONFLICTING_ARGS,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--zone-allowlist",
+                "example.com,example.net",
+                "--zone-denylist",
+                "Example.NET",
+            ])
+        );
+    }
+
+    #[test]
+    fn zone_allowed() {
+        let cli = Cli::try_parse_from([
+            "cloudflared-ingress-rs",
+            "run",
+            "--cloudflare-token",
+            "token",
+            "--cloudflare-account-id",
+            ACCOUNT_ID,
+            "--zone-allowlist",
+            "example.com,example.net",
+            "--zone-denylist",
+            "example.org",
+        ])
+        .unwrap();
+        let Commands::Run(args) = cli.commands() else {
+            unreachable!();
+        };
+        assert!(args.is_zone_allowed("Example.com"));
+        assert!(!args.is_zone_allowed("example.org"));
+        assert!(!args.is_zone_allowed("example.io"));
+    }
+
+    #[test]
+    fn invalid_namespace_name() {
+        assert_eq!(
+            EXIT_INVALID_NAMESPACE,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--cloudflare-tunnel-namespace",
+                "Cloudflared"
+            ])
+        );
+    }
+
+    #[test]
+    fn hostname_template_without_name() {
+        assert_eq!(
+            EXIT_INVALID_HOSTNAME,
+            exit_code(&[
+                "--cloudflare-account-id",
+                ACCOUNT_ID,
+                "--default-hostname-template",
+                "{namespace}.apps.example.com"
+            ])
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use kube::{
+    api::ListParams,
+    runtime::wait::{await_condition, Condition},
+    Api, Client, Resource as _,
+};
+
+use crate::{controllers::cloudflared::CloudflaredTunnel, Error, Result};
+
+/// Typed helpers for CloudflaredTunnel, intended for e2e tests and automation
+#[derive(Clone)]
+pub struct CloudflaredTunnelClient {
+    client: Client,
+}
+
+impl CloudflaredTunnelClient {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Wait until the CloudflaredTunnel reports the Ready condition
+    pub async fn wait_until_ready(
+        &self,
+        namespace: &str,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<CloudflaredTunnel> {
+        let api = Api::<CloudflaredTunnel>::namespaced(self.client.clone(), namespace);
+        let cfdt = tokio::time::timeout(timeout, await_condition(api, name, is_ready())).await??;
+        // 待機中に削除された場合
+        cfdt.ok_or_else(|| Error::object_not_found(CloudflaredTunnel::kind(&()), name))
+    }
+
+    /// List CloudflaredTunnels routing the given hostname
+    pub async fn list_by_hostname(&self, hostname: &str) -> Result<Vec<CloudflaredTunnel>> {
+        let api = Api::<CloudflaredTunnel>::all(self.client.clone());
+        let cfdts = api
+            .list(&ListParams::default())
+            .await?
+            .items
+            .into_iter()
+            .filter(|cfdt| {
+                // additional_hostnamesも含めて比較する
+                cfdt.spec
+                    .expanded_ingress()
+                    .iter()
+                    .any(|ingress| ingress.hostname.eq_ignore_ascii_case(hostname))
+            })
+            .collect();
+        Ok(cfdts)
+    }
+}
+
+fn is_ready() -> impl Condition<CloudflaredTunnel> {
+    |cfdt: Option<&CloudflaredTunnel>| {
+        cfdt.and_then(|cfdt| cfdt.status.as_ref())
+            .map_or(false, |status| status.is_ready())
+    }
+}
@@ -1,12 +1,29 @@
+mod access_policy;
 mod cf_api;
 mod cfd_config;
+mod credentials;
 mod customresource;
+mod dns_record;
+mod firewall_rules;
+mod image;
+mod keyed_lock;
 mod kube_api;
+mod secret;
+mod transform_rules;
+mod tunnel_owner;
+mod tunnel_route;
+mod txt_registry;
+mod uninstall;
+mod virtual_network;
+
+pub use self::uninstall::uninstall;
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
-    sync::Arc,
-    time::Duration,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use base64::Engine;
@@ -14,499 +31,3916 @@ use cloudflare::{
     endpoints::{
         cfd_tunnel::Tunnel,
         dns::{DnsContent, DnsRecord},
+        zone::Zone,
     },
     framework::{
         async_api::Client as HttpApiClient, auth::Credentials, Environment, HttpApiClientConfig,
     },
 };
 pub use customresource::{
-    CloudflaredTunnel, CloudflaredTunnelAccess, CloudflaredTunnelIngress,
-    CloudflaredTunnelOriginRequest, CloudflaredTunnelSpec,
+    CloudflaredTunnel, CloudflaredTunnelAccess, CloudflaredTunnelExistingDns,
+    CloudflaredTunnelIngress, CloudflaredTunnelOriginRequest, CloudflaredTunnelResources,
+    CloudflaredTunnelSpec, CloudflaredTunnelStatus,
 };
+pub use dns_record::CloudflareDNSRecord;
 use futures::{
-    future::{try_join_all, BoxFuture},
-    StreamExt as _,
+    future::{join_all, try_join_all},
+    Stream, StreamExt as _,
 };
 use k8s_openapi::{
-    api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::OwnerReference, ByteString,
+    api::{
+        apps::v1::{Deployment, StatefulSet},
+        core::v1::{Namespace, Node, Secret},
+    },
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+    chrono::{DateTime, SecondsFormat, Utc},
+    ByteString,
 };
 use kube::{
-    api::{DeleteParams, ObjectMeta, Patch, PatchParams},
-    runtime::{controller::Action, finalizer::finalizer, watcher::Config, Controller},
+    api::{DeleteParams, ObjectMeta, PartialObjectMeta, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::{Event, EventType, Recorder, Reporter},
+        finalizer::finalizer,
+        metadata_watcher, predicates,
+        reflector::{self, ObjectRef},
+        utils::Predicate as _,
+        watcher,
+        watcher::Config,
+        Controller, WatchStreamExt as _,
+    },
     Api, Client, Resource, ResourceExt as _,
 };
-use rand::{Rng, SeedableRng};
+use sha2::{Digest as _, Sha256};
 use tracing::{info, warn};
+pub use tunnel_route::CloudflaredTunnelRoute;
 use uuid::Uuid;
+pub use virtual_network::CloudflaredVirtualNetwork;
 
-use self::{cf_api::*, kube_api::*};
-use crate::{cli::ControllerArgs, Error, Result};
+use self::{
+    access_policy::access_application_settings,
+    cf_api::*,
+    credentials::{credentials_key, read_credentials, CloudflareAccount, CredentialCache},
+    customresource::{
+        CloudflaredTunnelAccessApplicationStatus, CloudflaredTunnelConnector,
+        CloudflaredTunnelCredentialsSecretRef, CloudflaredTunnelHealthCheckStatus,
+        CloudflaredTunnelReplacement, CloudflaredTunnelWorkerRoute, CONDITION_DEGRADED,
+        CONDITION_DEPLOYMENT_AVAILABLE, CONDITION_IMAGE_ALLOWED, CONDITION_ORIGIN_HEALTHY,
+        CONDITION_READY,
+    },
+    dns_record::run_dns_record_controller,
+    firewall_rules::source_range_rule,
+    image::is_image_allowed,
+    keyed_lock::KeyedLock,
+    kube_api::*,
+    secret::{generate_tunnel_secret, TUNNEL_SECRET_LEN},
+    transform_rules::{is_managed_rule, normalize_rule, response_header_rule, rule_ref_prefix},
+    tunnel_owner::{owner_of, tunnel_metadata, TunnelOwner},
+    tunnel_route::run_route_controller,
+    txt_registry::TxtRegistry,
+    virtual_network::run_virtual_network_controller,
+};
+use crate::{
+    cli::{ControllerArgs, OrphanTunnelPolicy},
+    metrics::Metrics,
+    readiness::Readiness,
+    resync::ResyncTrigger,
+    zone_resolver::ZoneResolver,
+    Error, Result,
+};
 
 const TUNNEL_SECRET_KEY: &str = "tunnel_secret";
+const SERVICE_TOKEN_CLIENT_ID_KEY: &str = "client_id";
+const SERVICE_TOKEN_CLIENT_SECRET_KEY: &str = "client_secret";
 const CFD_CONFIG_FILENAME: &str = "config.yml";
 const PATCH_PARAMS_APPLY_NAME: &str = "cloudflaredtunnel.chalharu.top";
 const CFD_DEPLOYMENT_IMAGE: &str = "cloudflare/cloudflared:2024.12.2";
+const NODE_DRAIN_ANNOTATION: &str = "cloudflared-ingress.chalharu.top/drain";
+/// Setting the annotation to a new value replaces the tunnel of the CloudflaredTunnel
+const REPLACE_TUNNEL_ANNOTATION: &str = "cloudflared-ingress.chalharu.top/replace-tunnel";
+/// Shortest `spec.secret_rotation_period`, matching the minimum of the CRD schema
+const MIN_SECRET_ROTATION_PERIOD: u64 = 3600;
+const EVENT_REPORTER_CONTROLLER: &str = "cloudflaredtunnel-controller";
+// statusの更新による再調整の連鎖を避けるため、last_reconcile_timeの更新間隔を制限する
+const LAST_RECONCILE_TIME_RESOLUTION: Duration = Duration::from_secs(10 * 60);
+
+/// Reason, action and note of the event published once a DNS record change is applied
+type DnsRecordEvent = (&'static str, &'static str, String);
+
+/// HTTP client of the Cloudflare API authenticated with the token
+fn http_api_client(args: &ControllerArgs, token: &str) -> Result<HttpApiClient> {
+    Ok(HttpApiClient::new(
+        Credentials::UserAuthToken {
+            token: token.to_string(),
+        },
+        HttpApiClientConfig {
+            http_timeout: args.cloudflare_api_timeout(),
+            ..Default::default()
+        },
+        args.cloudflare_api_url()
+            .map_or(Environment::Production, |url| {
+                Environment::Custom(url.clone())
+            }),
+    )?)
+}
+
+/// Cloudflare API client authenticated with the token and configured by the arguments
+fn cloudflare_api(args: &ControllerArgs, token: &str) -> Result<CloudflareApi> {
+    Ok(CloudflareApi::new(Arc::new(http_api_client(args, token)?))
+        .with_tunnel_cname_domain(args.tunnel_cname_domain())
+        .with_retry_policy(RetryPolicy {
+            max_retries: args.cloudflare_api_max_retries(),
+            base_delay: args.cloudflare_api_retry_base_delay(),
+            max_delay: args.cloudflare_api_retry_max_delay(),
+            rate_limit_delay: args.cloudflare_api_rate_limit_delay(),
+        })
+        .with_rate_limiter(Arc::new(RateLimiter::new(
+            args.cloudflare_api_requests_per_second(),
+            args.cloudflare_api_burst(),
+        )))
+        .with_per_page(args.cloudflare_api_per_page())
+        .with_dns_cache_ttl(args.dns_cache_ttl())
+        .with_dns_batch_size(args.dns_batch_size()))
+}
+
+/// Check that Cloudflare accepts the token and that it can read the zones and tunnels of the
+/// account, before any controller starts
+pub async fn verify_credentials(args: &ControllerArgs) -> Result<()> {
+    let cloudflare_api = cloudflare_api(args, args.cloudflare_token())?;
+    let token = cloudflare_api.verify_token().await?;
+    if token.status != "active" {
+        return Err(Error::cloudflare_token_inactive(token.status));
+    }
+    cloudflare_api
+        .check_access(args.cloudflare_account_id().to_string())
+        .await
+}
+
+/// Identity of the controller recorded in the metadata of the created tunnels, by default the
+/// namespace of the controller and the cluster UID, so that instances of a cluster differ
+async fn controller_id(client: &Client, args: &ControllerArgs) -> Result<String> {
+    match args.controller_id() {
+        Some(id) => Ok(id.to_string()),
+        None => Ok(format!(
+            "{}/{}",
+            client.default_namespace(),
+            get_cluster_uid(client).await?
+        )),
+    }
+}
 
 // Context for our reconciler
 struct Context {
     /// Kubernetes client
     client: Client,
     args: ControllerArgs,
-    cloudflare_api: CloudflareApi,
+    /// Only the tunnels created with this identity are deleted as orphans
+    controller_id: String,
+    /// Owner records of the CNAME records, when `--txt-owner-id` is set
+    txt_registry: Option<TxtRegistry>,
+    /// Client of `--cloudflare-token`, used unless a CloudflaredTunnel has credentials of its own
+    cloudflare_api: Arc<CloudflareApi>,
+    /// Accounts of `spec.cloudflare_credentials_secret_ref` and clients of the token Secrets of
+    /// `--zone-accounts-file`
+    credentials: CredentialCache<CloudflareAccount>,
+    /// Metadata of the watched Secrets, whose resourceVersion tells whether a cached account is
+    /// still current without reading the Secret
+    secrets: reflector::Store<PartialObjectMeta<Secret>>,
+    /// Event recorder for CloudflaredTunnel
+    recorder: Recorder,
+    metrics: Metrics,
+    /// Not ready while the Cloudflare credentials fail the verification
+    readiness: Readiness,
+    /// Normal Events published per CloudflaredTunnel since its last reconcile succeeded
+    changes: Mutex<HashMap<(Option<String>, String), BTreeMap<String, usize>>>,
+    /// Limits the DNS record list requests in flight across all reconciles
+    dns_list_permits: tokio::sync::Semaphore,
+    /// Zones holding the CNAME records of each CloudflaredTunnel after its last reconcile
+    managed_zones: Mutex<HashMap<(Option<String>, String), HashSet<String>>>,
+    /// When each orphaned tunnel was first found, it is deleted after the grace period
+    orphans_found: Mutex<HashMap<Uuid, Instant>>,
+    /// Orphaned tunnels already reported with an Event, reported again only once they were
+    /// no longer orphaned
+    orphans_reported: Mutex<HashSet<Uuid>>,
+    /// Serializes the reconciles and cleanups of each CloudflaredTunnel, as every trigger runs
+    /// a reconcile of all of them
+    tunnel_locks: KeyedLock<(Option<String>, String)>,
 }
 
-pub async fn run_controller(args: ControllerArgs) -> Result<()> {
+pub async fn run_controller(
+    args: ControllerArgs,
+    resync: ResyncTrigger,
+    metrics: Metrics,
+    readiness: Readiness,
+) -> Result<()> {
     info!("Starting controller for CloudflaredTunnel");
 
     let client = Client::try_default().await?;
-    let credential = Credentials::UserAuthToken {
-        token: args.cloudflare_token().to_string(),
-    };
-    let cloudflare_api = CloudflareApi::new(Arc::new(HttpApiClient::new(
-        credential,
-        HttpApiClientConfig::default(),
-        Environment::Production,
-    )?));
-
-    let context = Arc::new(Context {
-        client: client.clone(),
+    let controller_id = controller_id(&client, &args).await?;
+    let (secrets, secrets_writer) = reflector::store();
+    let context = Arc::new(Context::new(
+        client.clone(),
         args,
-        cloudflare_api,
-    });
+        controller_id,
+        metrics,
+        readiness,
+        secrets,
+    )?);
+
+    let api = Api::<CloudflaredTunnel>::all(client.clone());
+
+    // 自身が書き込むstatusの更新では再調整しない
+    // annotationやfinalizerの変更ではgenerationが変わらないため、別に拾う
+    let (reader, writer) = reflector::store();
+    let stream_cfdt = watcher(api, Config::default().any_semantic())
+        .default_backoff()
+        .reflect(writer)
+        .applied_objects()
+        .predicate_filter(
+            predicates::generation
+                .combine(predicates::annotations)
+                .combine(predicates::finalizers),
+        );
 
-    let api = Api::<CloudflaredTunnel>::all(client);
+    let controller = Controller::for_stream(stream_cfdt, reader)
+        .owns(Api::<Deployment>::all(client.clone()), Config::default())
+        .owns(Api::<StatefulSet>::all(client.clone()), Config::default());
 
-    Controller::new(api, Config::default().any_semantic())
+    // drain annotationの変更時は全てのCloudflaredTunnelを再調整する
+    let store = controller.store();
+    let secret_store = store.clone();
+    let stream_node = metadata_watcher(Api::<Node>::all(client.clone()), Config::default())
+        .default_backoff()
+        .touched_objects()
+        .predicate_filter(predicates::annotations);
+
+    // 利用者が管理するspec.secret_refのSecretが書き換えられた場合はtunnelへ反映し、
+    // 生成したSecretが削除された場合は作り直す
+    let evict_context = context.clone();
+    let stream_secret = metadata_watcher(Api::<Secret>::all(client.clone()), Config::default())
+        .default_backoff()
+        .reflect(secrets_writer)
+        .inspect(move |event| {
+            // 削除されたSecretから作ったclientは破棄する
+            if let Ok(watcher::Event::Delete(secret)) = event {
+                evict_context.credentials.evict(&format!(
+                    "{}/{}",
+                    secret.namespace().unwrap_or_default(),
+                    secret.name_any()
+                ));
+            }
+        })
+        .touched_objects()
+        .predicate_filter(predicates::resource_version);
+
+    let controller = controller
+        .watches_stream(stream_node, move |_| {
+            store
+                .state()
+                .into_iter()
+                .map(|cfdt| ObjectRef::from_obj(&*cfdt))
+                .collect::<Vec<_>>()
+        })
+        .watches_stream(stream_secret, move |secret| {
+            secret_store
+                .state()
+                .into_iter()
+                .filter(|cfdt| {
+                    cfdt.namespace() == secret.namespace()
+                        && (cfdt.spec.secret_ref.as_ref() == Some(&secret.name_any())
+                            || secret
+                                .owner_references()
+                                .iter()
+                                .any(|o| cfdt.uid().as_ref() == Some(&o.uid)))
+                })
+                .map(|cfdt| ObjectRef::from_obj(&*cfdt))
+                .collect::<Vec<_>>()
+        })
+        .reconcile_all_on(context.subscribe_resync(&resync))
         .shutdown_on_signal()
-        .run(reconcile, error_policy, context)
+        .run(reconcile, error_policy, context.clone())
         .filter_map(|x| async move { std::result::Result::ok(x) })
-        .for_each(|_| futures::future::ready(()))
-        .await;
+        .for_each(|_| futures::future::ready(()));
+
+    // connection statusの更新はcontrollerの停止と共に終了する
+    tokio::select! {
+        _ = futures::future::join4(
+            controller,
+            run_route_controller(context.clone(), resync.clone()),
+            run_virtual_network_controller(context.clone(), resync.clone()),
+            run_dns_record_controller(context.clone(), resync.clone()),
+        ) => {},
+        _ = context.run_connection_status_updater() => {},
+        _ = context.run_token_verifier() => {},
+        _ = context.run_token_secret_watcher() => {},
+        _ = context.run_token_secret_manager_refresher(resync) => {},
+    }
 
     info!("controller for CloudflaredTunnel shutdown");
     Ok(())
 }
 
 async fn reconcile(res: Arc<CloudflaredTunnel>, ctx: Arc<Context>) -> Result<Action> {
+    // 他のshardのCloudflaredTunnelはfinalizerも含め触らない
+    if !ctx.is_own_shard(&*res) {
+        return Ok(Action::await_change());
+    }
     // let name = res.name_any();
     let ns = res.namespace().unwrap();
     // info!("Reconciling CloudflaredTunnel \"{name}\" in {ns}");
     let api = Api::<CloudflaredTunnel>::namespaced(ctx.client.clone(), &ns);
-    let finalizer_name = format!("{}/finalizer", PATCH_PARAMS_APPLY_NAME);
-    finalizer(&api, &finalizer_name, res, |e| async move {
-        match e {
-            kube::runtime::finalizer::Event::Apply(_) => ctx.reconcile().await?,
-            kube::runtime::finalizer::Event::Cleanup(t) => ctx.delete_tunnel(t).await?,
-        }
-        Ok(Action::requeue(Duration::from_secs(60 * 60)))
-    })
-    .await
-    .map_err(|e| Error::from(Box::new(e)))
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let reconcile = finalizer(&api, &finalizer_name(), res.clone(), |e| {
+        let ctx = ctx.clone();
+        async move {
+            match e {
+                kube::runtime::finalizer::Event::Apply(_) => ctx.reconcile().await?,
+                kube::runtime::finalizer::Event::Cleanup(t) => ctx.delete_tunnel(t).await?,
+            }
+            Ok(Action::requeue(Duration::from_secs(60 * 60)))
+        }
+    });
+    tokio::pin!(reconcile);
+    // 遅い調整のみ記録するため、閾値を超えた時点で調整中に開始のEventを記録する
+    let (result, slow) = tokio::select! {
+        result = &mut reconcile => (result, false),
+        () = tokio::time::sleep(ctx.args.slow_reconcile_threshold()) => {
+            ctx.publish_timeline_event(
+                &res,
+                "ReconcileStarted",
+                format!(
+                    "Reconcile started at {}",
+                    started_at.to_rfc3339_opts(SecondsFormat::Millis, true)
+                ),
+            )
+            .await;
+            (reconcile.await, true)
+        }
+    };
+    let result = result.map_err(|e| Error::from(Box::new(e)));
+    if result.is_ok() {
+        ctx.publish_timeline(&res, slow, started.elapsed()).await;
+    }
+    result
 }
 
-fn error_policy<K>(_: Arc<K>, error: &Error, _ctx: Arc<Context>) -> Action {
+fn error_policy(cfdt: Arc<CloudflaredTunnel>, error: &Error, ctx: Arc<Context>) -> Action {
     warn!("reconcile failed: {error:?}");
+    if error.is_insufficient_token_permissions() {
+        ctx.metrics.inc_api_token_permission_errors();
+    }
+    // error_policyは同期関数のため、eventの送信は別タスクで行う
+    let reason = error.reason();
+    let note = error.to_string();
+    tokio::spawn(async move {
+        ctx.publish_event(&cfdt, EventType::Warning, reason, "Reconcile", Some(note))
+            .await
+    });
     Action::requeue(Duration::from_secs(60))
 }
 
-impl Context {
-    async fn delete_tunnel(&self, cfdt: Arc<CloudflaredTunnel>) -> Result<()> {
-        let Some(tunnel_id) = cfdt.status.as_ref().and_then(|x| x.tunnel_id.as_ref()) else {
-            return Ok(());
-        };
+/// Render the cloudflared `config.yml` the controller generates for the CloudflaredTunnel,
+/// using a nil tunnel id when the tunnel is not created yet
+pub fn render_config(cfdt: &CloudflaredTunnel) -> Result<String> {
+    let tunnel_id = cfdt
+        .status
+        .as_ref()
+        .and_then(|s| s.tunnel_id.clone())
+        .unwrap_or_else(|| Uuid::nil().as_hyphenated().to_string());
+    Ok(serde_yaml::to_string(&cfd_config::Config::new(
+        &tunnel_id, &cfdt.spec,
+    ))?)
+}
 
-        let tunnel = self
-            .cloudflare_api
-            .get_tunnel_opt(
-                self.args.cloudflare_account_id().to_string(),
-                tunnel_id.to_string(),
-            )
-            .await?;
+/// Contents of the Secret mounted by cloudflared: the tunnel credentials and `config.yml`
+fn tunnel_config_data(
+    account_id: &str,
+    tunnel_id: &str,
+    tunnel_secret: &[u8],
+    spec: &CloudflaredTunnelSpec,
+) -> Result<BTreeMap<String, String>> {
+    let credential = cfd_config::Credentials {
+        account_tag: account_id.to_string(),
+        tunnel_secret: base64::engine::general_purpose::STANDARD.encode(tunnel_secret),
+        tunnel_id: tunnel_id.to_string(),
+    };
+    let credential_string = serde_json::to_string(&credential)?;
+    let config_string = serde_yaml::to_string(&cfd_config::Config::new(tunnel_id, spec))?;
+    Ok(BTreeMap::from([
+        (
+            cfd_config::credentials_filename(tunnel_id),
+            credential_string,
+        ),
+        (CFD_CONFIG_FILENAME.to_string(), config_string),
+    ]))
+}
 
-        let zones = self.cloudflare_api.list_zone().await?;
-        try_join_all(zones.iter().map(|z| async {
-            let dns_records = self
-                .cloudflare_api
-                .list_dns_cname(z.id.clone(), tunnel_id.clone())
-                .await?;
-            for d in dns_records.into_iter() {
-                self.cloudflare_api
-                    .delete_dns_cname(d.zone_id, d.id)
-                    .await?;
-            }
-            Result::<_, Error>::Ok(())
-        }))
-        .await?;
+fn finalizer_name() -> String {
+    format!("{}/finalizer", PATCH_PARAMS_APPLY_NAME)
+}
 
-        if tunnel.is_some() {
-            self.cloudflare_api
-                .delete_tunnel(
-                    self.args.cloudflare_account_id().to_string(),
-                    tunnel_id.clone(),
-                )
-                .await?;
-        }
-        Ok(())
-    }
+/// Tunnel of the CloudflaredTunnel together with those of a replacement in progress
+fn tunnel_ids_of(cfdt: &CloudflaredTunnel) -> Vec<Uuid> {
+    cfdt.status
+        .as_ref()
+        .and_then(|s| s.tunnel_id.clone())
+        .into_iter()
+        .chain(replacement_tunnel_ids(cfdt))
+        .filter_map(|id| Uuid::parse_str(&id).ok())
+        .collect()
+}
 
-    async fn reconcile(&self) -> Result<()> {
-        let cfdt_list = get_cloudflaredtunnel(&self.client).await?;
-        let account_id = self.args.cloudflare_account_id().to_string();
-        let tunnel_list = self
-            .cloudflare_api
-            .list_tunnels(
-                account_id.clone(),
-                self.args.cloudflare_tunnel_prefix().to_string(),
-            )
-            .await?;
-        let mut tunnel_dic_by_id = tunnel_list
-            .into_iter()
-            .map(|x| (x.id, x))
-            .collect::<HashMap<_, _>>();
+/// Tunnels of a replacement in progress, the new one and the replaced one once switched
+fn replacement_tunnel_ids(cfdt: &CloudflaredTunnel) -> Vec<String> {
+    cfdt.status
+        .as_ref()
+        .and_then(|s| s.replacement.as_ref())
+        .map(|r| {
+            std::iter::once(r.tunnel_id.clone())
+                .chain(r.previous_tunnel_id.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-        for cfdt in cfdt_list {
-            let tunnel = cfdt
-                .status
-                .as_ref()
-                .and_then(|s| s.tunnel_id.as_ref())
-                .and_then(|id| Uuid::parse_str(id).ok())
-                .and_then(|id| tunnel_dic_by_id.remove(&id));
-            self.reconcile_tunnel(cfdt, tunnel).await?;
-        }
+fn namespace_of(cfdt: &CloudflaredTunnel) -> Result<String> {
+    cfdt.namespace()
+        .ok_or_else(|| Error::missing_namespace(CloudflaredTunnel::kind(&()), cfdt.name_any()))
+}
 
-        for t in tunnel_dic_by_id {
-            if t.1.name.starts_with(self.args.cloudflare_tunnel_prefix()) {
-                if let Err(e) = self
-                    .cloudflare_api
-                    .delete_tunnel(
-                        account_id.clone(),
-                        t.0.as_hyphenated()
-                            .encode_lower(&mut Uuid::encode_buffer())
-                            .to_string(),
-                    )
-                    .await
-                {
-                    // tunnel削除の失敗は警告のみとする
-                    warn!("Delete cloudflare tunnel failed: {}", e);
-                }
+impl Context {
+    fn new(
+        client: Client,
+        args: ControllerArgs,
+        controller_id: String,
+        metrics: Metrics,
+        readiness: Readiness,
+        secrets: reflector::Store<PartialObjectMeta<Secret>>,
+    ) -> Result<Self> {
+        let cloudflare_api =
+            Arc::new(cloudflare_api(&args, args.cloudflare_token())?.with_metrics(metrics.clone()));
+
+        let reporter = Reporter {
+            controller: EVENT_REPORTER_CONTROLLER.to_string(),
+            instance: std::env::var("HOSTNAME").ok(),
+        };
+        let txt_registry = args
+            .txt_owner_id()
+            .map(|owner_id| TxtRegistry::new(owner_id, args.txt_prefix()));
+        Ok(Self {
+            recorder: Recorder::new(client.clone(), reporter),
+            dns_list_permits: tokio::sync::Semaphore::new(args.dns_list_concurrency()),
+            client,
+            args,
+            controller_id,
+            txt_registry,
+            cloudflare_api,
+            credentials: CredentialCache::default(),
+            secrets,
+            metrics,
+            readiness,
+            changes: Mutex::default(),
+            managed_zones: Mutex::default(),
+            orphans_found: Mutex::default(),
+            orphans_reported: Mutex::default(),
+            tunnel_locks: KeyedLock::default(),
+        })
+    }
+
+    /// Resync requests, dropping the cached DNS records and managed zones first so that the
+    /// resync also sees the changes made outside of the controller
+    fn subscribe_resync(self: &Arc<Self>, resync: &ResyncTrigger) -> impl Stream<Item = ()> {
+        let ctx = self.clone();
+        resync.subscribe().map(move |()| {
+            ctx.cloudflare_api.invalidate_dns_cache();
+            for account in ctx.credentials.values() {
+                account.api.invalidate_dns_cache();
             }
-        }
+            ctx.managed_zones.lock().unwrap().clear();
+        })
+    }
 
-        Ok(())
+    /// Cloudflare account of the CloudflaredTunnel, read from
+    /// `spec.cloudflare_credentials_secret_ref` when set
+    async fn account_of(&self, cfdt: &CloudflaredTunnel) -> Result<CloudflareAccount> {
+        self.account_of_secret(
+            &namespace_of(cfdt)?,
+            cfdt.spec.cloudflare_credentials_secret_ref.as_ref(),
+        )
+        .await
     }
 
-    async fn create_tunnel(
+    /// Cloudflare account of a credentials Secret in the namespace, the default one when unset
+    async fn account_of_secret(
         &self,
-        name: &str,
         namespace: &str,
-        tunnel_secret: &[u8],
-    ) -> Result<Tunnel> {
-        let tunnel_name_prefix = self.args.cloudflare_tunnel_prefix();
-        let uid = Uuid::new_v4().as_hyphenated().to_string();
-        let tunnel_name = format!("{tunnel_name_prefix}{uid}");
-        let tunnel = self
-            .cloudflare_api
-            .create_tunnel(
-                self.args.cloudflare_account_id().to_string(),
-                tunnel_name.to_string(),
-                tunnel_secret.to_owned(),
-            )
+        secret_ref: Option<&CloudflaredTunnelCredentialsSecretRef>,
+    ) -> Result<CloudflareAccount> {
+        let Some(secret_ref) = secret_ref else {
+            return Ok(self.default_account());
+        };
+        let key = credentials_key(namespace, secret_ref);
+        let account = self
+            .cached_account(namespace, &secret_ref.name, &key, |secret| {
+                let (token, account_id) = read_credentials(namespace, secret_ref, secret)?;
+                Ok(CloudflareAccount {
+                    api: Arc::new(self.cloudflare_api_of(&token)?),
+                    account_id,
+                    zone_apis: HashMap::new(),
+                })
+            })
             .await?;
-        patch_cloudflaredtunnel_status(&self.client, namespace, name, |status| {
-            status.tunnel_id = Some(tunnel.id.as_hyphenated().to_string())
-        })
-        .await?;
-        Ok(tunnel)
+        Ok((*account).clone())
     }
 
-    async fn reconcile_tunnel(
+    /// Account built from the Secret, read again only once its resourceVersion changed
+    async fn cached_account(
         &self,
-        cfdt: CloudflaredTunnel,
-        tunnel: Option<Tunnel>,
-    ) -> Result<()> {
-        info!("Reconcile cloudflaredTunnel: {}", cfdt.name_any());
-        let name = cfdt.name_any();
-        let namespace = cfdt.namespace().unwrap();
-        let uid = cfdt.uid().unwrap();
-        let owner_ref = OwnerReference {
-            api_version: CloudflaredTunnel::api_version(&()).to_string(),
-            kind: CloudflaredTunnel::kind(&()).to_string(),
-            name: name.clone(),
-            uid,
-            ..Default::default()
-        };
-
-        // DNS ZoneのリストをCloudflareから取得
-        let zones = self.cloudflare_api.list_zone().await?;
-
-        // CloudflaredTunnel.spec.ingress[].hostnameがどの　DNS Zoneに当てはまるか確認
-        let mut dns_list = HashSet::new();
-        for ingress in cfdt.spec.ingress.as_ref().iter().flat_map(|x| x.iter()) {
-            let Some(zone_id) = zones
-                .iter()
-                .filter_map(|z| {
-                    if ingress.hostname.ends_with(&format!(".{}", z.name)) {
-                        Some(z.id.clone())
-                    } else {
-                        None
-                    }
-                })
-                .next()
-            else {
-                // hostnameがzoneに当てはまらない場合
-                return Err(Error::illegal_document());
-            };
-            dns_list.insert((ingress.hostname.clone(), zone_id));
+        namespace: &str,
+        name: &str,
+        key: &str,
+        build: impl FnOnce(&Secret) -> Result<CloudflareAccount>,
+    ) -> Result<Arc<CloudflareAccount>> {
+        // watchに未だ現れていないSecretや、watchしないuninstallでは毎回読み込む
+        let cached = self
+            .secrets
+            .get(&ObjectRef::new(name).within(namespace))
+            .and_then(|secret| secret.resource_version())
+            .and_then(|version| self.credentials.get(key, &version));
+        if let Some(account) = cached {
+            return Ok(account);
         }
-
-        // ZoneIDからDNSレコードを引く辞書を作成
-        let zone_dns_list = try_join_all(zones.iter().map(|z| async {
-            Result::<_, Error>::Ok(
-                self.cloudflare_api
-                    .list_dns(z.id.clone())
-                    .await?
-                    .into_iter()
-                    .fold(
-                        HashMap::new(),
-                        |mut acc: HashMap<String, Vec<DnsRecord>>, value| {
-                            acc.entry(value.zone_id.clone()).or_default().push(value);
-                            acc
-                        },
-                    ),
-            )
-        }))
-        .await?
-        .into_iter()
-        .flat_map(|x| x.into_iter())
-        .collect::<HashMap<_, _>>();
-
-        let tunnel_secret = self.get_tunnel_secret(&cfdt, owner_ref.clone()).await?;
-
-        let tunnel = tunnel
-            .map_or_else::<BoxFuture<Result<_>>, _, _>(
-                || Box::pin(self.create_tunnel(&name, &namespace, &tunnel_secret)),
-                |x| Box::pin(async { Ok(x) }),
-            )
+        let secret = Api::<Secret>::namespaced(self.client.clone(), namespace)
+            .get(name)
             .await?;
-        let tunnel_id = tunnel.id.as_hyphenated().to_string();
+        let account = build(&secret)?;
+        Ok(self
+            .credentials
+            .insert(key, &secret.resource_version().unwrap_or_default(), account))
+    }
 
-        // {tunnelid}.cfargotunnel.comのCNAMEレコードリストを作成する
-        let cname_content = format!("{tunnel_id}.cfargotunnel.com");
-        let mut current_cname_list = zone_dns_list
-            .iter()
-            .flat_map(|(_, rec)| {
-                rec.iter().flat_map(|rec| match rec.content {
-                    DnsContent::CNAME { ref content } if content.as_str() == cname_content => {
-                        Some((rec.id.clone(), rec.zone_id.clone()))
-                    }
-                    _ => None,
-                })
-            })
-            .collect::<HashSet<_>>();
+    /// Account of `--cloudflare-token` and `--cloudflare-account-id`
+    fn default_account(&self) -> CloudflareAccount {
+        CloudflareAccount {
+            api: self.cloudflare_api.clone(),
+            account_id: self.args.cloudflare_account_id().to_string(),
+            zone_apis: HashMap::new(),
+        }
+    }
 
-        // {tunnelid}.cfargotunnel.com以外のCNAMEレコード、Aレコード・AAAAレコードが無いことを確認する
-        for (ref hostname, ref zone_id) in &dns_list {
-            if let Some(dns_record) = zone_dns_list
-                .get(zone_id)
-                .ok_or_else(|| unreachable!())
-                .and_then(|dns_records| {
-                    dns_records
-                        .iter()
-                        .filter(|dns_record| dns_record.name.as_str() == hostname.as_str())
-                        .try_fold(None, |acc, dns_record| match &dns_record.content {
-                            DnsContent::CNAME { content } if content.as_str() == cname_content => {
-                                Ok(Some(dns_record))
-                            }
-                            DnsContent::A { .. }
-                            | DnsContent::AAAA { .. }
-                            | DnsContent::CNAME { .. } => Err(Error::illegal_document()),
-                            _ => Ok(acc),
+    /// Client of a token read from a Secret
+    fn cloudflare_api_of(&self, token: &str) -> Result<CloudflareApi> {
+        Ok(cloudflare_api(&self.args, token)?.with_metrics(self.metrics.clone()))
+    }
+
+    /// Zones of the account together with those mapped to other accounts by
+    /// `--zone-accounts-file`, whose clients are recorded in the account
+    async fn list_zones(&self, account: &mut CloudflareAccount) -> Result<Vec<Zone>> {
+        let Some(zone_accounts) = self.args.zone_accounts() else {
+            return account.api.list_zone().await;
+        };
+        // 対応付けられたzoneは、元のaccountから見える場合も対応先のtokenで管理する
+        let mut zones = account
+            .api
+            .list_zone()
+            .await?
+            .into_iter()
+            .filter(|z| zone_accounts.account_of(&z.name).is_none())
+            .collect::<Vec<_>>();
+        for zone_account in zone_accounts.accounts.iter() {
+            let secret_ref = &zone_account.token_secret_ref;
+            // tokenのみを読み込むため、account idは空のままとする
+            let api = self
+                .cached_account(
+                    secret_ref.namespace(),
+                    secret_ref.name(),
+                    &secret_ref.to_string(),
+                    |secret| {
+                        Ok(CloudflareAccount {
+                            api: Arc::new(self.cloudflare_api_of(&secret_ref.value_of(secret)?)?),
+                            account_id: String::new(),
+                            zone_apis: HashMap::new(),
                         })
-                })?
-            {
-                current_cname_list.remove(&(dns_record.id.clone(), dns_record.zone_id.clone()));
-            } else {
-                self.cloudflare_api
-                    .create_dns_cname(zone_id.clone(), tunnel_id.clone(), hostname.clone())
-                    .await?;
+                    },
+                )
+                .await?
+                .api
+                .clone();
+            for zone in api.list_zone().await? {
+                if zone_account
+                    .zones
+                    .iter()
+                    .any(|z| z.eq_ignore_ascii_case(&zone.name))
+                {
+                    account.zone_apis.insert(zone.id.clone(), api.clone());
+                    zones.push(zone);
+                }
             }
         }
-        for (dns_id, zone_id) in current_cname_list {
-            self.cloudflare_api
-                .delete_dns_cname(zone_id, dns_id)
-                .await?;
-        }
+        Ok(zones)
+    }
 
-        let (tunnel_config_secret_name, secret_updated) = self
-            .get_tunnel_config(&cfdt, owner_ref.clone(), tunnel, &tunnel_secret)
-            .await?;
+    /// Run a DNS record list request once a slot is free
+    async fn limit_dns_list<F: Future>(&self, request: F) -> F::Output {
+        let started_at = Instant::now();
+        self.metrics.dns_list_started();
+        let output = {
+            let _permit = self.dns_list_permits.acquire().await;
+            request.await
+        };
+        self.metrics.dns_list_finished(started_at.elapsed());
+        output
+    }
 
-        let deployment_name = format!("{}-{}", name, "cloudflared");
-        let created = patch_deployment(
-            &self.client,
-            &deployment_name,
-            &namespace,
-            &tunnel_config_secret_name,
-            &tunnel_id,
-            self.args.deployment_replicas().try_into()?,
-            &cfdt.spec,
-            Some(vec![owner_ref]),
+    /// Take the changes recorded for the CloudflaredTunnel as `reason=count` pairs
+    fn take_changes(&self, cfdt: &CloudflaredTunnel) -> Option<String> {
+        let changes = self
+            .changes
+            .lock()
+            .unwrap()
+            .remove(&(cfdt.namespace(), cfdt.name_any()))?;
+        Some(
+            changes
+                .into_iter()
+                .map(|(reason, count)| format!("{reason}={count}"))
+                .collect::<Vec<_>>()
+                .join(", "),
         )
-        .await?;
+    }
 
-        // secretが更新されている場合はrestartを行う
-        if !created && secret_updated {
-            restart_deployment(&self.client, &deployment_name, &namespace).await?;
+    /// Record the end of a slow reconcile with the changes made since the last recorded one,
+    /// which include those of the reconciles of other CloudflaredTunnels and of failed ones
+    async fn publish_timeline(&self, cfdt: &CloudflaredTunnel, slow: bool, elapsed: Duration) {
+        let changes = self.take_changes(cfdt);
+        // 閾値以下の場合はEventを記録しない
+        if !slow {
+            return;
         }
+        self.publish_timeline_event(
+            cfdt,
+            "ReconcileSucceeded",
+            format!(
+                "Reconcile succeeded in {}ms, changes: {}",
+                elapsed.as_millis(),
+                changes.as_deref().unwrap_or("none")
+            ),
+        )
+        .await;
+    }
 
-        Ok(())
+    /// Publish a timeline Event, which is not counted as a change
+    async fn publish_timeline_event(&self, cfdt: &CloudflaredTunnel, reason: &str, note: String) {
+        let event = Event {
+            type_: EventType::Normal,
+            reason: reason.to_string(),
+            note: Some(note),
+            action: "Reconcile".to_string(),
+            secondary: None,
+        };
+        if let Err(e) = self.recorder.publish(&event, &cfdt.object_ref(&())).await {
+            warn!("failed to publish event {reason}: {e:?}");
+        }
     }
 
-    async fn get_tunnel_secret(
+    /// Publish an Event on the CloudflaredTunnel, logging instead of failing on errors
+    async fn publish_event(
         &self,
         cfdt: &CloudflaredTunnel,
-        owner_ref: OwnerReference,
-    ) -> Result<Vec<u8>> {
-        let spec_ref = cfdt.spec.secret_ref.as_ref();
-        let status_ref = cfdt
-            .status
-            .as_ref()
-            .and_then(|s| s.tunnel_secret_ref.as_ref());
-        let ns = cfdt.namespace().ok_or_else(Error::illegal_document)?;
-        let api = Api::<Secret>::namespaced(self.client.clone(), &ns);
+        type_: EventType,
+        reason: &str,
+        action: &str,
+        note: Option<String>,
+    ) {
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note,
+            action: action.to_string(),
+            secondary: None,
+        };
+        if matches!(event.type_, EventType::Normal) {
+            *self
+                .changes
+                .lock()
+                .unwrap()
+                .entry((cfdt.namespace(), cfdt.name_any()))
+                .or_default()
+                .entry(event.reason.clone())
+                .or_default() += 1;
+        }
+        if let Err(e) = self.recorder.publish(&event, &cfdt.object_ref(&())).await {
+            warn!("failed to publish event {reason}: {e:?}");
+        }
+    }
 
-        let secret_ref = match (spec_ref, status_ref) {
-            (None, Some(st)) => st.to_string(),
-            (Some(sp), Some(st)) if sp == st => st.to_string(),
-            (sp, st) => {
-                let secret_ref = if let Some(sp) = sp {
-                    // もし自分自身が作成したリソースなら削除
-                    if let Some(st) = st {
-                        if let Some(secret) = api.get_opt(st.as_str()).await? {
-                            if secret.owner_references().contains(&owner_ref) {
-                                api.delete(&secret.name_any(), &DeleteParams::background())
-                                    .await?;
-                            }
-                        }
-                    }
-                    sp.to_string()
-                } else {
-                    Uuid::new_v4()
-                        .as_hyphenated()
-                        .encode_lower(&mut Uuid::encode_buffer())
-                        .to_string()
-                };
+    /// Whether the object is reconciled by this instance among the `--shard-count` instances
+    fn is_own_shard<K: Resource>(&self, obj: &K) -> bool {
+        self.args.is_own_shard(&format!(
+            "{}/{}",
+            obj.namespace().unwrap_or_default(),
+            obj.name_any()
+        ))
+    }
 
-                // statusに新しいsecret_refを設定
-                patch_cloudflaredtunnel_status(&self.client, &ns, &cfdt.name_any(), |status| {
-                    status.tunnel_secret_ref = Some(secret_ref.clone())
-                })
+    /// Publish an Event of another resource than a CloudflaredTunnel, left out of the timeline
+    async fn publish_object_event<K: Resource<DynamicType = ()>>(
+        &self,
+        obj: &K,
+        type_: EventType,
+        reason: &str,
+        action: &str,
+        note: Option<String>,
+    ) {
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note,
+            action: action.to_string(),
+            secondary: None,
+        };
+        if let Err(e) = self.recorder.publish(&event, &obj.object_ref(&())).await {
+            warn!("failed to publish event {reason}: {e:?}");
+        }
+    }
+
+    async fn run_connection_status_updater(&self) {
+        let mut interval = tokio::time::interval(self.args.connection_status_interval());
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.update_connection_status().await {
+                warn!("Update tunnel connection status failed: {e:?}");
+            }
+        }
+    }
+
+    async fn run_token_verifier(&self) {
+        let mut interval = tokio::time::interval(self.args.token_verify_interval());
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.verify_token().await {
+                warn!("Verify Cloudflare token failed: {e:?}");
+            }
+        }
+    }
+
+    /// Rebuild the Cloudflare client whenever the token in the Secret of
+    /// `--cloudflare-token-secret-ref` changes
+    async fn run_token_secret_watcher(&self) {
+        let Some(secret_ref) = self.args.cloudflare_token_secret_ref() else {
+            // select!を終了させないよう待ち続ける
+            return std::future::pending().await;
+        };
+        let api = Api::<Secret>::namespaced(self.client.clone(), secret_ref.namespace());
+        let config = Config::default().fields(&format!("metadata.name={}", secret_ref.name()));
+        let mut current = self.args.cloudflare_token().to_string();
+        let mut secrets = std::pin::pin!(watcher(api, config).default_backoff().applied_objects());
+        while let Some(secret) = secrets.next().await {
+            let token = match secret {
+                Ok(secret) => secret_ref.value_of(&secret),
+                Err(e) => {
+                    warn!("Watch Cloudflare token Secret {secret_ref} failed: {e:?}");
+                    continue;
+                }
+            };
+            let token = match token {
+                Ok(token) => token,
+                Err(e) => {
+                    warn!("Read Cloudflare token from {secret_ref} failed: {e:?}");
+                    continue;
+                }
+            };
+            if token != current && self.replace_token(&secret_ref, &token).await {
+                current = token;
+            }
+        }
+    }
+
+    /// Re-read the token of `--cloudflare-token-secret-manager` on every resync request and
+    /// every `--token-verify-interval-secs`, rebuilding the Cloudflare client when it changed
+    async fn run_token_secret_manager_refresher(&self, resync: ResyncTrigger) {
+        let Some(secret_manager_ref) = self.args.cloudflare_token_secret_manager() else {
+            // select!を終了させないよう待ち続ける
+            return std::future::pending().await;
+        };
+        let mut current = self.args.cloudflare_token().to_string();
+        let mut resync = resync.subscribe();
+        let mut interval = tokio::time::interval(self.args.token_verify_interval());
+        // 起動時に読み込んだばかりなので最初のtickは読み飛ばす
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                Some(()) = resync.next() => {},
+            }
+            let token = match secret_manager_ref.read().await {
+                Ok(token) => token,
+                Err(e) => {
+                    warn!("Read Cloudflare token from {secret_manager_ref} failed: {e:?}");
+                    continue;
+                }
+            };
+            if token != current && self.replace_token(secret_manager_ref, &token).await {
+                current = token;
+            }
+        }
+    }
+
+    /// Rebuild the Cloudflare client with the rotated token of the source, returning whether
+    /// the client was replaced
+    async fn replace_token(&self, source: &dyn fmt::Display, token: &str) -> bool {
+        match http_api_client(&self.args, token) {
+            Ok(api) => {
+                info!("Cloudflare token in {source} rotated, rebuilding the client");
+                self.cloudflare_api.replace_client(Arc::new(api));
+                if let Err(e) = self.verify_token().await {
+                    warn!("Verify Cloudflare token failed: {e:?}");
+                }
+                true
+            }
+            Err(e) => {
+                warn!("Build Cloudflare client failed: {e:?}");
+                false
+            }
+        }
+    }
+
+    /// Record the expiry of the Cloudflare token and warn on every CloudflaredTunnel when it
+    /// is inactive or about to expire. The controller is ready while the token is active and
+    /// can read the zones and tunnels of the account
+    async fn verify_token(&self) -> Result<()> {
+        let token = match self.cloudflare_api.verify_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                self.readiness.set_not_ready(e.to_string());
+                return Err(e);
+            }
+        };
+        let access = if token.status == "active" {
+            self.cloudflare_api
+                .check_access(self.args.cloudflare_account_id().to_string())
+                .await
+        } else {
+            Err(Error::cloudflare_token_inactive(token.status.clone()))
+        };
+        match access {
+            Ok(()) => self.readiness.set_ready(),
+            Err(ref e) => self.readiness.set_not_ready(e.to_string()),
+        }
+        self.metrics
+            .set_api_token_expiry(token.expires_on.map(|t| t.timestamp()));
+        let warning = if token.status != "active" {
+            Some((
+                "ApiTokenInactive",
+                format!("Cloudflare API token is {}", token.status),
+            ))
+        } else {
+            token
+                .expires_on
+                .filter(|t| {
+                    (*t - Utc::now()).to_std().map_or(true, |remaining| {
+                        remaining <= self.args.token_expiry_warning()
+                    })
+                })
+                .map(|t| {
+                    (
+                        "ApiTokenExpiring",
+                        format!(
+                            "Cloudflare API token expires at {}",
+                            t.to_rfc3339_opts(SecondsFormat::Secs, true)
+                        ),
+                    )
+                })
+        };
+        let Some((reason, note)) = warning else {
+            return access;
+        };
+        warn!("{note}");
+        for cfdt in get_cloudflaredtunnel(&self.client).await? {
+            if !self.is_own_shard(&cfdt) {
+                continue;
+            }
+            self.publish_event(
+                &cfdt,
+                EventType::Warning,
+                reason,
+                "VerifyToken",
+                Some(note.clone()),
+            )
+            .await;
+        }
+        access
+    }
+
+    async fn update_connection_status(&self) -> Result<()> {
+        for cfdt in get_cloudflaredtunnel(&self.client).await? {
+            if !self.is_own_shard(&cfdt) {
+                continue;
+            }
+            let Some(tunnel_id) = cfdt.status.as_ref().and_then(|s| s.tunnel_id.clone()) else {
+                continue;
+            };
+            let namespace = namespace_of(&cfdt)?;
+            let name = cfdt.name_any();
+            // 認証情報を読めないtunnelはreconcileがEventを記録するため、ここでは読み飛ばす
+            let account = match self.account_of(&cfdt).await {
+                Ok(account) => account,
+                Err(error) => {
+                    warn!("Credentials of cloudflaredTunnel {name} are unavailable: {error}");
+                    continue;
+                }
+            };
+            // 1つのtunnelの取得に失敗しても、残りのtunnelのstatusは更新する
+            let connections = match account
+                .api
+                .list_tunnel_connections(account.account_id.clone(), tunnel_id)
+                .await
+            {
+                Ok(connections) => connections,
+                Err(error) => {
+                    warn!("List connections of cloudflaredTunnel {name} failed: {error}");
+                    continue;
+                }
+            };
+            let connections = connections
+                .into_iter()
+                .map(|mut c| {
+                    // 再接続待ちのconnectionは数えない
+                    c.conns.retain(|x| !x.is_pending_reconnect);
+                    c
+                })
+                .collect::<Vec<_>>();
+            let mut connectors = connections
+                .iter()
+                .cloned()
+                .map(|c| {
+                    let mut colo_names = c
+                        .conns
+                        .iter()
+                        .map(|x| x.colo_name.clone())
+                        .collect::<Vec<_>>();
+                    colo_names.sort();
+                    colo_names.dedup();
+                    CloudflaredTunnelConnector {
+                        id: c.id.as_hyphenated().to_string(),
+                        version: c.version,
+                        arch: c.arch,
+                        colo_names,
+                        connections: c.conns.len().try_into().unwrap_or(u32::MAX),
+                    }
+                })
+                .collect::<Vec<_>>();
+            connectors.sort_by(|a, b| a.id.cmp(&b.id));
+            let active_connections = connectors.iter().map(|c| c.connections).sum::<u32>();
+            let colo_connections = connections.iter().flat_map(|c| c.conns.iter()).fold(
+                BTreeMap::new(),
+                |mut acc: BTreeMap<String, i64>, conn| {
+                    *acc.entry(conn.colo_name.clone()).or_default() += 1;
+                    acc
+                },
+            );
+            self.metrics.set_tunnel_colo_connections(
+                &namespace,
+                &name,
+                &format!("{name}-cloudflared"),
+                colo_connections,
+            );
+
+            let ready_timeout = self.args.tunnel_ready_timeout();
+            patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+                status.active_connections = Some(active_connections);
+                status.connectors = Some(connectors);
+                if active_connections > 0 {
+                    status.set_condition(CONDITION_READY, true, "TunnelConnected", None);
+                    return;
+                }
+                // 接続が無い状態がtimeoutを超えた場合はDegradedとする
+                let not_ready_since = status
+                    .condition(CONDITION_READY)
+                    .filter(|c| c.status == "False")
+                    .and_then(|c| c.last_transition_time.as_ref())
+                    .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                    .map(|t| t.with_timezone(&Utc));
+                let timed_out = not_ready_since.is_some_and(|t| {
+                    (Utc::now() - t)
+                        .to_std()
+                        .is_ok_and(|elapsed| elapsed >= ready_timeout)
+                });
+                if timed_out {
+                    status.set_condition(
+                        CONDITION_READY,
+                        false,
+                        "Degraded",
+                        Some(format!(
+                            "tunnel has no active connection for more than {}s",
+                            ready_timeout.as_secs()
+                        )),
+                    );
+                } else {
+                    status.set_condition(
+                        CONDITION_READY,
+                        false,
+                        "Connecting",
+                        Some("waiting for the tunnel to be connected".to_string()),
+                    );
+                }
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_tunnel(&self, cfdt: Arc<CloudflaredTunnel>) -> Result<()> {
+        let _guard = self
+            .tunnel_locks
+            .lock((cfdt.namespace(), cfdt.name_any()))
+            .await;
+        if let Some(ns) = cfdt.namespace() {
+            self.metrics.remove_tunnel(&ns, &cfdt.name_any());
+        }
+        self.managed_zones
+            .lock()
+            .unwrap()
+            .remove(&(cfdt.namespace(), cfdt.name_any()));
+        let Some(tunnel_id) = cfdt.status.as_ref().and_then(|x| x.tunnel_id.as_ref()) else {
+            return Ok(());
+        };
+        let mut account = match self.account_of(&cfdt).await {
+            Ok(account) => account,
+            // namespaceと共に認証情報のSecretが削除された場合は、既定のaccountのtunnelのみ削除し、
+            // それ以外はfinalizerを止めずに残ったものを報告する
+            Err(error) if error.is_not_found() => {
+                let account_id = cfdt.status.as_ref().and_then(|s| s.account_id.as_deref());
+                if account_id != Some(self.args.cloudflare_account_id()) {
+                    self.publish_event(
+                        &cfdt,
+                        EventType::Warning,
+                        "CredentialsUnavailable",
+                        "Cleanup",
+                        Some(format!(
+                            "Credentials Secret is gone, tunnel {tunnel_id} and its records are left in account {}",
+                            account_id.unwrap_or("unknown")
+                        )),
+                    )
+                    .await;
+                    return Ok(());
+                }
+                self.default_account()
+            }
+            Err(error) => return Err(error),
+        };
+        let zones = self.list_zones(&mut account).await?;
+
+        // 外部で削除済みのrouteは対象外とする
+        self.cleanup_step(&cfdt, "worker_routes", async {
+            let worker_routes = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.worker_routes.clone())
+                .unwrap_or_default();
+            let existing_route_ids = try_join_all(
+                worker_routes
+                    .iter()
+                    .map(|r| r.zone_id.clone())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|zone_id| account.zone_api(&zone_id).list_worker_routes(zone_id)),
+            )
+            .await?
+            .into_iter()
+            .flatten()
+            .map(|r| r.id)
+            .collect::<HashSet<_>>();
+            for route in worker_routes
+                .into_iter()
+                .filter(|r| existing_route_ids.contains(&r.id))
+            {
+                account
+                    .zone_api(&route.zone_id)
+                    .delete_worker_route(route.zone_id, route.id)
+                    .await?;
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "access_applications", async {
+            let access_applications = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.access_applications.clone())
+                .unwrap_or_default();
+            if access_applications.is_empty() {
+                return Ok(());
+            }
+            let existing_ids = account
+                .api
+                .list_access_applications(account.account_id.clone())
+                .await?
+                .into_iter()
+                .map(|a| a.id)
+                .collect::<HashSet<_>>();
+            for application in access_applications
+                .iter()
+                .filter(|a| existing_ids.contains(&a.id))
+            {
+                account
+                    .api
+                    .delete_access_application(account.account_id.clone(), application.id.clone())
+                    .await?;
+            }
+            // service tokenはapplicationの削除後に削除する
+            if access_applications
+                .iter()
+                .any(|a| a.service_token_id.is_some())
+            {
+                let existing_token_ids = account
+                    .api
+                    .list_access_service_tokens(account.account_id.clone())
+                    .await?
+                    .into_iter()
+                    .map(|t| t.id)
+                    .collect::<HashSet<_>>();
+                for token_id in access_applications
+                    .into_iter()
+                    .filter_map(|a| a.service_token_id)
+                    .filter(|id| existing_token_ids.contains(id))
+                {
+                    account
+                        .api
+                        .delete_access_service_token(account.account_id.clone(), token_id)
+                        .await?;
+                }
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "health_checks", async {
+            let health_checks = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.health_checks.clone())
+                .unwrap_or_default();
+            let existing_ids = try_join_all(
+                health_checks
+                    .iter()
+                    .map(|c| c.zone_id.clone())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|zone_id| account.zone_api(&zone_id).list_health_checks(zone_id)),
+            )
+            .await?
+            .into_iter()
+            .flatten()
+            .map(|c| c.id)
+            .collect::<HashSet<_>>();
+            for check in health_checks
+                .into_iter()
+                .filter(|c| existing_ids.contains(&c.id))
+            {
+                account
+                    .zone_api(&check.zone_id)
+                    .delete_health_check(check.zone_id, check.id)
+                    .await?;
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "response_headers", async {
+            let response_header_zones = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.response_header_zones.clone())
+                .unwrap_or_default();
+            for zone_id in response_header_zones {
+                self.replace_response_header_rules(&cfdt, &account, zone_id, Vec::new())
+                    .await?;
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "firewall_rules", async {
+            let firewall_rule_zones = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.firewall_rule_zones.clone())
+                .unwrap_or_default();
+            for zone_id in firewall_rule_zones {
+                self.replace_firewall_rules(&cfdt, &account, zone_id, Vec::new())
+                    .await?;
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "dns_records", async {
+            if !cfdt.spec.manage_dns.unwrap_or(true) {
+                return Ok(());
+            }
+            let zone_ids = zones
+                .iter()
+                .map(|z| z.id.clone())
+                .chain(
+                    cfdt.spec
+                        .ingress
+                        .iter()
+                        .flatten()
+                        .filter_map(|i| i.zone_id.clone()),
+                )
+                .chain(
+                    cfdt.status
+                        .as_ref()
+                        .and_then(|s| s.dns_record_zones.clone())
+                        .unwrap_or_default(),
+                )
+                .collect::<HashSet<_>>();
+            try_join_all(zone_ids.iter().map(|zone_id| async {
+                let dns_records = self
+                    .delete_tunnel_cnames(account.zone_api(zone_id), zone_id, tunnel_id)
+                    .await?;
+                for d in dns_records.into_iter() {
+                    self.publish_event(
+                        &cfdt,
+                        EventType::Normal,
+                        "DnsRecordDeleted",
+                        "DeleteDnsRecord",
+                        Some(format!("Deleted CNAME record {}", d.name)),
+                    )
+                    .await;
+                }
+                Result::<_, Error>::Ok(())
+            }))
+            .await?;
+            Result::<_, Error>::Ok(())
+        })
+        .await?;
+
+        self.cleanup_step(&cfdt, "tunnel", async {
+            let tunnel = account
+                .api
+                .get_tunnel_opt(account.account_id.clone(), tunnel_id.to_string())
+                .await?;
+            if let Some(tunnel) = tunnel {
+                // 異常終了したconnectorの接続が残っていると削除できないため、先に削除する
+                if !tunnel.connections.is_empty() {
+                    match account
+                        .api
+                        .cleanup_tunnel_connections(account.account_id.clone(), tunnel_id.clone())
+                        .await
+                    {
+                        Ok(()) => {
+                            self.publish_event(
+                                &cfdt,
+                                EventType::Normal,
+                                "TunnelConnectionsCleanedUp",
+                                "DeleteTunnel",
+                                Some(format!(
+                                    "Cleaned up {} connections of tunnel {tunnel_id}",
+                                    tunnel.connections.len()
+                                )),
+                            )
+                            .await
+                        }
+                        Err(e) => {
+                            self.publish_event(
+                                &cfdt,
+                                EventType::Warning,
+                                "TunnelConnectionsCleanupFailed",
+                                "DeleteTunnel",
+                                Some(format!(
+                                    "Failed to clean up the connections of tunnel {tunnel_id}: {e}"
+                                )),
+                            )
+                            .await
+                        }
+                    }
+                }
+                let cascade = cfdt
+                    .spec
+                    .cascade_delete
+                    .unwrap_or(self.args.tunnel_delete_cascade());
+                account
+                    .api
+                    .delete_tunnel(account.account_id.clone(), tunnel_id.clone(), cascade)
+                    .await?;
+                self.publish_event(
+                    &cfdt,
+                    EventType::Normal,
+                    "TunnelDeleted",
+                    "DeleteTunnel",
+                    Some(format!("Deleted tunnel {tunnel_id}")),
+                )
+                .await;
+            }
+            // 置き換え中のtunnelも削除する
+            for id in replacement_tunnel_ids(&cfdt)
+                .into_iter()
+                .filter(|id| id != tunnel_id)
+            {
+                account
+                    .api
+                    .delete_tunnel(account.account_id.clone(), id, true)
+                    .await?;
+            }
+            Result::<_, Error>::Ok(())
+        })
+        .await
+    }
+
+    /// Run a step of the finalizer cleanup, recording its outcome as metrics and a Warning
+    /// Event on failure
+    async fn cleanup_step(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        step: &str,
+        cleanup: impl Future<Output = Result<()>>,
+    ) -> Result<()> {
+        let started_at = Instant::now();
+        self.metrics.finalizer_cleanup_started(step);
+        let result = cleanup.await;
+        self.metrics
+            .finalizer_cleanup_finished(step, result.is_ok(), started_at.elapsed());
+        if let Err(ref error) = result {
+            self.publish_event(
+                cfdt,
+                EventType::Warning,
+                "FinalizerCleanupFailed",
+                "Cleanup",
+                Some(format!("Cleanup of {step} failed: {error}")),
+            )
+            .await;
+        }
+        result
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let cfdt_list = get_cloudflaredtunnel(&self.client).await?;
+        let account_id = self.args.cloudflare_account_id().to_string();
+        // CloudflaredTunnel毎にprefixを変更できるため、全てのprefixのtunnelを取得する
+        let prefixes = cfdt_list
+            .iter()
+            .filter_map(|cfdt| cfdt.spec.tunnel_prefix.clone())
+            .chain([self.args.cloudflare_tunnel_prefix().to_string()])
+            .collect::<BTreeSet<_>>();
+        let tunnel_list = try_join_all(prefixes.iter().map(|prefix| {
+            self.cloudflare_api
+                .list_tunnels(account_id.clone(), prefix.clone())
+        }))
+        .await?;
+        let mut tunnel_dic_by_id = tunnel_list
+            .into_iter()
+            .flatten()
+            .map(|x| (x.id, x))
+            .collect::<HashMap<_, _>>();
+
+        let draining_nodes = get_draining_nodes(&self.client).await?;
+
+        let mut failed = Vec::new();
+        for cfdt in cfdt_list {
+            // 他のshardのtunnelもorphanとして扱わないよう、一覧から除いてから読み飛ばす
+            if !self.is_own_shard(&cfdt) {
+                for id in tunnel_ids_of(&cfdt) {
+                    tunnel_dic_by_id.remove(&id);
+                }
+                continue;
+            }
+            // 1つのtunnelの失敗で他のtunnelの調整を止めないよう、失敗はtunnel毎に記録する
+            if let Err(error) = self
+                .reconcile_listed_tunnel(&cfdt, &mut tunnel_dic_by_id, &draining_nodes)
+                .await
+            {
+                warn!(
+                    "reconcile of CloudflaredTunnel {} failed: {error:?}",
+                    cfdt.name_any()
+                );
+                if error.is_insufficient_token_permissions() {
+                    self.metrics.inc_api_token_permission_errors();
+                }
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    error.reason(),
+                    "Reconcile",
+                    Some(error.to_string()),
+                )
+                .await;
+                // 失敗したCloudflaredTunnelのtunnelをorphanとして扱わない
+                for id in tunnel_ids_of(&cfdt) {
+                    tunnel_dic_by_id.remove(&id);
+                }
+                failed.push(format!(
+                    "{}/{}",
+                    cfdt.namespace().unwrap_or_default(),
+                    cfdt.name_any()
+                ));
+            }
+        }
+
+        // orphanの削除は最初のshardのみが行う
+        if self.args.is_first_shard() {
+            let orphans = tunnel_dic_by_id
+                .into_values()
+                .filter(|t| prefixes.iter().any(|prefix| t.name.starts_with(prefix)))
+                .collect::<Vec<_>>();
+            self.handle_orphan_tunnels(&account_id, orphans).await;
+        }
+
+        if !failed.is_empty() {
+            return Err(Error::cloudflared_tunnels_failed(failed));
+        }
+        Ok(())
+    }
+
+    /// Reconcile one of the listed CloudflaredTunnels, taking the tunnels it refers to out of
+    /// the orphan candidates
+    async fn reconcile_listed_tunnel(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        tunnel_dic_by_id: &mut HashMap<Uuid, Tunnel>,
+        draining_nodes: &[String],
+    ) -> Result<()> {
+        let account_id = self.args.cloudflare_account_id().to_string();
+        let _guard = self
+            .tunnel_locks
+            .lock((cfdt.namespace(), cfdt.name_any()))
+            .await;
+        // 待機中に他のreconcileがtunnelを作成している可能性があるため、最新の状態を取得する
+        let Some(cfdt) =
+            Api::<CloudflaredTunnel>::namespaced(self.client.clone(), &namespace_of(cfdt)?)
+                .get_opt(&cfdt.name_any())
+                .await?
+        else {
+            return Ok(());
+        };
+        // 独自の認証情報を持つtunnelは既定のaccountの一覧に含まれない
+        // 認証情報を読めないtunnelのみを読み飛ばし、orphanとしても扱わない
+        let account = match self.account_of(&cfdt).await {
+            Ok(account) => account,
+            Err(error) => {
+                warn!(
+                    "Credentials of cloudflaredTunnel {} are unavailable: {error}",
+                    cfdt.name_any()
+                );
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    error.reason(),
+                    "Reconcile",
+                    Some(error.to_string()),
+                )
+                .await;
+                for id in tunnel_ids_of(&cfdt) {
+                    tunnel_dic_by_id.remove(&id);
+                }
+                return Ok(());
+            }
+        };
+        // 認証情報の変更で別のaccountに移ったtunnelは、新しいaccountに作り直す
+        let previous_account_id = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.account_id.clone())
+            .filter(|id| id != &account.account_id);
+        let tunnel_id = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.tunnel_id.as_ref())
+            .and_then(|id| Uuid::parse_str(id).ok());
+        if let (Some(previous_account_id), Some(tunnel_id)) = (&previous_account_id, tunnel_id) {
+            // 既定のaccountに残るtunnelはorphanとして削除される
+            let note = if previous_account_id == &account_id {
+                format!("Tunnel {tunnel_id} is left in account {previous_account_id} and deleted as an orphan")
+            } else {
+                format!(
+                    "Tunnel {tunnel_id} is left in account {previous_account_id}, delete it there"
+                )
+            };
+            self.publish_event(
+                &cfdt,
+                EventType::Warning,
+                "AccountChanged",
+                "Reconcile",
+                Some(note),
+            )
+            .await;
+        }
+        let tunnel = match tunnel_id.filter(|_| previous_account_id.is_none()) {
+            Some(id) => match tunnel_dic_by_id.remove(&id) {
+                Some(tunnel) => Some(tunnel),
+                None => {
+                    account
+                        .api
+                        .get_tunnel_opt(account.account_id.clone(), id.as_hyphenated().to_string())
+                        .await?
+                }
+            },
+            None => None,
+        };
+        // 置き換え中のtunnelはorphanとして扱わない
+        for id in replacement_tunnel_ids(&cfdt) {
+            if let Ok(id) = Uuid::parse_str(&id) {
+                tunnel_dic_by_id.remove(&id);
+            }
+        }
+        // 削除中のものはfinalizerで処理する
+        if cfdt.metadata.deletion_timestamp.is_some() {
+            return Ok(());
+        }
+        self.reconcile_tunnel(cfdt, account, tunnel, draining_nodes)
+            .await
+    }
+
+    /// Delete or report the tunnels matching a managed prefix that no CloudflaredTunnel refers
+    /// to, following `--orphan-tunnel-policy`
+    async fn handle_orphan_tunnels(&self, account_id: &str, orphans: Vec<Tunnel>) {
+        let policy = self.args.orphan_tunnel_policy();
+        if policy == OrphanTunnelPolicy::Ignore {
+            // 他のpolicyから切り替えられた場合に以前の値が残らないようにする
+            self.metrics.set_orphan_tunnels(0);
+            return;
+        }
+        // 同じprefixを使う他のcontrollerが作成したtunnelは対象外とする
+        let orphans = orphans
+            .into_iter()
+            .filter(|t| owner_of(t, &self.controller_id) != TunnelOwner::OtherController)
+            .collect::<Vec<_>>();
+        self.metrics.set_orphan_tunnels(orphans.len());
+        // 同じtunnelのEventを毎回記録しないよう、新たにorphanとなったもののみ報告する
+        let newly_reported = {
+            let mut reported = self.orphans_reported.lock().unwrap();
+            reported.retain(|id| orphans.iter().any(|t| t.id == *id));
+            orphans
+                .iter()
+                .filter(|t| reported.insert(t.id))
+                .map(|t| t.id)
+                .collect::<HashSet<_>>()
+        };
+        // 作成直後でstatusが未反映のtunnelを削除しないよう、2回以上のreconcileで猶予期間を超えて
+        // 参照されなかったもののみ削除する
+        let now = Instant::now();
+        let grace_period = self.args.orphan_tunnel_grace_period();
+        let confirmed = {
+            let mut found = self.orphans_found.lock().unwrap();
+            found.retain(|id, _| orphans.iter().any(|t| t.id == *id));
+            orphans
+                .iter()
+                .filter(|t| match found.get(&t.id) {
+                    Some(found_at) => now.duration_since(*found_at) >= grace_period,
+                    None => {
+                        found.insert(t.id, now);
+                        false
+                    }
+                })
+                .map(|t| t.id)
+                .collect::<HashSet<_>>()
+        };
+        for t in orphans {
+            let tunnel_id =
+                t.id.as_hyphenated()
+                    .encode_lower(&mut Uuid::encode_buffer())
+                    .to_string();
+            // 作成者の分からないtunnelは削除せず報告のみとする
+            if owner_of(&t, &self.controller_id) == TunnelOwner::Unknown {
+                if !newly_reported.contains(&t.id) {
+                    continue;
+                }
+                warn!(
+                    "Orphaned cloudflare tunnel without owner found: {} ({tunnel_id})",
+                    t.name
+                );
+                self.publish_orphan_event(format!(
+                    "Tunnel {} ({tunnel_id}) matches a managed prefix but has no ownership metadata, delete it manually when unused",
+                    t.name
+                ))
+                .await;
+                continue;
+            }
+            if policy == OrphanTunnelPolicy::Report {
+                if !newly_reported.contains(&t.id) {
+                    continue;
+                }
+                warn!("Orphaned cloudflare tunnel found: {} ({tunnel_id})", t.name);
+                self.publish_orphan_event(format!(
+                    "Tunnel {} ({tunnel_id}) matches a managed prefix but no CloudflaredTunnel refers to it",
+                    t.name
+                ))
+                .await;
+                continue;
+            }
+            if !confirmed.contains(&t.id) {
+                info!(
+                    "Orphaned cloudflare tunnel is deleted after the grace period: {} ({tunnel_id})",
+                    t.name
+                );
+                continue;
+            }
+            if !t.connections.is_empty() {
+                if let Err(e) = self
+                    .cloudflare_api
+                    .cleanup_tunnel_connections(account_id.to_string(), tunnel_id.clone())
+                    .await
+                {
+                    warn!("Cleanup cloudflare tunnel connections failed: {}", e);
+                }
+            }
+            if let Err(e) = self
+                .cloudflare_api
+                .delete_tunnel(
+                    account_id.to_string(),
+                    tunnel_id,
+                    self.args.tunnel_delete_cascade(),
+                )
+                .await
+            {
+                // tunnel削除の失敗は警告のみとする
+                warn!("Delete cloudflare tunnel failed: {}", e);
+            }
+        }
+    }
+
+    /// Publish an Event about an orphaned tunnel on the tunnel namespace, as no
+    /// CloudflaredTunnel refers to it
+    async fn publish_orphan_event(&self, note: String) {
+        let namespace = Namespace {
+            metadata: ObjectMeta {
+                name: Some(self.args.cloudflare_tunnel_namespace().to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let event = Event {
+            type_: EventType::Warning,
+            reason: "OrphanTunnelFound".to_string(),
+            note: Some(note),
+            action: "Reconcile".to_string(),
+            secondary: None,
+        };
+        if let Err(e) = self
+            .recorder
+            .publish(&event, &namespace.object_ref(&()))
+            .await
+        {
+            warn!("failed to publish event OrphanTunnelFound: {e:?}");
+        }
+    }
+
+    /// Create a tunnel and record it as the tunnel of the CloudflaredTunnel
+    async fn create_tunnel(
+        &self,
+        account: &CloudflareAccount,
+        name: &str,
+        namespace: &str,
+        tunnel_name_prefix: &str,
+        tunnel_secret: &[u8],
+    ) -> Result<Tunnel> {
+        let tunnel = self
+            .new_tunnel(account, name, namespace, tunnel_name_prefix, tunnel_secret)
+            .await?;
+        patch_cloudflaredtunnel_status(&self.client, namespace, name, |status| {
+            status.tunnel_id = Some(tunnel.id.as_hyphenated().to_string());
+            status.account_id = Some(account.account_id.clone());
+        })
+        .await?;
+        Ok(tunnel)
+    }
+
+    /// Create a tunnel owned by the CloudflaredTunnel, without recording it in the status
+    async fn new_tunnel(
+        &self,
+        account: &CloudflareAccount,
+        name: &str,
+        namespace: &str,
+        tunnel_name_prefix: &str,
+        tunnel_secret: &[u8],
+    ) -> Result<Tunnel> {
+        let uid = Uuid::new_v4().as_hyphenated().to_string();
+        let tunnel_name = format!("{tunnel_name_prefix}{uid}");
+        account
+            .api
+            .create_tunnel(
+                account.account_id.clone(),
+                tunnel_name.to_string(),
+                tunnel_secret.to_owned(),
+                tunnel_metadata(&self.controller_id, namespace, name),
+            )
+            .await
+    }
+
+    async fn reconcile_tunnel(
+        &self,
+        cfdt: CloudflaredTunnel,
+        mut account: CloudflareAccount,
+        tunnel: Option<Tunnel>,
+        draining_nodes: &[String],
+    ) -> Result<()> {
+        info!("Reconcile cloudflaredTunnel: {}", cfdt.name_any());
+        let name = cfdt.name_any();
+        let namespace = cfdt.namespace().unwrap();
+        let uid = cfdt.uid().unwrap();
+        let owner_ref = OwnerReference {
+            api_version: CloudflaredTunnel::api_version(&()).to_string(),
+            kind: CloudflaredTunnel::kind(&()).to_string(),
+            name: name.clone(),
+            uid,
+            ..Default::default()
+        };
+
+        // DNS ZoneのリストをCloudflareから取得
+        let zones = self.list_zones(&mut account).await?;
+
+        // CloudflaredTunnel.spec.ingress[].hostnameがどの　DNS Zoneに当てはまるか確認
+        let zone_resolver = zones
+            .iter()
+            .map(|z| (z.name.as_str(), z.id.clone()))
+            .collect::<ZoneResolver<_>>();
+        let mut dns_list = HashSet::new();
+        for ingress in cfdt.spec.expanded_ingress().iter() {
+            // zoneが指定されたhostnameはzoneの検索を行わない
+            let zone_id = match ingress.zone_id {
+                Some(ref zone_id) => Some(zone_id.clone()),
+                None => zone_resolver.resolve(&ingress.hostname).cloned(),
+            };
+            // hostnameがzoneに当てはまらない場合、Eventはreconcileの失敗として記録される
+            let Some(zone_id) = zone_id else {
+                return Err(Error::hostname_zone_not_found(&ingress.hostname));
+            };
+            let zone_name = zones
+                .iter()
+                .find(|z| z.id == zone_id)
+                .map(|z| z.name.as_str());
+            let allowed = match zone_name {
+                Some(zone_name) => self.args.is_zone_allowed(zone_name),
+                // 名前の分からない指定されたzoneは、allowlistが無い場合のみ許可する
+                None => !self.args.has_zone_allowlist(),
+            };
+            if !allowed {
+                return Err(Error::zone_not_allowed(
+                    &ingress.hostname,
+                    zone_name.unwrap_or(&zone_id),
+                ));
+            }
+            dns_list.insert((ingress.hostname.clone(), zone_id));
+        }
+
+        let (tunnel_secret, generated) = self.get_tunnel_secret(&cfdt, owner_ref.clone()).await?;
+
+        let tunnel = match tunnel {
+            // 削除されたsecretを作り直した場合は、既存のtunnelにも新しいsecretを設定する
+            // spec.secret_refのsecretはsync_user_tunnel_secretで反映する
+            // 外部のcloudflaredは新しいsecretを受け取れないため、tunnelのsecretは変更しない
+            Some(tunnel)
+                if generated
+                    && cfdt.spec.secret_ref.is_none()
+                    && cfdt.spec.external_connector.unwrap_or_default() =>
+            {
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    "TunnelSecretMissing",
+                    "RotateSecret",
+                    Some(format!(
+                        "Tunnel secret Secret was missing, kept the secret of tunnel {} as spec.external_connector cannot receive a new one; the generated Secret does not match the tunnel",
+                        tunnel.id
+                    )),
+                )
+                .await;
+                tunnel
+            }
+            Some(tunnel) if generated && cfdt.spec.secret_ref.is_none() => {
+                account
+                    .api
+                    .update_tunnel_secret(
+                        account.account_id.clone(),
+                        tunnel.id.as_hyphenated().to_string(),
+                        &tunnel_secret,
+                    )
+                    .await?;
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    "TunnelSecretRecreated",
+                    "RotateSecret",
+                    Some(format!(
+                        "Tunnel secret Secret was missing, set a new secret on tunnel {}",
+                        tunnel.id
+                    )),
+                )
+                .await;
+                tunnel
+            }
+            Some(tunnel) => tunnel,
+            None => {
+                let tunnel_name_prefix = cfdt
+                    .spec
+                    .tunnel_prefix
+                    .as_deref()
+                    .unwrap_or(self.args.cloudflare_tunnel_prefix());
+                let tunnel = self
+                    .create_tunnel(
+                        &account,
+                        &name,
+                        &namespace,
+                        tunnel_name_prefix,
+                        &tunnel_secret,
+                    )
+                    .await?;
+                self.publish_event(
+                    &cfdt,
+                    EventType::Normal,
+                    "TunnelCreated",
+                    "CreateTunnel",
+                    Some(format!("Created tunnel {}", tunnel.id)),
+                )
+                .await;
+                tunnel
+            }
+        };
+        let tunnel_id = tunnel.id.as_hyphenated().to_string();
+
+        // 期間を過ぎたsecretは更新し、credentialsの書き換えでworkloadを更新する
+        let tunnel_secret = match self
+            .rotate_tunnel_secret(&cfdt, &account, owner_ref.clone(), &tunnel)
+            .await?
+        {
+            Some(rotated) => rotated,
+            None => tunnel_secret,
+        };
+        self.sync_user_tunnel_secret(&cfdt, &account, &tunnel, &tunnel_secret)
+            .await?;
+
+        // DNSが外部で管理される場合は、tunnelを指すCNAMEレコードも作成・削除しない
+        if cfdt.spec.manage_dns.unwrap_or(true) {
+            self.reconcile_dns_records(&cfdt, &account, &zones, &dns_list, &tunnel_id, None)
+                .await?;
+        }
+
+        self.reconcile_worker_routes(&cfdt, &account, &dns_list)
+            .await?;
+        self.reconcile_response_headers(&cfdt, &account, &dns_list)
+            .await?;
+        self.reconcile_source_ranges(&cfdt, &account, &dns_list)
+            .await?;
+        self.reconcile_health_checks(&cfdt, &account, &dns_list)
+            .await?;
+        self.reconcile_access_applications(&cfdt, &account, &owner_ref)
+            .await?;
+
+        let workload_name = format!("{}-{}", name, "cloudflared");
+        // cloudflaredがcluster外で動作する場合はtunnelとDNSレコードのみを管理する
+        if cfdt.spec.external_connector.unwrap_or_default() {
+            delete_owned_workloads(
+                &self.client,
+                &namespace,
+                &workload_name,
+                std::slice::from_ref(&owner_ref),
+            )
+            .await?;
+            // 外部のcloudflaredへ渡すため、configのSecretは作成する
+            let (config_secret_name, _, config_hash) = self
+                .get_tunnel_config(&cfdt, &account, owner_ref.clone(), tunnel, &tunnel_secret)
+                .await?;
+            // 外部のcloudflaredへは新しいtunnelの認証情報を配布できないため、置き換えは行わない
+            let rejected_trigger = cfdt
+                .annotations()
+                .get(REPLACE_TUNNEL_ANNOTATION)
+                .filter(|t| {
+                    cfdt.status
+                        .as_ref()
+                        .and_then(|s| s.last_replacement_trigger.as_ref())
+                        != Some(*t)
+                })
+                .cloned();
+            if let Some(ref trigger) = rejected_trigger {
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    "TunnelReplacementRejected",
+                    "ReplaceTunnel",
+                    Some(format!(
+                        "Tunnel replacement {trigger} is not supported with spec.external_connector"
+                    )),
+                )
+                .await;
+            }
+            patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+                if rejected_trigger.is_some() {
+                    status.last_replacement_trigger = rejected_trigger.clone();
+                }
+                status.account_id = Some(account.account_id.clone());
+                status.config_hash = Some(config_hash);
+                status.replicas = None;
+                status.selector = None;
+                status.ready_replicas = None;
+                status.available_replicas = None;
+                status.set_condition(
+                    CONDITION_DEPLOYMENT_AVAILABLE,
+                    false,
+                    "ExternalConnector",
+                    Some(format!(
+                        "cloudflared runs outside of the cluster with the config of secret {config_secret_name}"
+                    )),
+                );
+            })
+            .await?;
+            return Ok(());
+        }
+
+        let (tunnel_config_secret_name, secret_updated, config_hash) = self
+            .get_tunnel_config(&cfdt, &account, owner_ref.clone(), tunnel, &tunnel_secret)
+            .await?;
+
+        // 許可されていないregistryのimageは適用しない
+        let image_allowed = cfdt.spec.image.as_deref().map_or(true, |image| {
+            is_image_allowed(image, self.args.allowed_image_registries())
+        });
+        patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+            if image_allowed {
+                status.set_condition(CONDITION_IMAGE_ALLOWED, true, "RegistryAllowed", None)
+            } else {
+                status.set_condition(
+                    CONDITION_IMAGE_ALLOWED,
+                    false,
+                    "RegistryNotAllowed",
+                    cfdt.spec
+                        .image
+                        .as_ref()
+                        .map(|image| format!("image {image} is not in the allowed registries")),
+                )
+            }
+        })
+        .await?;
+        if !image_allowed {
+            self.publish_event(
+                &cfdt,
+                EventType::Warning,
+                "ImageNotAllowed",
+                "ApplyWorkload",
+                cfdt.spec
+                    .image
+                    .as_ref()
+                    .map(|image| format!("image {image} is not in the allowed registries")),
+            )
+            .await;
+            warn!(
+                "Image of cloudflaredTunnel {name} is not allowed: {:?}",
+                cfdt.spec.image
+            );
+            return Ok(());
+        }
+
+        let replicas = match cfdt.spec.replicas {
+            Some(replicas) => replicas,
+            None => self.args.deployment_replicas().try_into()?,
+        };
+        let workload_params = WorkloadParams {
+            name: &workload_name,
+            namespace: &namespace,
+            tunnel_config_secret_name: &tunnel_config_secret_name,
+            tunnel_id: &tunnel_id,
+            replicas,
+            cfdt: &cfdt.spec,
+            owner_ref: Some(vec![owner_ref.clone()]),
+            draining_nodes,
+            progress_deadline_seconds: self.args.progress_deadline_seconds(),
+        };
+        let workload = patch_workload(&self.client, &workload_params).await?;
+        let rolled_out = !workload.updated
+            && !secret_updated
+            && workload.rollout_complete
+            && workload.available_replicas >= workload.replicas;
+
+        // rolloutの失敗は状態が変化した時のみEventを記録する
+        let was_degraded = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.condition(CONDITION_DEGRADED))
+            .is_some_and(|c| c.status == "True");
+        if let Some(ref message) = workload.rollout_failure {
+            if !was_degraded {
+                self.publish_event(
+                    &cfdt,
+                    EventType::Warning,
+                    "ProgressDeadlineExceeded",
+                    "Rollout",
+                    Some(format!("Rollout of {workload_name} failed: {message}")),
+                )
+                .await;
+            }
+        }
+
+        patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+            let available = workload.available_replicas >= workload.replicas;
+            status.replicas = Some(workload.current_replicas);
+            status.selector = Some(workload_params.pod_selector());
+            status.ready_replicas = Some(workload.ready_replicas);
+            status.available_replicas = Some(workload.available_replicas);
+            status.set_condition(
+                CONDITION_DEPLOYMENT_AVAILABLE,
+                available,
+                if available {
+                    "ReplicasAvailable"
+                } else {
+                    "ReplicasUnavailable"
+                },
+                Some(format!(
+                    "{}/{} replicas available",
+                    workload.available_replicas, workload.replicas
+                )),
+            );
+            match workload.rollout_failure {
+                Some(ref message) => status.set_condition(
+                    CONDITION_DEGRADED,
+                    true,
+                    "ProgressDeadlineExceeded",
+                    Some(message.clone()),
+                ),
+                None => status.set_condition(CONDITION_DEGRADED, false, "RolloutProgressing", None),
+            }
+        })
+        .await?;
+
+        if secret_updated {
+            self.publish_event(
+                &cfdt,
+                EventType::Normal,
+                "ConfigSecretUpdated",
+                "UpdateSecret",
+                Some(format!(
+                    "Updated tunnel config secret {tunnel_config_secret_name}"
+                )),
+            )
+            .await;
+        }
+
+        // secretが更新されている場合はrestartを行う
+        if !workload.updated && secret_updated {
+            restart_workload(
+                &self.client,
+                cfdt.spec.workload_kind.unwrap_or_default(),
+                &workload_name,
+                &namespace,
+            )
+            .await?;
+        } else if rolled_out {
+            // rolloutが完了し、全podが現在のsecretを参照している場合のみ以前のsecretを削除する
+            self.prune_superseded_secrets(&cfdt, &owner_ref).await?;
+        }
+
+        self.reconcile_replacement(
+            &cfdt,
+            &account,
+            &owner_ref,
+            &dns_list,
+            &workload_params,
+            rolled_out,
+        )
+        .await?;
+
+        patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+            let now = Utc::now();
+            let stale = status
+                .last_reconcile_time
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .and_then(|t| (now - t.with_timezone(&Utc)).to_std().ok())
+                .map_or(true, |elapsed| elapsed >= LAST_RECONCILE_TIME_RESOLUTION);
+            if stale || status.config_hash.as_ref() != Some(&config_hash) {
+                status.last_reconcile_time = Some(now.to_rfc3339_opts(SecondsFormat::Secs, true));
+            }
+            status.config_hash = Some(config_hash);
+            status.account_id = Some(account.account_id.clone());
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replace the tunnel without downtime once the `replace-tunnel` annotation changes: a new
+    /// tunnel is served by a second workload, the CNAME records are switched to it once the
+    /// workload is available, and the previous tunnel and the second workload are deleted after
+    /// the workload of the CloudflaredTunnel rolled onto the new tunnel
+    async fn reconcile_replacement(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        owner_ref: &OwnerReference,
+        dns_list: &HashSet<(String, String)>,
+        workload_params: &WorkloadParams<'_>,
+        rolled_out: bool,
+    ) -> Result<()> {
+        let status = cfdt.status.clone().unwrap_or_default();
+        let ns = namespace_of(cfdt)?;
+        let name = cfdt.name_any();
+        let tunnel_id = workload_params.tunnel_id;
+        let replacement = match status.replacement {
+            Some(replacement) => replacement,
+            None => {
+                let Some(trigger) = cfdt
+                    .annotations()
+                    .get(REPLACE_TUNNEL_ANNOTATION)
+                    .filter(|t| status.last_replacement_trigger.as_ref() != Some(*t))
+                else {
+                    return Ok(());
+                };
+                let new_ref = || {
+                    Uuid::new_v4()
+                        .as_hyphenated()
+                        .encode_lower(&mut Uuid::encode_buffer())
+                        .to_string()
+                };
+                let tunnel_secret_ref = new_ref();
+                let tunnel_secret = generate_tunnel_secret()?;
+                self.apply_tunnel_secret(
+                    &ns,
+                    &tunnel_secret_ref,
+                    &tunnel_secret,
+                    owner_ref.clone(),
+                )
+                .await?;
+                let tunnel_name_prefix = cfdt
+                    .spec
+                    .tunnel_prefix
+                    .as_deref()
+                    .unwrap_or(self.args.cloudflare_tunnel_prefix());
+                // 新しいtunnelのworkloadが利用可能になるまでstatus.tunnel_idは切り替えない
+                let tunnel = self
+                    .new_tunnel(account, &name, &ns, tunnel_name_prefix, &tunnel_secret)
+                    .await?;
+                let replacement = CloudflaredTunnelReplacement {
+                    trigger: trigger.clone(),
+                    tunnel_id: tunnel.id.as_hyphenated().to_string(),
+                    tunnel_secret_ref,
+                    config_secret_ref: new_ref(),
+                    previous_tunnel_id: None,
+                };
+                patch_cloudflaredtunnel_status(&self.client, &ns, &name, |status| {
+                    status.replacement = Some(replacement.clone());
+                })
+                .await?;
+                self.publish_event(
+                    cfdt,
+                    EventType::Normal,
+                    "TunnelReplacementStarted",
+                    "ReplaceTunnel",
+                    Some(format!(
+                        "Created tunnel {} to replace tunnel {tunnel_id}",
+                        replacement.tunnel_id
+                    )),
+                )
+                .await;
+                replacement
+            }
+        };
+
+        let workload_name = format!("{name}-cloudflared-replacement");
+        let Some(ref previous_tunnel_id) = replacement.previous_tunnel_id else {
+            // 新しいtunnelのworkloadが利用可能になってからDNSレコードを切り替える
+            let tunnel_secret = Api::<Secret>::namespaced(self.client.clone(), &ns)
+                .get(&replacement.tunnel_secret_ref)
+                .await?
+                .data
+                .and_then(|mut data| data.remove(TUNNEL_SECRET_KEY))
+                .ok_or_else(|| {
+                    Error::secret_key_missing(&replacement.tunnel_secret_ref, TUNNEL_SECRET_KEY)
+                })?
+                .0;
+            let config_data = tunnel_config_data(
+                &account.account_id,
+                &replacement.tunnel_id,
+                &tunnel_secret,
+                &cfdt.spec,
+            )?;
+            patch_opaque_secret_string(
+                &self.client,
+                &replacement.config_secret_ref,
+                &ns,
+                config_data,
+                Some(vec![owner_ref.clone()]),
+            )
+            .await?;
+            let workload = patch_workload(
+                &self.client,
+                &WorkloadParams {
+                    name: &workload_name,
+                    tunnel_config_secret_name: &replacement.config_secret_ref,
+                    tunnel_id: &replacement.tunnel_id,
+                    owner_ref: Some(vec![owner_ref.clone()]),
+                    ..*workload_params
+                },
+            )
+            .await?;
+            if workload.available_replicas < workload.replicas {
+                return Ok(());
+            }
+
+            if cfdt.spec.manage_dns.unwrap_or(true) {
+                self.reconcile_dns_records(
+                    cfdt,
+                    account,
+                    &[],
+                    dns_list,
+                    &replacement.tunnel_id,
+                    Some(tunnel_id),
+                )
+                .await?;
+            }
+            patch_cloudflaredtunnel_status(&self.client, &ns, &name, |status| {
+                // 以前のtunnel secretはrollout後に削除する
+                let previous_secret_ref = status.tunnel_secret_ref.take();
+                status.supersede_secret_ref(previous_secret_ref);
+                status.tunnel_id = Some(replacement.tunnel_id.clone());
+                status.tunnel_secret_ref = Some(replacement.tunnel_secret_ref.clone());
+                status.replacement = Some(CloudflaredTunnelReplacement {
+                    previous_tunnel_id: Some(tunnel_id.to_string()),
+                    ..replacement.clone()
+                });
+            })
+            .await?;
+            self.publish_event(
+                cfdt,
+                EventType::Normal,
+                "TunnelSwitched",
+                "ReplaceTunnel",
+                Some(format!(
+                    "Switched the DNS records from tunnel {tunnel_id} to tunnel {}",
+                    replacement.tunnel_id
+                )),
+            )
+            .await;
+            return Ok(());
+        };
+
+        // workloadが新しいtunnelへrolloutした後、以前のtunnelと一時的なworkloadを削除する
+        if !rolled_out {
+            return Ok(());
+        }
+        delete_owned_workloads(
+            &self.client,
+            &ns,
+            &workload_name,
+            std::slice::from_ref(owner_ref),
+        )
+        .await?;
+        if cfdt.spec.manage_dns.unwrap_or(true) {
+            let zone_ids = dns_list
+                .iter()
+                .map(|(_, zone_id)| zone_id.clone())
+                .collect::<HashSet<_>>();
+            for zone_id in zone_ids.iter() {
+                self.delete_tunnel_cnames(account.zone_api(zone_id), zone_id, previous_tunnel_id)
+                    .await?;
+            }
+        }
+        account
+            .api
+            .cleanup_tunnel_connections(account.account_id.clone(), previous_tunnel_id.clone())
+            .await?;
+        account
+            .api
+            .delete_tunnel(account.account_id.clone(), previous_tunnel_id.clone(), true)
+            .await?;
+        patch_cloudflaredtunnel_status(&self.client, &ns, &name, |status| {
+            status.supersede_secret_ref(Some(replacement.config_secret_ref.clone()));
+            status.replacement = None;
+            status.last_replacement_trigger = Some(replacement.trigger.clone());
+        })
+        .await?;
+        self.publish_event(
+            cfdt,
+            EventType::Normal,
+            "TunnelReplaced",
+            "ReplaceTunnel",
+            Some(format!(
+                "Deleted tunnel {previous_tunnel_id} replaced by tunnel {}",
+                replacement.tunnel_id
+            )),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Keep the tunnel CNAME records of the hostnames, deleting those no longer served. The
+    /// records of `replaced_tunnel_id` are switched to the tunnel
+    async fn reconcile_dns_records(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        zones: &[Zone],
+        dns_list: &HashSet<(String, String)>,
+        tunnel_id: &str,
+        replaced_tunnel_id: Option<&str>,
+    ) -> Result<()> {
+        // 前回のreconcileでtunnelのレコードが無かったzoneは、hostnameが無ければ取得しない
+        let key = (cfdt.namespace(), cfdt.name_any());
+        let cached_zones = self.managed_zones.lock().unwrap().get(&key).cloned();
+        // 指定されたzoneや、指定が外れる前にレコードを作成したzoneはlist_zoneで列挙されない場合も取得する
+        let recorded_zone_ids = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.dns_record_zones.clone())
+            .unwrap_or_default();
+        let listed_zone_ids = zones
+            .iter()
+            .map(|z| z.id.clone())
+            .filter(|zone_id| {
+                cached_zones
+                    .as_ref()
+                    .map_or(true, |cached| cached.contains(zone_id))
+            })
+            .chain(dns_list.iter().map(|(_, zone_id)| zone_id.clone()))
+            .chain(recorded_zone_ids)
+            .collect::<HashSet<_>>();
+        self.metrics.add_dns_list_zones_skipped(
+            zones
+                .iter()
+                .filter(|z| !listed_zone_ids.contains(&z.id))
+                .count(),
+        );
+
+        // ZoneIDからDNSレコードを引く辞書を作成、キャッシュに無いゾーンのみ一覧を取得する
+        let zone_dns_list = try_join_all(listed_zone_ids.iter().map(|zone_id| async {
+            let api = account.zone_api(zone_id);
+            let dns_records = match api.cached_dns(zone_id) {
+                Some(dns_records) => dns_records,
+                None => {
+                    self.limit_dns_list(api.refresh_dns(zone_id.clone()))
+                        .await?
+                }
+            };
+            Result::<_, Error>::Ok((zone_id.clone(), dns_records))
+        }))
+        .await?
+        .into_iter()
+        .collect::<HashMap<String, Arc<Vec<DnsRecord>>>>();
+
+        // {tunnelid}.cfargotunnel.comのCNAMEレコードリストを作成する
+        let cname_content = self.args.tunnel_cname(tunnel_id);
+        let replaced_cname_content = replaced_tunnel_id.map(|id| self.args.tunnel_cname(id));
+        let mut current_cname_list = zone_dns_list
+            .iter()
+            .flat_map(|(_, rec)| {
+                rec.iter().flat_map(|rec| match rec.content {
+                    DnsContent::CNAME { ref content } if content.as_str() == cname_content => {
+                        Some((rec.id.clone(), rec.zone_id.clone()))
+                    }
+                    _ => None,
+                })
+            })
+            .collect::<HashSet<_>>();
+
+        // 移行用に既存のA/AAAAレコードを許容するhostname
+        let expanded_ingress = cfdt.spec.expanded_ingress();
+        let allow_existing_dns = expanded_ingress
+            .iter()
+            .filter_map(|i| Some((i.hostname.as_str(), i.allow_existing_dns?)))
+            .fold(HashMap::new(), |mut acc, (hostname, policy)| {
+                // 指定が異なる場合は既存のレコードを削除しない側に倒す
+                let entry = acc.entry(hostname).or_insert(policy);
+                if policy == CloudflaredTunnelExistingDns::Keep {
+                    *entry = policy;
+                }
+                acc
+            });
+
+        // 指定が異なる場合はproxyする側に倒す
+        let dns_proxied =
+            expanded_ingress
+                .iter()
+                .fold(HashMap::<&str, bool>::new(), |mut acc, i| {
+                    *acc.entry(i.hostname.as_str()).or_default() |= i.proxied.unwrap_or(true);
+                    acc
+                });
+        let record_settings = self.dns_record_settings(cfdt, &namespace_of(cfdt)?);
+        if !DnsRecordSettings::is_valid_ttl(record_settings.ttl) {
+            return Err(Error::invalid_dns_record_ttl(
+                format!("{}/{}", namespace_of(cfdt)?, cfdt.name_any()),
+                record_settings.ttl,
+            ));
+        }
+        let record_settings = |hostname: &str| DnsRecordSettings {
+            proxied: dns_proxied.get(hostname).copied().unwrap_or(true),
+            ..record_settings.clone()
+        };
+
+        // TXTレコードで所有が記録されたhostnameとそのTXTレコードのID
+        let owned = self.txt_registry.as_ref().map(|registry| {
+            zone_dns_list
+                .iter()
+                .map(|(zone_id, dns_records)| (zone_id.as_str(), registry.owned(dns_records)))
+                .collect::<HashMap<_, _>>()
+        });
+        let owned_txt_id = |zone_id: &str, hostname: &str| {
+            owned
+                .as_ref()
+                .and_then(|owned| owned.get(zone_id))
+                .and_then(|owned| owned.get(hostname))
+        };
+        let (namespace, name) = (namespace_of(cfdt)?, cfdt.name_any());
+        let txt_record = |registry: &TxtRegistry, hostname: &str| {
+            (
+                registry.record_name(hostname),
+                registry.record_content(&namespace, &name),
+            )
+        };
+
+        // markerの付いたtunnelのCNAMEレコード、markerの導入前に作成されたレコードには付け直す
+        let marked = match self.args.dns_record_marker() {
+            Some(marker) => {
+                let cname_zone_ids = current_cname_list
+                    .iter()
+                    .map(|(_, zone_id)| zone_id.clone())
+                    .collect::<HashSet<_>>();
+                Some(
+                    try_join_all(cname_zone_ids.iter().map(|zone_id| {
+                        self.limit_dns_list(account.zone_api(zone_id).list_marked_dns_cname(
+                            zone_id.clone(),
+                            tunnel_id.to_string(),
+                            marker.to_string(),
+                        ))
+                    }))
+                    .await?
+                    .into_iter()
+                    .flatten()
+                    .map(|r| (r.id, r.zone_id))
+                    .collect::<HashSet<_>>(),
+                )
+            }
+            None => None,
+        };
+
+        // {tunnelid}.cfargotunnel.com以外のCNAMEレコード、Aレコード・AAAAレコードが無いことを確認する
+        // 変更はzoneごとにまとめて適用し、成功したzoneの変更のみイベントを発行する
+        let mut zone_changes = HashMap::<&str, (DnsCnameBatch, Vec<DnsRecordEvent>)>::new();
+        for (ref hostname, ref zone_id) in dns_list {
+            let mut existing_records = Vec::new();
+            if let Some(dns_record) = zone_dns_list
+                .get(zone_id)
+                .ok_or_else(|| unreachable!())
+                .and_then(|dns_records| {
+                    dns_records
+                        .iter()
+                        .filter(|dns_record| dns_record.name.as_str() == hostname.as_str())
+                        .try_fold(None, |acc, dns_record| match &dns_record.content {
+                            DnsContent::CNAME { content }
+                                if content.as_str() == cname_content
+                                    || Some(content) == replaced_cname_content.as_ref() =>
+                            {
+                                Ok(Some(dns_record))
+                            }
+                            DnsContent::A { .. } | DnsContent::AAAA { .. }
+                                if allow_existing_dns.contains_key(hostname.as_str()) =>
+                            {
+                                existing_records.push(dns_record);
+                                Ok(acc)
+                            }
+                            DnsContent::A { .. }
+                            | DnsContent::AAAA { .. }
+                            | DnsContent::CNAME { .. } => {
+                                Err(Error::conflicting_dns_record(hostname.as_str()))
+                            }
+                            _ => Ok(acc),
+                        })
+                })?
+            {
+                let record_key = (dns_record.id.clone(), dns_record.zone_id.clone());
+                let unmarked = current_cname_list.remove(&record_key)
+                    && marked.as_ref().is_some_and(|m| !m.contains(&record_key));
+                if let Some(ref registry) = self.txt_registry {
+                    // 所有の記録が無いtunnelのCNAMEレコードは引き継ぐ
+                    if owned_txt_id(zone_id, hostname).is_none() {
+                        let (batch, events) = zone_changes.entry(zone_id).or_default();
+                        events.push((
+                            "DnsRecordAdopted",
+                            "CreateDnsRecord",
+                            format!("Created TXT registry record of {hostname}"),
+                        ));
+                        batch.txt_creates.push(txt_record(registry, hostname));
+                    }
+                }
+                let settings = record_settings(hostname);
+                let switched = !matches!(
+                    dns_record.content,
+                    DnsContent::CNAME { ref content } if content.as_str() == cname_content
+                );
+                // commentとtagは取得できないため、proxiedとTTLの差分とmarkerの有無のみ検出する
+                if switched
+                    || unmarked
+                    || dns_record.proxied != settings.proxied
+                    || !settings.ttl_matches(dns_record.ttl)
+                {
+                    let (batch, events) = zone_changes.entry(zone_id).or_default();
+                    events.push(if switched {
+                        (
+                            "DnsRecordSwitched",
+                            "UpdateDnsRecord",
+                            format!("Switched CNAME record {hostname} to tunnel {tunnel_id}"),
+                        )
+                    } else if unmarked {
+                        (
+                            "DnsRecordMarked",
+                            "UpdateDnsRecord",
+                            format!("Marked CNAME record {hostname} with the comment marker"),
+                        )
+                    } else {
+                        (
+                            "DnsRecordUpdated",
+                            "UpdateDnsRecord",
+                            format!(
+                                "Updated CNAME record {hostname} to proxied: {}, ttl: {}",
+                                settings.proxied, settings.ttl
+                            ),
+                        )
+                    });
+                    batch
+                        .updates
+                        .push((dns_record.id.clone(), hostname.clone(), settings));
+                }
+            } else if !existing_records.is_empty()
+                && allow_existing_dns.get(hostname.as_str())
+                    == Some(&CloudflaredTunnelExistingDns::Keep)
+            {
+                self.publish_event(
+                    cfdt,
+                    EventType::Normal,
+                    "ExistingDnsRecordKept",
+                    "CreateDnsRecord",
+                    Some(format!(
+                        "Kept existing records of {hostname}, CNAME record is not created"
+                    )),
+                )
+                .await;
+            } else {
+                // 同じバッチ内で削除が作成より先に適用される
+                let (batch, events) = zone_changes.entry(zone_id).or_default();
+                for dns_record in existing_records {
+                    events.push((
+                        "DnsRecordReplaced",
+                        "DeleteDnsRecord",
+                        format!(
+                            "Deleted record {} of {hostname} to replace it with the tunnel CNAME",
+                            dns_record.id
+                        ),
+                    ));
+                    batch.deletes.push(dns_record.id.clone());
+                }
+                events.push((
+                    "DnsRecordCreated",
+                    "CreateDnsRecord",
+                    format!("Created CNAME record {hostname}"),
+                ));
+                batch
+                    .creates
+                    .push((hostname.clone(), record_settings(hostname)));
+                // CNAMEレコードのみ外部で削除された場合はTXTレコードが残っている
+                if let Some(ref registry) = self.txt_registry {
+                    if owned_txt_id(zone_id, hostname).is_none() {
+                        batch.txt_creates.push(txt_record(registry, hostname));
+                    }
+                }
+            }
+        }
+        if let Some(ref marked) = marked {
+            // markerの無いレコードは手動などで作成されたものとして残す
+            current_cname_list.retain(|record| {
+                let keep = marked.contains(record);
+                if !keep {
+                    info!("Skip CNAME record {} without the marker", record.0);
+                }
+                keep
+            });
+        }
+        for (dns_id, zone_id) in current_cname_list.iter() {
+            let mut txt_id = None;
+            if self.txt_registry.is_some() {
+                // 所有が記録されていないレコードは手動などで作成されたものとして残す
+                let hostname = zone_dns_list
+                    .get(zone_id)
+                    .and_then(|dns_records| dns_records.iter().find(|r| &r.id == dns_id))
+                    .map(|r| r.name.as_str());
+                match hostname.and_then(|hostname| owned_txt_id(zone_id, hostname)) {
+                    Some(id) => txt_id = Some(id.clone()),
+                    None => {
+                        info!("Skip CNAME record {dns_id} not owned in the TXT registry");
+                        continue;
+                    }
+                }
+            }
+            let (batch, events) = zone_changes.entry(zone_id).or_default();
+            events.push((
+                "DnsRecordDeleted",
+                "DeleteDnsRecord",
+                format!("Deleted CNAME record {dns_id}"),
+            ));
+            batch.deletes.push(dns_id.clone());
+            batch.deletes.extend(txt_id);
+        }
+        let results = join_all(zone_changes.into_iter().map(
+            |(zone_id, (batch, events))| async move {
+                let result = account
+                    .zone_api(zone_id)
+                    .batch_dns_cname(zone_id.to_string(), tunnel_id.to_string(), &batch)
+                    .await;
+                (result, events)
+            },
+        ))
+        .await;
+        let mut result = Ok(());
+        for (zone_result, events) in results {
+            match zone_result {
+                Ok(()) => {
+                    for (reason, action, note) in events {
+                        self.publish_event(cfdt, EventType::Normal, reason, action, Some(note))
+                            .await;
+                    }
+                }
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result?;
+        // tunnelのCNAMEレコードはhostnameのzoneにのみ残る
+        let record_zone_ids = dns_list
+            .iter()
+            .map(|(_, zone_id)| zone_id.clone())
+            .collect::<BTreeSet<_>>();
+        patch_cloudflaredtunnel_status(&self.client, &namespace, &name, |status| {
+            status.dns_record_zones = Some(record_zone_ids.iter().cloned().collect());
+        })
+        .await?;
+        self.managed_zones
+            .lock()
+            .unwrap()
+            .insert(key, record_zone_ids.into_iter().collect());
+        Ok(())
+    }
+
+    /// Delete the tunnel CNAME records of a zone, only those carrying `--dns-record-marker` and
+    /// owned in the TXT registry when they are enabled, and return the deleted records
+    async fn delete_tunnel_cnames(
+        &self,
+        api: &CloudflareApi,
+        zone_id: &str,
+        tunnel_id: &str,
+    ) -> Result<Vec<DnsRecord>> {
+        let dns_records = match self.args.dns_record_marker() {
+            Some(marker) => {
+                self.limit_dns_list(api.list_marked_dns_cname(
+                    zone_id.to_string(),
+                    tunnel_id.to_string(),
+                    marker.to_string(),
+                ))
+                .await?
+            }
+            None => {
+                self.limit_dns_list(api.list_dns_cname(zone_id.to_string(), tunnel_id.to_string()))
+                    .await?
+            }
+        };
+        let owned = match self.txt_registry {
+            Some(ref registry) if !dns_records.is_empty() => Some(
+                registry.owned(
+                    &self
+                        .limit_dns_list(api.list_dns(zone_id.to_string()))
+                        .await?,
+                ),
+            ),
+            _ => None,
+        };
+        let mut deleted = Vec::new();
+        for d in dns_records {
+            let txt_id = match owned.as_ref().map(|owned| owned.get(&d.name)) {
+                Some(None) => {
+                    info!("Skip CNAME record {} not owned in the TXT registry", d.name);
+                    continue;
+                }
+                Some(Some(txt_id)) => Some(txt_id.clone()),
+                None => None,
+            };
+            api.delete_dns_cname(d.zone_id.clone(), d.id.clone())
+                .await?;
+            if let Some(txt_id) = txt_id {
+                api.delete_dns_cname(d.zone_id.clone(), txt_id).await?;
+            }
+            deleted.push(d);
+        }
+        Ok(deleted)
+    }
+
+    /// Experimental: keep the Workers routes `{hostname}/*` in sync with `spec.ingress[].worker_script`
+    async fn reconcile_worker_routes(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        dns_list: &HashSet<(String, String)>,
+    ) -> Result<()> {
+        let hostname_zones = dns_list.iter().cloned().collect::<HashMap<_, _>>();
+        let mut desired = BTreeMap::new();
+        for ingress in cfdt.spec.expanded_ingress() {
+            let Some(ref script) = ingress.worker_script else {
+                continue;
+            };
+            let pattern = format!("{}/*", ingress.hostname);
+            let zone_id = hostname_zones
+                .get(&ingress.hostname)
+                .cloned()
+                .ok_or_else(|| Error::hostname_zone_not_found(&ingress.hostname))?;
+            match desired.insert(pattern, (zone_id, script.clone())) {
+                // 同一hostnameに異なるscriptは指定できない
+                Some((_, other)) if &other != script => {
+                    return Err(Error::conflicting_worker_script(&ingress.hostname))
+                }
+                _ => {}
+            }
+        }
+
+        let owned = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.worker_routes.clone())
+            .unwrap_or_default();
+        if desired.is_empty() && owned.is_empty() {
+            return Ok(());
+        }
+
+        // 外部で削除されたrouteを検出するため、現在のrouteを取得する
+        let zone_ids = desired
+            .values()
+            .map(|(zone_id, _)| zone_id.clone())
+            .chain(owned.iter().map(|r| r.zone_id.clone()))
+            .collect::<HashSet<_>>();
+        let existing = try_join_all(zone_ids.into_iter().map(|zone_id| async move {
+            let routes = account
+                .zone_api(&zone_id)
+                .list_worker_routes(zone_id.clone())
+                .await?;
+            Result::<_, Error>::Ok(routes.into_iter().map(move |r| (zone_id.clone(), r)))
+        }))
+        .await?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        let existing_ids = existing
+            .iter()
+            .map(|(_, r)| r.id.clone())
+            .collect::<HashSet<_>>();
+
+        let mut routes = Vec::new();
+        for route in owned {
+            if !existing_ids.contains(&route.id) {
+                continue;
+            }
+            let keep = desired
+                .get(&route.pattern)
+                .is_some_and(|(zone_id, script)| {
+                    zone_id == &route.zone_id && script == &route.script
+                });
+            if keep {
+                desired.remove(&route.pattern);
+                routes.push(route);
+            } else {
+                account
+                    .zone_api(&route.zone_id)
+                    .delete_worker_route(route.zone_id, route.id)
+                    .await?;
+            }
+        }
+        for (pattern, (zone_id, script)) in desired {
+            // statusへの記録前に中断された作成のrouteは、patternとscriptから引き継ぐ
+            if let Some((_, adopted)) = existing.iter().find(|(z, r)| {
+                z == &zone_id && r.pattern == pattern && r.script.as_ref() == Some(&script)
+            }) {
+                routes.push(CloudflaredTunnelWorkerRoute {
+                    zone_id,
+                    id: adopted.id.clone(),
+                    pattern,
+                    script,
+                });
+                continue;
+            }
+            let created = account
+                .zone_api(&zone_id)
+                .create_worker_route(zone_id.clone(), pattern, script.clone())
+                .await?;
+            self.publish_event(
+                cfdt,
+                EventType::Normal,
+                "WorkerRouteCreated",
+                "CreateWorkerRoute",
+                Some(format!(
+                    "Created worker route {} for script {script}",
+                    created.pattern
+                )),
+            )
+            .await;
+            routes.push(CloudflaredTunnelWorkerRoute {
+                zone_id,
+                id: created.id,
+                pattern: created.pattern,
+                script,
+            });
+        }
+
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| status.worker_routes = Some(routes),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Keep the Cloudflare health checks in sync with `spec.ingress[].health_check`, reporting
+    /// their state in the status and the `OriginHealthy` condition
+    async fn reconcile_health_checks(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        dns_list: &HashSet<(String, String)>,
+    ) -> Result<()> {
+        let hostname_zones = dns_list.iter().cloned().collect::<HashMap<_, _>>();
+        let mut desired = BTreeMap::new();
+        for ingress in cfdt.spec.expanded_ingress() {
+            let Some(ref health_check) = ingress.health_check else {
+                continue;
+            };
+            // ワイルドカードのhostnameには接続先が定まらず、TCPのserviceはHTTPSで確認できない
+            if ingress.hostname.starts_with("*.") || ingress.is_tcp() {
+                continue;
+            }
+            let zone_id = hostname_zones
+                .get(&ingress.hostname)
+                .cloned()
+                .ok_or_else(|| Error::hostname_zone_not_found(&ingress.hostname))?;
+            let path = health_check.path.clone().unwrap_or_else(|| "/".to_string());
+            desired
+                .entry(ingress.hostname.clone())
+                .or_insert((zone_id, path));
+        }
+
+        let owned = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.health_checks.clone())
+            .unwrap_or_default();
+        if desired.is_empty() && owned.is_empty() {
+            return Ok(());
+        }
+
+        // 外部で削除されたhealth checkを検出し、状態を更新するため現在のものを取得する
+        let zone_ids = desired
+            .values()
+            .map(|(zone_id, _)| zone_id.clone())
+            .chain(owned.iter().map(|c| c.zone_id.clone()))
+            .collect::<HashSet<_>>();
+        let existing = try_join_all(
+            zone_ids
+                .into_iter()
+                .map(|zone_id| account.zone_api(&zone_id).list_health_checks(zone_id)),
+        )
+        .await?
+        .into_iter()
+        .flatten()
+        .map(|c| (c.id.clone(), c))
+        .collect::<HashMap<_, _>>();
+
+        let owned_ids = owned.iter().map(|c| c.id.clone()).collect::<HashSet<_>>();
+        let mut checks = Vec::new();
+        for check in owned {
+            let Some(current) = existing.get(&check.id) else {
+                continue;
+            };
+            let keep = desired
+                .get(&check.hostname)
+                .is_some_and(|(zone_id, path)| zone_id == &check.zone_id && path == &check.path);
+            if keep {
+                desired.remove(&check.hostname);
+                checks.push(CloudflaredTunnelHealthCheckStatus {
+                    status: current.status.clone(),
+                    failure_reason: current.failure_reason.clone().filter(|r| !r.is_empty()),
+                    ..check
+                });
+            } else {
+                account
+                    .zone_api(&check.zone_id)
+                    .delete_health_check(check.zone_id, check.id)
+                    .await?;
+            }
+        }
+        let description = format!("{}/{}", namespace_of(cfdt)?, cfdt.name_any());
+        for (hostname, (zone_id, path)) in desired {
+            // statusへの記録前に中断された作成のhealth checkは、hostnameとdescriptionから引き継ぐ
+            let adopted = existing.values().find(|c| {
+                c.address == hostname
+                    && c.description.as_ref() == Some(&description)
+                    && c.http_config.as_ref().and_then(|h| h.path.as_ref()) == Some(&path)
+                    && !owned_ids.contains(&c.id)
+            });
+            if let Some(adopted) = adopted {
+                checks.push(CloudflaredTunnelHealthCheckStatus {
+                    zone_id,
+                    id: adopted.id.clone(),
+                    hostname,
+                    path,
+                    status: adopted.status.clone(),
+                    failure_reason: adopted.failure_reason.clone().filter(|r| !r.is_empty()),
+                });
+                continue;
+            }
+            let created = account
+                .zone_api(&zone_id)
+                .create_health_check(
+                    zone_id.clone(),
+                    hostname.replace('.', "-"),
+                    hostname.clone(),
+                    path.clone(),
+                    description.clone(),
+                )
+                .await?;
+            self.publish_event(
+                cfdt,
+                EventType::Normal,
+                "HealthCheckCreated",
+                "CreateHealthCheck",
+                Some(format!("Created health check of {hostname}{path}")),
+            )
+            .await;
+            checks.push(CloudflaredTunnelHealthCheckStatus {
+                zone_id,
+                id: created.id,
+                hostname,
+                path,
+                status: created.status,
+                failure_reason: created.failure_reason.filter(|r| !r.is_empty()),
+            });
+        }
+
+        // 未判定のhealth checkは異常として扱わない
+        let unhealthy = checks
+            .iter()
+            .filter(|c| matches!(c.status.as_deref(), Some("unhealthy")))
+            .map(|c| match c.failure_reason {
+                Some(ref reason) => format!("{}{}: {reason}", c.hostname, c.path),
+                None => format!("{}{}", c.hostname, c.path),
+            })
+            .collect::<Vec<_>>();
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| {
+                if checks.is_empty() {
+                    // health checkが無くなった場合は条件も取り除く
+                    if let Some(ref mut conditions) = status.conditions {
+                        conditions.retain(|c| c.type_ != CONDITION_ORIGIN_HEALTHY);
+                    }
+                } else if unhealthy.is_empty() {
+                    status.set_condition(
+                        CONDITION_ORIGIN_HEALTHY,
+                        true,
+                        "HealthChecksHealthy",
+                        None,
+                    );
+                } else {
+                    status.set_condition(
+                        CONDITION_ORIGIN_HEALTHY,
+                        false,
+                        "HealthChecksUnhealthy",
+                        Some(unhealthy.join(", ")),
+                    );
+                }
+                status.health_checks = Some(checks);
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Keep the Access applications of the account in sync with
+    /// `spec.ingress[].access_application`, together with their service tokens
+    async fn reconcile_access_applications(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        owner_ref: &OwnerReference,
+    ) -> Result<()> {
+        let mut desired = BTreeMap::new();
+        for ingress in cfdt.spec.expanded_ingress() {
+            let Some(ref application) = ingress.access_application else {
+                continue;
+            };
+            match desired.insert(ingress.hostname.clone(), application.clone()) {
+                // 同一hostnameに異なるpolicyは指定できない
+                Some(other) if &other != application => {
+                    return Err(Error::conflicting_access_application(&ingress.hostname))
+                }
+                _ => {}
+            }
+        }
+
+        let owned = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.access_applications.clone())
+            .unwrap_or_default();
+        if desired.is_empty() && owned.is_empty() {
+            return Ok(());
+        }
+
+        // 外部で削除されたapplicationとservice tokenを検出するため、現在のものを取得する
+        let existing_ids = account
+            .api
+            .list_access_applications(account.account_id.clone())
+            .await?
+            .into_iter()
+            .map(|a| a.id)
+            .collect::<HashSet<_>>();
+        let uses_tokens = desired.values().any(|a| a.service_token_secret.is_some())
+            || owned.iter().any(|s| s.service_token_id.is_some());
+        let existing_tokens = if uses_tokens {
+            account
+                .api
+                .list_access_service_tokens(account.account_id.clone())
+                .await?
+        } else {
+            Vec::new()
+        };
+        let existing_token_ids = existing_tokens
+            .iter()
+            .map(|t| t.id.clone())
+            .collect::<HashSet<_>>();
+
+        let mut owned = owned
+            .into_iter()
+            .map(|s| (s.hostname.clone(), s))
+            .collect::<HashMap<_, _>>();
+        let mut applications = Vec::new();
+        // policyから外したservice tokenはapplicationの更新後に削除する
+        let mut unused_token_ids = Vec::new();
+        let mut unused_secrets = Vec::new();
+        for (hostname, application) in desired {
+            let current = owned.remove(&hostname);
+            let token_id = current
+                .as_ref()
+                .and_then(|s| s.service_token_id.clone())
+                .filter(|id| existing_token_ids.contains(id));
+            let old_secret = current
+                .as_ref()
+                .and_then(|s| s.application.service_token_secret.clone());
+            if old_secret.is_some() && old_secret != application.service_token_secret {
+                unused_secrets.extend(old_secret);
+            }
+            let token_id = match application.service_token_secret {
+                Some(ref secret_name) => Some(
+                    self.ensure_service_token(
+                        cfdt,
+                        account,
+                        owner_ref,
+                        &hostname,
+                        secret_name,
+                        token_id,
+                        &existing_tokens,
+                    )
+                    .await?,
+                ),
+                None => {
+                    unused_token_ids.extend(token_id);
+                    None
+                }
+            };
+
+            let settings = access_application_settings(&application, token_id.clone());
+            let (id, aud) = match current.filter(|s| existing_ids.contains(&s.id)) {
+                Some(status)
+                    if status.application == application && status.service_token_id == token_id =>
+                {
+                    (status.id, status.aud)
+                }
+                Some(status) => {
+                    let updated = account
+                        .api
+                        .update_access_application(
+                            account.account_id.clone(),
+                            status.id,
+                            hostname.clone(),
+                            &settings,
+                        )
+                        .await?;
+                    (updated.id, updated.aud)
+                }
+                None => {
+                    let created = account
+                        .api
+                        .create_access_application(
+                            account.account_id.clone(),
+                            hostname.clone(),
+                            &settings,
+                        )
+                        .await?;
+                    self.publish_event(
+                        cfdt,
+                        EventType::Normal,
+                        "AccessApplicationCreated",
+                        "CreateAccessApplication",
+                        Some(format!("Created access application of {hostname}")),
+                    )
+                    .await;
+                    // 後続の失敗で作成したapplicationを見失わないよう、作成ごとにstatusへ記録する
+                    let created_status = CloudflaredTunnelAccessApplicationStatus {
+                        id: created.id.clone(),
+                        hostname: hostname.clone(),
+                        aud: created.aud.clone(),
+                        application: application.clone(),
+                        service_token_id: token_id.clone(),
+                    };
+                    patch_cloudflaredtunnel_status(
+                        &self.client,
+                        &namespace_of(cfdt)?,
+                        &cfdt.name_any(),
+                        |status| {
+                            let applications =
+                                status.access_applications.get_or_insert_with(Vec::new);
+                            applications.retain(|a| a.hostname != hostname);
+                            applications.push(created_status.clone());
+                        },
+                    )
+                    .await?;
+                    (created.id, created.aud)
+                }
+            };
+            applications.push(CloudflaredTunnelAccessApplicationStatus {
+                id,
+                hostname,
+                aud,
+                application,
+                service_token_id: token_id,
+            });
+        }
+        for status in owned.into_values() {
+            if existing_ids.contains(&status.id) {
+                account
+                    .api
+                    .delete_access_application(account.account_id.clone(), status.id)
+                    .await?;
+            }
+            unused_token_ids.extend(
+                status
+                    .service_token_id
+                    .filter(|id| existing_token_ids.contains(id)),
+            );
+            unused_secrets.extend(status.application.service_token_secret);
+        }
+        for token_id in unused_token_ids {
+            account
+                .api
+                .delete_access_service_token(account.account_id.clone(), token_id)
+                .await?;
+        }
+
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| {
+                status.access_applications = Some(applications);
+                // 不要になったSecretはrollout後に削除する
+                for secret in unused_secrets {
+                    status.supersede_secret_ref(Some(secret));
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Service token of the hostname whose credentials are kept in the Secret, created or
+    /// given a new client secret when the token or the Secret has gone. A token missing from
+    /// the status is adopted by the client id kept in the Secret
+    #[allow(clippy::too_many_arguments)]
+    async fn ensure_service_token(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        owner_ref: &OwnerReference,
+        hostname: &str,
+        secret_name: &str,
+        token_id: Option<String>,
+        existing_tokens: &[ServiceToken],
+    ) -> Result<String> {
+        let namespace = namespace_of(cfdt)?;
+        // 利用者の同名のSecretを上書きや削除の対象にしない
+        if !self
+            .is_secret_adoptable(&namespace, secret_name, owner_ref)
+            .await?
+        {
+            return Err(Error::secret_not_owned(
+                format!("{namespace}/{secret_name}"),
+                cfdt.name_any(),
+            ));
+        }
+        let api = Api::<Secret>::namespaced(self.client.clone(), &namespace);
+        let client_id = api
+            .get_opt(secret_name)
+            .await?
+            .and_then(|secret| secret.data)
+            .filter(|data| data.contains_key(SERVICE_TOKEN_CLIENT_SECRET_KEY))
+            .and_then(|data| {
+                data.get(SERVICE_TOKEN_CLIENT_ID_KEY)
+                    .and_then(|id| String::from_utf8(id.0.clone()).ok())
+            });
+        let has_credentials = client_id.is_some();
+        // statusへの記録前に中断された作成のtokenは、Secretのclient idから引き継ぐ
+        let token_id = token_id.or_else(|| {
+            existing_tokens
+                .iter()
+                .find(|t| Some(&t.client_id) == client_id.as_ref())
+                .map(|t| t.id.clone())
+        });
+        let (token, reason) = match token_id {
+            Some(id) if has_credentials => return Ok(id),
+            // client secretは再取得できないため、Secretが失われた場合は再発行する
+            Some(id) => (
+                account
+                    .api
+                    .rotate_access_service_token(account.account_id.clone(), id)
+                    .await?,
+                "ServiceTokenRotated",
+            ),
+            None => (
+                account
+                    .api
+                    .create_access_service_token(account.account_id.clone(), hostname.to_string())
+                    .await?,
+                "ServiceTokenCreated",
+            ),
+        };
+        patch_opaque_secret_string(
+            &self.client,
+            secret_name,
+            &namespace,
+            BTreeMap::from([
+                (
+                    SERVICE_TOKEN_CLIENT_ID_KEY.to_string(),
+                    token.client_id.clone(),
+                ),
+                (
+                    SERVICE_TOKEN_CLIENT_SECRET_KEY.to_string(),
+                    token.client_secret.clone().unwrap_or_default(),
+                ),
+            ]),
+            Some(vec![owner_ref.clone()]),
+        )
+        .await?;
+        self.publish_event(
+            cfdt,
+            EventType::Normal,
+            reason,
+            "ApplyServiceToken",
+            Some(format!(
+                "Stored the service token of {hostname} in secret {secret_name}"
+            )),
+        )
+        .await;
+        Ok(token.id)
+    }
+
+    /// Keep the response header Transform Rules in sync with `spec.response_headers`
+    async fn reconcile_response_headers(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        dns_list: &HashSet<(String, String)>,
+    ) -> Result<()> {
+        let hostname_zones = dns_list.iter().cloned().collect::<HashMap<_, _>>();
+        let ref_prefix = rule_ref_prefix(&namespace_of(cfdt)?, &cfdt.name_any());
+        let mut desired = BTreeMap::<String, Vec<serde_json::Value>>::new();
+        for headers in cfdt.spec.response_headers.iter().flatten() {
+            let zone_id = hostname_zones
+                .get(&headers.hostname)
+                .cloned()
+                .ok_or_else(|| Error::hostname_zone_not_found(&headers.hostname))?;
+            desired
+                .entry(zone_id)
+                .or_default()
+                .push(response_header_rule(&ref_prefix, headers));
+        }
+
+        let owned = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.response_header_zones.clone())
+            .unwrap_or_default();
+        if desired.is_empty() && owned.is_empty() {
+            return Ok(());
+        }
+
+        // 不要になったzoneのruleは削除する
+        for zone_id in owned {
+            if !desired.contains_key(&zone_id) {
+                self.replace_response_header_rules(cfdt, account, zone_id, Vec::new())
+                    .await?;
+            }
+        }
+        for (zone_id, rules) in desired.iter() {
+            self.replace_response_header_rules(cfdt, account, zone_id.clone(), rules.clone())
+                .await?;
+        }
+
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| {
+                status.response_header_zones = Some(desired.into_keys().collect());
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the rules managed for the CloudflaredTunnel in the zone entry point ruleset,
+    /// keeping the other rules unchanged
+    async fn replace_response_header_rules(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        zone_id: String,
+        rules: Vec<serde_json::Value>,
+    ) -> Result<()> {
+        let ref_prefix = rule_ref_prefix(&namespace_of(cfdt)?, &cfdt.name_any());
+        let (managed, others): (Vec<_>, Vec<_>) = account
+            .zone_api(&zone_id)
+            .list_response_header_rules(zone_id.clone())
+            .await?
+            .iter()
+            .map(normalize_rule)
+            .partition(|rule| is_managed_rule(rule, &ref_prefix));
+        if managed == rules {
+            return Ok(());
+        }
+
+        account
+            .zone_api(&zone_id)
+            .update_response_header_rules(
+                zone_id.clone(),
+                others.into_iter().chain(rules).collect(),
+            )
+            .await?;
+        self.publish_event(
+            cfdt,
+            EventType::Normal,
+            "ResponseHeaderRulesUpdated",
+            "UpdateTransformRules",
+            Some(format!(
+                "Updated response header transform rules in zone {zone_id}"
+            )),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// TTL, comment and tags of the CNAME records of the CloudflaredTunnel, `spec.dns_record`
+    /// taking precedence over the controller arguments
+    fn dns_record_settings(&self, cfdt: &CloudflaredTunnel, namespace: &str) -> DnsRecordSettings {
+        let spec = cfdt.spec.dns_record.clone().unwrap_or_default();
+        let render = |template: &str| {
+            template
+                .replace("{namespace}", namespace)
+                .replace("{name}", &cfdt.name_any())
+        };
+        let comment = spec
+            .comment
+            .as_deref()
+            .or(self.args.dns_record_comment())
+            .map(render);
+        DnsRecordSettings {
+            ttl: spec.ttl.unwrap_or(self.args.dns_record_ttl()),
+            // markerはcommentの先頭に付ける
+            comment: match (comment, self.args.dns_record_marker()) {
+                (Some(comment), Some(marker)) if !comment.contains(marker) => {
+                    Some(format!("{marker} {comment}"))
+                }
+                (None, Some(marker)) => Some(marker.to_string()),
+                (comment, _) => comment,
+            },
+            tags: spec
+                .tags
+                .as_deref()
+                .unwrap_or(self.args.dns_record_tags())
+                .iter()
+                .map(|tag| render(tag))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Keep the WAF custom rules in sync with `spec.ingress[].source_ranges`
+    async fn reconcile_source_ranges(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        dns_list: &HashSet<(String, String)>,
+    ) -> Result<()> {
+        let hostname_zones = dns_list.iter().cloned().collect::<HashMap<_, _>>();
+        let ref_prefix = rule_ref_prefix(&namespace_of(cfdt)?, &cfdt.name_any());
+        let mut source_ranges = BTreeMap::new();
+        let mut proxied = HashMap::<String, bool>::new();
+        for ingress in cfdt.spec.expanded_ingress() {
+            *proxied.entry(ingress.hostname.clone()).or_default() |=
+                ingress.proxied.unwrap_or(true);
+            let mut ranges = ingress.source_ranges.clone();
+            if let Some(ref mut ranges) = ranges {
+                ranges.sort();
+                ranges.dedup();
+            }
+            match source_ranges.insert(ingress.hostname.clone(), ranges.clone()) {
+                // ruleはhostname単位のため、pathごとに異なる制限は指定できない
+                Some(other) if other != ranges => {
+                    return Err(Error::conflicting_source_ranges(&ingress.hostname))
+                }
+                _ => {}
+            }
+        }
+        let mut desired = BTreeMap::<String, Vec<serde_json::Value>>::new();
+        for (hostname, ranges) in source_ranges {
+            let Some(ranges) = ranges else {
+                continue;
+            };
+            // proxyされないhostnameにはruleが適用されない
+            if proxied.get(&hostname) == Some(&false) {
+                let error = Error::source_ranges_not_proxied(&hostname);
+                self.publish_event(
+                    cfdt,
+                    EventType::Warning,
+                    error.reason(),
+                    "UpdateFirewallRules",
+                    Some(error.to_string()),
+                )
+                .await;
+            }
+            let zone_id = hostname_zones
+                .get(&hostname)
+                .cloned()
+                .ok_or_else(|| Error::hostname_zone_not_found(&hostname))?;
+            desired.entry(zone_id).or_default().push(source_range_rule(
+                &ref_prefix,
+                &hostname,
+                &ranges,
+            ));
+        }
+
+        let owned = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.firewall_rule_zones.clone())
+            .unwrap_or_default();
+        if desired.is_empty() && owned.is_empty() {
+            return Ok(());
+        }
+
+        // 不要になったzoneのruleは削除する
+        for zone_id in owned {
+            if !desired.contains_key(&zone_id) {
+                self.replace_firewall_rules(cfdt, account, zone_id, Vec::new())
+                    .await?;
+            }
+        }
+        for (zone_id, rules) in desired.iter() {
+            self.replace_firewall_rules(cfdt, account, zone_id.clone(), rules.clone())
+                .await?;
+        }
+
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| {
+                status.firewall_rule_zones = Some(desired.into_keys().collect());
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the WAF custom rules managed for the CloudflaredTunnel in the zone entry point
+    /// ruleset, keeping the other rules unchanged
+    async fn replace_firewall_rules(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        zone_id: String,
+        rules: Vec<serde_json::Value>,
+    ) -> Result<()> {
+        let ref_prefix = rule_ref_prefix(&namespace_of(cfdt)?, &cfdt.name_any());
+        let (managed, others): (Vec<_>, Vec<_>) = account
+            .zone_api(&zone_id)
+            .list_firewall_custom_rules(zone_id.clone())
+            .await?
+            .iter()
+            .map(normalize_rule)
+            .partition(|rule| is_managed_rule(rule, &ref_prefix));
+        if managed == rules {
+            return Ok(());
+        }
+
+        account
+            .zone_api(&zone_id)
+            .update_firewall_custom_rules(
+                zone_id.clone(),
+                others.into_iter().chain(rules).collect(),
+            )
+            .await?;
+        self.publish_event(
+            cfdt,
+            EventType::Normal,
+            "FirewallRulesUpdated",
+            "UpdateFirewallRules",
+            Some(format!("Updated WAF custom rules in zone {zone_id}")),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Tunnel secret of the CloudflaredTunnel and whether it was generated, as its Secret did not
+    /// exist
+    async fn get_tunnel_secret(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        owner_ref: OwnerReference,
+    ) -> Result<(Vec<u8>, bool)> {
+        let spec_ref = cfdt.spec.secret_ref.as_ref();
+        let status_ref = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.tunnel_secret_ref.as_ref());
+        let ns = namespace_of(cfdt)?;
+        let api = Api::<Secret>::namespaced(self.client.clone(), &ns);
+
+        let secret_ref = match (spec_ref, status_ref) {
+            (None, Some(st)) => st.to_string(),
+            (Some(sp), Some(st)) if sp == st => st.to_string(),
+            (sp, st) => {
+                let secret_ref = if let Some(sp) = sp {
+                    sp.to_string()
+                } else {
+                    Uuid::new_v4()
+                        .as_hyphenated()
+                        .encode_lower(&mut Uuid::encode_buffer())
+                        .to_string()
+                };
+
+                // statusに新しいsecret_refを設定し、以前のsecretはrollout後に削除する
+                patch_cloudflaredtunnel_status(&self.client, &ns, &cfdt.name_any(), |status| {
+                    status.supersede_secret_ref(st.cloned());
+                    status.tunnel_secret_ref = Some(secret_ref.clone())
+                })
                 .await?;
                 secret_ref
             }
         };
 
-        let secret = if let Some(mut data) = api
+        let (secret, generated) = if let Some(mut data) = api
             .get_opt(&secret_ref)
             .await?
             .and_then(|secret| secret.data)
         {
-            data.remove(TUNNEL_SECRET_KEY)
-                .ok_or_else(Error::illegal_document)?
-                .0
+            let secret = data
+                .remove(TUNNEL_SECRET_KEY)
+                .ok_or_else(|| Error::secret_key_missing(&secret_ref, TUNNEL_SECRET_KEY))?
+                .0;
+            (secret, false)
         } else {
-            let mut raw_data = vec![0u8; 32];
-            tokio::task::spawn_blocking(rand::rngs::StdRng::from_entropy)
-                .await?
-                .try_fill(raw_data.as_mut_slice())?;
-            let data =
-                BTreeMap::from([(TUNNEL_SECRET_KEY.to_string(), ByteString(raw_data.clone()))]);
-            api.patch(
-                &secret_ref,
-                &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
-                &Patch::Apply(Secret {
-                    data: Some(data),
-                    type_: Some("Opaque".to_string()),
-                    metadata: ObjectMeta {
-                        owner_references: Some(vec![owner_ref.clone()]),
-                        name: Some(secret_ref.clone()),
-                        ..Default::default()
-                    },
+            let raw_data = generate_tunnel_secret()?;
+            self.apply_tunnel_secret(&ns, &secret_ref, &raw_data, owner_ref)
+                .await?;
+            self.publish_event(
+                cfdt,
+                EventType::Normal,
+                "TunnelSecretCreated",
+                "CreateSecret",
+                Some(format!("Created tunnel secret {secret_ref}")),
+            )
+            .await;
+            (raw_data, true)
+        };
+
+        if secret.len() < TUNNEL_SECRET_LEN {
+            return Err(Error::secret_too_short(&secret_ref, secret.len()));
+        };
+
+        Ok((secret, generated))
+    }
+
+    /// Write the tunnel secret to a Secret owned by the CloudflaredTunnel
+    async fn apply_tunnel_secret(
+        &self,
+        namespace: &str,
+        secret_ref: &str,
+        tunnel_secret: &[u8],
+        owner_ref: OwnerReference,
+    ) -> Result<()> {
+        let api = Api::<Secret>::namespaced(self.client.clone(), namespace);
+        let data = BTreeMap::from([(
+            TUNNEL_SECRET_KEY.to_string(),
+            ByteString(tunnel_secret.to_vec()),
+        )]);
+        api.patch(
+            secret_ref,
+            &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+            &Patch::Apply(Secret {
+                data: Some(data),
+                type_: Some("Opaque".to_string()),
+                metadata: ObjectMeta {
+                    owner_references: Some(vec![owner_ref]),
+                    name: Some(secret_ref.to_string()),
                     ..Default::default()
-                }),
+                },
+                ..Default::default()
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Rotate the generated tunnel secret once `spec.secret_rotation_period` has passed since
+    /// the last rotation or the creation of the tunnel, returning the new secret. A rotation
+    /// recorded in `status.pending_tunnel_secret_ref` is finished with its secret first
+    async fn rotate_tunnel_secret(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        owner_ref: OwnerReference,
+        tunnel: &Tunnel,
+    ) -> Result<Option<Vec<u8>>> {
+        let ns = namespace_of(cfdt)?;
+        let name = cfdt.name_any();
+        let now = Utc::now();
+        let pending = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.pending_tunnel_secret_ref.clone());
+        if pending.is_none() {
+            let Some(period) = cfdt.spec.secret_rotation_period else {
+                return Ok(None);
+            };
+            // 指定されたsecretは利用者が管理するためローテーションしない
+            if cfdt.spec.secret_ref.is_some() {
+                return Ok(None);
+            }
+            // 外部のcloudflaredへは新しいsecretを配布できない
+            if cfdt.spec.external_connector.unwrap_or_default() {
+                return Err(Error::secret_rotation_unsupported(format!("{ns}/{name}")));
+            }
+            if period < MIN_SECRET_ROTATION_PERIOD {
+                return Err(Error::secret_rotation_period_too_short(
+                    format!("{ns}/{name}"),
+                    period,
+                    MIN_SECRET_ROTATION_PERIOD,
+                ));
+            }
+            let last_rotation = cfdt
+                .status
+                .as_ref()
+                .and_then(|s| s.last_secret_rotation_time.as_deref())
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map_or(tunnel.created_at, |t| t.with_timezone(&Utc));
+            let due = (now - last_rotation)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= Duration::from_secs(period));
+            if !due {
+                return Ok(None);
+            }
+        }
+
+        let previous = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.tunnel_secret_ref.clone());
+        let pending_secret = match pending {
+            Some(ref secret_ref) => Api::<Secret>::namespaced(self.client.clone(), &ns)
+                .get_opt(secret_ref)
+                .await?
+                .and_then(|secret| secret.data)
+                .and_then(|mut data| data.remove(TUNNEL_SECRET_KEY))
+                .map(|secret| (secret_ref.clone(), secret.0))
+                .filter(|(_, secret)| secret.len() >= TUNNEL_SECRET_LEN),
+            None => None,
+        };
+        let (secret_ref, tunnel_secret) = match pending_secret {
+            // 中断されたローテーションは同じsecretで再開する
+            Some(pending_secret) => pending_secret,
+            None => {
+                let secret_ref = pending.unwrap_or_else(|| {
+                    Uuid::new_v4()
+                        .as_hyphenated()
+                        .encode_lower(&mut Uuid::encode_buffer())
+                        .to_string()
+                });
+                let tunnel_secret = generate_tunnel_secret()?;
+                // 新しいsecretを保存し、tunnelを更新する前にローテーションを記録する
+                self.apply_tunnel_secret(&ns, &secret_ref, &tunnel_secret, owner_ref)
+                    .await?;
+                patch_cloudflaredtunnel_status(&self.client, &ns, &name, |status| {
+                    status.pending_tunnel_secret_ref = Some(secret_ref.clone());
+                })
+                .await?;
+                (secret_ref, tunnel_secret)
+            }
+        };
+        account
+            .api
+            .update_tunnel_secret(
+                account.account_id.clone(),
+                tunnel.id.as_hyphenated().to_string(),
+                &tunnel_secret,
             )
             .await?;
-            raw_data
-        };
+        patch_cloudflaredtunnel_status(&self.client, &ns, &name, |status| {
+            // 以前のsecretはrollout後に削除する
+            status.supersede_secret_ref(previous);
+            status.tunnel_secret_ref = Some(secret_ref.clone());
+            status.pending_tunnel_secret_ref = None;
+            status.last_secret_rotation_time = Some(now.to_rfc3339_opts(SecondsFormat::Secs, true));
+        })
+        .await?;
+        self.publish_event(
+            cfdt,
+            EventType::Normal,
+            "TunnelSecretRotated",
+            "RotateSecret",
+            Some(format!("Rotated tunnel secret to {secret_ref}")),
+        )
+        .await;
+        Ok(Some(tunnel_secret))
+    }
 
-        if secret.len() < 32 {
-            return Err(Error::illegal_document());
+    /// Apply the tunnel secret of `spec.secret_ref` to the tunnel once its content changes, as
+    /// the user rotates it instead of the controller
+    async fn sync_user_tunnel_secret(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
+        tunnel: &Tunnel,
+        tunnel_secret: &[u8],
+    ) -> Result<()> {
+        let Some(ref secret_ref) = cfdt.spec.secret_ref else {
+            return Ok(());
         };
+        let hash = format!("sha256:{:x}", Sha256::digest(tunnel_secret));
+        let applied = cfdt
+            .status
+            .as_ref()
+            .and_then(|s| s.tunnel_secret_hash.as_deref());
+        if applied == Some(hash.as_str()) {
+            return Ok(());
+        }
+        // 記録の無いsecretは作成時と同じ内容か分からないため、tunnelへ反映し直す
+        account
+            .api
+            .update_tunnel_secret(
+                account.account_id.clone(),
+                tunnel.id.as_hyphenated().to_string(),
+                tunnel_secret,
+            )
+            .await?;
+        let rotated = applied.is_some();
+        patch_cloudflaredtunnel_status(
+            &self.client,
+            &namespace_of(cfdt)?,
+            &cfdt.name_any(),
+            |status| status.tunnel_secret_hash = Some(hash.clone()),
+        )
+        .await?;
+        if rotated {
+            self.publish_event(
+                cfdt,
+                EventType::Normal,
+                "TunnelSecretUpdated",
+                "UpdateSecret",
+                Some(format!("Applied the rotated tunnel secret of {secret_ref}")),
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    /// The Secret does not exist yet or is owned by the CloudflaredTunnel
+    async fn is_secret_adoptable(
+        &self,
+        namespace: &str,
+        name: &str,
+        owner_ref: &OwnerReference,
+    ) -> Result<bool> {
+        let api = Api::<Secret>::namespaced(self.client.clone(), namespace);
+        Ok(api.get_metadata_opt(name).await?.map_or(true, |secret| {
+            secret
+                .owner_references()
+                .iter()
+                .any(|o| o.uid == owner_ref.uid)
+        }))
+    }
 
-        Ok(secret)
+    /// Delete the superseded Secrets owned by the CloudflaredTunnel once the workload has
+    /// rolled onto the current ones
+    async fn prune_superseded_secrets(
+        &self,
+        cfdt: &CloudflaredTunnel,
+        owner_ref: &OwnerReference,
+    ) -> Result<()> {
+        let Some(status) = cfdt.status.as_ref() else {
+            return Ok(());
+        };
+        let superseded = status.superseded_secret_refs.clone().unwrap_or_default();
+        if superseded.is_empty() {
+            return Ok(());
+        }
+        let ns = namespace_of(cfdt)?;
+        let api = Api::<Secret>::namespaced(self.client.clone(), &ns);
+        for secret_ref in superseded.iter() {
+            // 現在参照中のsecretは削除しない
+            if Some(secret_ref) == status.config_secret_ref.as_ref()
+                || Some(secret_ref) == status.tunnel_secret_ref.as_ref()
+            {
+                continue;
+            }
+            let owned = api
+                .get_metadata_opt(secret_ref)
+                .await?
+                .is_some_and(|secret| {
+                    secret
+                        .owner_references()
+                        .iter()
+                        .any(|o| o.uid == owner_ref.uid)
+                });
+            if owned {
+                api.delete(secret_ref, &DeleteParams::background()).await?;
+                self.publish_event(
+                    cfdt,
+                    EventType::Normal,
+                    "SecretPruned",
+                    "DeleteSecret",
+                    Some(format!("Deleted superseded secret {secret_ref}")),
+                )
+                .await;
+            }
+        }
+        patch_cloudflaredtunnel_status(&self.client, &ns, &cfdt.name_any(), |status| {
+            if let Some(refs) = status.superseded_secret_refs.as_mut() {
+                refs.retain(|r| !superseded.contains(r));
+            }
+        })
+        .await?;
+        Ok(())
     }
 
     async fn get_tunnel_config(
         &self,
         cfdt: &CloudflaredTunnel,
+        account: &CloudflareAccount,
         owner_ref: OwnerReference,
         tunnel: Tunnel,
         tunnel_secret: &Vec<u8>,
-    ) -> Result<(String, bool)> {
+    ) -> Result<(String, bool, String)> {
         let tunnel_id = tunnel.id.as_hyphenated().to_string();
-        let ns = cfdt.namespace().ok_or_else(Error::illegal_document)?;
-
-        let credential = cfd_config::Credentials {
-            account_tag: self.args.cloudflare_account_id().to_string(),
-            tunnel_secret: base64::engine::general_purpose::STANDARD
-                .encode(tunnel_secret.as_slice()),
-            tunnel_id: tunnel_id.clone(),
-        };
-        let credential_filename = format!("{tunnel_id}.json");
-
-        let credential_string = serde_json::to_string(&credential)?;
-        let config = cfd_config::Config {
-            tunnel: tunnel_id.clone(),
-            credentials_file: Some(format!("/etc/cloudflared/{}", credential_filename)),
-            origin_request: cfdt.spec.origin_request.as_ref().cloned().map(Into::into),
-            ingress: cfdt
-                .spec
-                .ingress
-                .as_ref()
-                .iter()
-                .flat_map(|x| x.iter().cloned().map(Into::into))
-                .chain([cfd_config::Ingress {
-                    hostname: None,
-                    service: cfdt.spec.default_ingress_service.clone(),
-                    path: None,
-                    origin_request: None,
-                }])
-                .collect(),
-        };
-        let config_string = serde_yaml::to_string(&config)?;
-        let secret_data = BTreeMap::from([
-            (credential_filename, credential_string),
-            (CFD_CONFIG_FILENAME.to_string(), config_string),
-        ]);
+        let ns = namespace_of(cfdt)?;
+
+        let secret_data =
+            tunnel_config_data(&account.account_id, &tunnel_id, tunnel_secret, &cfdt.spec)?;
+        let config_hash = format!(
+            "sha256:{:x}",
+            Sha256::digest(secret_data[CFD_CONFIG_FILENAME].as_bytes())
+        );
 
-        let config_ref = if let Some(config_ref) = cfdt
+        let current_ref = cfdt
             .status
             .as_ref()
-            .and_then(|s| s.config_secret_ref.as_ref())
-        {
-            config_ref.to_string()
-        } else {
-            let config_ref = Uuid::new_v4()
-                .as_hyphenated()
-                .encode_lower(&mut Uuid::encode_buffer())
-                .to_string();
-
-            // statusに新しいconfig_refを設定
-            patch_cloudflaredtunnel_status(&self.client, &ns, &cfdt.name_any(), |status| {
-                status.config_secret_ref = Some(config_ref.clone())
-            })
-            .await?;
-            config_ref
+            .and_then(|s| s.config_secret_ref.clone());
+        // 他のリソースが所有するsecretは上書きせず、新しいsecretに切り替える
+        let adoptable = match current_ref {
+            Some(ref config_ref) => {
+                self.is_secret_adoptable(&ns, config_ref, &owner_ref)
+                    .await?
+            }
+            None => false,
+        };
+        let config_ref = match current_ref {
+            Some(config_ref) if adoptable => config_ref,
+            previous => {
+                let config_ref = Uuid::new_v4()
+                    .as_hyphenated()
+                    .encode_lower(&mut Uuid::encode_buffer())
+                    .to_string();
+
+                // statusに新しいconfig_refを設定
+                patch_cloudflaredtunnel_status(&self.client, &ns, &cfdt.name_any(), |status| {
+                    status.supersede_secret_ref(previous);
+                    status.config_secret_ref = Some(config_ref.clone())
+                })
+                .await?;
+                config_ref
+            }
         };
 
         let secret_updated = patch_opaque_secret_string(
@@ -518,6 +3952,6 @@ impl Context {
         )
         .await?;
 
-        Ok((config_ref, secret_updated))
+        Ok((config_ref, secret_updated, config_hash))
     }
 }
@@ -0,0 +1,61 @@
+use serde_json::json;
+
+use super::{
+    cf_api::AccessApplicationSettings, customresource::CloudflaredTunnelAccessApplication,
+};
+
+/// Settings of the Access application, allowing anyone matching one of the emails, email
+/// domains or groups, and the service token
+pub(super) fn access_application_settings(
+    application: &CloudflaredTunnelAccessApplication,
+    service_token_id: Option<String>,
+) -> AccessApplicationSettings {
+    let emails = application
+        .emails
+        .iter()
+        .flatten()
+        .map(|email| json!({ "email": { "email": email } }));
+    let email_domains = application
+        .email_domains
+        .iter()
+        .flatten()
+        .map(|domain| json!({ "email_domain": { "domain": domain } }));
+    let groups = application
+        .groups
+        .iter()
+        .flatten()
+        .map(|id| json!({ "group": { "id": id } }));
+    AccessApplicationSettings {
+        session_duration: application.session_duration.clone(),
+        identity_providers: application.identity_providers.clone().unwrap_or_default(),
+        include: emails.chain(email_domains).chain(groups).collect(),
+        service_token_id,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn include_rules() {
+        let settings = access_application_settings(
+            &CloudflaredTunnelAccessApplication {
+                emails: Some(vec!["user@example.com".to_string()]),
+                email_domains: Some(vec!["example.net".to_string()]),
+                groups: Some(vec!["00000000-0000-0000-0000-000000000001".to_string()]),
+                ..Default::default()
+            },
+            None,
+        );
+        assert_eq!(
+            vec![
+                json!({ "email": { "email": "user@example.com" } }),
+                json!({ "email_domain": { "domain": "example.net" } }),
+                json!({ "group": { "id": "00000000-0000-0000-0000-000000000001" } }),
+            ],
+            settings.include
+        );
+        assert!(settings.identity_providers.is_empty());
+    }
+}
@@ -1,40 +1,325 @@
-use std::sync::Arc;
+mod access_apps;
+mod cfd_tunnel;
+mod dns_cache;
+mod dns_records;
+mod healthchecks;
+mod rate_limit;
+mod retry;
+mod rulesets;
+mod service_tokens;
+mod teamnet_routes;
+mod user_tokens;
+mod virtual_networks;
+mod workers;
+
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
 
 use cloudflare::{
     endpoints::{
         cfd_tunnel::Tunnel,
-        dns::{DeleteDnsRecordResponse, DnsRecord},
+        dns::{DeleteDnsRecordResponse, DnsContent, DnsRecord},
         zone::Zone,
     },
-    framework::{async_api::Client as HttpApiClient, response::ApiFailure},
+    framework::{
+        async_api::Client as HttpApiClient,
+        endpoint::Endpoint,
+        response::{ApiFailure, ApiResponse, ApiResult},
+    },
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use self::dns_cache::DnsRecordCache;
+pub(super) use self::{
+    access_apps::AccessApplication, cfd_tunnel::TunnelConnector, healthchecks::HealthCheck,
+    rate_limit::RateLimiter, retry::RetryPolicy, service_tokens::ServiceToken,
+    teamnet_routes::TunnelRoute, user_tokens::TokenStatus, virtual_networks::VirtualNetwork,
+    workers::WorkerRoute,
+};
+use crate::{
+    cli::{tunnel_cname, DEFAULT_TUNNEL_CNAME_DOMAIN},
+    metrics::Metrics,
+    Error, Result,
 };
-use tracing::info;
 
-use crate::{Error, Result};
+/// Items requested per page of the list endpoints
+const DEFAULT_PER_PAGE: u32 = 100;
+/// Largest page the zones endpoint accepts
+const MAX_ZONES_PER_PAGE: u32 = 50;
+/// DNS record changes per batch request, the limit of the free plan
+const DEFAULT_DNS_BATCH_SIZE: usize = 200;
+
+/// Settings of the tunnel CNAME records
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct DnsRecordSettings {
+    pub proxied: bool,
+    /// TTL in seconds, 1 for automatic
+    pub ttl: u32,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl DnsRecordSettings {
+    /// Whether Cloudflare accepts the TTL, 1 for automatic or 60 to 86400 seconds
+    pub(super) fn is_valid_ttl(ttl: u32) -> bool {
+        ttl == 1 || (60..=86400).contains(&ttl)
+    }
+
+    /// Whether a record with the TTL is up to date, Cloudflare always reporting proxied
+    /// records as automatic
+    pub(super) fn ttl_matches(&self, ttl: u32) -> bool {
+        self.proxied || self.ttl == ttl
+    }
+}
+
+impl Default for DnsRecordSettings {
+    fn default() -> Self {
+        Self {
+            proxied: true,
+            ttl: 1,
+            comment: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Settings and allow policy of an Access application of a hostname
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct AccessApplicationSettings {
+    pub session_duration: Option<String>,
+    /// Ids of the identity providers, every provider of the account when empty
+    pub identity_providers: Vec<String>,
+    /// Include rules of the allow policy
+    pub include: Vec<serde_json::Value>,
+    /// Service token allowed by a service auth policy
+    pub service_token_id: Option<String>,
+}
+
+/// Private network CIDR routed through a tunnel
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct TunnelRouteSettings {
+    pub network: String,
+    pub tunnel_id: String,
+    pub comment: String,
+    /// Default virtual network of the account when `None`
+    pub virtual_network_id: Option<String>,
+}
+
+impl TunnelRouteSettings {
+    fn params(&self) -> self::teamnet_routes::TunnelRouteParams<'_> {
+        self::teamnet_routes::TunnelRouteParams {
+            network: &self.network,
+            tunnel_id: &self.tunnel_id,
+            comment: &self.comment,
+            virtual_network_id: self.virtual_network_id.as_deref(),
+        }
+    }
+}
+
+/// Name, comment and default flag of a virtual network
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct VirtualNetworkSettings {
+    pub name: String,
+    pub comment: String,
+    pub is_default_network: bool,
+}
+
+impl VirtualNetworkSettings {
+    fn params(&self) -> self::virtual_networks::VirtualNetworkParams<'_> {
+        self::virtual_networks::VirtualNetworkParams {
+            name: &self.name,
+            comment: &self.comment,
+            is_default_network: self.is_default_network,
+        }
+    }
+}
+
+/// Changes of the tunnel CNAME records of a zone, applied by [`CloudflareApi::batch_dns_cname`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct DnsCnameBatch {
+    /// IDs of the records to delete
+    pub deletes: Vec<String>,
+    /// IDs and hostnames of the records to rewrite
+    pub updates: Vec<(String, String, DnsRecordSettings)>,
+    /// Hostnames of the records to create
+    pub creates: Vec<(String, DnsRecordSettings)>,
+    /// Names and contents of the TXT registry records to create
+    pub txt_creates: Vec<(String, String)>,
+}
+
+impl DnsCnameBatch {
+    pub(super) fn is_empty(&self) -> bool {
+        self.deletes.is_empty()
+            && self.updates.is_empty()
+            && self.creates.is_empty()
+            && self.txt_creates.is_empty()
+    }
+}
 
 pub struct CloudflareApi {
-    api: Arc<HttpApiClient>,
+    /// Replaced when the token rotates
+    api: RwLock<Arc<HttpApiClient>>,
+    tunnel_cname_domain: String,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    per_page: u32,
+    dns_cache: DnsRecordCache,
+    dns_batch_size: usize,
+    metrics: Option<Metrics>,
 }
 
 impl CloudflareApi {
     pub fn new(api: Arc<HttpApiClient>) -> Self {
-        Self { api }
+        Self {
+            api: RwLock::new(api),
+            tunnel_cname_domain: DEFAULT_TUNNEL_CNAME_DOMAIN.to_string(),
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            per_page: DEFAULT_PER_PAGE,
+            dns_cache: DnsRecordCache::default(),
+            dns_batch_size: DEFAULT_DNS_BATCH_SIZE,
+            metrics: None,
+        }
+    }
+
+    pub fn with_tunnel_cname_domain(mut self, domain: &str) -> Self {
+        self.tunnel_cname_domain = domain.to_string();
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limit the rate of the requests, including their retries
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Items requested per page when listing tunnels, zones and DNS records
+    pub fn with_per_page(mut self, per_page: u32) -> Self {
+        self.per_page = per_page;
+        self
+    }
+
+    /// Keep the DNS records listed by [`Self::refresh_dns`] for the TTL, zero disables the
+    /// cache
+    pub fn with_dns_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.dns_cache = DnsRecordCache::new(ttl);
+        self
+    }
+
+    /// DNS record changes sent per batch request
+    pub fn with_dns_batch_size(mut self, dns_batch_size: usize) -> Self {
+        self.dns_batch_size = dns_batch_size.max(1);
+        self
+    }
+
+    /// Record the requests, their latency, retries and the wait for the rate limiter
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Send the following requests with another client, e.g. built with a rotated token
+    pub(super) fn replace_client(&self, api: Arc<HttpApiClient>) {
+        *self.api.write().unwrap() = api;
+    }
+
+    /// Send a request, retrying it with an exponential backoff on a transient failure
+    async fn request<ResultType, QueryType, BodyType>(
+        &self,
+        endpoint: &(dyn Endpoint<ResultType, QueryType, BodyType> + Send + Sync),
+    ) -> ApiResponse<ResultType>
+    where
+        ResultType: ApiResult,
+        QueryType: Serialize,
+        BodyType: Serialize,
+    {
+        let method = endpoint.method();
+        let endpoint_label = endpoint_label(&endpoint.path());
+        let mut attempt = 0;
+        loop {
+            if let Some(ref rate_limiter) = self.rate_limiter {
+                let started_at = Instant::now();
+                rate_limiter.acquire().await;
+                if let Some(ref metrics) = self.metrics {
+                    metrics.observe_cloudflare_api_rate_limit_wait(started_at.elapsed());
+                }
+            }
+            let started_at = Instant::now();
+            let api = self.api.read().unwrap().clone();
+            let response = api.request(endpoint).await;
+            if let Some(ref metrics) = self.metrics {
+                let status = response.as_ref().map(|_| ()).map_err(|e| match e {
+                    ApiFailure::Error(status, _) => Some(status.as_u16()),
+                    ApiFailure::Invalid(e) => e.status().map(|s| s.as_u16()),
+                });
+                metrics.cloudflare_api_request_finished(
+                    method.as_str(),
+                    &endpoint_label,
+                    status,
+                    started_at.elapsed(),
+                );
+            }
+            match response {
+                Err(e)
+                    if attempt < self.retry_policy.max_retries
+                        && retry::is_retryable(&method, &e) =>
+                {
+                    if let Some(ref metrics) = self.metrics {
+                        metrics.inc_cloudflare_api_retries(method.as_str(), &endpoint_label);
+                    }
+                    let delay = self.retry_policy.delay_after(attempt, &e);
+                    warn!(
+                        "Retry cloudflare api request {} in {delay:?}: {e}",
+                        endpoint.path()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                response => return response,
+            }
+        }
+    }
+
+    /// Request every page of a list endpoint
+    async fn request_pages<T, QueryType, E>(&self, endpoint: impl Fn(u32) -> E) -> Result<Vec<T>>
+    where
+        Vec<T>: ApiResult,
+        QueryType: Serialize,
+        E: Endpoint<Vec<T>, QueryType> + Send + Sync,
+    {
+        let mut items = Vec::new();
+        for page in 1.. {
+            let response = self.request(&endpoint(page)).await?;
+            let count = response.result.len();
+            items.extend(response.result);
+            if !has_next_page(response.result_info.as_ref(), page, count) {
+                break;
+            }
+        }
+        Ok(items)
     }
 
     pub async fn list_tunnels(&self, account_id: String, prefix: String) -> Result<Vec<Tunnel>> {
-        use cloudflare::endpoints::cfd_tunnel::list_tunnels::{ListTunnels, Params};
-        let api = self.api.clone();
+        use self::cfd_tunnel::{ListTunnelsPage, ListTunnelsPageParams};
 
-        let endpoint = ListTunnels {
-            params: Params {
-                is_deleted: Some(false),
-                include_prefix: Some(prefix),
-                ..Default::default()
-            },
+        self.request_pages(|page| ListTunnelsPage {
             account_identifier: account_id.as_str(),
-        };
-        let response = api.request(&endpoint).await?;
-        Ok(response.result)
+            params: ListTunnelsPageParams {
+                is_deleted: false,
+                include_prefix: prefix.as_str(),
+                page,
+                per_page: self.per_page,
+            },
+        })
+        .await
     }
 
     pub(super) async fn get_tunnel_opt(
@@ -43,7 +328,6 @@ impl CloudflareApi {
         tunnel_id: String,
     ) -> Result<Option<Tunnel>> {
         use cloudflare::endpoints::cfd_tunnel::list_tunnels::{ListTunnels, Params};
-        let api = self.api.clone();
 
         let endpoint = ListTunnels {
             params: Params {
@@ -53,7 +337,7 @@ impl CloudflareApi {
             },
             account_identifier: account_id.as_str(),
         };
-        let response = api.request(&endpoint).await?;
+        let response = self.request(&endpoint).await?;
         Ok(response.result.into_iter().next())
     }
 
@@ -62,12 +346,12 @@ impl CloudflareApi {
         account_id: String,
         tunnel_name: String,
         tunnel_secret: Vec<u8>,
+        metadata: serde_json::Value,
     ) -> Result<Tunnel> {
         use cloudflare::endpoints::cfd_tunnel::{
             create_tunnel::{CreateTunnel, Params},
             ConfigurationSrc,
         };
-        let api = self.api.clone();
         info!("Create cloudflare tunnel: {}", tunnel_name);
 
         let endpoint = CreateTunnel {
@@ -75,27 +359,56 @@ impl CloudflareApi {
             params: Params {
                 name: tunnel_name.as_str(),
                 tunnel_secret: &tunnel_secret,
-                metadata: None,
+                metadata: Some(metadata),
                 config_src: &ConfigurationSrc::Local,
             },
         };
-        let response = api.request(&endpoint).await?;
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn update_tunnel_secret(
+        &self,
+        account_id: String,
+        tunnel_id: String,
+        tunnel_secret: &[u8],
+    ) -> Result<Tunnel> {
+        use self::cfd_tunnel::{UpdateTunnelSecret, UpdateTunnelSecretParams};
+        use base64::Engine as _;
+        info!("Update cloudflare tunnel secret: {}", tunnel_id);
+
+        let endpoint = UpdateTunnelSecret {
+            account_identifier: account_id.as_str(),
+            tunnel_id: tunnel_id.as_str(),
+            params: UpdateTunnelSecretParams {
+                tunnel_secret: base64::engine::general_purpose::STANDARD.encode(tunnel_secret),
+            },
+        };
+        let response = self.request(&endpoint).await?;
         Ok(response.result)
     }
 
-    pub(super) async fn delete_tunnel(&self, account_id: String, tunnel_id: String) -> Result<()> {
+    /// Delete the tunnel, with `cascade` also when connections remain
+    pub(super) async fn delete_tunnel(
+        &self,
+        account_id: String,
+        tunnel_id: String,
+        cascade: bool,
+    ) -> Result<()> {
         use cloudflare::endpoints::cfd_tunnel::delete_tunnel::{DeleteTunnel, Params};
-        let api = self.api.clone();
 
-        info!("Delete cloudflare tunnel: {}", tunnel_id);
+        info!(
+            "Delete cloudflare tunnel: {{ tunnel_id: {}, cascade: {} }}",
+            tunnel_id, cascade
+        );
 
         let endpoint = DeleteTunnel {
             account_identifier: account_id.as_str(),
             tunnel_id: &tunnel_id,
-            params: Params { cascade: false },
+            params: Params { cascade },
         };
 
-        api.request(&endpoint).await.map_or_else(
+        self.request(&endpoint).await.map_or_else(
             |e| match e {
                 // Tunnelが削除済みであった場合、Decode errorが発生する
                 ApiFailure::Invalid(inner) if inner.is_decode() => Ok(()),
@@ -105,70 +418,202 @@ impl CloudflareApi {
         )
     }
 
+    pub(super) async fn list_tunnel_connections(
+        &self,
+        account_id: String,
+        tunnel_id: String,
+    ) -> Result<Vec<TunnelConnector>> {
+        use self::cfd_tunnel::ListTunnelConnections;
+
+        let endpoint = ListTunnelConnections {
+            account_identifier: account_id.as_str(),
+            tunnel_id: tunnel_id.as_str(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn cleanup_tunnel_connections(
+        &self,
+        account_id: String,
+        tunnel_id: String,
+    ) -> Result<()> {
+        use self::cfd_tunnel::CleanupTunnelConnections;
+
+        info!("Cleanup cloudflare tunnel connections: {}", tunnel_id);
+
+        let endpoint = CleanupTunnelConnections {
+            account_identifier: account_id.as_str(),
+            tunnel_id: tunnel_id.as_str(),
+        };
+        self.request(&endpoint).await.map_or_else(
+            |e| match e {
+                // resultが空の場合、Decode errorが発生する
+                ApiFailure::Invalid(inner) if inner.is_decode() => Ok(()),
+                _ => Err(Error::from(e)),
+            },
+            |_| Ok(()),
+        )
+    }
+
     pub(super) async fn list_dns_cname(
         &self,
         zone_id: String,
         tunnel_id: String,
     ) -> Result<Vec<DnsRecord>> {
-        use cloudflare::endpoints::dns::{DnsContent, ListDnsRecords, ListDnsRecordsParams};
-        let api = self.api.clone();
-        let endpoint = ListDnsRecords {
+        use cloudflare::endpoints::dns::{ListDnsRecords, ListDnsRecordsParams};
+        self.request_pages(|page| ListDnsRecords {
             zone_identifier: zone_id.as_str(),
             params: ListDnsRecordsParams {
                 record_type: Some(DnsContent::CNAME {
-                    content: format!("{}.cfargotunnel.com", tunnel_id),
+                    content: tunnel_cname(&tunnel_id, &self.tunnel_cname_domain),
                 }),
+                page: Some(page),
+                per_page: Some(self.per_page),
                 ..Default::default()
             },
-        };
+        })
+        .await
+    }
 
-        let result = api.request(&endpoint).await?;
+    /// Tunnel CNAME records whose comment contains the marker
+    pub(super) async fn list_marked_dns_cname(
+        &self,
+        zone_id: String,
+        tunnel_id: String,
+        marker: String,
+    ) -> Result<Vec<DnsRecord>> {
+        use self::dns_records::{ListMarkedDnsCnames, ListMarkedDnsCnamesParams};
 
-        Ok(result.result)
+        let content = tunnel_cname(&tunnel_id, &self.tunnel_cname_domain);
+        self.request_pages(|page| ListMarkedDnsCnames {
+            zone_identifier: zone_id.as_str(),
+            params: ListMarkedDnsCnamesParams {
+                record_type: "CNAME",
+                content: content.as_str(),
+                comment_contains: marker.as_str(),
+                page,
+                per_page: self.per_page,
+            },
+        })
+        .await
     }
 
     pub(super) async fn list_dns(&self, zone_id: String) -> Result<Vec<DnsRecord>> {
         use cloudflare::endpoints::dns::{ListDnsRecords, ListDnsRecordsParams};
-        let api = self.api.clone();
 
-        let endpoint = ListDnsRecords {
+        self.request_pages(|page| ListDnsRecords {
             zone_identifier: zone_id.as_str(),
-            params: ListDnsRecordsParams::default(),
-        };
+            params: ListDnsRecordsParams {
+                page: Some(page),
+                per_page: Some(self.per_page),
+                ..Default::default()
+            },
+        })
+        .await
+    }
+
+    /// Drop the cached DNS records of every zone
+    pub(super) fn invalidate_dns_cache(&self) {
+        self.dns_cache.invalidate_all();
+    }
 
-        let result = api.request(&endpoint).await?;
+    /// DNS records of the zone while they are cached
+    pub(super) fn cached_dns(&self, zone_id: &str) -> Option<Arc<Vec<DnsRecord>>> {
+        self.dns_cache.get(zone_id, Instant::now())
+    }
 
-        Ok(result.result)
+    /// List the DNS records of the zone and cache them
+    pub(super) async fn refresh_dns(&self, zone_id: String) -> Result<Arc<Vec<DnsRecord>>> {
+        let generation = self.dns_cache.generation(&zone_id);
+        let records = self.list_dns(zone_id.clone()).await?;
+        Ok(self
+            .dns_cache
+            .insert(&zone_id, generation, records, Instant::now()))
     }
 
-    pub(super) async fn create_dns_cname(
+    /// Apply the changes of the tunnel CNAME records of a zone with the batch endpoint, at most
+    /// `dns_batch_size` changes per request. A request is applied in one transaction with the
+    /// deletes first, so that a record is replaced without the hostname going unresolved
+    pub(super) async fn batch_dns_cname(
         &self,
         zone_id: String,
         tunnel_id: String,
-        target: String,
-    ) -> Result<DnsRecord> {
-        use cloudflare::endpoints::dns::{CreateDnsRecord, CreateDnsRecordParams, DnsContent};
-        let api = self.api.clone();
+        batch: &DnsCnameBatch,
+    ) -> Result<()> {
+        use self::dns_records::{
+            BatchDnsRecords, DnsRecordChange, DnsRecordId, DnsRecordParams, PutDnsRecord,
+        };
         info!(
-            "Create cloudflare dns cname record: {{ zone_id: {} , tunnel_id: {}, tunnel_id: {}}}",
-            zone_id, target, tunnel_id
+            "Batch cloudflare dns cname records: {{ zone_id: {}, tunnel_id: {}, deletes: {}, updates: {}, creates: {}, txt_creates: {} }}",
+            zone_id,
+            tunnel_id,
+            batch.deletes.len(),
+            batch.updates.len(),
+            batch.creates.len(),
+            batch.txt_creates.len()
         );
 
-        let endpoint = CreateDnsRecord {
-            zone_identifier: zone_id.as_str(),
-            params: CreateDnsRecordParams {
-                name: target.as_str(),
+        fn cname_params<'a>(
+            name: &'a str,
+            content: &str,
+            settings: &'a DnsRecordSettings,
+        ) -> DnsRecordParams<'a> {
+            DnsRecordParams {
+                name,
                 content: DnsContent::CNAME {
-                    content: format!("{}.cfargotunnel.com", tunnel_id),
+                    content: content.to_string(),
                 },
-                proxied: Some(true),
-                ttl: None,
-                priority: None,
-            },
-        };
-        let result = api.request(&endpoint).await?;
+                proxied: settings.proxied,
+                ttl: settings.ttl,
+                comment: settings.comment.as_deref(),
+                tags: &settings.tags,
+            }
+        }
+
+        let content = tunnel_cname(&tunnel_id, &self.tunnel_cname_domain);
+        let changes = batch
+            .deletes
+            .iter()
+            .map(|id| DnsRecordChange::Delete(DnsRecordId { id }))
+            .chain(batch.updates.iter().map(|(id, name, settings)| {
+                DnsRecordChange::Put(PutDnsRecord {
+                    id,
+                    params: cname_params(name, &content, settings),
+                })
+            }))
+            .chain(batch.creates.iter().map(|(name, settings)| {
+                DnsRecordChange::Post(cname_params(name, &content, settings))
+            }))
+            .chain(batch.txt_creates.iter().map(|(name, content)| {
+                DnsRecordChange::Post(DnsRecordParams {
+                    name,
+                    content: DnsContent::TXT {
+                        content: content.clone(),
+                    },
+                    proxied: false,
+                    ttl: 1,
+                    comment: None,
+                    tags: &[],
+                })
+            }))
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        for chunk in changes.chunks(self.dns_batch_size) {
+            let endpoint = BatchDnsRecords {
+                zone_identifier: zone_id.as_str(),
+                params: chunk.iter().cloned().collect(),
+            };
+            result = self.request(&endpoint).await.map(|_| ());
+            if result.is_err() {
+                break;
+            }
+        }
+        // 失敗した場合でも一部が反映されている可能性があるため破棄する
+        self.dns_cache.invalidate(&zone_id);
 
-        Ok(result.result)
+        Ok(result?)
     }
 
     pub(super) async fn delete_dns_cname(
@@ -177,7 +622,6 @@ impl CloudflareApi {
         dns_record_id: String,
     ) -> Result<DeleteDnsRecordResponse> {
         use cloudflare::endpoints::dns::DeleteDnsRecord;
-        let api = self.api.clone();
         info!(
             "Delete cloudflare dns cname record: {{ zone_id: {} , dns_record_id: {}}}",
             zone_id, dns_record_id
@@ -187,205 +631,1684 @@ impl CloudflareApi {
             identifier: dns_record_id.as_str(),
         };
 
-        let result = api.request(&endpoint).await?;
+        let result = self.request(&endpoint).await;
+        self.dns_cache.invalidate(&zone_id);
 
-        Ok(result.result)
+        Ok(result?.result)
     }
 
-    pub(super) async fn list_zone(&self) -> Result<Vec<Zone>> {
-        use cloudflare::endpoints::zone::{ListZones, ListZonesParams};
-        let api = self.api.clone();
+    /// Create a record of any type, unlike the tunnel CNAME records
+    pub(super) async fn create_dns_record(
+        &self,
+        zone_id: String,
+        name: String,
+        content: DnsContent,
+        settings: &DnsRecordSettings,
+    ) -> Result<DnsRecord> {
+        use self::dns_records::{CreateDnsRecord, DnsRecordParams};
+        info!(
+            "Create cloudflare dns record: {{ zone_id: {}, name: {}, content: {:?} }}",
+            zone_id, name, content
+        );
 
-        let endpoint = ListZones {
-            params: ListZonesParams::default(),
+        let endpoint = CreateDnsRecord {
+            zone_identifier: zone_id.as_str(),
+            params: DnsRecordParams {
+                name: name.as_str(),
+                content,
+                proxied: settings.proxied,
+                ttl: settings.ttl,
+                comment: settings.comment.as_deref(),
+                tags: &settings.tags,
+            },
         };
+        let result = self.request(&endpoint).await;
+        self.dns_cache.invalidate(&zone_id);
 
-        let result = api.request(&endpoint).await?;
-
-        Ok(result.result)
+        Ok(result?.result)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use cloudflare::framework::{
-        async_api::Client as HttpApiClient, auth::Credentials, Environment, HttpApiClientConfig,
-    };
-    use mockito::{Matcher, ServerGuard};
+    pub(super) async fn update_dns_record(
+        &self,
+        zone_id: String,
+        dns_record_id: String,
+        name: String,
+        content: DnsContent,
+        settings: &DnsRecordSettings,
+    ) -> Result<DnsRecord> {
+        use self::dns_records::{DnsRecordParams, UpdateDnsRecord};
+        info!(
+            "Update cloudflare dns record: {{ zone_id: {}, dns_record_id: {}, name: {}, content: {:?} }}",
+            zone_id, dns_record_id, name, content
+        );
 
-    use super::*;
+        let endpoint = UpdateDnsRecord {
+            zone_identifier: zone_id.as_str(),
+            identifier: dns_record_id.as_str(),
+            params: DnsRecordParams {
+                name: name.as_str(),
+                content,
+                proxied: settings.proxied,
+                ttl: settings.ttl,
+                comment: settings.comment.as_deref(),
+                tags: &settings.tags,
+            },
+        };
+        let result = self.request(&endpoint).await;
+        self.dns_cache.invalidate(&zone_id);
 
-    async fn start_mock_server() -> ServerGuard {
-        let mut server = mockito::Server::new_async().await;
+        Ok(result?.result)
+    }
 
-        // list_tunnel or get_tunnel_opt
-        server
-            .mock(
-                "GET",
-                "/accounts/a0000000000000000000000000000001/cfd_tunnel",
-            )
-            .match_query(Matcher::AllOf(vec![
-                Matcher::UrlEncoded("is_deleted".into(), "false".into()),
-                Matcher::AnyOf(vec![
-                    Matcher::UrlEncoded("include_prefix".into(), "test-prefix".into()),
-                    Matcher::UrlEncoded("uuid".into(), "a0000000000000000000000000000002".into()),
-                ]),
-            ]))
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+    /// Comment of the DNS record, not listed with the records
+    pub(super) async fn get_dns_record_comment(
+        &self,
+        zone_id: String,
+        dns_record_id: String,
+    ) -> Result<Option<String>> {
+        use self::dns_records::GetDnsRecordComment;
 
-        // delete dns record
-        server
-            .mock("DELETE", "/zones/00000000000000000000000000000001/dns_records/00000000000000000000000000000002")
-            .with_status(201)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":{"id":"00000000000000000000000000000001"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+        let endpoint = GetDnsRecordComment {
+            zone_identifier: zone_id.as_str(),
+            identifier: dns_record_id.as_str(),
+        };
+        Ok(self.request(&endpoint).await?.result.comment)
+    }
 
-        // list zones
-        server
-            .mock("GET", "/zones?")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":[
-                {"id":"00000000000000000000000000000001","name":"example.com","status":"active","paused":false,"type":"full","development_mode":0,"name_servers":[],"original_name_servers":[],"original_registrar":null,"original_dnshost":null,"modified_on":"2000-01-01T00:00:00.000000Z","created_on":"2000-01-01T00:00:00.000000Z","activated_on":"2000-01-01T00:00:00.000000Z","meta":{"step":0,"custom_certificate_quota":0,"page_rule_quota":0,"phishing_detected":false},"owner":{"id":null,"type":"user","email":null},"account":{"id":"","name":"Example account"},"tenant":{},"tenant_unit":{},"permissions":[],"plan":{"id":"","name":"","price":0,"currency":"","frequency":"","is_subscribed":false,"can_subscribe":false,"legacy_id":"","legacy_discount":false,"externally_managed":false}}
-            ],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+    pub(super) async fn delete_dns_record(
+        &self,
+        zone_id: String,
+        dns_record_id: String,
+    ) -> Result<DeleteDnsRecordResponse> {
+        use cloudflare::endpoints::dns::DeleteDnsRecord;
+        info!(
+            "Delete cloudflare dns record: {{ zone_id: {}, dns_record_id: {} }}",
+            zone_id, dns_record_id
+        );
+        let endpoint = DeleteDnsRecord {
+            zone_identifier: zone_id.as_str(),
+            identifier: dns_record_id.as_str(),
+        };
 
-        // delete tunnel
-        server
-            .mock("DELETE", "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002")
-            .match_query(
-                    Matcher::UrlEncoded("cascade".into(), "false".into()),
-            )
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":{"id":"00000000000000000000000000000001"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+        let result = self.request(&endpoint).await;
+        self.dns_cache.invalidate(&zone_id);
 
-        // create dns recoad
-        server
-            .mock(
-                "POST",
-                "/zones/00000000000000000000000000000001/dns_records",
-            )
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":{"id":"a0000000000000000000000000000001","zone_id":"00000000000000000000000000000001","zone_name":"example.com","name":"example.example.com","type":"CNAME","content":"example.com","proxiable":true,"proxied":true,"ttl":1,"settings":{},"meta":{"auto_added":false,"managed_by_apps":false,"managed_by_argo_tunnel":false},"comment":null,"tags":[],"created_on":"2000-01-01T00:00:00.000000Z","modified_on":"2000-01-01T00:00:00.000000Z"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+        Ok(result?.result)
+    }
 
-        // create tunnel
-        server
-            .mock(
-                "POST",
-                "/accounts/a0000000000000000000000000000001/cfd_tunnel",
-            )
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"{"result":{"id":"00000000000000000000000000000001","created_at":"2000-01-01T00:00:00.000000Z","deleted_at":null,"name":"example-tunnel","connections":[],"metadata":{}},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
-            .create_async()
-            .await;
+    pub(super) async fn list_worker_routes(&self, zone_id: String) -> Result<Vec<WorkerRoute>> {
+        use self::workers::ListWorkerRoutes;
 
-        // list dns records
-        server
-            .mock("GET", "/zones/00000000000000000000000000000001/dns_records")
-            .match_query(Matcher::AnyOf(vec![
-                Matcher::Missing,
-                Matcher::AllOf(vec![
-                    Matcher::UrlEncoded("type".into(), "CNAME".into()),
-                    Matcher::UrlEncoded(
-                        "content".into(),
-                        "a0000000000000000000000000000002.cfargotunnel.com".into(),
-                    ),
-                ]),
+        let endpoint = ListWorkerRoutes {
+            zone_identifier: zone_id.as_str(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn create_worker_route(
+        &self,
+        zone_id: String,
+        pattern: String,
+        script: String,
+    ) -> Result<WorkerRoute> {
+        use self::workers::{CreateWorkerRoute, CreateWorkerRouteParams};
+        info!(
+            "Create cloudflare worker route: {{ zone_id: {}, pattern: {}, script: {} }}",
+            zone_id, pattern, script
+        );
+
+        let endpoint = CreateWorkerRoute {
+            zone_identifier: zone_id.as_str(),
+            params: CreateWorkerRouteParams {
+                pattern: pattern.as_str(),
+                script: script.as_str(),
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_worker_route(
+        &self,
+        zone_id: String,
+        route_id: String,
+    ) -> Result<()> {
+        use self::workers::DeleteWorkerRoute;
+        info!(
+            "Delete cloudflare worker route: {{ zone_id: {}, route_id: {} }}",
+            zone_id, route_id
+        );
+
+        let endpoint = DeleteWorkerRoute {
+            zone_identifier: zone_id.as_str(),
+            identifier: route_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_health_checks(&self, zone_id: String) -> Result<Vec<HealthCheck>> {
+        use self::healthchecks::ListHealthChecks;
+
+        let endpoint = ListHealthChecks {
+            zone_identifier: zone_id.as_str(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn create_health_check(
+        &self,
+        zone_id: String,
+        name: String,
+        hostname: String,
+        path: String,
+        description: String,
+    ) -> Result<HealthCheck> {
+        use self::healthchecks::{
+            CreateHealthCheck, CreateHealthCheckParams, HealthCheckHttpConfig,
+        };
+        info!(
+            "Create cloudflare health check: {{ zone_id: {}, hostname: {}, path: {} }}",
+            zone_id, hostname, path
+        );
+
+        let endpoint = CreateHealthCheck {
+            zone_identifier: zone_id.as_str(),
+            params: CreateHealthCheckParams {
+                name: name.as_str(),
+                address: hostname.as_str(),
+                description: description.as_str(),
+                check_type: "HTTPS",
+                http_config: HealthCheckHttpConfig {
+                    path: path.as_str(),
+                },
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_health_check(
+        &self,
+        zone_id: String,
+        health_check_id: String,
+    ) -> Result<()> {
+        use self::healthchecks::DeleteHealthCheck;
+        info!(
+            "Delete cloudflare health check: {{ zone_id: {}, health_check_id: {} }}",
+            zone_id, health_check_id
+        );
+
+        let endpoint = DeleteHealthCheck {
+            zone_identifier: zone_id.as_str(),
+            identifier: health_check_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_access_applications(
+        &self,
+        account_id: String,
+    ) -> Result<Vec<AccessApplication>> {
+        use self::access_apps::{ListAccessApplications, ListAccessApplicationsParams};
+
+        self.request_pages(|page| ListAccessApplications {
+            account_identifier: account_id.as_str(),
+            params: ListAccessApplicationsParams {
+                page,
+                per_page: self.per_page,
+            },
+        })
+        .await
+    }
+
+    pub(super) async fn create_access_application(
+        &self,
+        account_id: String,
+        hostname: String,
+        settings: &AccessApplicationSettings,
+    ) -> Result<AccessApplication> {
+        use self::access_apps::CreateAccessApplication;
+        info!(
+            "Create cloudflare access application: {{ account_id: {}, hostname: {} }}",
+            account_id, hostname
+        );
+
+        let endpoint = CreateAccessApplication {
+            account_identifier: account_id.as_str(),
+            params: access_application_params(&hostname, settings),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn update_access_application(
+        &self,
+        account_id: String,
+        application_id: String,
+        hostname: String,
+        settings: &AccessApplicationSettings,
+    ) -> Result<AccessApplication> {
+        use self::access_apps::UpdateAccessApplication;
+        info!(
+            "Update cloudflare access application: {{ account_id: {}, application_id: {}, hostname: {} }}",
+            account_id, application_id, hostname
+        );
+
+        let endpoint = UpdateAccessApplication {
+            account_identifier: account_id.as_str(),
+            identifier: application_id.as_str(),
+            params: access_application_params(&hostname, settings),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_access_application(
+        &self,
+        account_id: String,
+        application_id: String,
+    ) -> Result<()> {
+        use self::access_apps::DeleteAccessApplication;
+        info!(
+            "Delete cloudflare access application: {{ account_id: {}, application_id: {} }}",
+            account_id, application_id
+        );
+
+        let endpoint = DeleteAccessApplication {
+            account_identifier: account_id.as_str(),
+            identifier: application_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_access_service_tokens(
+        &self,
+        account_id: String,
+    ) -> Result<Vec<ServiceToken>> {
+        use self::service_tokens::{ListServiceTokens, ListServiceTokensParams};
+
+        self.request_pages(|page| ListServiceTokens {
+            account_identifier: account_id.as_str(),
+            params: ListServiceTokensParams {
+                page,
+                per_page: self.per_page,
+            },
+        })
+        .await
+    }
+
+    pub(super) async fn create_access_service_token(
+        &self,
+        account_id: String,
+        name: String,
+    ) -> Result<ServiceToken> {
+        use self::service_tokens::{CreateServiceToken, CreateServiceTokenParams};
+        info!(
+            "Create cloudflare access service token: {{ account_id: {}, name: {} }}",
+            account_id, name
+        );
+
+        let endpoint = CreateServiceToken {
+            account_identifier: account_id.as_str(),
+            params: CreateServiceTokenParams {
+                name: name.as_str(),
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn rotate_access_service_token(
+        &self,
+        account_id: String,
+        service_token_id: String,
+    ) -> Result<ServiceToken> {
+        use self::service_tokens::RotateServiceToken;
+        info!(
+            "Rotate cloudflare access service token: {{ account_id: {}, service_token_id: {} }}",
+            account_id, service_token_id
+        );
+
+        let endpoint = RotateServiceToken {
+            account_identifier: account_id.as_str(),
+            identifier: service_token_id.as_str(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_access_service_token(
+        &self,
+        account_id: String,
+        service_token_id: String,
+    ) -> Result<()> {
+        use self::service_tokens::DeleteServiceToken;
+        info!(
+            "Delete cloudflare access service token: {{ account_id: {}, service_token_id: {} }}",
+            account_id, service_token_id
+        );
+
+        let endpoint = DeleteServiceToken {
+            account_identifier: account_id.as_str(),
+            identifier: service_token_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn get_tunnel_route_opt(
+        &self,
+        account_id: String,
+        route_id: String,
+    ) -> Result<Option<TunnelRoute>> {
+        use self::teamnet_routes::{ListTunnelRoutes, ListTunnelRoutesParams};
+
+        let endpoint = ListTunnelRoutes {
+            account_identifier: account_id.as_str(),
+            params: ListTunnelRoutesParams {
+                is_deleted: false,
+                route_id: Some(route_id.as_str()),
+                comment: None,
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result.into_iter().find(|r| r.id == route_id))
+    }
+
+    /// Tunnel route of the network with the comment, left by a creation not recorded in a status
+    pub(super) async fn find_tunnel_route(
+        &self,
+        account_id: String,
+        network: &str,
+        comment: &str,
+    ) -> Result<Option<TunnelRoute>> {
+        use self::teamnet_routes::{ListTunnelRoutes, ListTunnelRoutesParams};
+
+        let endpoint = ListTunnelRoutes {
+            account_identifier: account_id.as_str(),
+            params: ListTunnelRoutesParams {
+                is_deleted: false,
+                route_id: None,
+                comment: Some(comment),
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response
+            .result
+            .into_iter()
+            .find(|r| r.network == network && r.comment.as_deref() == Some(comment)))
+    }
+
+    pub(super) async fn create_tunnel_route(
+        &self,
+        account_id: String,
+        settings: &TunnelRouteSettings,
+    ) -> Result<TunnelRoute> {
+        use self::teamnet_routes::CreateTunnelRoute;
+        info!(
+            "Create cloudflare tunnel route: {{ account_id: {}, network: {}, tunnel_id: {} }}",
+            account_id, settings.network, settings.tunnel_id
+        );
+
+        let endpoint = CreateTunnelRoute {
+            account_identifier: account_id.as_str(),
+            params: settings.params(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn update_tunnel_route(
+        &self,
+        account_id: String,
+        route_id: String,
+        settings: &TunnelRouteSettings,
+    ) -> Result<TunnelRoute> {
+        use self::teamnet_routes::UpdateTunnelRoute;
+        info!(
+            "Update cloudflare tunnel route: {{ account_id: {}, route_id: {}, network: {}, tunnel_id: {} }}",
+            account_id, route_id, settings.network, settings.tunnel_id
+        );
+
+        let endpoint = UpdateTunnelRoute {
+            account_identifier: account_id.as_str(),
+            identifier: route_id.as_str(),
+            params: settings.params(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_tunnel_route(
+        &self,
+        account_id: String,
+        route_id: String,
+    ) -> Result<()> {
+        use self::teamnet_routes::DeleteTunnelRoute;
+        info!(
+            "Delete cloudflare tunnel route: {{ account_id: {}, route_id: {} }}",
+            account_id, route_id
+        );
+
+        let endpoint = DeleteTunnelRoute {
+            account_identifier: account_id.as_str(),
+            identifier: route_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn get_virtual_network_opt(
+        &self,
+        account_id: String,
+        virtual_network_id: String,
+    ) -> Result<Option<VirtualNetwork>> {
+        use self::virtual_networks::{ListVirtualNetworks, ListVirtualNetworksParams};
+
+        let endpoint = ListVirtualNetworks {
+            account_identifier: account_id.as_str(),
+            params: ListVirtualNetworksParams {
+                is_deleted: false,
+                id: virtual_network_id.as_str(),
+            },
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response
+            .result
+            .into_iter()
+            .find(|n| n.id == virtual_network_id))
+    }
+
+    pub(super) async fn create_virtual_network(
+        &self,
+        account_id: String,
+        settings: &VirtualNetworkSettings,
+    ) -> Result<VirtualNetwork> {
+        use self::virtual_networks::CreateVirtualNetwork;
+        info!(
+            "Create cloudflare virtual network: {{ account_id: {}, name: {} }}",
+            account_id, settings.name
+        );
+
+        let endpoint = CreateVirtualNetwork {
+            account_identifier: account_id.as_str(),
+            params: settings.params(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn update_virtual_network(
+        &self,
+        account_id: String,
+        virtual_network_id: String,
+        settings: &VirtualNetworkSettings,
+    ) -> Result<VirtualNetwork> {
+        use self::virtual_networks::UpdateVirtualNetwork;
+        info!(
+            "Update cloudflare virtual network: {{ account_id: {}, virtual_network_id: {}, name: {} }}",
+            account_id, virtual_network_id, settings.name
+        );
+
+        let endpoint = UpdateVirtualNetwork {
+            account_identifier: account_id.as_str(),
+            identifier: virtual_network_id.as_str(),
+            params: settings.params(),
+        };
+        let response = self.request(&endpoint).await?;
+        Ok(response.result)
+    }
+
+    pub(super) async fn delete_virtual_network(
+        &self,
+        account_id: String,
+        virtual_network_id: String,
+    ) -> Result<()> {
+        use self::virtual_networks::DeleteVirtualNetwork;
+        info!(
+            "Delete cloudflare virtual network: {{ account_id: {}, virtual_network_id: {} }}",
+            account_id, virtual_network_id
+        );
+
+        let endpoint = DeleteVirtualNetwork {
+            account_identifier: account_id.as_str(),
+            identifier: virtual_network_id.as_str(),
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    /// Rules of the response header Transform Rules entry point, empty if it does not exist
+    pub(super) async fn list_response_header_rules(
+        &self,
+        zone_id: String,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.list_phase_rules(zone_id, rulesets::HTTP_RESPONSE_HEADERS_TRANSFORM_PHASE)
+            .await
+    }
+
+    pub(super) async fn update_response_header_rules(
+        &self,
+        zone_id: String,
+        rules: Vec<serde_json::Value>,
+    ) -> Result<()> {
+        info!(
+            "Update cloudflare response header transform rules: {{ zone_id: {} }}",
+            zone_id
+        );
+        self.update_phase_rules(
+            zone_id,
+            rulesets::HTTP_RESPONSE_HEADERS_TRANSFORM_PHASE,
+            rules,
+        )
+        .await
+    }
+
+    /// Rules of the WAF custom rules entry point, empty if it does not exist
+    pub(super) async fn list_firewall_custom_rules(
+        &self,
+        zone_id: String,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.list_phase_rules(zone_id, rulesets::HTTP_REQUEST_FIREWALL_CUSTOM_PHASE)
+            .await
+    }
+
+    pub(super) async fn update_firewall_custom_rules(
+        &self,
+        zone_id: String,
+        rules: Vec<serde_json::Value>,
+    ) -> Result<()> {
+        info!(
+            "Update cloudflare WAF custom rules: {{ zone_id: {} }}",
+            zone_id
+        );
+        self.update_phase_rules(zone_id, rulesets::HTTP_REQUEST_FIREWALL_CUSTOM_PHASE, rules)
+            .await
+    }
+
+    async fn list_phase_rules(
+        &self,
+        zone_id: String,
+        phase: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        use self::rulesets::GetZonePhaseEntrypoint;
+
+        let endpoint = GetZonePhaseEntrypoint {
+            zone_identifier: zone_id.as_str(),
+            phase,
+        };
+        self.request(&endpoint).await.map_or_else(
+            |e| match e {
+                // entry point rulesetは最初のruleが作成されるまで存在しない
+                ApiFailure::Error(status, _) if status == 404 => Ok(Vec::new()),
+                _ => Err(Error::from(e)),
+            },
+            |response| Ok(response.result.rules),
+        )
+    }
+
+    async fn update_phase_rules(
+        &self,
+        zone_id: String,
+        phase: &str,
+        rules: Vec<serde_json::Value>,
+    ) -> Result<()> {
+        use self::rulesets::{UpdateZonePhaseEntrypoint, UpdateZonePhaseEntrypointParams};
+
+        let endpoint = UpdateZonePhaseEntrypoint {
+            zone_identifier: zone_id.as_str(),
+            phase,
+            params: UpdateZonePhaseEntrypointParams { rules },
+        };
+        self.request(&endpoint).await?;
+        Ok(())
+    }
+
+    pub(super) async fn verify_token(&self) -> Result<TokenStatus> {
+        use self::user_tokens::VerifyToken;
+
+        let response = self.request(&VerifyToken).await?;
+        Ok(response.result)
+    }
+
+    /// Request the first page of the zones and of the tunnels of the account, to check that the
+    /// token can read them
+    pub(super) async fn check_access(&self, account_id: String) -> Result<()> {
+        use self::cfd_tunnel::{ListTunnelsPage, ListTunnelsPageParams};
+        use cloudflare::endpoints::zone::{ListZones, ListZonesParams};
+
+        self.request(&ListZones {
+            params: ListZonesParams {
+                page: Some(1),
+                per_page: Some(5),
+                ..Default::default()
+            },
+        })
+        .await?;
+        self.request(&ListTunnelsPage {
+            account_identifier: account_id.as_str(),
+            params: ListTunnelsPageParams {
+                is_deleted: false,
+                include_prefix: "",
+                page: 1,
+                per_page: 5,
+            },
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_zone(&self) -> Result<Vec<Zone>> {
+        use cloudflare::endpoints::zone::{ListZones, ListZonesParams};
+
+        self.request_pages(|page| ListZones {
+            params: ListZonesParams {
+                page: Some(page),
+                per_page: Some(self.per_page.min(MAX_ZONES_PER_PAGE)),
+                ..Default::default()
+            },
+        })
+        .await
+    }
+}
+
+/// Whether a list endpoint has pages after `page`. `result_info` gives the number of pages,
+/// or the number of items when the endpoint does not count the pages.
+fn has_next_page(result_info: Option<&serde_json::Value>, page: u32, count: usize) -> bool {
+    if count == 0 {
+        return false;
+    }
+    let info = |key| {
+        result_info
+            .and_then(|i| i.get(key))
+            .and_then(serde_json::Value::as_u64)
+    };
+    match (info("total_pages"), info("total_count"), info("per_page")) {
+        (Some(total_pages), _, _) => u64::from(page) < total_pages,
+        (None, Some(total_count), Some(per_page)) => u64::from(page) * per_page < total_count,
+        _ => false,
+    }
+}
+
+/// Self-hosted application of the hostname with an allow policy, and a service auth policy for
+/// the service token
+fn access_application_params<'a>(
+    hostname: &'a str,
+    settings: &'a AccessApplicationSettings,
+) -> self::access_apps::AccessApplicationParams<'a> {
+    use self::access_apps::{AccessApplicationParams, AccessPolicyParams};
+
+    let allow = (!settings.include.is_empty()).then(|| AccessPolicyParams {
+        name: "cloudflared-ingress allow",
+        decision: "allow",
+        precedence: 1,
+        include: settings.include.clone(),
+    });
+    let service_auth = settings
+        .service_token_id
+        .as_ref()
+        .map(|token_id| AccessPolicyParams {
+            name: "cloudflared-ingress service token",
+            decision: "non_identity",
+            precedence: 2,
+            include: vec![serde_json::json!({ "service_token": { "token_id": token_id } })],
+        });
+    AccessApplicationParams {
+        name: hostname,
+        domain: hostname,
+        app_type: "self_hosted",
+        session_duration: settings.session_duration.as_deref(),
+        allowed_idps: &settings.identity_providers,
+        policies: allow.into_iter().chain(service_auth).collect(),
+    }
+}
+
+/// Path of an endpoint with the account, zone, tunnel and record IDs replaced by `{id}`, so
+/// that the metrics stay low cardinality
+fn endpoint_label(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            let hex_id = segment.len() == 32 && segment.bytes().all(|b| b.is_ascii_hexdigit());
+            if hex_id || uuid::Uuid::try_parse(segment).is_ok() {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use cloudflare::framework::{
+        async_api::Client as HttpApiClient, auth::Credentials, Environment, HttpApiClientConfig,
+    };
+    use mockito::{Matcher, ServerGuard};
+
+    use super::*;
+
+    async fn start_mock_server() -> ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+
+        // list_tunnel or get_tunnel_opt
+        server
+            .mock(
+                "GET",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel",
+            )
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("is_deleted".into(), "false".into()),
+                Matcher::AnyOf(vec![
+                    Matcher::UrlEncoded("include_prefix".into(), "test-prefix".into()),
+                    Matcher::UrlEncoded("uuid".into(), "a0000000000000000000000000000002".into()),
+                ]),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // list tunnel connections
+        server
+            .mock(
+                "GET",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002/connections",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[
+                {"id":"00000000-0000-0000-0000-000000000001","arch":"linux_amd64","version":"2024.12.2","run_at":"2000-01-01T00:00:00.000000Z","conns":[
+                    {"id":"00000000-0000-0000-0000-000000000002","colo_name":"nrt01","is_pending_reconnect":false,"opened_at":"2000-01-01T00:00:00.000000Z","origin_ip":"192.0.2.1","client_id":"00000000-0000-0000-0000-000000000001","client_version":"2024.12.2"}
+                ]}
+            ],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // list worker routes
+        server
+            .mock("GET", "/zones/00000000000000000000000000000001/workers/routes")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[
+                {"id":"00000000000000000000000000000003","pattern":"example.example.com/*","script":"example-worker"}
+            ],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // create worker route
+        server
+            .mock("POST", "/zones/00000000000000000000000000000001/workers/routes")
+            .match_body(Matcher::Json(serde_json::json!({
+                "pattern": "example.example.com/*",
+                "script": "example-worker",
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000003","pattern":"example.example.com/*","script":"example-worker"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // delete worker route
+        server
+            .mock("DELETE", "/zones/00000000000000000000000000000001/workers/routes/00000000000000000000000000000003")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000003"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // get response header transform rules
+        server
+            .mock(
+                "GET",
+                "/zones/00000000000000000000000000000001/rulesets/phases/http_response_headers_transform/entrypoint",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000004","name":"default","kind":"zone","phase":"http_response_headers_transform","rules":[
+                {"id":"00000000000000000000000000000005","ref":"other","expression":"true","action":"rewrite","action_parameters":{"headers":{"X-Other":{"operation":"remove"}}},"enabled":true}
+            ]},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // response header transform rules not created yet
+        server
+            .mock(
+                "GET",
+                "/zones/00000000000000000000000000000002/rulesets/phases/http_response_headers_transform/entrypoint",
+            )
+            .with_status(404)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":null,"success":false,"errors":[{"code":10003,"message":"could not find entrypoint ruleset in the http_response_headers_transform phase"}],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // update response header transform rules
+        server
+            .mock(
+                "PUT",
+                "/zones/00000000000000000000000000000001/rulesets/phases/http_response_headers_transform/entrypoint",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000004","name":"default","kind":"zone","phase":"http_response_headers_transform","rules":[]},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // get WAF custom rules
+        server
+            .mock(
+                "GET",
+                "/zones/00000000000000000000000000000001/rulesets/phases/http_request_firewall_custom/entrypoint",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000006","name":"default","kind":"zone","phase":"http_request_firewall_custom","rules":[
+                {"id":"00000000000000000000000000000007","ref":"other","expression":"(ip.src eq 192.0.2.1)","action":"block","enabled":true}
+            ]},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // update WAF custom rules
+        server
+            .mock(
+                "PUT",
+                "/zones/00000000000000000000000000000001/rulesets/phases/http_request_firewall_custom/entrypoint",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000006","name":"default","kind":"zone","phase":"http_request_firewall_custom","rules":[]},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // delete dns record
+        server
+            .mock("DELETE", "/zones/00000000000000000000000000000001/dns_records/00000000000000000000000000000002")
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000001"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // verify token
+        server
+            .mock("GET", "/user/tokens/verify")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000006","status":"active","expires_on":"2000-01-31T00:00:00Z","not_before":"2000-01-01T00:00:00Z"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // list zones
+        server
+            .mock("GET", "/zones")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("page".into(), "1".into()),
+                Matcher::UrlEncoded("per_page".into(), "50".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[
+                {"id":"00000000000000000000000000000001","name":"example.com","status":"active","paused":false,"type":"full","development_mode":0,"name_servers":[],"original_name_servers":[],"original_registrar":null,"original_dnshost":null,"modified_on":"2000-01-01T00:00:00.000000Z","created_on":"2000-01-01T00:00:00.000000Z","activated_on":"2000-01-01T00:00:00.000000Z","meta":{"step":0,"custom_certificate_quota":0,"page_rule_quota":0,"phishing_detected":false},"owner":{"id":null,"type":"user","email":null},"account":{"id":"","name":"Example account"},"tenant":{},"tenant_unit":{},"permissions":[],"plan":{"id":"","name":"","price":0,"currency":"","frequency":"","is_subscribed":false,"can_subscribe":false,"legacy_id":"","legacy_discount":false,"externally_managed":false}}
+            ],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // delete tunnel
+        server
+            .mock("DELETE", "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002")
+            .match_query(
+                    Matcher::UrlEncoded("cascade".into(), "false".into()),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000001"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // batch dns records
+        server
+            .mock(
+                "POST",
+                "/zones/00000000000000000000000000000001/dns_records/batch",
+            )
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "deletes": [{"id": "00000000000000000000000000000002"}],
+                "puts": [{
+                    "id": "a0000000000000000000000000000001",
+                    "name": "www.example.com",
+                    "type": "CNAME",
+                    "content": "a0000000000000000000000000000002.cfargotunnel.com",
+                    "proxied": false,
+                }],
+                "posts": [{
+                    "name": "example.example.com",
+                    "type": "CNAME",
+                    "content": "a0000000000000000000000000000002.cfargotunnel.com",
+                    "proxied": true,
+                    "ttl": 1,
+                    "comment": "managed-by=cloudflared-ingress",
+                    "tags": ["cr:default/tunnel"],
+                }, {
+                    "name": "_cloudflared-ingress.example.example.com",
+                    "type": "TXT",
+                    "content": "\"heritage=cloudflared-ingress\"",
+                    "proxied": false,
+                    "ttl": 1,
+                }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"deletes":[{"id":"00000000000000000000000000000002"}],"puts":[{"id":"a0000000000000000000000000000001"}],"posts":[{"id":"a0000000000000000000000000000003"}]},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // create tunnel
+        server
+            .mock(
+                "POST",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel",
+            )
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "metadata": { "name": "tunnel" },
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000001","created_at":"2000-01-01T00:00:00.000000Z","deleted_at":null,"name":"example-tunnel","connections":[],"metadata":{}},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        // list dns records
+        server
+            .mock("GET", "/zones/00000000000000000000000000000001/dns_records")
+            .match_query(Matcher::AnyOf(vec![
+                Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("page".into(), "1".into()),
+                    Matcher::UrlEncoded("per_page".into(), "100".into()),
+                ]),
+                Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("type".into(), "CNAME".into()),
+                    Matcher::UrlEncoded(
+                        "content".into(),
+                        "a0000000000000000000000000000002.cfargotunnel.com".into(),
+                    ),
+                ]),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+
+        server
+    }
+
+    async fn create_api_client(url: &str) -> HttpApiClient {
+        let api = HttpApiClient::new(
+            Credentials::UserAuthToken {
+                token: "DEADBEAF".to_string(),
+            },
+            HttpApiClientConfig::default(),
+            Environment::Custom(url::Url::parse(&url).unwrap()),
+        )
+        .unwrap();
+
+        api
+    }
+
+    #[tokio::test]
+    async fn list_tunnels() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+
+        let _response = api
+            .list_tunnels(
+                "a0000000000000000000000000000001".to_string(),
+                "test-prefix".to_string(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_tunnel_opt() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let _response = api
+            .get_tunnel_opt(
+                "a0000000000000000000000000000001".to_string(),
+                "a0000000000000000000000000000002".to_string(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_tunnel() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let _response = api
+            .create_tunnel(
+                "a0000000000000000000000000000001".to_string(),
+                "tunnel-name".to_string(),
+                "tunnel-secret".as_bytes().to_vec(),
+                serde_json::json!({ "name": "tunnel" }),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_tunnel_secret() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let update = server
+            .mock(
+                "PATCH",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002",
+            )
+            .match_body(Matcher::Json(serde_json::json!({
+                "tunnel_secret": "dHVubmVsLXNlY3JldA==",
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"a0000000000000000000000000000002","created_at":"2000-01-01T00:00:00.000000Z","deleted_at":null,"name":"example-tunnel","connections":[],"metadata":{}},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        api.update_tunnel_secret(
+            "a0000000000000000000000000000001".to_string(),
+            "a0000000000000000000000000000002".to_string(),
+            b"tunnel-secret",
+        )
+        .await
+        .unwrap();
+        update.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn delete_tunnel() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let _response = api
+            .delete_tunnel(
+                "a0000000000000000000000000000001".to_string(),
+                "a0000000000000000000000000000002".to_string(),
+                false,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_tunnel_cascade() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let delete = server
+            .mock("DELETE", "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002")
+            .match_query(Matcher::UrlEncoded("cascade".into(), "true".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000001"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        api.delete_tunnel(
+            "a0000000000000000000000000000001".to_string(),
+            "a0000000000000000000000000000002".to_string(),
+            true,
+        )
+        .await
+        .unwrap();
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn list_tunnel_connections() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let connectors = api
+            .list_tunnel_connections(
+                "a0000000000000000000000000000001".to_string(),
+                "a0000000000000000000000000000002".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(1, connectors.len());
+        assert_eq!("nrt01", connectors[0].conns[0].colo_name);
+    }
+
+    #[tokio::test]
+    async fn cleanup_tunnel_connections() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let cleanup = server
+            .mock(
+                "DELETE",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel/a0000000000000000000000000000002/connections",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":null,"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        api.cleanup_tunnel_connections(
+            "a0000000000000000000000000000001".to_string(),
+            "a0000000000000000000000000000002".to_string(),
+        )
+        .await
+        .unwrap();
+        cleanup.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn list_worker_routes() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let routes = api
+            .list_worker_routes("00000000000000000000000000000001".to_string())
+            .await
+            .unwrap();
+        assert_eq!(1, routes.len());
+        assert_eq!(Some("example-worker"), routes[0].script.as_deref());
+    }
+
+    #[tokio::test]
+    async fn create_health_check() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/zones/00000000000000000000000000000001/healthchecks")
+            .match_body(Matcher::Json(serde_json::json!({
+                "name": "www-example-com",
+                "address": "www.example.com",
+                "description": "default/tunnel",
+                "type": "HTTPS",
+                "http_config": { "path": "/healthz" },
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000000000000000000000000004","name":"www-example-com","address":"www.example.com","status":"unknown","failure_reason":"","http_config":{"path":"/healthz"}},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let health_check = api
+            .create_health_check(
+                "00000000000000000000000000000001".to_string(),
+                "www-example-com".to_string(),
+                "www.example.com".to_string(),
+                "/healthz".to_string(),
+                "default/tunnel".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("00000000000000000000000000000004", health_check.id);
+        assert_eq!(Some("unknown"), health_check.status.as_deref());
+        create.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn create_access_application() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/accounts/00000000000000000000000000000000/access/apps")
+            .match_body(Matcher::Json(serde_json::json!({
+                "name": "www.example.com",
+                "domain": "www.example.com",
+                "type": "self_hosted",
+                "session_duration": "24h",
+                "allowed_idps": [],
+                "policies": [{
+                    "name": "cloudflared-ingress allow",
+                    "decision": "allow",
+                    "precedence": 1,
+                    "include": [{ "email": { "email": "user@example.com" } }],
+                }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000-0000-0000-0000-000000000005","aud":"0123456789abcdef","name":"www.example.com","domain":"www.example.com","type":"self_hosted"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let application = api
+            .create_access_application(
+                "00000000000000000000000000000000".to_string(),
+                "www.example.com".to_string(),
+                &AccessApplicationSettings {
+                    session_duration: Some("24h".to_string()),
+                    identity_providers: Vec::new(),
+                    include: vec![serde_json::json!({ "email": { "email": "user@example.com" } })],
+                    service_token_id: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!("00000000-0000-0000-0000-000000000005", application.id);
+        assert_eq!(Some("0123456789abcdef"), application.aud.as_deref());
+        create.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn service_token_policy() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let token = server
+            .mock("POST", "/accounts/00000000000000000000000000000000/access/service_tokens")
+            .match_body(Matcher::Json(serde_json::json!({ "name": "api.example.com" })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000-0000-0000-0000-000000000006","name":"api.example.com","client_id":"0123456789abcdef.access","client_secret":"secret"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let update = server
+            .mock(
+                "PUT",
+                "/accounts/00000000000000000000000000000000/access/apps/00000000-0000-0000-0000-000000000005",
+            )
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "policies": [{
+                    "name": "cloudflared-ingress service token",
+                    "decision": "non_identity",
+                    "precedence": 2,
+                    "include": [{
+                        "service_token": { "token_id": "00000000-0000-0000-0000-000000000006" }
+                    }],
+                }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000-0000-0000-0000-000000000005","aud":"0123456789abcdef","name":"api.example.com","domain":"api.example.com","type":"self_hosted"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let service_token = api
+            .create_access_service_token(
+                "00000000000000000000000000000000".to_string(),
+                "api.example.com".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(Some("secret"), service_token.client_secret.as_deref());
+        api.update_access_application(
+            "00000000000000000000000000000000".to_string(),
+            "00000000-0000-0000-0000-000000000005".to_string(),
+            "api.example.com".to_string(),
+            &AccessApplicationSettings {
+                service_token_id: Some(service_token.id),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        token.assert_async().await;
+        update.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn find_tunnel_route() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/accounts/00000000000000000000000000000000/teamnet/routes")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("is_deleted".into(), "false".into()),
+                Matcher::UrlEncoded("comment".into(), "default/services".into()),
             ]))
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .with_body(r#"{"result":[{"id":"00000000-0000-0000-0000-000000000006","network":"10.96.0.0/12","tunnel_id":"00000000-0000-0000-0000-000000000001","comment":"default/services-b","virtual_network_id":"00000000-0000-0000-0000-000000000008"},{"id":"00000000-0000-0000-0000-000000000007","network":"10.96.0.0/12","tunnel_id":"00000000-0000-0000-0000-000000000001","comment":"default/services","virtual_network_id":"00000000-0000-0000-0000-000000000008"}],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
             .create_async()
             .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let route = api
+            .find_tunnel_route(
+                "00000000000000000000000000000000".to_string(),
+                "10.96.0.0/12",
+                "default/services",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            Some("00000000-0000-0000-0000-000000000007".to_string()),
+            route.map(|r| r.id)
+        );
+        list.assert_async().await;
+    }
 
-        server
+    #[tokio::test]
+    async fn create_tunnel_route() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/accounts/00000000000000000000000000000000/teamnet/routes")
+            .match_body(Matcher::Json(serde_json::json!({
+                "network": "10.96.0.0/12",
+                "tunnel_id": "00000000-0000-0000-0000-000000000001",
+                "comment": "default/services",
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000-0000-0000-0000-000000000007","network":"10.96.0.0/12","tunnel_id":"00000000-0000-0000-0000-000000000001","comment":"default/services","virtual_network_id":"00000000-0000-0000-0000-000000000008"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let route = api
+            .create_tunnel_route(
+                "00000000000000000000000000000000".to_string(),
+                &TunnelRouteSettings {
+                    network: "10.96.0.0/12".to_string(),
+                    tunnel_id: "00000000-0000-0000-0000-000000000001".to_string(),
+                    comment: "default/services".to_string(),
+                    virtual_network_id: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!("00000000-0000-0000-0000-000000000007", route.id);
+        assert_eq!(
+            Some("00000000-0000-0000-0000-000000000008"),
+            route.virtual_network_id.as_deref()
+        );
+        create.assert_async().await;
     }
 
-    async fn create_api_client(url: &str) -> HttpApiClient {
-        let api = HttpApiClient::new(
-            Credentials::UserAuthToken {
-                token: "DEADBEAF".to_string(),
-            },
-            HttpApiClientConfig::default(),
-            Environment::Custom(url::Url::parse(&url).unwrap()),
+    #[tokio::test]
+    async fn create_dns_record() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/zones/00000000000000000000000000000003/dns_records")
+            .match_body(Matcher::Json(serde_json::json!({
+                "name": "mail.example.com",
+                "type": "A",
+                "content": "192.0.2.1",
+                "proxied": false,
+                "ttl": 300,
+                "comment": "default/mail",
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"a0000000000000000000000000000009","zone_id":"00000000000000000000000000000003","zone_name":"example.com","name":"mail.example.com","type":"A","content":"192.0.2.1","proxiable":true,"proxied":false,"ttl":300,"settings":{},"meta":{"auto_added":false,"managed_by_apps":false,"managed_by_argo_tunnel":false},"comment":"default/mail","tags":[],"created_on":"2000-01-01T00:00:00.000000Z","modified_on":"2000-01-01T00:00:00.000000Z"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let record = api
+            .create_dns_record(
+                "00000000000000000000000000000003".to_string(),
+                "mail.example.com".to_string(),
+                DnsContent::A {
+                    content: "192.0.2.1".parse().unwrap(),
+                },
+                &DnsRecordSettings {
+                    proxied: false,
+                    ttl: 300,
+                    comment: Some("default/mail".to_string()),
+                    tags: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!("a0000000000000000000000000000009", record.id);
+        create.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn get_dns_record_comment() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let get = server
+            .mock(
+                "GET",
+                "/zones/00000000000000000000000000000003/dns_records/a0000000000000000000000000000009",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"a0000000000000000000000000000009","zone_id":"00000000000000000000000000000003","zone_name":"example.com","name":"mail.example.com","type":"A","content":"192.0.2.1","proxiable":true,"proxied":false,"ttl":300,"settings":{},"meta":{"auto_added":false,"managed_by_apps":false,"managed_by_argo_tunnel":false},"comment":"default/mail","tags":[],"created_on":"2000-01-01T00:00:00.000000Z","modified_on":"2000-01-01T00:00:00.000000Z"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let comment = api
+            .get_dns_record_comment(
+                "00000000000000000000000000000003".to_string(),
+                "a0000000000000000000000000000009".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(Some("default/mail".to_string()), comment);
+        get.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn update_virtual_network() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let update = server
+            .mock(
+                "PATCH",
+                "/accounts/00000000000000000000000000000000/teamnet/virtual_networks/00000000-0000-0000-0000-000000000008",
+            )
+            .match_body(Matcher::Json(serde_json::json!({
+                "name": "cluster-b",
+                "comment": "default/cluster",
+                "is_default_network": false,
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{"id":"00000000-0000-0000-0000-000000000008","name":"cluster-b","comment":"default/cluster","is_default_network":false,"created_at":"2000-01-01T00:00:00Z"},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let network = api
+            .update_virtual_network(
+                "00000000000000000000000000000000".to_string(),
+                "00000000-0000-0000-0000-000000000008".to_string(),
+                &VirtualNetworkSettings {
+                    name: "cluster-b".to_string(),
+                    comment: "default/cluster".to_string(),
+                    is_default_network: false,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!("cluster-b", network.name);
+        update.assert_async().await;
+    }
+
+    #[test]
+    fn endpoint_label_hides_ids() {
+        assert_eq!(
+            "zones/{id}/dns_records/{id}",
+            endpoint_label(
+                "zones/00000000000000000000000000000001/dns_records/a0000000000000000000000000000001"
+            )
+        );
+        assert_eq!(
+            "accounts/{id}/cfd_tunnel/{id}/connections",
+            endpoint_label(
+                "accounts/a0000000000000000000000000000001/cfd_tunnel/00000000-0000-0000-0000-000000000001/connections"
+            )
+        );
+        assert_eq!(
+            "zones/{id}/rulesets/phases/http_request_firewall_custom/entrypoint",
+            endpoint_label(
+                "zones/00000000000000000000000000000001/rulesets/phases/http_request_firewall_custom/entrypoint"
+            )
+        );
+        assert_eq!("user/tokens/verify", endpoint_label("user/tokens/verify"));
+    }
+
+    #[tokio::test]
+    async fn retry_transient_failure() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server
+            .mock("GET", "/zones/00000000000000000000000000000001/workers/routes")
+            .with_status(503)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":null,"success":false,"errors":[{"code":10000,"message":"service unavailable"}],"messages":[]}"#)
+            .expect(3)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api)).with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(1),
+            rate_limit_delay: std::time::Duration::from_millis(1),
+        });
+        let error = api
+            .list_worker_routes("00000000000000000000000000000001".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::CloudflareApiFailure { .. }));
+        unavailable.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn no_retry_of_failed_post() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server
+            .mock("POST", "/zones/00000000000000000000000000000001/workers/routes")
+            .with_status(503)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":null,"success":false,"errors":[{"code":10000,"message":"service unavailable"}],"messages":[]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api)).with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(1),
+            rate_limit_delay: std::time::Duration::from_millis(1),
+        });
+        api.create_worker_route(
+            "00000000000000000000000000000001".to_string(),
+            "example.example.com/*".to_string(),
+            "example-worker".to_string(),
         )
-        .unwrap();
+        .await
+        .unwrap_err();
+        unavailable.assert_async().await;
+    }
 
-        api
+    #[tokio::test]
+    async fn create_worker_route() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let route = api
+            .create_worker_route(
+                "00000000000000000000000000000001".to_string(),
+                "example.example.com/*".to_string(),
+                "example-worker".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!("00000000000000000000000000000003", route.id);
     }
 
     #[tokio::test]
-    async fn list_tunnels() {
+    async fn delete_worker_route() {
         let _ = env_logger::try_init();
         let server = start_mock_server().await;
         let api = create_api_client(server.url().as_str()).await;
         let api = CloudflareApi::new(Arc::new(api));
+        api.delete_worker_route(
+            "00000000000000000000000000000001".to_string(),
+            "00000000000000000000000000000003".to_string(),
+        )
+        .await
+        .unwrap();
+    }
 
-        let _response = api
-            .list_tunnels(
-                "a0000000000000000000000000000001".to_string(),
-                "test-prefix".to_string(),
-            )
+    #[tokio::test]
+    async fn list_response_header_rules() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let rules = api
+            .list_response_header_rules("00000000000000000000000000000001".to_string())
+            .await
+            .unwrap();
+        assert_eq!(1, rules.len());
+        assert_eq!("other", rules[0]["ref"]);
+
+        let rules = api
+            .list_response_header_rules("00000000000000000000000000000002".to_string())
             .await
             .unwrap();
+        assert!(rules.is_empty());
     }
 
     #[tokio::test]
-    async fn get_tunnel_opt() {
+    async fn update_response_header_rules() {
         let _ = env_logger::try_init();
         let server = start_mock_server().await;
         let api = create_api_client(server.url().as_str()).await;
         let api = CloudflareApi::new(Arc::new(api));
-        let _response = api
-            .get_tunnel_opt(
-                "a0000000000000000000000000000001".to_string(),
-                "a0000000000000000000000000000002".to_string(),
-            )
+        api.update_response_header_rules("00000000000000000000000000000001".to_string(), vec![])
             .await
             .unwrap();
     }
 
     #[tokio::test]
-    async fn create_tunnel() {
+    async fn list_firewall_custom_rules() {
         let _ = env_logger::try_init();
         let server = start_mock_server().await;
         let api = create_api_client(server.url().as_str()).await;
         let api = CloudflareApi::new(Arc::new(api));
-        let _response = api
-            .create_tunnel(
-                "a0000000000000000000000000000001".to_string(),
-                "tunnel-name".to_string(),
-                "tunnel-secret".as_bytes().to_vec(),
-            )
+        let rules = api
+            .list_firewall_custom_rules("00000000000000000000000000000001".to_string())
             .await
             .unwrap();
+        assert_eq!(1, rules.len());
+        assert_eq!("other", rules[0]["ref"]);
     }
 
     #[tokio::test]
-    async fn delete_tunnel() {
+    async fn update_firewall_custom_rules() {
         let _ = env_logger::try_init();
         let server = start_mock_server().await;
         let api = create_api_client(server.url().as_str()).await;
         let api = CloudflareApi::new(Arc::new(api));
-        let _response = api
-            .delete_tunnel(
-                "a0000000000000000000000000000001".to_string(),
-                "a0000000000000000000000000000002".to_string(),
-            )
+        api.update_firewall_custom_rules("00000000000000000000000000000001".to_string(), vec![])
             .await
             .unwrap();
     }
@@ -405,6 +2328,42 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn list_marked_dns_cname() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/zones/00000000000000000000000000000001/dns_records")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("type".into(), "CNAME".into()),
+                Matcher::UrlEncoded(
+                    "content".into(),
+                    "a0000000000000000000000000000002.cfargotunnel.com".into(),
+                ),
+                Matcher::UrlEncoded(
+                    "comment.contains".into(),
+                    "managed-by=cloudflared-ingress".into(),
+                ),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let response = api
+            .list_marked_dns_cname(
+                "00000000000000000000000000000001".to_string(),
+                "a0000000000000000000000000000002".to_string(),
+                "managed-by=cloudflared-ingress".to_string(),
+            )
+            .await
+            .unwrap();
+        assert!(response.is_empty());
+        list.assert_async().await;
+    }
+
     #[tokio::test]
     async fn list_dns() {
         let _ = env_logger::try_init();
@@ -418,20 +2377,116 @@ mod test {
     }
 
     #[tokio::test]
-    async fn create_dns_cname() {
+    async fn list_dns_pages() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        for page in 1..=2 {
+            server
+                .mock("GET", "/zones/00000000000000000000000000000003/dns_records")
+                .match_query(Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("page".into(), page.to_string()),
+                    Matcher::UrlEncoded("per_page".into(), "1".into()),
+                ]))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(format!(r#"{{"result":[
+                    {{"id":"a000000000000000000000000000000{page}","zone_id":"00000000000000000000000000000003","zone_name":"example.com","name":"www{page}.example.com","type":"CNAME","content":"example.com","proxiable":true,"proxied":true,"ttl":1,"settings":{{}},"meta":{{"auto_added":false,"managed_by_apps":false,"managed_by_argo_tunnel":false}},"comment":null,"tags":[],"created_on":"2000-01-01T00:00:00.000000Z","modified_on":"2000-01-01T00:00:00.000000Z"}}
+                ],"result_info":{{"page":{page},"per_page":1,"count":1,"total_count":2,"total_pages":2}},"success":true,"errors":[],"messages":[]}}"#))
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api)).with_per_page(1);
+        let records = api
+            .list_dns("00000000000000000000000000000003".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["www1.example.com", "www2.example.com"],
+            records.iter().map(|r| r.name.as_str()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn next_page() {
+        let info = serde_json::json!({"page": 1, "per_page": 2, "total_count": 3});
+        assert!(has_next_page(Some(&info), 1, 2));
+        assert!(!has_next_page(Some(&info), 2, 1));
+        let info = serde_json::json!({"page": 1, "total_pages": 1});
+        assert!(!has_next_page(Some(&info), 1, 100));
+        assert!(!has_next_page(None, 1, 100));
+        assert!(!has_next_page(Some(&serde_json::json!({})), 1, 100));
+    }
+
+    fn dns_cname_batch() -> DnsCnameBatch {
+        DnsCnameBatch {
+            deletes: vec!["00000000000000000000000000000002".to_string()],
+            updates: vec![(
+                "a0000000000000000000000000000001".to_string(),
+                "www.example.com".to_string(),
+                DnsRecordSettings {
+                    proxied: false,
+                    ..Default::default()
+                },
+            )],
+            creates: vec![(
+                "example.example.com".to_string(),
+                DnsRecordSettings {
+                    comment: Some("managed-by=cloudflared-ingress".to_string()),
+                    tags: vec!["cr:default/tunnel".to_string()],
+                    ..Default::default()
+                },
+            )],
+            txt_creates: vec![(
+                "_cloudflared-ingress.example.example.com".to_string(),
+                "\"heritage=cloudflared-ingress\"".to_string(),
+            )],
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_dns_cname() {
         let _ = env_logger::try_init();
         let server = start_mock_server().await;
         let api = create_api_client(server.url().as_str()).await;
         let api = CloudflareApi::new(Arc::new(api));
 
-        let _response = api
-            .create_dns_cname(
-                "00000000000000000000000000000001".to_string(),
-                "a0000000000000000000000000000002".to_string(),
-                "example.example.com".to_string(),
+        api.batch_dns_cname(
+            "00000000000000000000000000000001".to_string(),
+            "a0000000000000000000000000000002".to_string(),
+            &dns_cname_batch(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn batch_dns_cname_chunks() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        let batch = server
+            .mock(
+                "POST",
+                "/zones/00000000000000000000000000000001/dns_records/batch",
             )
-            .await
-            .unwrap();
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":{},"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api)).with_dns_batch_size(2);
+
+        api.batch_dns_cname(
+            "00000000000000000000000000000001".to_string(),
+            "a0000000000000000000000000000002".to_string(),
+            &dns_cname_batch(),
+        )
+        .await
+        .unwrap();
+        batch.assert_async().await;
     }
 
     #[tokio::test]
@@ -450,6 +2505,54 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn verify_token() {
+        let _ = env_logger::try_init();
+        let server = start_mock_server().await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+        let token = api.verify_token().await.unwrap();
+        assert_eq!("active", token.status);
+        assert_eq!(
+            Some("2000-01-31T00:00:00+00:00".to_string()),
+            token.expires_on.map(|t| t.to_rfc3339())
+        );
+    }
+
+    #[tokio::test]
+    async fn check_access() {
+        let _ = env_logger::try_init();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/zones")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":[],"result_info":{},"success":true,"errors":[],"messages":[]}"#)
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                "/accounts/a0000000000000000000000000000001/cfd_tunnel",
+            )
+            .match_query(Matcher::Any)
+            .with_status(403)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":null,"success":false,"errors":[{"code":10000,"message":"Authentication error"}],"messages":[]}"#)
+            .create_async()
+            .await;
+        let api = create_api_client(server.url().as_str()).await;
+        let api = CloudflareApi::new(Arc::new(api));
+
+        let error = api
+            .check_access("a0000000000000000000000000000001".to_string())
+            .await
+            .unwrap_err();
+        assert!(error.is_rejected_credentials());
+        assert!(error.is_insufficient_token_permissions());
+    }
+
     #[tokio::test]
     async fn list_zone() {
         let _ = env_logger::try_init();
@@ -0,0 +1,145 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the Access applications of an account, a page at a time
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/applications/methods/list/>
+#[derive(Debug)]
+pub struct ListAccessApplications<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListAccessApplicationsParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListAccessApplicationsParams {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl Endpoint<Vec<AccessApplication>, ListAccessApplicationsParams> for ListAccessApplications<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/access/apps", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<ListAccessApplicationsParams> {
+        Some(self.params.clone())
+    }
+}
+
+/// Create a self-hosted Access application with its policies
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/applications/methods/create/>
+#[derive(Debug)]
+pub struct CreateAccessApplication<'a> {
+    pub account_identifier: &'a str,
+    pub params: AccessApplicationParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AccessApplicationParams<'a> {
+    pub name: &'a str,
+    pub domain: &'a str,
+    #[serde(rename = "type")]
+    pub app_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_duration: Option<&'a str>,
+    pub allowed_idps: &'a [String],
+    pub policies: Vec<AccessPolicyParams<'a>>,
+}
+
+/// Policy of an application, created together with it
+#[derive(Serialize, Clone, Debug)]
+pub struct AccessPolicyParams<'a> {
+    pub name: &'a str,
+    pub decision: &'a str,
+    pub precedence: u32,
+    pub include: Vec<serde_json::Value>,
+}
+
+impl<'a> Endpoint<AccessApplication, (), AccessApplicationParams<'a>>
+    for CreateAccessApplication<'a>
+{
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/access/apps", self.account_identifier)
+    }
+
+    fn body(&self) -> Option<AccessApplicationParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Replace the settings and policies of an Access application
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/applications/methods/update/>
+#[derive(Debug)]
+pub struct UpdateAccessApplication<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: AccessApplicationParams<'a>,
+}
+
+impl<'a> Endpoint<AccessApplication, (), AccessApplicationParams<'a>>
+    for UpdateAccessApplication<'a>
+{
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/access/apps/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+
+    fn body(&self) -> Option<AccessApplicationParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Delete an Access application
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/applications/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteAccessApplication<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<AccessApplicationId> for DeleteAccessApplication<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/access/apps/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AccessApplication {
+    pub id: String,
+    /// Audience tag of the application, verified by the origin
+    pub aud: Option<String>,
+    pub name: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl ApiResult for AccessApplication {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AccessApplicationId {
+    pub id: String,
+}
+
+impl ApiResult for AccessApplicationId {}
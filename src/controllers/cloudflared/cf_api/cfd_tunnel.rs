@@ -0,0 +1,139 @@
+use cloudflare::{
+    endpoints::cfd_tunnel::Tunnel,
+    framework::{
+        endpoint::{Endpoint, Method},
+        response::ApiResult,
+    },
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// List the connectors (cloudflared processes) currently attached to a tunnel
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/connections/methods/get/>
+#[derive(Debug)]
+pub struct ListTunnelConnections<'a> {
+    pub account_identifier: &'a str,
+    pub tunnel_id: &'a str,
+}
+
+impl Endpoint<Vec<TunnelConnector>> for ListTunnelConnections<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/connections",
+            self.account_identifier, self.tunnel_id
+        )
+    }
+}
+
+/// Replace the secret of a tunnel, which the endpoint of cloudflare-rs does not support
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/cloudflared/methods/edit/>
+#[derive(Debug)]
+pub struct UpdateTunnelSecret<'a> {
+    pub account_identifier: &'a str,
+    pub tunnel_id: &'a str,
+    pub params: UpdateTunnelSecretParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpdateTunnelSecretParams {
+    /// Base64 encoded
+    pub tunnel_secret: String,
+}
+
+impl Endpoint<Tunnel, (), UpdateTunnelSecretParams> for UpdateTunnelSecret<'_> {
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}",
+            self.account_identifier, self.tunnel_id
+        )
+    }
+
+    fn body(&self) -> Option<UpdateTunnelSecretParams> {
+        Some(self.params.clone())
+    }
+}
+
+/// Remove the connections of a tunnel, including those left by a crashed connector
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/subresources/connections/methods/delete/>
+#[derive(Debug)]
+pub struct CleanupTunnelConnections<'a> {
+    pub account_identifier: &'a str,
+    pub tunnel_id: &'a str,
+}
+
+impl Endpoint<CleanupTunnelConnectionsResult> for CleanupTunnelConnections<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/cfd_tunnel/{}/connections",
+            self.account_identifier, self.tunnel_id
+        )
+    }
+}
+
+/// The result carries nothing of use
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CleanupTunnelConnectionsResult {}
+
+impl ApiResult for CleanupTunnelConnectionsResult {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TunnelConnector {
+    pub id: Uuid,
+    pub arch: Option<String>,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub conns: Vec<TunnelConnectorConnection>,
+}
+
+impl ApiResult for TunnelConnector {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TunnelConnectorConnection {
+    pub colo_name: String,
+    #[serde(default)]
+    pub is_pending_reconnect: bool,
+}
+
+/// List the tunnels of an account a page at a time, which the endpoint of cloudflare-rs does
+/// not support
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/tunnels/methods/list/>
+#[derive(Debug)]
+pub struct ListTunnelsPage<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListTunnelsPageParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListTunnelsPageParams<'a> {
+    pub is_deleted: bool,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub include_prefix: &'a str,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl<'a> Endpoint<Vec<Tunnel>, ListTunnelsPageParams<'a>> for ListTunnelsPage<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/cfd_tunnel", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<ListTunnelsPageParams<'a>> {
+        Some(self.params.clone())
+    }
+}
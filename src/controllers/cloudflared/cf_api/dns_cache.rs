@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use cloudflare::endpoints::dns::DnsRecord;
+
+/// DNS records of each zone, kept until they expire or the controller changes a record of
+/// the zone
+#[derive(Debug, Default)]
+pub(super) struct DnsRecordCache {
+    ttl: Duration,
+    zones: Mutex<HashMap<String, CachedZone>>,
+}
+
+#[derive(Debug, Default)]
+struct CachedZone {
+    /// Incremented on every change, so that a list started before a change is not cached
+    generation: u64,
+    records: Option<(Instant, Arc<Vec<DnsRecord>>)>,
+}
+
+impl DnsRecordCache {
+    pub(super) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            zones: Mutex::default(),
+        }
+    }
+
+    pub(super) fn get(&self, zone_id: &str, now: Instant) -> Option<Arc<Vec<DnsRecord>>> {
+        let zones = self.zones.lock().unwrap();
+        let (fetched_at, records) = zones.get(zone_id)?.records.as_ref()?;
+        (now.saturating_duration_since(*fetched_at) < self.ttl).then(|| records.clone())
+    }
+
+    /// Generation of the zone to pass to [`Self::insert`] with the records listed afterwards
+    pub(super) fn generation(&self, zone_id: &str) -> u64 {
+        self.zones
+            .lock()
+            .unwrap()
+            .get(zone_id)
+            .map_or(0, |z| z.generation)
+    }
+
+    /// Cache the records listed since `generation`, unless the zone has changed meanwhile
+    pub(super) fn insert(
+        &self,
+        zone_id: &str,
+        generation: u64,
+        records: Vec<DnsRecord>,
+        now: Instant,
+    ) -> Arc<Vec<DnsRecord>> {
+        let records = Arc::new(records);
+        let mut zones = self.zones.lock().unwrap();
+        let zone = zones.entry(zone_id.to_string()).or_default();
+        if zone.generation == generation {
+            zone.records = Some((now, records.clone()));
+        }
+        records
+    }
+
+    pub(super) fn invalidate(&self, zone_id: &str) {
+        let mut zones = self.zones.lock().unwrap();
+        let zone = zones.entry(zone_id.to_string()).or_default();
+        zone.generation += 1;
+        zone.records = None;
+    }
+
+    /// Drop the records of every zone, e.g. before a forced resync
+    pub(super) fn invalidate_all(&self) {
+        for zone in self.zones.lock().unwrap().values_mut() {
+            zone.generation += 1;
+            zone.records = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expires_after_ttl() {
+        let cache = DnsRecordCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let generation = cache.generation("zone");
+        cache.insert("zone", generation, Vec::new(), now);
+        assert!(cache.get("zone", now + Duration::from_secs(59)).is_some());
+        assert!(cache.get("zone", now + Duration::from_secs(60)).is_none());
+        assert!(cache.get("other", now).is_none());
+    }
+
+    #[test]
+    fn change_invalidates_the_zone() {
+        let cache = DnsRecordCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert("zone", cache.generation("zone"), Vec::new(), now);
+        cache.invalidate("zone");
+        assert!(cache.get("zone", now).is_none());
+    }
+
+    #[test]
+    fn resync_invalidates_every_zone() {
+        let cache = DnsRecordCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert("zone", cache.generation("zone"), Vec::new(), now);
+        cache.insert("other", cache.generation("other"), Vec::new(), now);
+        cache.invalidate_all();
+        assert!(cache.get("zone", now).is_none());
+        assert!(cache.get("other", now).is_none());
+    }
+
+    #[test]
+    fn list_started_before_a_change_is_not_cached() {
+        let cache = DnsRecordCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let generation = cache.generation("zone");
+        cache.invalidate("zone");
+        cache.insert("zone", generation, Vec::new(), now);
+        assert!(cache.get("zone", now).is_none());
+    }
+
+    #[test]
+    fn zero_ttl_disables_the_cache() {
+        let cache = DnsRecordCache::new(Duration::ZERO);
+        let now = Instant::now();
+        cache.insert("zone", cache.generation("zone"), Vec::new(), now);
+        assert!(cache.get("zone", now).is_none());
+    }
+}
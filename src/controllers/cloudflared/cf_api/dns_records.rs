@@ -0,0 +1,215 @@
+use cloudflare::{
+    endpoints::dns::{DnsContent, DnsRecord},
+    framework::{
+        endpoint::{Endpoint, Method},
+        response::ApiResult,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// Apply DNS record changes of a zone in one transaction, deletes first, then puts and posts.
+/// Records are created with a comment and tags, which the endpoints of cloudflare-rs do not
+/// support
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/batch/>
+#[derive(Debug)]
+pub struct BatchDnsRecords<'a> {
+    pub zone_identifier: &'a str,
+    pub params: BatchDnsRecordsParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BatchDnsRecordsParams<'a> {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deletes: Vec<DnsRecordId<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub puts: Vec<PutDnsRecord<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub posts: Vec<DnsRecordParams<'a>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DnsRecordId<'a> {
+    pub id: &'a str,
+}
+
+/// Overwrite a DNS record, including its comment and tags
+#[derive(Serialize, Clone, Debug)]
+pub struct PutDnsRecord<'a> {
+    pub id: &'a str,
+    #[serde(flatten)]
+    pub params: DnsRecordParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DnsRecordParams<'a> {
+    pub name: &'a str,
+    #[serde(flatten)]
+    pub content: DnsContent,
+    pub proxied: bool,
+    /// 1 for automatic
+    pub ttl: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub tags: &'a [String],
+}
+
+/// A change of a batch, split into requests in this order
+#[derive(Clone, Debug)]
+pub enum DnsRecordChange<'a> {
+    Delete(DnsRecordId<'a>),
+    Put(PutDnsRecord<'a>),
+    Post(DnsRecordParams<'a>),
+}
+
+impl<'a> FromIterator<DnsRecordChange<'a>> for BatchDnsRecordsParams<'a> {
+    fn from_iter<T: IntoIterator<Item = DnsRecordChange<'a>>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |mut params, change| {
+                match change {
+                    DnsRecordChange::Delete(record) => params.deletes.push(record),
+                    DnsRecordChange::Put(record) => params.puts.push(record),
+                    DnsRecordChange::Post(record) => params.posts.push(record),
+                }
+                params
+            })
+    }
+}
+
+impl<'a> Endpoint<BatchDnsRecordsResult, (), BatchDnsRecordsParams<'a>> for BatchDnsRecords<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records/batch", self.zone_identifier)
+    }
+
+    fn body(&self) -> Option<BatchDnsRecordsParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Records are kept as raw JSON, only the number of applied changes is used
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BatchDnsRecordsResult {
+    #[serde(default)]
+    pub deletes: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub puts: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub posts: Vec<serde_json::Value>,
+}
+
+impl ApiResult for BatchDnsRecordsResult {}
+
+/// List the CNAME records of a target whose comment contains a marker, which the endpoint of
+/// cloudflare-rs cannot filter on
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/list/>
+#[derive(Debug)]
+pub struct ListMarkedDnsCnames<'a> {
+    pub zone_identifier: &'a str,
+    pub params: ListMarkedDnsCnamesParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListMarkedDnsCnamesParams<'a> {
+    #[serde(rename = "type")]
+    pub record_type: &'a str,
+    pub content: &'a str,
+    #[serde(rename = "comment.contains")]
+    pub comment_contains: &'a str,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl<'a> Endpoint<Vec<DnsRecord>, ListMarkedDnsCnamesParams<'a>> for ListMarkedDnsCnames<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records", self.zone_identifier)
+    }
+
+    fn query(&self) -> Option<ListMarkedDnsCnamesParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Get the comment of a DNS record, which the record of cloudflare-rs does not hold
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/get/>
+#[derive(Debug)]
+pub struct GetDnsRecordComment<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl<'a> Endpoint<DnsRecordComment> for GetDnsRecordComment<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/dns_records/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DnsRecordComment {
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl ApiResult for DnsRecordComment {}
+
+/// Create a DNS record with a comment and tags
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/create/>
+#[derive(Debug)]
+pub struct CreateDnsRecord<'a> {
+    pub zone_identifier: &'a str,
+    pub params: DnsRecordParams<'a>,
+}
+
+impl<'a> Endpoint<DnsRecord, (), DnsRecordParams<'a>> for CreateDnsRecord<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records", self.zone_identifier)
+    }
+
+    fn body(&self) -> Option<DnsRecordParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Overwrite a DNS record, including its comment and tags
+/// <https://developers.cloudflare.com/api/resources/dns/subresources/records/methods/update/>
+#[derive(Debug)]
+pub struct UpdateDnsRecord<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: DnsRecordParams<'a>,
+}
+
+impl<'a> Endpoint<DnsRecord, (), DnsRecordParams<'a>> for UpdateDnsRecord<'a> {
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/dns_records/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+
+    fn body(&self) -> Option<DnsRecordParams<'a>> {
+        Some(self.params.clone())
+    }
+}
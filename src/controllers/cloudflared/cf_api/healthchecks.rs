@@ -0,0 +1,106 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the standalone health checks of a zone
+/// <https://developers.cloudflare.com/api/resources/healthchecks/methods/list/>
+#[derive(Debug)]
+pub struct ListHealthChecks<'a> {
+    pub zone_identifier: &'a str,
+}
+
+impl Endpoint<Vec<HealthCheck>> for ListHealthChecks<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/healthchecks", self.zone_identifier)
+    }
+}
+
+/// Create an HTTPS health check of a hostname
+/// <https://developers.cloudflare.com/api/resources/healthchecks/methods/create/>
+#[derive(Debug)]
+pub struct CreateHealthCheck<'a> {
+    pub zone_identifier: &'a str,
+    pub params: CreateHealthCheckParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateHealthCheckParams<'a> {
+    pub name: &'a str,
+    pub address: &'a str,
+    pub description: &'a str,
+    #[serde(rename = "type")]
+    pub check_type: &'a str,
+    pub http_config: HealthCheckHttpConfig<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HealthCheckHttpConfig<'a> {
+    pub path: &'a str,
+}
+
+impl<'a> Endpoint<HealthCheck, (), CreateHealthCheckParams<'a>> for CreateHealthCheck<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/healthchecks", self.zone_identifier)
+    }
+
+    fn body(&self) -> Option<CreateHealthCheckParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Delete a health check
+/// <https://developers.cloudflare.com/api/resources/healthchecks/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteHealthCheck<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<HealthCheckId> for DeleteHealthCheck<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/healthchecks/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthCheck {
+    pub id: String,
+    pub name: String,
+    pub address: String,
+    pub description: Option<String>,
+    /// `unknown`, `healthy`, `unhealthy` or `suspended`
+    pub status: Option<String>,
+    pub failure_reason: Option<String>,
+    pub http_config: Option<HealthCheckHttpConfigResult>,
+}
+
+impl ApiResult for HealthCheck {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthCheckHttpConfigResult {
+    pub path: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthCheckId {
+    pub id: String,
+}
+
+impl ApiResult for HealthCheckId {}
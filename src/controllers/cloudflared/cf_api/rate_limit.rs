@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// Token bucket limiting the rate of the Cloudflare API requests
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens added per second
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket::new(requests_per_second, burst, Instant::now())),
+        }
+    }
+
+    /// Wait until a request is allowed
+    pub(super) async fn acquire(&self) {
+        // 待機中もlockを保持し、待機しているrequestを到着順に通す
+        let mut bucket = self.bucket.lock().await;
+        if let Some(wait) = bucket.take(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Bucket {
+    fn new(rate: f64, burst: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            burst: burst as f64,
+            tokens: burst as f64,
+            updated_at: now,
+        }
+    }
+
+    /// Take a token, returning how long to wait for it when the bucket is empty
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated_at = now;
+        self.tokens -= 1.0;
+        // 不足分は待機後に補充されるため、tokenは負の値のまま持ち越す
+        (self.tokens < 0.0).then(|| Duration::from_secs_f64(-self.tokens / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn burst_then_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2.0, 3, start);
+        assert_eq!(None, bucket.take(start));
+        assert_eq!(None, bucket.take(start));
+        assert_eq!(None, bucket.take(start));
+        assert_eq!(Some(Duration::from_millis(500)), bucket.take(start));
+        assert_eq!(Some(Duration::from_secs(1)), bucket.take(start));
+        // 待機後のrequestは補充されたtokenを使う
+        let later = start + Duration::from_secs(1);
+        assert_eq!(Some(Duration::from_millis(500)), bucket.take(later));
+    }
+
+    #[test]
+    fn refill_is_capped_at_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(10.0, 2, start);
+        let later = start + Duration::from_secs(60);
+        assert_eq!(None, bucket.take(later));
+        assert_eq!(None, bucket.take(later));
+        assert!(bucket.take(later).is_some());
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use cloudflare::framework::{endpoint::Method, response::ApiFailure};
+use rand::Rng as _;
+
+/// Retries of the Cloudflare API requests failed by a transient error
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay of the first retry, doubled on each retry
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Least delay of a retry after a rate limit, as the Cloudflare client does not expose
+    /// the `Retry-After` header
+    pub rate_limit_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            rate_limit_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Upper bound of the delay before the retry following `attempt` failed requests
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+
+    /// Delay before the retry following `attempt` failed requests, jittered between half and
+    /// all of the backoff so that concurrent reconciles do not retry at once
+    pub(super) fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        let half = backoff / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+
+    /// Delay before the retry of a request failed by `failure`, waiting at least
+    /// [`Self::rate_limit_delay`] once rate limited
+    pub(super) fn delay_after(&self, attempt: u32, failure: &ApiFailure) -> Duration {
+        let delay = self.delay(attempt);
+        if is_rate_limited(failure) {
+            delay.max(self.rate_limit_delay)
+        } else {
+            delay
+        }
+    }
+}
+
+fn is_rate_limited(failure: &ApiFailure) -> bool {
+    matches!(failure, ApiFailure::Error(status, _) if status.as_u16() == 429)
+}
+
+/// Whether sending the request again has the same effect as sending it once
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+/// Whether the request failed by rate limiting or a connection error, which Cloudflare has
+/// not processed, or by a server error or a timeout of an idempotent request. A POST that
+/// timed out may have created the resource, and sending it again would create a duplicate
+pub(super) fn is_retryable(method: &Method, failure: &ApiFailure) -> bool {
+    match failure {
+        _ if is_rate_limited(failure) => true,
+        ApiFailure::Error(status, _) => status.is_server_error() && is_idempotent(method),
+        // decode errorは再送しても変わらない
+        ApiFailure::Invalid(e) => e.is_connect() || (e.is_timeout() && is_idempotent(method)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            rate_limit_delay: Duration::from_secs(30),
+        };
+        assert_eq!(Duration::from_millis(100), policy.backoff(0));
+        assert_eq!(Duration::from_millis(400), policy.backoff(2));
+        assert_eq!(Duration::from_secs(1), policy.backoff(5));
+        assert_eq!(Duration::from_secs(1), policy.backoff(40));
+        for attempt in 0..10 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= policy.backoff(attempt) / 2 && delay <= policy.backoff(attempt));
+        }
+    }
+}
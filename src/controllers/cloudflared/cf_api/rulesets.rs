@@ -0,0 +1,74 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// Phase of the response header Transform Rules
+pub const HTTP_RESPONSE_HEADERS_TRANSFORM_PHASE: &str = "http_response_headers_transform";
+
+/// Phase of the WAF custom rules
+pub const HTTP_REQUEST_FIREWALL_CUSTOM_PHASE: &str = "http_request_firewall_custom";
+
+/// Get the entry point ruleset of a zone phase
+/// <https://developers.cloudflare.com/api/resources/rulesets/subresources/phases/methods/get/>
+#[derive(Debug)]
+pub struct GetZonePhaseEntrypoint<'a> {
+    pub zone_identifier: &'a str,
+    pub phase: &'a str,
+}
+
+impl Endpoint<Ruleset> for GetZonePhaseEntrypoint<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/rulesets/phases/{}/entrypoint",
+            self.zone_identifier, self.phase
+        )
+    }
+}
+
+/// Replace the rules of the entry point ruleset of a zone phase
+/// <https://developers.cloudflare.com/api/resources/rulesets/subresources/phases/methods/update/>
+#[derive(Debug)]
+pub struct UpdateZonePhaseEntrypoint<'a> {
+    pub zone_identifier: &'a str,
+    pub phase: &'a str,
+    pub params: UpdateZonePhaseEntrypointParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpdateZonePhaseEntrypointParams {
+    pub rules: Vec<serde_json::Value>,
+}
+
+impl Endpoint<Ruleset, (), UpdateZonePhaseEntrypointParams> for UpdateZonePhaseEntrypoint<'_> {
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/rulesets/phases/{}/entrypoint",
+            self.zone_identifier, self.phase
+        )
+    }
+
+    fn body(&self) -> Option<UpdateZonePhaseEntrypointParams> {
+        Some(self.params.clone())
+    }
+}
+
+/// Rules are kept as raw JSON so that rules not managed by this controller are written back
+/// unchanged
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Ruleset {
+    pub id: String,
+    #[serde(default)]
+    pub rules: Vec<serde_json::Value>,
+}
+
+impl ApiResult for Ruleset {}
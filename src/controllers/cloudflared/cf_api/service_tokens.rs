@@ -0,0 +1,120 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the Access service tokens of an account, a page at a time
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/service_tokens/methods/list/>
+#[derive(Debug)]
+pub struct ListServiceTokens<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListServiceTokensParams,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListServiceTokensParams {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl Endpoint<Vec<ServiceToken>, ListServiceTokensParams> for ListServiceTokens<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/access/service_tokens", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<ListServiceTokensParams> {
+        Some(self.params.clone())
+    }
+}
+
+/// Create a service token, the client secret is only returned here
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/service_tokens/methods/create/>
+#[derive(Debug)]
+pub struct CreateServiceToken<'a> {
+    pub account_identifier: &'a str,
+    pub params: CreateServiceTokenParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateServiceTokenParams<'a> {
+    pub name: &'a str,
+}
+
+impl<'a> Endpoint<ServiceToken, (), CreateServiceTokenParams<'a>> for CreateServiceToken<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/access/service_tokens", self.account_identifier)
+    }
+
+    fn body(&self) -> Option<CreateServiceTokenParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Issue a new client secret of a service token
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/service_tokens/methods/rotate/>
+#[derive(Debug)]
+pub struct RotateServiceToken<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<ServiceToken> for RotateServiceToken<'_> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/access/service_tokens/{}/rotate",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+/// Delete a service token
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/access/subresources/service_tokens/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteServiceToken<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<ServiceTokenId> for DeleteServiceToken<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/access/service_tokens/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ServiceToken {
+    pub id: String,
+    pub name: Option<String>,
+    pub client_id: String,
+    /// Only returned on creation and rotation
+    pub client_secret: Option<String>,
+}
+
+impl ApiResult for ServiceToken {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ServiceTokenId {
+    pub id: String,
+}
+
+impl ApiResult for ServiceTokenId {}
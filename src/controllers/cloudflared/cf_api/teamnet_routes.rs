@@ -0,0 +1,126 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the tunnel routes of an account
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/routes/methods/list/>
+#[derive(Debug)]
+pub struct ListTunnelRoutes<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListTunnelRoutesParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListTunnelRoutesParams<'a> {
+    pub is_deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_id: Option<&'a str>,
+    /// Routes whose comment contains it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<&'a str>,
+}
+
+impl<'a> Endpoint<Vec<TunnelRoute>, ListTunnelRoutesParams<'a>> for ListTunnelRoutes<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/teamnet/routes", self.account_identifier)
+    }
+
+    fn query(&self) -> Option<ListTunnelRoutesParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Route a private network CIDR through a tunnel
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/routes/methods/create/>
+#[derive(Debug)]
+pub struct CreateTunnelRoute<'a> {
+    pub account_identifier: &'a str,
+    pub params: TunnelRouteParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelRouteParams<'a> {
+    pub network: &'a str,
+    pub tunnel_id: &'a str,
+    pub comment: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virtual_network_id: Option<&'a str>,
+}
+
+impl<'a> Endpoint<TunnelRoute, (), TunnelRouteParams<'a>> for CreateTunnelRoute<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("accounts/{}/teamnet/routes", self.account_identifier)
+    }
+
+    fn body(&self) -> Option<TunnelRouteParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Update the CIDR, tunnel, comment or virtual network of a tunnel route
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/routes/methods/edit/>
+#[derive(Debug)]
+pub struct UpdateTunnelRoute<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: TunnelRouteParams<'a>,
+}
+
+impl<'a> Endpoint<TunnelRoute, (), TunnelRouteParams<'a>> for UpdateTunnelRoute<'a> {
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/routes/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+
+    fn body(&self) -> Option<TunnelRouteParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Delete a tunnel route
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/routes/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteTunnelRoute<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<TunnelRoute> for DeleteTunnelRoute<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/routes/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TunnelRoute {
+    pub id: String,
+    pub network: String,
+    pub tunnel_id: String,
+    pub comment: Option<String>,
+    pub virtual_network_id: Option<String>,
+}
+
+impl ApiResult for TunnelRoute {}
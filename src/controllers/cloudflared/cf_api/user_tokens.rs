@@ -0,0 +1,32 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use k8s_openapi::chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Verify the API token used by the client
+/// <https://developers.cloudflare.com/api/resources/user/subresources/tokens/methods/verify/>
+#[derive(Debug)]
+pub struct VerifyToken;
+
+impl Endpoint<TokenStatus> for VerifyToken {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        "user/tokens/verify".to_string()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenStatus {
+    pub id: String,
+    /// `active`, `disabled` or `expired`
+    pub status: String,
+    pub expires_on: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+}
+
+impl ApiResult for TokenStatus {}
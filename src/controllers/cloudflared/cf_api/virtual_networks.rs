@@ -0,0 +1,125 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the virtual networks of an account
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/virtual_networks/methods/list/>
+#[derive(Debug)]
+pub struct ListVirtualNetworks<'a> {
+    pub account_identifier: &'a str,
+    pub params: ListVirtualNetworksParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ListVirtualNetworksParams<'a> {
+    pub is_deleted: bool,
+    pub id: &'a str,
+}
+
+impl<'a> Endpoint<Vec<VirtualNetwork>, ListVirtualNetworksParams<'a>> for ListVirtualNetworks<'a> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/virtual_networks",
+            self.account_identifier
+        )
+    }
+
+    fn query(&self) -> Option<ListVirtualNetworksParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Create a virtual network
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/virtual_networks/methods/create/>
+#[derive(Debug)]
+pub struct CreateVirtualNetwork<'a> {
+    pub account_identifier: &'a str,
+    pub params: VirtualNetworkParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VirtualNetworkParams<'a> {
+    pub name: &'a str,
+    pub comment: &'a str,
+    pub is_default_network: bool,
+}
+
+impl<'a> Endpoint<VirtualNetwork, (), VirtualNetworkParams<'a>> for CreateVirtualNetwork<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/virtual_networks",
+            self.account_identifier
+        )
+    }
+
+    fn body(&self) -> Option<VirtualNetworkParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Rename a virtual network or change its comment or default flag
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/virtual_networks/methods/edit/>
+#[derive(Debug)]
+pub struct UpdateVirtualNetwork<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+    pub params: VirtualNetworkParams<'a>,
+}
+
+impl<'a> Endpoint<VirtualNetwork, (), VirtualNetworkParams<'a>> for UpdateVirtualNetwork<'a> {
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/virtual_networks/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+
+    fn body(&self) -> Option<VirtualNetworkParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Delete a virtual network, refused while it is the default one or routes use it
+/// <https://developers.cloudflare.com/api/resources/zero_trust/subresources/networks/subresources/virtual_networks/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteVirtualNetwork<'a> {
+    pub account_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<VirtualNetwork> for DeleteVirtualNetwork<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "accounts/{}/teamnet/virtual_networks/{}",
+            self.account_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct VirtualNetwork {
+    pub id: String,
+    pub name: String,
+    pub comment: Option<String>,
+    pub is_default_network: bool,
+}
+
+impl ApiResult for VirtualNetwork {}
@@ -0,0 +1,87 @@
+use cloudflare::framework::{
+    endpoint::{Endpoint, Method},
+    response::ApiResult,
+};
+use serde::{Deserialize, Serialize};
+
+/// List the Workers routes of a zone
+/// <https://developers.cloudflare.com/api/resources/workers/subresources/routes/methods/list/>
+#[derive(Debug)]
+pub struct ListWorkerRoutes<'a> {
+    pub zone_identifier: &'a str,
+}
+
+impl Endpoint<Vec<WorkerRoute>> for ListWorkerRoutes<'_> {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/workers/routes", self.zone_identifier)
+    }
+}
+
+/// Create a Workers route
+/// <https://developers.cloudflare.com/api/resources/workers/subresources/routes/methods/create/>
+#[derive(Debug)]
+pub struct CreateWorkerRoute<'a> {
+    pub zone_identifier: &'a str,
+    pub params: CreateWorkerRouteParams<'a>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateWorkerRouteParams<'a> {
+    pub pattern: &'a str,
+    pub script: &'a str,
+}
+
+impl<'a> Endpoint<WorkerRoute, (), CreateWorkerRouteParams<'a>> for CreateWorkerRoute<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("zones/{}/workers/routes", self.zone_identifier)
+    }
+
+    fn body(&self) -> Option<CreateWorkerRouteParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+/// Delete a Workers route
+/// <https://developers.cloudflare.com/api/resources/workers/subresources/routes/methods/delete/>
+#[derive(Debug)]
+pub struct DeleteWorkerRoute<'a> {
+    pub zone_identifier: &'a str,
+    pub identifier: &'a str,
+}
+
+impl Endpoint<WorkerRouteId> for DeleteWorkerRoute<'_> {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/workers/routes/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkerRoute {
+    pub id: String,
+    pub pattern: String,
+    pub script: Option<String>,
+}
+
+impl ApiResult for WorkerRoute {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkerRouteId {
+    pub id: String,
+}
+
+impl ApiResult for WorkerRouteId {}
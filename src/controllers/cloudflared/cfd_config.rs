@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use super::customresource::{
     CloudflaredTunnelAccess, CloudflaredTunnelIngress, CloudflaredTunnelOriginRequest,
+    CloudflaredTunnelSpec,
 };
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -25,6 +26,35 @@ pub struct Config {
     pub ingress: Vec<Ingress>,
 }
 
+impl Config {
+    /// cloudflared config of the tunnel, ending with the catch-all rule
+    pub fn new(tunnel_id: &str, spec: &CloudflaredTunnelSpec) -> Self {
+        Self {
+            tunnel: tunnel_id.to_string(),
+            credentials_file: Some(format!(
+                "/etc/cloudflared/{}",
+                credentials_filename(tunnel_id)
+            )),
+            origin_request: spec.origin_request.as_ref().cloned().map(Into::into),
+            ingress: spec
+                .expanded_ingress()
+                .into_iter()
+                .map(Into::into)
+                .chain([Ingress {
+                    hostname: None,
+                    service: spec.default_ingress_service.clone(),
+                    path: None,
+                    origin_request: None,
+                }])
+                .collect(),
+        }
+    }
+}
+
+pub fn credentials_filename(tunnel_id: &str) -> String {
+    format!("{tunnel_id}.json")
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct OriginRequest {
     #[serde(rename = "originServerName", skip_serializing_if = "Option::is_none")]
@@ -114,10 +144,12 @@ impl From<CloudflaredTunnelOriginRequest> for OriginRequest {
 
 impl From<CloudflaredTunnelIngress> for Ingress {
     fn from(value: CloudflaredTunnelIngress) -> Self {
+        // TCPのserviceはhostnameのみで振り分けられる
+        let path = value.path.clone().filter(|_| !value.is_tcp());
         Self {
             hostname: Some(value.hostname),
             service: value.service,
-            path: value.path,
+            path,
             origin_request: value.origin_request.map(Into::into),
         }
     }
@@ -132,3 +164,95 @@ impl From<CloudflaredTunnelAccess> for Access {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_ends_with_catch_all() {
+        let spec = CloudflaredTunnelSpec {
+            ingress: Some(vec![CloudflaredTunnelIngress {
+                hostname: "www.example.com".to_string(),
+                service: "http://web.default.svc".to_string(),
+                ..Default::default()
+            }]),
+            default_ingress_service: "http_status:404".to_string(),
+            ..Default::default()
+        };
+        let config = Config::new("00000000-0000-0000-0000-000000000000", &spec);
+        assert_eq!(
+            Some("/etc/cloudflared/00000000-0000-0000-0000-000000000000.json"),
+            config.credentials_file.as_deref()
+        );
+        assert_eq!(2, config.ingress.len());
+        assert_eq!(
+            Some("www.example.com"),
+            config.ingress[0].hostname.as_deref()
+        );
+        assert_eq!(None, config.ingress[1].hostname);
+        assert_eq!("http_status:404", config.ingress[1].service);
+    }
+
+    #[test]
+    fn additional_hostnames_get_rules_of_their_own() {
+        let spec = CloudflaredTunnelSpec {
+            ingress: Some(vec![CloudflaredTunnelIngress {
+                hostname: "www.example.com".to_string(),
+                service: "http://web.default.svc".to_string(),
+                path: Some("^/app(/|$)".to_string()),
+                additional_hostnames: Some(vec![
+                    "example.com".to_string(),
+                    "www.example.net".to_string(),
+                ]),
+                ..Default::default()
+            }]),
+            default_ingress_service: "http_status:404".to_string(),
+            ..Default::default()
+        };
+        let config = Config::new("00000000-0000-0000-0000-000000000000", &spec);
+        let hostnames = config
+            .ingress
+            .iter()
+            .map(|i| i.hostname.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Some("www.example.com"),
+                Some("example.com"),
+                Some("www.example.net"),
+                None
+            ],
+            hostnames
+        );
+        assert!(config.ingress[..3]
+            .iter()
+            .all(|i| i.service == "http://web.default.svc"
+                && i.path.as_deref() == Some("^/app(/|$)")));
+    }
+
+    #[test]
+    fn tcp_rules_have_no_path() {
+        let spec = CloudflaredTunnelSpec {
+            ingress: Some(vec![
+                CloudflaredTunnelIngress {
+                    hostname: "db.example.com".to_string(),
+                    service: "tcp://postgres.default.svc:5432".to_string(),
+                    path: Some("^/".to_string()),
+                    ..Default::default()
+                },
+                CloudflaredTunnelIngress {
+                    hostname: "www.example.com".to_string(),
+                    service: "http://web.default.svc".to_string(),
+                    path: Some("^/".to_string()),
+                    ..Default::default()
+                },
+            ]),
+            default_ingress_service: "http_status:404".to_string(),
+            ..Default::default()
+        };
+        let config = Config::new("00000000-0000-0000-0000-000000000000", &spec);
+        assert_eq!(None, config.ingress[0].path);
+        assert_eq!(Some("^/"), config.ingress[1].path.as_deref());
+    }
+}
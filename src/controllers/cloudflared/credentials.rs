@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use k8s_openapi::api::core::v1::Secret;
+
+use super::{cf_api::CloudflareApi, customresource::CloudflaredTunnelCredentialsSecretRef};
+use crate::{secret_ref::SecretKeyRef, Result};
+
+const DEFAULT_TOKEN_KEY: &str = "token";
+const DEFAULT_ACCOUNT_ID_KEY: &str = "account_id";
+
+/// Cloudflare API client and account a CloudflaredTunnel is managed with
+#[derive(Clone)]
+pub(super) struct CloudflareAccount {
+    pub(super) api: Arc<CloudflareApi>,
+    pub(super) account_id: String,
+    /// Clients of the zones mapped to other accounts by `--zone-accounts-file`, keyed by zone id
+    pub(super) zone_apis: HashMap<String, Arc<CloudflareApi>>,
+}
+
+impl CloudflareAccount {
+    /// Client managing the zone, the one of the account unless the zone is mapped to another
+    pub(super) fn zone_api(&self, zone_id: &str) -> &CloudflareApi {
+        self.zone_apis.get(zone_id).unwrap_or(&self.api)
+    }
+}
+
+/// Key of the credentials cache, naming the keys read from the Secret
+pub(super) fn credentials_key(
+    namespace: &str,
+    secret_ref: &CloudflaredTunnelCredentialsSecretRef,
+) -> String {
+    format!(
+        "{namespace}/{}#{}#{}",
+        secret_ref.name,
+        secret_ref.token_key.as_deref().unwrap_or(DEFAULT_TOKEN_KEY),
+        secret_ref
+            .account_id_key
+            .as_deref()
+            .unwrap_or(DEFAULT_ACCOUNT_ID_KEY)
+    )
+}
+
+/// Token and account id read from `spec.cloudflare_credentials_secret_ref`
+pub(super) fn read_credentials(
+    namespace: &str,
+    secret_ref: &CloudflaredTunnelCredentialsSecretRef,
+    secret: &Secret,
+) -> Result<(String, String)> {
+    let key_ref = |key: Option<&str>, default: &str| {
+        format!("{namespace}/{}#{}", secret_ref.name, key.unwrap_or(default))
+            .parse::<SecretKeyRef>()
+    };
+    let token = key_ref(secret_ref.token_key.as_deref(), DEFAULT_TOKEN_KEY)?.value_of(secret)?;
+    let account_id =
+        key_ref(secret_ref.account_id_key.as_deref(), DEFAULT_ACCOUNT_ID_KEY)?.value_of(secret)?;
+    Ok((token, account_id))
+}
+
+/// Accounts built for the credentials Secrets, keyed by the Secret and the keys read from it,
+/// as `namespace/name#key`, and by its resourceVersion, so that they are rebuilt once it changes
+pub(super) struct CredentialCache<T> {
+    entries: Mutex<HashMap<String, (String, Arc<T>)>>,
+}
+
+impl<T> Default for CredentialCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::default(),
+        }
+    }
+}
+
+impl<T> CredentialCache<T> {
+    /// Entry built for the resourceVersion of the Secret
+    pub(super) fn get(&self, key: &str, resource_version: &str) -> Option<Arc<T>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(version, _)| version == resource_version)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Record the entry built for the resourceVersion of the Secret, replacing older ones
+    pub(super) fn insert(&self, key: &str, resource_version: &str, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        self.entries.lock().unwrap().insert(
+            key.to_string(),
+            (resource_version.to_string(), value.clone()),
+        );
+        value
+    }
+
+    /// Drop the entries of a removed Secret, given as `namespace/name`
+    pub(super) fn evict(&self, secret: &str) {
+        self.entries.lock().unwrap().retain(|key, _| {
+            key != secret && !key.strip_prefix(secret).is_some_and(|k| k.starts_with('#'))
+        });
+    }
+
+    /// Every cached entry
+    pub(super) fn values(&self) -> Vec<Arc<T>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::ByteString;
+
+    use super::*;
+
+    #[test]
+    fn read_default_keys() {
+        let secret = Secret {
+            data: Some(BTreeMap::from([
+                ("token".to_string(), ByteString(b"secret-token\n".to_vec())),
+                ("account_id".to_string(), ByteString(b"account".to_vec())),
+            ])),
+            ..Default::default()
+        };
+        let secret_ref = CloudflaredTunnelCredentialsSecretRef {
+            name: "cloudflare".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            ("secret-token".to_string(), "account".to_string()),
+            read_credentials("default", &secret_ref, &secret).unwrap()
+        );
+
+        let secret_ref = CloudflaredTunnelCredentialsSecretRef {
+            account_id_key: Some("ACCOUNT_ID".to_string()),
+            ..secret_ref
+        };
+        let error = read_credentials("default", &secret_ref, &secret).unwrap_err();
+        assert_eq!("SecretKeyMissing", error.reason());
+    }
+
+    #[test]
+    fn entry_is_rebuilt_on_secret_change() {
+        let cache = CredentialCache::default();
+        let first = cache.insert("default/cloudflare", "1", "a".to_string());
+        let cached = cache.get("default/cloudflare", "1").unwrap();
+        assert!(Arc::ptr_eq(&first, &cached));
+        assert!(cache.get("default/cloudflare", "2").is_none());
+        cache.insert("default/cloudflare", "2", "b".to_string());
+        assert_eq!("b", *cache.get("default/cloudflare", "2").unwrap());
+        assert_eq!(1, cache.values().len());
+    }
+
+    #[test]
+    fn evict_removed_secret() {
+        let cache = CredentialCache::default();
+        cache.insert("default/cloudflare", "1", "a".to_string());
+        cache.insert("default/cloudflare#token", "1", "b".to_string());
+        cache.insert("default/cloudflare-2", "1", "c".to_string());
+        cache.evict("default/cloudflare");
+        assert!(cache.get("default/cloudflare", "1").is_none());
+        assert!(cache.get("default/cloudflare#token", "1").is_none());
+        assert!(cache.get("default/cloudflare-2", "1").is_some());
+    }
+}
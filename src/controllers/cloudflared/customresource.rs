@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::chrono::{SecondsFormat, Utc};
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -13,6 +16,7 @@ use serde::{Deserialize, Serialize};
     plural = "cloudflaredtunnels",
     shortname = "cfdt",
     status = "CloudflaredTunnelStatus",
+    scale = r#"{"specReplicasPath":".spec.replicas", "statusReplicasPath":".status.replicas", "labelSelectorPath":".status.selector"}"#,
     namespaced,
 )]
 pub struct CloudflaredTunnelSpec {
@@ -23,6 +27,79 @@ pub struct CloudflaredTunnelSpec {
     pub args: Option<Vec<String>>,
     pub command: Option<Vec<String>>,
     pub default_ingress_service: String,
+    pub workload_kind: Option<CloudflaredTunnelWorkloadKind>,
+    /// Whether the CNAME records of the hostnames are managed, `true` by default. When `false`
+    /// the records are left to external-dns or Terraform and never created or deleted
+    pub manage_dns: Option<bool>,
+    /// cloudflared runs outside of the cluster, so only the tunnel and its DNS records are
+    /// managed, without the workload and the config Secret
+    pub external_connector: Option<bool>,
+    /// Number of cloudflared replicas, defaults to `--deployment-replicas`
+    // ingress controllerのapplyでscale subresourceによる変更を上書きしないよう、未設定時は出力しない
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<i32>,
+    /// Response headers rewritten at the Cloudflare edge via Transform Rules
+    pub response_headers: Option<Vec<CloudflaredTunnelResponseHeaders>>,
+    /// Cloudflare tunnel name prefix, defaults to `--cloudflare-tunnel-prefix`. An empty prefix
+    /// would match every tunnel of the account
+    #[schemars(length(min = 1))]
+    pub tunnel_prefix: Option<String>,
+    pub resources: Option<CloudflaredTunnelResources>,
+    /// Settings of the CNAME records, defaulting to the `--dns-record-*` arguments
+    pub dns_record: Option<CloudflaredTunnelDnsRecord>,
+    /// Cloudflare credentials of the tunnel, instead of `--cloudflare-token` and
+    /// `--cloudflare-account-id`
+    pub cloudflare_credentials_secret_ref: Option<CloudflaredTunnelCredentialsSecretRef>,
+    /// Seconds between rotations of the tunnel secret generated by the controller, never rotated
+    /// unless set, at least an hour. A Secret given by `secret_ref` is not rotated, and an
+    /// external connector cannot be rotated
+    #[schemars(range(min = 3600))]
+    pub secret_rotation_period: Option<u64>,
+    /// Whether the tunnel is deleted with cascade, also when connections remain, defaults to
+    /// `--tunnel-delete-cascade`
+    pub cascade_delete: Option<bool>,
+}
+
+/// Secret in the namespace of the CloudflaredTunnel holding an API token and an account id
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelCredentialsSecretRef {
+    pub name: String,
+    /// Key of the API token, `token` by default
+    pub token_key: Option<String>,
+    /// Key of the account id, `account_id` by default
+    pub account_id_key: Option<String>,
+}
+
+/// TTL, comment and tags of the CNAME records, `{namespace}` and `{name}` in the comment and
+/// tags are replaced with those of the CloudflaredTunnel
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelDnsRecord {
+    /// TTL in seconds, 1 for automatic
+    pub ttl: Option<u32>,
+    pub comment: Option<String>,
+    /// Tags in the `name:value` form
+    pub tags: Option<Vec<String>>,
+}
+
+/// Compute resources of the cloudflared container
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelResources {
+    pub limits: Option<BTreeMap<String, String>>,
+    pub requests: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelResponseHeaders {
+    pub hostname: String,
+    pub set: Option<BTreeMap<String, String>>,
+    pub remove: Option<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, JsonSchema, Default)]
+pub enum CloudflaredTunnelWorkloadKind {
+    #[default]
+    Deployment,
+    StatefulSet,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
@@ -31,6 +108,52 @@ pub struct CloudflaredTunnelIngress {
     pub service: String,
     pub path: Option<String>,
     pub origin_request: Option<CloudflaredTunnelOriginRequest>,
+    /// Experimental: Worker script attached in front of the hostname via a Workers route
+    pub worker_script: Option<String>,
+    /// A/AAAA records already existing for the hostname are replaced by the tunnel CNAME or kept
+    /// without creating it, instead of being rejected
+    pub allow_existing_dns: Option<CloudflaredTunnelExistingDns>,
+    /// Aliases served by the same rule, each getting a CNAME record and a rule of its own
+    pub additional_hostnames: Option<Vec<String>>,
+    /// Zone of the hostname, used instead of looking it up among the zones of the account
+    pub zone_id: Option<String>,
+    /// Whether the CNAME record of the hostname is proxied by Cloudflare, `true` by default
+    pub proxied: Option<bool>,
+    /// Client CIDRs allowed to reach the hostname, other clients are blocked by a WAF custom rule
+    pub source_ranges: Option<Vec<String>>,
+    /// Cloudflare health check of the hostname, whose state is reported in the status
+    pub health_check: Option<CloudflaredTunnelHealthCheck>,
+    /// Cloudflare Access application protecting the hostname
+    pub access_application: Option<CloudflaredTunnelAccessApplication>,
+}
+
+/// Who may sign in to the Access application of a hostname, at least one of the emails, the
+/// email domains, the groups or the service token must be given
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelAccessApplication {
+    pub emails: Option<Vec<String>>,
+    pub email_domains: Option<Vec<String>>,
+    /// Ids of the Access groups
+    pub groups: Option<Vec<String>>,
+    /// Ids of the identity providers offered on sign in, every provider of the account by default
+    pub identity_providers: Option<Vec<String>>,
+    /// Session duration such as `24h`, the default of the account when unset
+    pub session_duration: Option<String>,
+    /// Secret created in the namespace of the CloudflaredTunnel with the `client_id` and
+    /// `client_secret` of a service token allowed to reach the hostname
+    pub service_token_secret: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelHealthCheck {
+    /// Path requested over HTTPS, `/` by default
+    pub path: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub enum CloudflaredTunnelExistingDns {
+    Replace,
+    Keep,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
@@ -56,8 +179,206 @@ pub struct CloudflaredTunnelOriginRequest {
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct CloudflaredTunnelStatus {
     pub tunnel_id: Option<String>,
+    /// Cloudflare account holding the tunnel
+    pub account_id: Option<String>,
     pub config_secret_ref: Option<String>,
     pub tunnel_secret_ref: Option<String>,
+    /// Hash of the tunnel secret of `spec.secret_ref` last applied to the tunnel
+    pub tunnel_secret_hash: Option<String>,
+    pub active_connections: Option<u32>,
+    pub connectors: Option<Vec<CloudflaredTunnelConnector>>,
+    pub ready_replicas: Option<i32>,
+    pub available_replicas: Option<i32>,
+    pub replicas: Option<i32>,
+    /// Label selector of the cloudflared pods, for the scale subresource
+    pub selector: Option<String>,
+    pub conditions: Option<Vec<CloudflaredTunnelCondition>>,
+    /// Hash of the last applied cloudflared config
+    pub config_hash: Option<String>,
+    pub last_reconcile_time: Option<String>,
+    /// When the tunnel secret was last rotated by `spec.secret_rotation_period`
+    pub last_secret_rotation_time: Option<String>,
+    /// Secret of a rotation started and not yet applied to the tunnel, finished by the next
+    /// reconcile instead of rotating again
+    pub pending_tunnel_secret_ref: Option<String>,
+    /// Workers routes created for `spec.ingress[].worker_script`
+    pub worker_routes: Option<Vec<CloudflaredTunnelWorkerRoute>>,
+    /// Zones holding Transform Rules created for `spec.response_headers`
+    pub response_header_zones: Option<Vec<String>>,
+    /// Zones holding WAF custom rules created for `spec.ingress[].source_ranges`
+    pub firewall_rule_zones: Option<Vec<String>>,
+    /// Zones holding the tunnel CNAME records, also a zone pinned by `spec.ingress[].zone_id`
+    /// that the token does not list
+    pub dns_record_zones: Option<Vec<String>>,
+    /// Secrets replaced by a new config or tunnel secret, deleted after the rollout
+    pub superseded_secret_refs: Option<Vec<String>>,
+    /// Health checks created for `spec.ingress[].health_check`
+    pub health_checks: Option<Vec<CloudflaredTunnelHealthCheckStatus>>,
+    /// Access applications created for `spec.ingress[].access_application`
+    pub access_applications: Option<Vec<CloudflaredTunnelAccessApplicationStatus>>,
+    /// Tunnel replacement in progress, started by the `replace-tunnel` annotation
+    pub replacement: Option<CloudflaredTunnelReplacement>,
+    /// Annotation value of the last completed tunnel replacement
+    pub last_replacement_trigger: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelReplacement {
+    /// Value of the annotation that started the replacement
+    pub trigger: String,
+    /// The new tunnel
+    pub tunnel_id: String,
+    pub tunnel_secret_ref: String,
+    /// Config Secret of the workload serving the new tunnel until the switch
+    pub config_secret_ref: String,
+    /// The replaced tunnel, set once the DNS records point at the new tunnel
+    pub previous_tunnel_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelWorkerRoute {
+    pub zone_id: String,
+    pub id: String,
+    pub pattern: String,
+    pub script: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelHealthCheckStatus {
+    pub zone_id: String,
+    pub id: String,
+    pub hostname: String,
+    pub path: String,
+    /// `unknown`, `healthy`, `unhealthy` or `suspended`
+    pub status: Option<String>,
+    pub failure_reason: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelAccessApplicationStatus {
+    pub id: String,
+    pub hostname: String,
+    /// Audience tag of the application, for `service.aud`
+    pub aud: Option<String>,
+    /// Settings the application was last created or updated with
+    pub application: CloudflaredTunnelAccessApplication,
+    pub service_token_id: Option<String>,
+}
+
+/// Schemes of the services cloudflared proxies as TCP streams, reached by the clients through
+/// `cloudflared access` instead of HTTP
+pub const TCP_SERVICE_SCHEMES: &[&str] = &["tcp", "ssh", "rdp", "smb"];
+
+pub const CONDITION_IMAGE_ALLOWED: &str = "ImageAllowed";
+pub const CONDITION_READY: &str = "Ready";
+pub const CONDITION_DEPLOYMENT_AVAILABLE: &str = "DeploymentAvailable";
+pub const CONDITION_DEGRADED: &str = "Degraded";
+pub const CONDITION_ORIGIN_HEALTHY: &str = "OriginHealthy";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelCondition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status: String,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub last_transition_time: Option<String>,
+}
+
+impl CloudflaredTunnelIngress {
+    /// Whether the service is proxied as a TCP stream, for which the path does not apply
+    pub fn is_tcp(&self) -> bool {
+        self.service
+            .split_once("://")
+            .is_some_and(|(scheme, _)| TCP_SERVICE_SCHEMES.contains(&scheme))
+    }
+}
+
+impl CloudflaredTunnelSpec {
+    /// Ingress rules with the `additional_hostnames` expanded into rules of their own, following
+    /// the rule they belong to
+    pub fn expanded_ingress(&self) -> Vec<CloudflaredTunnelIngress> {
+        self.ingress
+            .iter()
+            .flatten()
+            .flat_map(|ingress| {
+                let rule = CloudflaredTunnelIngress {
+                    additional_hostnames: None,
+                    ..ingress.clone()
+                };
+                let aliases = ingress
+                    .additional_hostnames
+                    .iter()
+                    .flatten()
+                    .map(|hostname| CloudflaredTunnelIngress {
+                        hostname: hostname.clone(),
+                        ..rule.clone()
+                    })
+                    .collect::<Vec<_>>();
+                std::iter::once(rule).chain(aliases)
+            })
+            .collect()
+    }
+}
+
+impl CloudflaredTunnelStatus {
+    pub fn condition(&self, type_: &str) -> Option<&CloudflaredTunnelCondition> {
+        self.conditions.iter().flatten().find(|c| c.type_ == type_)
+    }
+
+    /// Remember a replaced secret ref for pruning
+    pub fn supersede_secret_ref(&mut self, secret_ref: Option<String>) {
+        let Some(secret_ref) = secret_ref else {
+            return;
+        };
+        let refs = self.superseded_secret_refs.get_or_insert_with(Vec::new);
+        if !refs.contains(&secret_ref) {
+            refs.push(secret_ref);
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.condition(CONDITION_READY)
+            .map_or(false, |c| c.status == "True")
+    }
+
+    /// Set the condition, keeping last_transition_time unless the status changes
+    pub fn set_condition(
+        &mut self,
+        type_: &str,
+        status: bool,
+        reason: &str,
+        message: Option<String>,
+    ) {
+        let status = if status { "True" } else { "False" }.to_string();
+        let conditions = self.conditions.get_or_insert_with(Vec::new);
+        let last_transition_time = conditions
+            .iter()
+            .find(|c| c.type_ == type_ && c.status == status)
+            .and_then(|c| c.last_transition_time.clone())
+            .or_else(|| Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)));
+        let condition = CloudflaredTunnelCondition {
+            type_: type_.to_string(),
+            status,
+            reason: Some(reason.to_string()),
+            message,
+            last_transition_time,
+        };
+        if let Some(current) = conditions.iter_mut().find(|c| c.type_ == type_) {
+            *current = condition;
+        } else {
+            conditions.push(condition);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelConnector {
+    pub id: String,
+    pub version: Option<String>,
+    pub arch: Option<String>,
+    pub colo_names: Vec<String>,
+    pub connections: u32,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
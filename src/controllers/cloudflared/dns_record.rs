@@ -0,0 +1,423 @@
+use std::{sync::Arc, time::Duration};
+
+use cloudflare::endpoints::dns::{DnsContent, DnsRecord};
+use futures::StreamExt as _;
+use kube::{
+    api::{ObjectMeta, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::EventType,
+        finalizer::{finalizer, Event as FinalizerEvent},
+        watcher::Config,
+        Controller,
+    },
+    Api, CustomResource, Resource as _, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    cf_api::DnsRecordSettings, credentials::CloudflareAccount,
+    customresource::CloudflaredTunnelCredentialsSecretRef, Context, PATCH_PARAMS_APPLY_NAME,
+};
+use crate::{resync::ResyncTrigger, zone_resolver::ZoneResolver, Error, Result};
+
+#[derive(CustomResource, Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[kube(
+    // Required properties
+    group = "chalharu.top",
+    version = "v1alpha1",
+    kind = "CloudflareDNSRecord",
+    // Optional properties
+    singular = "cloudflarednsrecord",
+    plural = "cloudflarednsrecords",
+    shortname = "cfdns",
+    status = "CloudflareDNSRecordStatus",
+    namespaced,
+)]
+pub struct CloudflareDNSRecordSpec {
+    /// Hostname of the record
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: CloudflareDNSRecordType,
+    /// Address of an A or AAAA record, target of a CNAME record or text of a TXT record
+    pub content: String,
+    /// Whether the record is proxied by Cloudflare, false by default
+    pub proxied: Option<bool>,
+    /// TTL in seconds, 1 for automatic
+    pub ttl: Option<u32>,
+    /// Comment of the record, `{namespace}/{name}` of the CloudflareDNSRecord by default
+    pub comment: Option<String>,
+    /// Zone of the record, looked up by the name when unset
+    pub zone_id: Option<String>,
+    /// Cloudflare credentials of the record, instead of `--cloudflare-token` and
+    /// `--cloudflare-account-id`
+    pub cloudflare_credentials_secret_ref: Option<CloudflaredTunnelCredentialsSecretRef>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, JsonSchema, Default)]
+pub enum CloudflareDNSRecordType {
+    #[default]
+    A,
+    AAAA,
+    CNAME,
+    TXT,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflareDNSRecordStatus {
+    pub zone_id: Option<String>,
+    pub record_id: Option<String>,
+}
+
+/// Reconcile the CloudflareDNSRecords until the controller is shut down
+pub(super) async fn run_dns_record_controller(ctx: Arc<Context>, resync: ResyncTrigger) {
+    info!("Starting controller for CloudflareDNSRecord");
+
+    let api = Api::<CloudflareDNSRecord>::all(ctx.client.clone());
+    Controller::new(api, Config::default().any_semantic())
+        .reconcile_all_on(ctx.subscribe_resync(&resync))
+        .shutdown_on_signal()
+        .run(reconcile, error_policy, ctx)
+        .filter_map(|x| async move { std::result::Result::ok(x) })
+        .for_each(|_| futures::future::ready(()))
+        .await;
+
+    info!("controller for CloudflareDNSRecord shutdown");
+}
+
+async fn reconcile(record: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Result<Action> {
+    if !ctx.is_own_shard(&*record) {
+        return Ok(Action::await_change());
+    }
+    let ns = dns_record_namespace(&record)?;
+    let api = Api::<CloudflareDNSRecord>::namespaced(ctx.client.clone(), &ns);
+    finalizer(&api, &finalizer_name(), record, |e| {
+        let ctx = ctx.clone();
+        async move {
+            match e {
+                FinalizerEvent::Apply(record) => ctx.apply_dns_record(&record).await,
+                FinalizerEvent::Cleanup(record) => ctx.cleanup_dns_record(&record).await,
+            }
+        }
+    })
+    .await
+    .map_err(|e| Error::from(Box::new(e)))
+}
+
+fn error_policy(record: Arc<CloudflareDNSRecord>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile of CloudflareDNSRecord failed: {error:?}");
+    // error_policyは同期関数のため、eventの送信は別タスクで行う
+    let reason = error.reason();
+    let note = error.to_string();
+    tokio::spawn(async move {
+        ctx.publish_object_event(
+            &*record,
+            EventType::Warning,
+            reason,
+            "Reconcile",
+            Some(note),
+        )
+        .await
+    });
+    Action::requeue(Duration::from_secs(60))
+}
+
+pub(super) fn finalizer_name() -> String {
+    format!("{}/dns-record-finalizer", PATCH_PARAMS_APPLY_NAME)
+}
+
+fn dns_record_namespace(record: &CloudflareDNSRecord) -> Result<String> {
+    record
+        .namespace()
+        .ok_or_else(|| Error::missing_namespace(CloudflareDNSRecord::kind(&()), record.name_any()))
+}
+
+/// Content of the record, with the address of an A or AAAA record parsed
+fn dns_content(spec: &CloudflareDNSRecordSpec) -> Result<DnsContent> {
+    let invalid = || Error::invalid_dns_record_content(&spec.name, &spec.content);
+    Ok(match spec.type_ {
+        CloudflareDNSRecordType::A => DnsContent::A {
+            content: spec.content.parse().map_err(|_| invalid())?,
+        },
+        CloudflareDNSRecordType::AAAA => DnsContent::AAAA {
+            content: spec.content.parse().map_err(|_| invalid())?,
+        },
+        CloudflareDNSRecordType::CNAME => DnsContent::CNAME {
+            content: spec.content.clone(),
+        },
+        CloudflareDNSRecordType::TXT => DnsContent::TXT {
+            content: spec.content.clone(),
+        },
+    })
+}
+
+/// Type and content of a record of the supported types
+fn content_of(content: &DnsContent) -> Option<(CloudflareDNSRecordType, String)> {
+    match content {
+        DnsContent::A { content } => Some((CloudflareDNSRecordType::A, content.to_string())),
+        DnsContent::AAAA { content } => Some((CloudflareDNSRecordType::AAAA, content.to_string())),
+        DnsContent::CNAME { content } => Some((CloudflareDNSRecordType::CNAME, content.clone())),
+        DnsContent::TXT { content } => Some((CloudflareDNSRecordType::TXT, content.clone())),
+        _ => None,
+    }
+}
+
+/// Whether Cloudflare would refuse the record next to an existing one of the same name: a CNAME
+/// record shares its name with no other record, and other records must differ in content
+fn conflicts(existing: &DnsRecord, name: &str, content: &DnsContent) -> bool {
+    if !existing.name.eq_ignore_ascii_case(name) {
+        return false;
+    }
+    let is_cname = |c: &DnsContent| matches!(c, DnsContent::CNAME { .. });
+    is_cname(&existing.content)
+        || is_cname(content)
+        || content_of(&existing.content).is_some_and(|c| Some(c) == content_of(content))
+}
+
+/// Whether the listed fields of the record match, the comment is not listed and compared apart
+fn is_up_to_date(
+    record: &DnsRecord,
+    name: &str,
+    content: &DnsContent,
+    settings: &DnsRecordSettings,
+) -> bool {
+    record.name.eq_ignore_ascii_case(name)
+        && content_of(&record.content).is_some_and(|c| Some(c) == content_of(content))
+        && record.proxied == settings.proxied
+        && settings.ttl_matches(record.ttl)
+}
+
+impl Context {
+    /// Zone of the hostname among the zones of the account, which must be allowed
+    async fn zone_of_record(
+        &self,
+        account: &mut CloudflareAccount,
+        spec: &CloudflareDNSRecordSpec,
+    ) -> Result<String> {
+        let zones = self.list_zones(account).await?;
+        let zone_id = match spec.zone_id {
+            Some(ref zone_id) => zone_id.clone(),
+            None => zones
+                .iter()
+                .map(|z| (z.name.as_str(), z.id.clone()))
+                .collect::<ZoneResolver<_>>()
+                .resolve(&spec.name)
+                .cloned()
+                .ok_or_else(|| Error::hostname_zone_not_found(&spec.name))?,
+        };
+        let zone_name = zones
+            .iter()
+            .find(|z| z.id == zone_id)
+            .map(|z| z.name.as_str());
+        let allowed = match zone_name {
+            Some(zone_name) => self.args.is_zone_allowed(zone_name),
+            // 名前の分からない指定されたzoneは、allowlistが無い場合のみ許可する
+            None => !self.args.has_zone_allowlist(),
+        };
+        if !allowed {
+            return Err(Error::zone_not_allowed(
+                &spec.name,
+                zone_name.unwrap_or(&zone_id),
+            ));
+        }
+        Ok(zone_id)
+    }
+
+    /// Create or update the DNS record of the CloudflareDNSRecord
+    async fn apply_dns_record(&self, record: &CloudflareDNSRecord) -> Result<Action> {
+        let namespace = dns_record_namespace(record)?;
+        let name = record.name_any();
+        let mut account = self
+            .account_of_secret(
+                &namespace,
+                record.spec.cloudflare_credentials_secret_ref.as_ref(),
+            )
+            .await?;
+        let zone_id = self.zone_of_record(&mut account, &record.spec).await?;
+        let content = dns_content(&record.spec)?;
+        let settings = DnsRecordSettings {
+            proxied: record.spec.proxied.unwrap_or(false),
+            ttl: record.spec.ttl.unwrap_or(1),
+            comment: Some(
+                record
+                    .spec
+                    .comment
+                    .clone()
+                    .unwrap_or_else(|| format!("{namespace}/{name}")),
+            ),
+            tags: Vec::new(),
+        };
+        if !DnsRecordSettings::is_valid_ttl(settings.ttl) {
+            return Err(Error::invalid_dns_record_ttl(
+                &record.spec.name,
+                settings.ttl,
+            ));
+        }
+
+        let status = record.status.clone().unwrap_or_default();
+        // zoneが変わった場合は元のzoneのrecordを削除してから作り直す
+        if let (Some(old_zone_id), Some(record_id)) = (status.zone_id, status.record_id.clone()) {
+            if old_zone_id != zone_id {
+                let api = account.zone_api(&old_zone_id);
+                if api
+                    .refresh_dns(old_zone_id.clone())
+                    .await?
+                    .iter()
+                    .any(|r| r.id == record_id)
+                {
+                    api.delete_dns_record(old_zone_id, record_id).await?;
+                }
+            }
+        }
+
+        let api = account.zone_api(&zone_id);
+        let records = match api.cached_dns(&zone_id) {
+            Some(records) => records,
+            None => api.refresh_dns(zone_id.clone()).await?,
+        };
+        let mut current = status
+            .record_id
+            .as_ref()
+            .and_then(|id| records.iter().find(|r| &r.id == id));
+        if current.is_none() {
+            // statusへの記録前に中断された作成のrecordを、名前と内容とcommentから引き継ぐ
+            for candidate in records.iter().filter(|r| {
+                r.name.eq_ignore_ascii_case(&record.spec.name)
+                    && content_of(&r.content).is_some_and(|c| Some(c) == content_of(&content))
+            }) {
+                if api
+                    .get_dns_record_comment(zone_id.clone(), candidate.id.clone())
+                    .await?
+                    == settings.comment
+                {
+                    current = Some(candidate);
+                    break;
+                }
+            }
+        }
+        if records
+            .iter()
+            .filter(|r| current.map_or(true, |c| c.id != r.id))
+            .any(|r| conflicts(r, &record.spec.name, &content))
+        {
+            return Err(Error::conflicting_dns_record(&record.spec.name));
+        }
+        let up_to_date = match current {
+            Some(current) if is_up_to_date(current, &record.spec.name, &content, &settings) => {
+                api.get_dns_record_comment(zone_id.clone(), current.id.clone())
+                    .await?
+                    == settings.comment
+            }
+            _ => false,
+        };
+        let applied_id = match current {
+            Some(current) if up_to_date => current.id.clone(),
+            Some(current) => {
+                api.update_dns_record(
+                    zone_id.clone(),
+                    current.id.clone(),
+                    record.spec.name.clone(),
+                    content,
+                    &settings,
+                )
+                .await?
+                .id
+            }
+            None => {
+                let created = api
+                    .create_dns_record(
+                        zone_id.clone(),
+                        record.spec.name.clone(),
+                        content,
+                        &settings,
+                    )
+                    .await?;
+                self.publish_object_event(
+                    record,
+                    EventType::Normal,
+                    "DnsRecordCreated",
+                    "CreateDnsRecord",
+                    Some(format!("Created DNS record {}", created.name)),
+                )
+                .await;
+                created.id
+            }
+        };
+
+        let status = CloudflareDNSRecordStatus {
+            zone_id: Some(zone_id),
+            record_id: Some(applied_id),
+        };
+        if record.status.as_ref() != Some(&status) {
+            Api::<CloudflareDNSRecord>::namespaced(self.client.clone(), &namespace)
+                .patch_status(
+                    &name,
+                    &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+                    &Patch::Apply(CloudflareDNSRecord {
+                        metadata: ObjectMeta::default(),
+                        spec: CloudflareDNSRecordSpec::default(),
+                        status: Some(status),
+                    }),
+                )
+                .await?;
+        }
+        Ok(Action::requeue(Duration::from_secs(60 * 60)))
+    }
+
+    /// Delete the DNS record of the CloudflareDNSRecord
+    pub(super) async fn cleanup_dns_record(&self, record: &CloudflareDNSRecord) -> Result<Action> {
+        let Some(CloudflareDNSRecordStatus {
+            zone_id: Some(zone_id),
+            record_id: Some(record_id),
+        }) = record.status.clone()
+        else {
+            return Ok(Action::await_change());
+        };
+        let namespace = dns_record_namespace(record)?;
+        let mut account = self
+            .account_of_secret(
+                &namespace,
+                record.spec.cloudflare_credentials_secret_ref.as_ref(),
+            )
+            .await?;
+        // 別accountに対応付けられたzoneのclientを得るためzoneの一覧を取得する
+        self.list_zones(&mut account).await?;
+        let api = account.zone_api(&zone_id);
+        if api
+            .refresh_dns(zone_id.clone())
+            .await?
+            .iter()
+            .any(|r| r.id == record_id)
+        {
+            api.delete_dns_record(zone_id, record_id).await?;
+        }
+        Ok(Action::await_change())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_content() {
+        let spec = CloudflareDNSRecordSpec {
+            name: "mail.example.com".to_string(),
+            type_: CloudflareDNSRecordType::AAAA,
+            content: "2001:db8::1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some((CloudflareDNSRecordType::AAAA, "2001:db8::1".to_string())),
+            content_of(&dns_content(&spec).unwrap())
+        );
+        assert!(dns_content(&CloudflareDNSRecordSpec {
+            type_: CloudflareDNSRecordType::A,
+            ..spec
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use serde_json::{json, Value};
+
+/// Build the WAF custom rule blocking the clients outside of the source ranges of a hostname
+pub(super) fn source_range_rule(
+    ref_prefix: &str,
+    hostname: &str,
+    source_ranges: &[String],
+) -> Value {
+    json!({
+        "ref": format!("{ref_prefix}{hostname}"),
+        "description": format!("Managed by cloudflared-ingress: {hostname}"),
+        "expression": format!(
+            "({} and not ip.src in {{{}}})",
+            host_expression(hostname),
+            source_ranges.join(" ")
+        ),
+        "action": "block",
+        "enabled": true,
+    })
+}
+
+/// Expression matching the requests to the hostname, any subdomain of a wildcard one
+fn host_expression(hostname: &str) -> String {
+    match hostname.strip_prefix('*') {
+        Some(suffix) => format!("ends_with(http.host, \"{suffix}\")"),
+        None => format!("http.host eq \"{hostname}\""),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controllers::cloudflared::transform_rules::{is_managed_rule, rule_ref_prefix};
+
+    #[test]
+    fn build_rule() {
+        let prefix = rule_ref_prefix("ns", "tunnel");
+        let rule = source_range_rule(
+            &prefix,
+            "www.example.com",
+            &["10.0.0.0/8".to_string(), "2001:db8::/32".to_string()],
+        );
+        assert!(is_managed_rule(&rule, &prefix));
+        assert_eq!(
+            "(http.host eq \"www.example.com\" and not ip.src in {10.0.0.0/8 2001:db8::/32})",
+            rule["expression"]
+        );
+        assert_eq!("block", rule["action"]);
+    }
+
+    #[test]
+    fn build_wildcard_rule() {
+        let rule = source_range_rule(
+            &rule_ref_prefix("ns", "tunnel"),
+            "*.example.com",
+            &["10.0.0.0/8".to_string()],
+        );
+        assert_eq!(
+            "(ends_with(http.host, \".example.com\") and not ip.src in {10.0.0.0/8})",
+            rule["expression"]
+        );
+    }
+}
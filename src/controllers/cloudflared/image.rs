@@ -0,0 +1,71 @@
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Normalize an image reference to `registry/repository[:tag|@digest]`
+/// (`cloudflare/cloudflared` -> `docker.io/cloudflare/cloudflared`)
+pub(super) fn normalize_image_reference(image: &str) -> String {
+    let image = image.trim().to_lowercase();
+    match image.split_once('/') {
+        Some((registry, _))
+            if registry.contains('.') || registry.contains(':') || registry == "localhost" =>
+        {
+            image
+        }
+        Some(_) => format!("{DEFAULT_REGISTRY}/{image}"),
+        None => format!("{DEFAULT_REGISTRY}/library/{image}"),
+    }
+}
+
+/// An empty allowlist allows every image.
+/// Each entry is either a registry host (`ghcr.io`) or a repository prefix (`ghcr.io/org`).
+pub(super) fn is_image_allowed(image: &str, allowed_registries: &[String]) -> bool {
+    if allowed_registries.is_empty() {
+        return true;
+    }
+    let image = normalize_image_reference(image);
+    allowed_registries.iter().any(|allowed| {
+        let allowed = allowed.trim().trim_end_matches('/').to_lowercase();
+        !allowed.is_empty()
+            && image
+                .strip_prefix(allowed.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            "docker.io/cloudflare/cloudflared:2024.12.2",
+            normalize_image_reference("cloudflare/cloudflared:2024.12.2")
+        );
+        assert_eq!(
+            "docker.io/library/busybox",
+            normalize_image_reference("busybox")
+        );
+        assert_eq!(
+            "ghcr.io/org/cloudflared",
+            normalize_image_reference("ghcr.io/org/cloudflared")
+        );
+        assert_eq!(
+            "localhost:5000/cloudflared",
+            normalize_image_reference("localhost:5000/cloudflared")
+        );
+    }
+
+    #[test]
+    fn allowlist() {
+        let allowed = vec!["docker.io/cloudflare".to_string(), "ghcr.io".to_string()];
+        assert!(is_image_allowed("cloudflare/cloudflared:latest", &allowed));
+        assert!(is_image_allowed("ghcr.io/any/image", &allowed));
+        assert!(!is_image_allowed("cloudflarex/cloudflared", &allowed));
+        assert!(!is_image_allowed("ghcr.io.evil.example/image", &allowed));
+        assert!(!is_image_allowed(
+            "quay.io/cloudflare/cloudflared",
+            &allowed
+        ));
+        assert!(is_image_allowed("quay.io/cloudflare/cloudflared", &[]));
+    }
+}
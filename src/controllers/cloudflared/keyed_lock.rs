@@ -0,0 +1,87 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Async locks keyed by object, so that overlapping reconciles handle an object one at a time
+pub(super) struct KeyedLock<K> {
+    locks: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl<K> Default for KeyedLock<K> {
+    fn default() -> Self {
+        Self {
+            locks: Mutex::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> KeyedLock<K> {
+    /// Wait until no other task holds the lock of the key
+    pub(super) async fn lock(&self, key: K) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // 使用中でないlockは削除する
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(key).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn same_key_is_single_flight() {
+        let locks = Arc::new(KeyedLock::default());
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..4)
+            .map(|_| {
+                let locks = locks.clone();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    let _guard = locks.lock("cloudflared/tunnel").await;
+                    let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(current, Ordering::SeqCst);
+                    // 他のtaskが割り込めるよう処理の途中で待機する
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(1, max_running.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn different_keys_run_concurrently() {
+        let locks = KeyedLock::default();
+        let _first = locks.lock("cloudflared/first").await;
+        // 別のkeyのlockは待機せずに取得できる
+        tokio::time::timeout(Duration::from_secs(1), locks.lock("cloudflared/second"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unused_locks_are_removed() {
+        let locks = KeyedLock::default();
+        drop(locks.lock("cloudflared/first").await);
+        let _second = locks.lock("cloudflared/second").await;
+        assert_eq!(1, locks.locks.lock().unwrap().len());
+    }
+}
@@ -1,26 +1,37 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Debug};
 
 use k8s_openapi::{
     api::{
-        apps::v1::{Deployment, DeploymentSpec},
+        apps::v1::{Deployment, DeploymentSpec, StatefulSet, StatefulSetSpec},
         core::v1::{
-            Container, PodSpec, PodTemplateSpec, Secret, SecretVolumeSource, Volume, VolumeMount,
+            Affinity, Container, Namespace, Node, NodeAffinity, NodeSelector,
+            NodeSelectorRequirement, NodeSelectorTerm, PodSpec, PodTemplateSpec,
+            ResourceRequirements, Secret, SecretVolumeSource, Volume, VolumeMount,
         },
     },
-    apimachinery::pkg::apis::meta::v1::{LabelSelector, OwnerReference},
-    ByteString,
+    apimachinery::pkg::{
+        api::resource::Quantity,
+        apis::meta::v1::{LabelSelector, OwnerReference},
+    },
+    ByteString, NamespaceResourceScope,
 };
 use kube::{
-    api::{ListParams, ObjectMeta, Patch, PatchParams},
+    api::{DeleteParams, ListParams, ObjectMeta, Patch, PatchParams},
     Api, Client,
 };
+use serde::de::DeserializeOwned;
 
 use super::{
-    customresource::{CloudflaredTunnelSpec, CloudflaredTunnelStatus},
-    CloudflaredTunnel, CFD_DEPLOYMENT_IMAGE, PATCH_PARAMS_APPLY_NAME,
+    customresource::{
+        CloudflaredTunnelResources, CloudflaredTunnelSpec, CloudflaredTunnelStatus,
+        CloudflaredTunnelWorkloadKind,
+    },
+    CloudflaredTunnel, CFD_DEPLOYMENT_IMAGE, NODE_DRAIN_ANNOTATION, PATCH_PARAMS_APPLY_NAME,
 };
 use crate::Result;
 
+const WORKLOAD_LABEL: &str = "cloudflared-ingress.chalharu.top/workload";
+
 pub(super) async fn patch_cloudflaredtunnel_status<F: FnOnce(&mut CloudflaredTunnelStatus)>(
     client: &Client,
     namespace: &str,
@@ -65,23 +76,21 @@ pub(super) async fn patch_opaque_secret_string(
     data: BTreeMap<String, String>,
     owner_ref: Option<Vec<OwnerReference>>,
 ) -> Result<bool> {
-    let binary_data = data
-        .into_iter()
-        .map(|(k, v)| (k, ByteString(v.as_bytes().to_vec())))
-        .collect();
+    patch_opaque_secret(client, name, namespace, binary_data(data), owner_ref).await
+}
 
-    patch_opaque_secret(client, name, namespace, binary_data, owner_ref).await
+fn binary_data(data: BTreeMap<String, String>) -> BTreeMap<String, ByteString> {
+    data.into_iter()
+        .map(|(k, v)| (k, ByteString(v.as_bytes().to_vec())))
+        .collect()
 }
 
-pub(super) async fn patch_opaque_secret(
-    client: &Client,
+fn opaque_secret(
     name: &str,
-    namespace: &str,
     data: BTreeMap<String, ByteString>,
     owner_ref: Option<Vec<OwnerReference>>,
-) -> Result<bool> {
-    let api = Api::<Secret>::namespaced(client.clone(), namespace);
-    let secret = Secret {
+) -> Secret {
+    Secret {
         metadata: ObjectMeta {
             name: Some(name.to_string()),
             owner_references: owner_ref,
@@ -90,7 +99,18 @@ pub(super) async fn patch_opaque_secret(
         data: Some(data),
         type_: Some("Opaque".to_string()),
         ..Default::default()
-    };
+    }
+}
+
+pub(super) async fn patch_opaque_secret(
+    client: &Client,
+    name: &str,
+    namespace: &str,
+    data: BTreeMap<String, ByteString>,
+    owner_ref: Option<Vec<OwnerReference>>,
+) -> Result<bool> {
+    let api = Api::<Secret>::namespaced(client.clone(), namespace);
+    let secret = opaque_secret(name, data, owner_ref);
 
     let before = api.get_opt(name).await?;
 
@@ -107,112 +127,516 @@ pub(super) async fn patch_opaque_secret(
     }))
 }
 
-pub(super) async fn restart_deployment(
-    client: &Client,
-    name: &str,
-    namespace: &str,
-) -> Result<Deployment> {
-    let api = Api::<Deployment>::namespaced(client.clone(), namespace);
-    Ok(api.restart(name).await?)
-}
-
 pub(super) async fn get_cloudflaredtunnel(client: &Client) -> Result<Vec<CloudflaredTunnel>> {
     let api = Api::<CloudflaredTunnel>::all(client.clone());
     let results = api.list(&ListParams::default()).await?.items;
     Ok(results)
 }
 
-pub(super) async fn patch_deployment(
-    client: &Client,
-    name: &str,
-    namespace: &str,
-    tunnel_config_secret_name: &str,
-    tunnel_id: &str,
-    replicas: i32,
-    cfdt: &CloudflaredTunnelSpec,
-    owner_ref: Option<Vec<OwnerReference>>,
-) -> Result<bool> {
-    let api = Api::<Deployment>::namespaced(client.clone(), namespace);
+/// UID of the `kube-system` namespace, identifying the cluster
+pub(super) async fn get_cluster_uid(client: &Client) -> Result<String> {
+    let namespace = Api::<Namespace>::all(client.clone())
+        .get_metadata("kube-system")
+        .await?;
+    Ok(namespace.metadata.uid.unwrap_or_default())
+}
 
-    let deployment = Deployment {
-        metadata: ObjectMeta {
-            name: Some(name.to_string()),
-            namespace: Some(namespace.to_string()),
-            owner_references: owner_ref,
+/// Names of the nodes annotated for draining cloudflared replicas
+pub(super) async fn get_draining_nodes(client: &Client) -> Result<Vec<String>> {
+    let api = Api::<Node>::all(client.clone());
+    let mut nodes = api
+        .list_metadata(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter(|node| {
+            node.metadata
+                .annotations
+                .as_ref()
+                .and_then(|a| a.get(NODE_DRAIN_ANNOTATION))
+                .map_or(false, |x| x.to_lowercase() == "true")
+        })
+        .filter_map(|node| node.metadata.name)
+        .collect::<Vec<_>>();
+    nodes.sort();
+    Ok(nodes)
+}
+
+pub(super) struct WorkloadParams<'a> {
+    pub name: &'a str,
+    pub namespace: &'a str,
+    pub tunnel_config_secret_name: &'a str,
+    pub tunnel_id: &'a str,
+    pub replicas: i32,
+    pub cfdt: &'a CloudflaredTunnelSpec,
+    pub owner_ref: Option<Vec<OwnerReference>>,
+    /// Nodes the pods must be moved away from
+    pub draining_nodes: &'a [String],
+    /// `progressDeadlineSeconds` of the Deployment
+    pub progress_deadline_seconds: i32,
+}
+
+impl WorkloadParams<'_> {
+    fn kind(&self) -> CloudflaredTunnelWorkloadKind {
+        self.cfdt.workload_kind.unwrap_or_default()
+    }
+
+    fn metadata(&self) -> ObjectMeta {
+        ObjectMeta {
+            name: Some(self.name.to_string()),
+            namespace: Some(self.namespace.to_string()),
+            owner_references: self.owner_ref.clone(),
             ..Default::default()
-        },
-        spec: Some(DeploymentSpec {
-            replicas: Some(replicas),
-            selector: LabelSelector {
-                match_labels: Some(BTreeMap::from([(
-                    "app".to_string(),
-                    "cloudflared".to_string(),
-                )])),
+        }
+    }
+
+    fn pod_labels(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("app".to_string(), "cloudflared".to_string()),
+            (WORKLOAD_LABEL.to_string(), self.name.to_string()),
+        ])
+    }
+
+    /// Label selector string matching only the pods of this workload
+    pub fn pod_selector(&self) -> String {
+        self.pod_labels()
+            .into_iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    // selectorは変更不可のため、workload固有のlabelはpod templateにのみ付与する
+    fn selector(&self) -> LabelSelector {
+        LabelSelector {
+            match_labels: Some(BTreeMap::from([(
+                "app".to_string(),
+                "cloudflared".to_string(),
+            )])),
+            ..Default::default()
+        }
+    }
+
+    fn pod_template(&self) -> PodTemplateSpec {
+        let cfdt = self.cfdt;
+        PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                labels: Some(self.pod_labels()),
                 ..Default::default()
-            },
-            template: PodTemplateSpec {
-                metadata: Some(ObjectMeta {
-                    labels: Some(BTreeMap::from([(
-                        "app".to_string(),
-                        "cloudflared".to_string(),
-                    )])),
-                    ..Default::default()
-                }),
-                spec: Some(PodSpec {
-                    containers: vec![Container {
-                        command: cfdt.command.as_ref().cloned(),
-                        args: cfdt.args.as_ref().cloned().or_else(|| {
-                            Some(vec![
-                                "tunnel".to_string(),
-                                "--no-autoupdate".to_string(),
-                                "--config".to_string(),
-                                "/etc/cloudflared/config.yml".to_string(),
-                                "run".to_string(),
-                                tunnel_id.to_string(),
-                            ])
-                        }),
-                        image: cfdt
-                            .image
-                            .as_ref()
-                            .cloned()
-                            .or(Some(CFD_DEPLOYMENT_IMAGE.to_string())),
-                        name: name.to_string(),
-                        volume_mounts: Some(vec![VolumeMount {
-                            mount_path: "/etc/cloudflared".to_string(),
-                            name: "tunnel-config".to_string(),
-                            read_only: Some(true),
-                            ..Default::default()
-                        }]),
-                        ..Default::default()
-                    }],
-                    volumes: Some(vec![Volume {
+            }),
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    command: cfdt.command.as_ref().cloned(),
+                    args: cfdt.args.as_ref().cloned().or_else(|| {
+                        Some(vec![
+                            "tunnel".to_string(),
+                            "--no-autoupdate".to_string(),
+                            "--config".to_string(),
+                            "/etc/cloudflared/config.yml".to_string(),
+                            "run".to_string(),
+                            self.tunnel_id.to_string(),
+                        ])
+                    }),
+                    image: cfdt
+                        .image
+                        .as_ref()
+                        .cloned()
+                        .or(Some(CFD_DEPLOYMENT_IMAGE.to_string())),
+                    name: self.name.to_string(),
+                    resources: cfdt.resources.as_ref().map(resource_requirements),
+                    volume_mounts: Some(vec![VolumeMount {
+                        mount_path: "/etc/cloudflared".to_string(),
                         name: "tunnel-config".to_string(),
-                        secret: Some(SecretVolumeSource {
-                            default_mode: Some(0o644),
-                            optional: Some(false),
-                            secret_name: Some(tunnel_config_secret_name.to_string()),
-                            ..Default::default()
-                        }),
+                        read_only: Some(true),
                         ..Default::default()
                     }]),
                     ..Default::default()
+                }],
+                affinity: (!self.draining_nodes.is_empty()).then(|| Affinity {
+                    node_affinity: Some(NodeAffinity {
+                        required_during_scheduling_ignored_during_execution: Some(NodeSelector {
+                            node_selector_terms: vec![NodeSelectorTerm {
+                                match_fields: Some(vec![NodeSelectorRequirement {
+                                    key: "metadata.name".to_string(),
+                                    operator: "NotIn".to_string(),
+                                    values: Some(self.draining_nodes.to_vec()),
+                                }]),
+                                ..Default::default()
+                            }],
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
                 }),
-            },
+                volumes: Some(vec![Volume {
+                    name: "tunnel-config".to_string(),
+                    secret: Some(SecretVolumeSource {
+                        default_mode: Some(0o644),
+                        optional: Some(false),
+                        secret_name: Some(self.tunnel_config_secret_name.to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn deployment(&self) -> Deployment {
+        Deployment {
+            metadata: self.metadata(),
+            spec: Some(DeploymentSpec {
+                replicas: Some(self.replicas),
+                selector: self.selector(),
+                template: self.pod_template(),
+                progress_deadline_seconds: Some(self.progress_deadline_seconds),
+                ..Default::default()
+            }),
             ..Default::default()
-        }),
+        }
+    }
+
+    fn statefulset(&self) -> StatefulSet {
+        StatefulSet {
+            metadata: self.metadata(),
+            spec: Some(StatefulSetSpec {
+                replicas: Some(self.replicas),
+                selector: self.selector(),
+                template: self.pod_template(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+fn resource_requirements(resources: &CloudflaredTunnelResources) -> ResourceRequirements {
+    let quantities = |q: &Option<BTreeMap<String, String>>| {
+        q.as_ref().map(|q| {
+            q.iter()
+                .map(|(k, v)| (k.clone(), Quantity(v.clone())))
+                .collect()
+        })
+    };
+    ResourceRequirements {
+        limits: quantities(&resources.limits),
+        requests: quantities(&resources.requests),
         ..Default::default()
+    }
+}
+
+pub(super) struct PatchedWorkload {
+    /// The workload was created or its spec generation has changed
+    pub updated: bool,
+    pub replicas: i32,
+    pub current_replicas: i32,
+    pub ready_replicas: i32,
+    pub available_replicas: i32,
+    /// The workload controller observed the current spec and every replica runs it
+    pub rollout_complete: bool,
+    /// Message of the rollout failure, if the Deployment exceeded its progress deadline
+    pub rollout_failure: Option<String>,
+}
+
+/// Applies the workload (Deployment or StatefulSet) selected by `spec.workload_kind`
+/// and removes the workload of the other kind if this CloudflaredTunnel owns it.
+pub(super) async fn patch_workload(
+    client: &Client,
+    params: &WorkloadParams<'_>,
+) -> Result<PatchedWorkload> {
+    let owner_refs = params.owner_ref.as_deref().unwrap_or_default();
+    match params.kind() {
+        CloudflaredTunnelWorkloadKind::Deployment => {
+            delete_owned_workload::<StatefulSet>(client, params.namespace, params.name, owner_refs)
+                .await?;
+            patch_deployment(client, params).await
+        }
+        CloudflaredTunnelWorkloadKind::StatefulSet => {
+            delete_owned_workload::<Deployment>(client, params.namespace, params.name, owner_refs)
+                .await?;
+            patch_statefulset(client, params).await
+        }
+    }
+}
+
+/// Removes the workloads of both kinds this CloudflaredTunnel owns
+pub(super) async fn delete_owned_workloads(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    owner_refs: &[OwnerReference],
+) -> Result<()> {
+    delete_owned_workload::<Deployment>(client, namespace, name, owner_refs).await?;
+    delete_owned_workload::<StatefulSet>(client, namespace, name, owner_refs).await
+}
+
+pub(super) async fn restart_workload(
+    client: &Client,
+    kind: CloudflaredTunnelWorkloadKind,
+    name: &str,
+    namespace: &str,
+) -> Result<()> {
+    match kind {
+        CloudflaredTunnelWorkloadKind::Deployment => {
+            Api::<Deployment>::namespaced(client.clone(), namespace)
+                .restart(name)
+                .await?;
+        }
+        CloudflaredTunnelWorkloadKind::StatefulSet => {
+            Api::<StatefulSet>::namespaced(client.clone(), namespace)
+                .restart(name)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn delete_owned_workload<K>(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    owner_refs: &[OwnerReference],
+) -> Result<()>
+where
+    K: kube::Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+{
+    let api = Api::<K>::namespaced(client.clone(), namespace);
+    let Some(current) = api.get_metadata_opt(name).await? else {
+        return Ok(());
     };
+    let owned = owner_refs.iter().any(|owner| {
+        current
+            .metadata
+            .owner_references
+            .iter()
+            .flatten()
+            .any(|o| o.uid == owner.uid)
+    });
+    if owned {
+        api.delete(name, &DeleteParams::background()).await?;
+    }
+    Ok(())
+}
+
+async fn patch_deployment(client: &Client, params: &WorkloadParams<'_>) -> Result<PatchedWorkload> {
+    let api = Api::<Deployment>::namespaced(client.clone(), params.namespace);
+    let deployment = params.deployment();
 
-    let before = api.get_metadata_opt(name).await?;
+    let before = api.get_metadata_opt(params.name).await?;
     let patched = api
         .patch(
-            name,
+            params.name,
             &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
             &Patch::Apply(deployment),
         )
         .await?;
 
-    Ok(!before.map_or(false, |b| {
-        b.metadata.generation == patched.metadata.generation
-    }))
+    let status = patched.status.unwrap_or_default();
+    let rollout_failure = status
+        .conditions
+        .iter()
+        .flatten()
+        .find(|c| {
+            c.type_ == "Progressing"
+                && c.status == "False"
+                && c.reason.as_deref() == Some("ProgressDeadlineExceeded")
+        })
+        .map(|c| c.message.clone().unwrap_or_default());
+    let rollout_complete = status.observed_generation == patched.metadata.generation
+        && status.updated_replicas.unwrap_or_default() == params.replicas
+        && status.replicas.unwrap_or_default() == params.replicas;
+    Ok(PatchedWorkload {
+        updated: !before.map_or(false, |b| {
+            b.metadata.generation == patched.metadata.generation
+        }),
+        replicas: params.replicas,
+        current_replicas: status.replicas.unwrap_or_default(),
+        ready_replicas: status.ready_replicas.unwrap_or_default(),
+        available_replicas: status.available_replicas.unwrap_or_default(),
+        rollout_complete,
+        rollout_failure,
+    })
+}
+
+async fn patch_statefulset(
+    client: &Client,
+    params: &WorkloadParams<'_>,
+) -> Result<PatchedWorkload> {
+    let api = Api::<StatefulSet>::namespaced(client.clone(), params.namespace);
+    let statefulset = params.statefulset();
+
+    let before = api.get_metadata_opt(params.name).await?;
+    let patched = api
+        .patch(
+            params.name,
+            &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+            &Patch::Apply(statefulset),
+        )
+        .await?;
+
+    let status = patched.status.unwrap_or_default();
+    // 全podが新しいrevisionで動作するとcurrentRevisionがupdateRevisionに揃う
+    let rollout_complete = status.observed_generation == patched.metadata.generation
+        && status.updated_replicas.unwrap_or_default() == params.replicas
+        && status.replicas == params.replicas
+        && status.current_revision.is_some()
+        && status.current_revision == status.update_revision;
+    Ok(PatchedWorkload {
+        updated: !before.map_or(false, |b| {
+            b.metadata.generation == patched.metadata.generation
+        }),
+        replicas: params.replicas,
+        current_replicas: status.replicas,
+        ready_replicas: status.ready_replicas.unwrap_or_default(),
+        available_replicas: status.available_replicas.unwrap_or_default(),
+        rollout_complete,
+        // StatefulSetにはprogress deadlineが無い
+        rollout_failure: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::controllers::cloudflared::{
+        customresource::CloudflaredTunnelOriginRequest, secret::TUNNEL_SECRET_LEN,
+        tunnel_config_data, CloudflaredTunnelIngress,
+    };
+
+    const TUNNEL_ID: &str = "00000000-0000-0000-0000-000000000001";
+    const CONFIG_SECRET_NAME: &str = "00000000-0000-0000-0000-000000000010";
+
+    /// Compare the serialized object with the snapshot in `snapshots/`, ignoring formatting.
+    /// Set `UPDATE_SNAPSHOTS=1` to write the snapshot instead after an intended change.
+    fn assert_snapshot<T: Serialize>(name: &str, object: &T) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/controllers/cloudflared/snapshots")
+            .join(format!("{name}.yaml"));
+        let actual = serde_yaml::to_string(object).unwrap();
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read snapshot {}: {e}", path.display()));
+        assert_eq!(
+            serde_yaml::from_str::<serde_yaml::Value>(&expected).unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>(&actual).unwrap(),
+            "snapshot {name} changed, the new object is:\n{actual}"
+        );
+    }
+
+    fn owner_ref() -> Option<Vec<OwnerReference>> {
+        Some(vec![OwnerReference {
+            api_version: "chalharu.top/v1alpha1".to_string(),
+            kind: "CloudflaredTunnel".to_string(),
+            name: "example".to_string(),
+            uid: "00000000-0000-0000-0000-000000000100".to_string(),
+            controller: Some(true),
+            block_owner_deletion: Some(true),
+        }])
+    }
+
+    fn workload_params<'a>(
+        cfdt: &'a CloudflaredTunnelSpec,
+        replicas: i32,
+        draining_nodes: &'a [String],
+    ) -> WorkloadParams<'a> {
+        WorkloadParams {
+            name: "example-cloudflared",
+            namespace: "cloudflared",
+            tunnel_config_secret_name: CONFIG_SECRET_NAME,
+            tunnel_id: TUNNEL_ID,
+            replicas,
+            cfdt,
+            owner_ref: owner_ref(),
+            draining_nodes,
+            progress_deadline_seconds: 600,
+        }
+    }
+
+    #[test]
+    fn deployment_default() {
+        let spec = CloudflaredTunnelSpec::default();
+        assert_snapshot(
+            "deployment_default",
+            &workload_params(&spec, 1, &[]).deployment(),
+        );
+    }
+
+    #[test]
+    fn deployment_custom_image_and_args() {
+        let spec = CloudflaredTunnelSpec {
+            image: Some("registry.example.com/cloudflared:2025.1.0".to_string()),
+            command: Some(vec!["cloudflared".to_string()]),
+            args: Some(vec![
+                "tunnel".to_string(),
+                "--config".to_string(),
+                "/etc/cloudflared/config.yml".to_string(),
+                "run".to_string(),
+            ]),
+            resources: Some(CloudflaredTunnelResources {
+                limits: Some(BTreeMap::from([(
+                    "memory".to_string(),
+                    "128Mi".to_string(),
+                )])),
+                requests: Some(BTreeMap::from([("cpu".to_string(), "100m".to_string())])),
+            }),
+            ..Default::default()
+        };
+        assert_snapshot(
+            "deployment_custom_image_and_args",
+            &workload_params(&spec, 1, &[]).deployment(),
+        );
+    }
+
+    #[test]
+    fn deployment_replicas_and_draining_nodes() {
+        let spec = CloudflaredTunnelSpec {
+            replicas: Some(3),
+            ..Default::default()
+        };
+        let draining_nodes = ["node-1".to_string()];
+        assert_snapshot(
+            "deployment_replicas_and_draining_nodes",
+            &workload_params(&spec, 3, &draining_nodes).deployment(),
+        );
+    }
+
+    #[test]
+    fn config_secret_origin_request() {
+        let spec = CloudflaredTunnelSpec {
+            origin_request: Some(CloudflaredTunnelOriginRequest {
+                no_tls_verify: Some(true),
+                http_host_header: Some("web.example.com".to_string()),
+                ..Default::default()
+            }),
+            ingress: Some(vec![CloudflaredTunnelIngress {
+                hostname: "www.example.com".to_string(),
+                service: "http://web.default.svc".to_string(),
+                ..Default::default()
+            }]),
+            default_ingress_service: "http_status:404".to_string(),
+            ..Default::default()
+        };
+        let data = tunnel_config_data(
+            "a0000000000000000000000000000001",
+            TUNNEL_ID,
+            &[1u8; TUNNEL_SECRET_LEN],
+            &spec,
+        )
+        .unwrap();
+        assert_snapshot(
+            "config_secret_origin_request",
+            &opaque_secret(CONFIG_SECRET_NAME, binary_data(data), owner_ref()),
+        );
+    }
 }
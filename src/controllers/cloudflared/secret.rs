@@ -0,0 +1,41 @@
+use crate::Result;
+
+/// Length of the generated tunnel secret, which is also the minimum accepted length
+pub(super) const TUNNEL_SECRET_LEN: usize = 32;
+
+/// Generate a tunnel secret from the operating system's CSPRNG
+#[cfg(not(feature = "fips"))]
+pub(super) fn generate_tunnel_secret() -> Result<Vec<u8>> {
+    use rand::{rngs::OsRng, RngCore as _};
+
+    let mut secret = vec![0u8; TUNNEL_SECRET_LEN];
+    OsRng.try_fill_bytes(&mut secret)?;
+    Ok(secret)
+}
+
+/// Generate a tunnel secret from the FIPS validated aws-lc module
+#[cfg(feature = "fips")]
+pub(super) fn generate_tunnel_secret() -> Result<Vec<u8>> {
+    let mut secret = vec![0u8; TUNNEL_SECRET_LEN];
+    aws_lc_rs::rand::fill(&mut secret)?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tunnel_secret_is_32_bytes() {
+        let secret = generate_tunnel_secret().unwrap();
+        assert_eq!(32, secret.len());
+    }
+
+    #[test]
+    fn tunnel_secrets_differ() {
+        assert_ne!(
+            generate_tunnel_secret().unwrap(),
+            generate_tunnel_secret().unwrap()
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use serde_json::{json, Value};
+
+use super::customresource::CloudflaredTunnelResponseHeaders;
+
+/// Prefix of the `ref` of the rules managed for a CloudflaredTunnel
+pub(super) fn rule_ref_prefix(namespace: &str, name: &str) -> String {
+    format!("cloudflared-ingress:{namespace}/{name}:")
+}
+
+pub(super) fn is_managed_rule(rule: &Value, ref_prefix: &str) -> bool {
+    rule.get("ref")
+        .and_then(Value::as_str)
+        .is_some_and(|r| r.starts_with(ref_prefix))
+}
+
+/// Build the response header rewrite rule for a hostname
+pub(super) fn response_header_rule(
+    ref_prefix: &str,
+    headers: &CloudflaredTunnelResponseHeaders,
+) -> Value {
+    let operations = headers
+        .set
+        .iter()
+        .flatten()
+        .map(|(k, v)| (k.clone(), json!({ "operation": "set", "value": v })))
+        .chain(
+            headers
+                .remove
+                .iter()
+                .flatten()
+                .map(|k| (k.clone(), json!({ "operation": "remove" }))),
+        )
+        .collect::<serde_json::Map<_, _>>();
+    json!({
+        "ref": format!("{ref_prefix}{}", headers.hostname),
+        "description": format!("Managed by cloudflared-ingress: {}", headers.hostname),
+        "expression": format!("(http.host eq \"{}\")", headers.hostname),
+        "action": "rewrite",
+        "action_parameters": { "headers": operations },
+        "enabled": true,
+    })
+}
+
+/// Strip the read-only fields returned by the API so that rules can be compared and written back
+pub(super) fn normalize_rule(rule: &Value) -> Value {
+    let mut rule = rule.clone();
+    if let Some(obj) = rule.as_object_mut() {
+        for key in ["id", "version", "last_updated", "categories"] {
+            obj.remove(key);
+        }
+    }
+    rule
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn build_rule() {
+        let prefix = rule_ref_prefix("ns", "tunnel");
+        let rule = response_header_rule(
+            &prefix,
+            &CloudflaredTunnelResponseHeaders {
+                hostname: "www.example.com".to_string(),
+                set: Some(BTreeMap::from([(
+                    "X-Frame-Options".to_string(),
+                    "DENY".to_string(),
+                )])),
+                remove: Some(vec!["Server".to_string()]),
+            },
+        );
+        assert!(is_managed_rule(&rule, &prefix));
+        assert!(!is_managed_rule(&rule, &rule_ref_prefix("ns", "tunnel2")));
+        assert_eq!("(http.host eq \"www.example.com\")", rule["expression"]);
+        assert_eq!(
+            json!({
+                "X-Frame-Options": { "operation": "set", "value": "DENY" },
+                "Server": { "operation": "remove" },
+            }),
+            rule["action_parameters"]["headers"]
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let rule = json!({ "id": "1", "version": "2", "last_updated": "now", "ref": "r" });
+        assert_eq!(json!({ "ref": "r" }), normalize_rule(&rule));
+    }
+}
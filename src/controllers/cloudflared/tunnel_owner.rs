@@ -0,0 +1,67 @@
+use cloudflare::endpoints::cfd_tunnel::Tunnel;
+use serde_json::{json, Value};
+
+/// Key of the tunnel metadata holding the identity of the controller that created it
+const CONTROLLER_KEY: &str = "cloudflared_ingress_controller";
+
+/// Metadata of a created tunnel, naming the controller and the CloudflaredTunnel it belongs to
+pub(super) fn tunnel_metadata(controller_id: &str, namespace: &str, name: &str) -> Value {
+    json!({
+        CONTROLLER_KEY: controller_id,
+        "namespace": namespace,
+        "name": name,
+    })
+}
+
+/// Who created a tunnel, according to its metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TunnelOwner {
+    /// This controller
+    Controller,
+    /// Another controller sharing the tunnel prefix
+    OtherController,
+    /// Created without the metadata, e.g. by an older version of the controller
+    Unknown,
+}
+
+pub(super) fn owner_of(tunnel: &Tunnel, controller_id: &str) -> TunnelOwner {
+    match tunnel.metadata.get(CONTROLLER_KEY).and_then(Value::as_str) {
+        Some(id) if id == controller_id => TunnelOwner::Controller,
+        Some(_) => TunnelOwner::OtherController,
+        None => TunnelOwner::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tunnel(metadata: Value) -> Tunnel {
+        serde_json::from_value(json!({
+            "id": "00000000000000000000000000000001",
+            "created_at": "2000-01-01T00:00:00.000000Z",
+            "deleted_at": null,
+            "name": "k8s-ingress-tunnel",
+            "connections": [],
+            "metadata": metadata,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn owner_from_metadata() {
+        let metadata = tunnel_metadata("cluster-a", "default", "web");
+        assert_eq!(
+            TunnelOwner::Controller,
+            owner_of(&tunnel(metadata.clone()), "cluster-a")
+        );
+        assert_eq!(
+            TunnelOwner::OtherController,
+            owner_of(&tunnel(metadata), "cluster-b")
+        );
+        assert_eq!(
+            TunnelOwner::Unknown,
+            owner_of(&tunnel(json!({})), "cluster-a")
+        );
+    }
+}
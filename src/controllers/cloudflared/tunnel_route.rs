@@ -0,0 +1,393 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt as _;
+use kube::{
+    api::{ObjectMeta, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::EventType,
+        finalizer::{finalizer, Event as FinalizerEvent},
+        reflector::ObjectRef,
+        watcher::Config,
+        Controller,
+    },
+    Api, CustomResource, Resource as _, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    cf_api::{TunnelRoute, TunnelRouteSettings},
+    customresource::CloudflaredTunnelCredentialsSecretRef,
+    virtual_network::CloudflaredVirtualNetwork,
+    CloudflaredTunnel, Context, PATCH_PARAMS_APPLY_NAME,
+};
+use crate::{resync::ResyncTrigger, Error, Result};
+
+#[derive(CustomResource, Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[kube(
+    // Required properties
+    group = "chalharu.top",
+    version = "v1alpha1",
+    kind = "CloudflaredTunnelRoute",
+    // Optional properties
+    singular = "cloudflaredtunnelroute",
+    plural = "cloudflaredtunnelroutes",
+    shortname = "cfdtroute",
+    status = "CloudflaredTunnelRouteStatus",
+    namespaced,
+)]
+pub struct CloudflaredTunnelRouteSpec {
+    /// CloudflaredTunnel in the same namespace whose tunnel the network is routed through
+    pub tunnel: String,
+    /// Private network CIDR reached by the WARP clients, such as `10.96.0.0/12`
+    pub network: String,
+    /// Comment of the route, `{namespace}/{name}` of the CloudflaredTunnelRoute by default
+    pub comment: Option<String>,
+    /// Virtual network of the route, the default one of the account when unset
+    pub virtual_network_id: Option<String>,
+    /// CloudflaredVirtualNetwork in the same namespace holding the virtual network of the route,
+    /// instead of `virtual_network_id`
+    pub virtual_network: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredTunnelRouteStatus {
+    pub route_id: Option<String>,
+    pub tunnel_id: Option<String>,
+    pub virtual_network_id: Option<String>,
+    /// Account holding the route, deleted with its credentials also after the tunnel
+    pub account_id: Option<String>,
+    pub cloudflare_credentials_secret_ref: Option<CloudflaredTunnelCredentialsSecretRef>,
+}
+
+/// Reconcile the CloudflaredTunnelRoutes until the controller is shut down
+pub(super) async fn run_route_controller(ctx: Arc<Context>, resync: ResyncTrigger) {
+    info!("Starting controller for CloudflaredTunnelRoute");
+
+    let api = Api::<CloudflaredTunnelRoute>::all(ctx.client.clone());
+    let controller = Controller::new(api, Config::default().any_semantic());
+
+    // tunnelやvirtual networkの作成や削除に追従するため、参照先の変更時も再調整する
+    let store = controller.store();
+    let vnet_store = store.clone();
+    controller
+        .watches(
+            Api::<CloudflaredTunnel>::all(ctx.client.clone()),
+            Config::default(),
+            move |cfdt| {
+                store
+                    .state()
+                    .into_iter()
+                    .filter(|route| {
+                        route.namespace() == cfdt.namespace()
+                            && route.spec.tunnel == cfdt.name_any()
+                    })
+                    .map(|route| ObjectRef::from_obj(&*route))
+                    .collect::<Vec<_>>()
+            },
+        )
+        .watches(
+            Api::<CloudflaredVirtualNetwork>::all(ctx.client.clone()),
+            Config::default(),
+            move |vnet| {
+                vnet_store
+                    .state()
+                    .into_iter()
+                    .filter(|route| {
+                        route.namespace() == vnet.namespace()
+                            && route.spec.virtual_network.as_ref() == Some(&vnet.name_any())
+                    })
+                    .map(|route| ObjectRef::from_obj(&*route))
+                    .collect::<Vec<_>>()
+            },
+        )
+        .reconcile_all_on(ctx.subscribe_resync(&resync))
+        .shutdown_on_signal()
+        .run(reconcile, error_policy, ctx)
+        .filter_map(|x| async move { std::result::Result::ok(x) })
+        .for_each(|_| futures::future::ready(()))
+        .await;
+
+    info!("controller for CloudflaredTunnelRoute shutdown");
+}
+
+async fn reconcile(route: Arc<CloudflaredTunnelRoute>, ctx: Arc<Context>) -> Result<Action> {
+    if !ctx.is_own_shard(&*route) {
+        return Ok(Action::await_change());
+    }
+    let ns = route_namespace(&route)?;
+    let api = Api::<CloudflaredTunnelRoute>::namespaced(ctx.client.clone(), &ns);
+    finalizer(&api, &finalizer_name(), route, |e| {
+        let ctx = ctx.clone();
+        async move {
+            match e {
+                FinalizerEvent::Apply(route) => ctx.apply_tunnel_route(&route).await,
+                FinalizerEvent::Cleanup(route) => ctx.cleanup_tunnel_route(&route).await,
+            }
+        }
+    })
+    .await
+    .map_err(|e| Error::from(Box::new(e)))
+}
+
+fn error_policy(route: Arc<CloudflaredTunnelRoute>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile of CloudflaredTunnelRoute failed: {error:?}");
+    // error_policyは同期関数のため、eventの送信は別タスクで行う
+    let reason = error.reason();
+    let note = error.to_string();
+    tokio::spawn(async move {
+        ctx.publish_object_event(&*route, EventType::Warning, reason, "Reconcile", Some(note))
+            .await
+    });
+    Action::requeue(Duration::from_secs(60))
+}
+
+pub(super) fn finalizer_name() -> String {
+    format!("{}/route-finalizer", PATCH_PARAMS_APPLY_NAME)
+}
+
+fn route_namespace(route: &CloudflaredTunnelRoute) -> Result<String> {
+    route.namespace().ok_or_else(|| {
+        Error::missing_namespace(CloudflaredTunnelRoute::kind(&()), route.name_any())
+    })
+}
+
+/// Whether the route already matches the settings, any virtual network matching an unset one
+fn is_up_to_date(route: &TunnelRoute, settings: &TunnelRouteSettings) -> bool {
+    route.network == settings.network
+        && route.tunnel_id == settings.tunnel_id
+        && route.comment.as_deref() == Some(settings.comment.as_str())
+        && settings
+            .virtual_network_id
+            .as_ref()
+            .map_or(true, |id| route.virtual_network_id.as_ref() == Some(id))
+}
+
+impl Context {
+    /// Create or update the tunnel route of the CloudflaredTunnelRoute, once its tunnel exists
+    async fn apply_tunnel_route(&self, route: &CloudflaredTunnelRoute) -> Result<Action> {
+        let namespace = route_namespace(route)?;
+        let name = route.name_any();
+        let Some(cfdt) = Api::<CloudflaredTunnel>::namespaced(self.client.clone(), &namespace)
+            .get_opt(&route.spec.tunnel)
+            .await?
+        else {
+            return Err(Error::object_not_found(
+                CloudflaredTunnel::kind(&()),
+                format!("{namespace}/{}", route.spec.tunnel),
+            ));
+        };
+        // tunnelの作成後、CloudflaredTunnelの変更を契機に再調整される
+        let Some(tunnel_id) = cfdt.status.as_ref().and_then(|s| s.tunnel_id.clone()) else {
+            return Ok(Action::await_change());
+        };
+        let virtual_network_id = match route.spec.virtual_network {
+            Some(ref vnet_name) => {
+                let Some(vnet) =
+                    Api::<CloudflaredVirtualNetwork>::namespaced(self.client.clone(), &namespace)
+                        .get_opt(vnet_name)
+                        .await?
+                else {
+                    return Err(Error::object_not_found(
+                        CloudflaredVirtualNetwork::kind(&()),
+                        format!("{namespace}/{vnet_name}"),
+                    ));
+                };
+                // virtual networkの作成後、CloudflaredVirtualNetworkの変更を契機に再調整される
+                let Some(id) = vnet.status.and_then(|s| s.virtual_network_id) else {
+                    return Ok(Action::await_change());
+                };
+                Some(id)
+            }
+            None => route.spec.virtual_network_id.clone(),
+        };
+        let account = self.account_of(&cfdt).await?;
+        let settings = TunnelRouteSettings {
+            network: route.spec.network.clone(),
+            tunnel_id,
+            comment: route
+                .spec
+                .comment
+                .clone()
+                .unwrap_or_else(|| format!("{namespace}/{name}")),
+            virtual_network_id,
+        };
+
+        let current = match route.status.as_ref().and_then(|s| s.route_id.clone()) {
+            Some(route_id) => {
+                account
+                    .api
+                    .get_tunnel_route_opt(account.account_id.clone(), route_id)
+                    .await?
+            }
+            // statusへの記録前に中断された作成のrouteを引き継ぐ
+            None => {
+                account
+                    .api
+                    .find_tunnel_route(
+                        account.account_id.clone(),
+                        &settings.network,
+                        &settings.comment,
+                    )
+                    .await?
+            }
+        };
+        let applied = match current {
+            Some(current) if is_up_to_date(&current, &settings) => current,
+            Some(current) => {
+                account
+                    .api
+                    .update_tunnel_route(account.account_id.clone(), current.id, &settings)
+                    .await?
+            }
+            None => {
+                let created = account
+                    .api
+                    .create_tunnel_route(account.account_id.clone(), &settings)
+                    .await?;
+                self.publish_object_event(
+                    route,
+                    EventType::Normal,
+                    "TunnelRouteCreated",
+                    "CreateTunnelRoute",
+                    Some(format!(
+                        "Routed {} through tunnel {}",
+                        created.network, created.tunnel_id
+                    )),
+                )
+                .await;
+                created
+            }
+        };
+
+        let status = CloudflaredTunnelRouteStatus {
+            route_id: Some(applied.id),
+            tunnel_id: Some(applied.tunnel_id),
+            virtual_network_id: applied.virtual_network_id,
+            account_id: Some(account.account_id.clone()),
+            cloudflare_credentials_secret_ref: cfdt.spec.cloudflare_credentials_secret_ref.clone(),
+        };
+        if route.status.as_ref() != Some(&status) {
+            Api::<CloudflaredTunnelRoute>::namespaced(self.client.clone(), &namespace)
+                .patch_status(
+                    &name,
+                    &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+                    &Patch::Apply(CloudflaredTunnelRoute {
+                        metadata: ObjectMeta::default(),
+                        spec: CloudflaredTunnelRouteSpec::default(),
+                        status: Some(status),
+                    }),
+                )
+                .await?;
+        }
+        Ok(Action::requeue(Duration::from_secs(60 * 60)))
+    }
+
+    /// Delete the tunnel route of the CloudflaredTunnelRoute with the credentials it was created
+    /// with
+    pub(super) async fn cleanup_tunnel_route(
+        &self,
+        route: &CloudflaredTunnelRoute,
+    ) -> Result<Action> {
+        let Some(status) = route.status.as_ref() else {
+            return Ok(Action::await_change());
+        };
+        let Some(route_id) = status.route_id.clone() else {
+            return Ok(Action::await_change());
+        };
+        let namespace = route_namespace(route)?;
+        let account = match status.account_id {
+            Some(ref account_id) => {
+                match self
+                    .account_of_secret(
+                        &namespace,
+                        status.cloudflare_credentials_secret_ref.as_ref(),
+                    )
+                    .await
+                {
+                    Ok(account) => account,
+                    // 認証情報のSecretが削除された場合は、既定のaccountのrouteのみ削除する
+                    Err(error)
+                        if error.is_not_found()
+                            && account_id == self.args.cloudflare_account_id() =>
+                    {
+                        self.default_account()
+                    }
+                    Err(error) if error.is_not_found() => {
+                        self.publish_object_event(
+                            route,
+                            EventType::Warning,
+                            "CredentialsUnavailable",
+                            "Cleanup",
+                            Some(format!(
+                                "Credentials Secret is gone, route {route_id} is left in account {account_id}"
+                            )),
+                        )
+                        .await;
+                        return Ok(Action::await_change());
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            // accountを記録する前に作成されたrouteは、CloudflaredTunnelか既定のaccountで削除する
+            None => match Api::<CloudflaredTunnel>::namespaced(self.client.clone(), &namespace)
+                .get_opt(&route.spec.tunnel)
+                .await?
+            {
+                Some(cfdt) => self.account_of(&cfdt).await?,
+                None => self.default_account(),
+            },
+        };
+        if account
+            .api
+            .get_tunnel_route_opt(account.account_id.clone(), route_id.clone())
+            .await?
+            .is_some()
+        {
+            account
+                .api
+                .delete_tunnel_route(account.account_id.clone(), route_id)
+                .await?;
+        }
+        Ok(Action::await_change())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn route_up_to_date() {
+        let route = TunnelRoute {
+            id: "00000000-0000-0000-0000-000000000007".to_string(),
+            network: "10.96.0.0/12".to_string(),
+            tunnel_id: "00000000-0000-0000-0000-000000000001".to_string(),
+            comment: Some("default/services".to_string()),
+            virtual_network_id: Some("00000000-0000-0000-0000-000000000008".to_string()),
+        };
+        let settings = TunnelRouteSettings {
+            network: "10.96.0.0/12".to_string(),
+            tunnel_id: "00000000-0000-0000-0000-000000000001".to_string(),
+            comment: "default/services".to_string(),
+            virtual_network_id: None,
+        };
+        assert!(is_up_to_date(&route, &settings));
+        assert!(!is_up_to_date(
+            &route,
+            &TunnelRouteSettings {
+                virtual_network_id: Some("00000000-0000-0000-0000-000000000009".to_string()),
+                ..settings.clone()
+            }
+        ));
+        assert!(!is_up_to_date(
+            &route,
+            &TunnelRouteSettings {
+                tunnel_id: "00000000-0000-0000-0000-000000000002".to_string(),
+                ..settings
+            }
+        ));
+    }
+}
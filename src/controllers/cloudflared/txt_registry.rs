@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use cloudflare::endpoints::dns::{DnsContent, DnsRecord};
+
+const HERITAGE: &str = "heritage=cloudflared-ingress";
+const OWNER_KEY: &str = "cloudflared-ingress/owner=";
+/// Label replacing the `*` of a wildcard hostname, as a TXT record cannot be named after it
+const WILDCARD_LABEL: &str = "_wildcard";
+
+/// TXT records naming the owner of the CNAME records, in the way of the external-dns TXT
+/// registry. A CNAME record is only deleted when the TXT record of its hostname names this
+/// controller, so records created by hand or by other tooling are never touched
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct TxtRegistry {
+    owner_id: String,
+    prefix: String,
+}
+
+impl TxtRegistry {
+    pub(super) fn new(owner_id: &str, prefix: &str) -> Self {
+        Self {
+            owner_id: owner_id.to_string(),
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Name of the TXT record of a hostname, prefixed as a CNAME record cannot share its name
+    pub(super) fn record_name(&self, hostname: &str) -> String {
+        match hostname.strip_prefix("*.") {
+            Some(domain) => format!("{}{WILDCARD_LABEL}.{domain}", self.prefix),
+            None => format!("{}{hostname}", self.prefix),
+        }
+    }
+
+    /// Content of the TXT record, naming the owner and the CloudflaredTunnel of the record
+    pub(super) fn record_content(&self, namespace: &str, name: &str) -> String {
+        format!(
+            "\"{HERITAGE},{OWNER_KEY}{},cloudflared-ingress/resource=cloudflaredtunnel/{namespace}/{name}\"",
+            self.owner_id
+        )
+    }
+
+    /// Hostnames whose TXT record names this owner, with the id of the TXT record
+    pub(super) fn owned(&self, records: &[DnsRecord]) -> HashMap<String, String> {
+        records
+            .iter()
+            .filter_map(|record| {
+                let DnsContent::TXT { ref content } = record.content else {
+                    return None;
+                };
+                let hostname = record.name.strip_prefix(&self.prefix)?;
+                self.is_owned(content).then(|| {
+                    let hostname = match hostname.strip_prefix(WILDCARD_LABEL) {
+                        Some(domain) if domain.starts_with('.') => format!("*{domain}"),
+                        _ => hostname.to_string(),
+                    };
+                    (hostname, record.id.clone())
+                })
+            })
+            .collect()
+    }
+
+    fn is_owned(&self, content: &str) -> bool {
+        let fields = content.trim_matches('"').split(',').collect::<Vec<_>>();
+        fields.contains(&HERITAGE)
+            && fields
+                .iter()
+                .any(|f| f.strip_prefix(OWNER_KEY) == Some(self.owner_id.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn txt_record(id: &str, name: &str, content: &str) -> DnsRecord {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "zone_id": "00000000000000000000000000000001",
+            "zone_name": "example.com",
+            "name": name,
+            "type": "TXT",
+            "content": content,
+            "proxiable": false,
+            "proxied": false,
+            "ttl": 1,
+            "settings": {},
+            "meta": {
+                "auto_added": false,
+                "managed_by_apps": false,
+                "managed_by_argo_tunnel": false,
+            },
+            "comment": null,
+            "tags": [],
+            "created_on": "2000-01-01T00:00:00.000000Z",
+            "modified_on": "2000-01-01T00:00:00.000000Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn owned_hostnames() {
+        let registry = TxtRegistry::new("cluster-a", "_cfi.");
+        let other = TxtRegistry::new("cluster-b", "_cfi.");
+        assert_eq!(
+            "_cfi._wildcard.example.com",
+            registry.record_name("*.example.com")
+        );
+        let records = [
+            txt_record(
+                "1",
+                &registry.record_name("www.example.com"),
+                &registry.record_content("default", "web"),
+            ),
+            txt_record(
+                "2",
+                &registry.record_name("*.example.com"),
+                &registry.record_content("default", "web"),
+            ),
+            txt_record(
+                "3",
+                &other.record_name("api.example.com"),
+                &other.record_content("default", "api"),
+            ),
+            txt_record("4", "_cfi.docs.example.com", "v=spf1 -all"),
+        ];
+        assert_eq!(
+            HashMap::from([
+                ("www.example.com".to_string(), "1".to_string()),
+                ("*.example.com".to_string(), "2".to_string()),
+            ]),
+            registry.owned(&records)
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::{fmt::Debug, future::Future, sync::Arc};
+
+use cloudflare::endpoints::{cfd_tunnel::Tunnel, zone::Zone};
+use k8s_openapi::{api::apps::v1::Deployment, NamespaceResourceScope};
+use kube::{
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+    runtime::{
+        reflector,
+        wait::{await_condition, conditions},
+    },
+    Api, Client, Resource, ResourceExt as _,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    controller_id,
+    credentials::CloudflareAccount,
+    dns_record, finalizer_name,
+    tunnel_owner::{owner_of, TunnelOwner},
+    tunnel_route, virtual_network, CloudflareDNSRecord, CloudflaredTunnel, CloudflaredTunnelRoute,
+    CloudflaredVirtualNetwork, Context,
+};
+use crate::{cli::UninstallArgs, metrics::Metrics, readiness::Readiness, Error, Result};
+
+/// Delete the managed Cloudflare resources and custom resources in dependency order:
+/// the controller is stopped first so that nothing is recreated, then the CloudflareDNSRecords
+/// and CloudflaredTunnelRoutes, each tunnel (routes, DNS records and the tunnel itself) before
+/// its CloudflaredTunnel, whose workloads and secrets are garbage collected through the owner
+/// references, and last the CloudflaredVirtualNetworks the routes were in.
+pub async fn uninstall(args: &UninstallArgs) -> Result<()> {
+    let client = Client::try_default().await?;
+    tokio::time::timeout(args.uninstall_timeout(), run(client, args)).await?
+}
+
+async fn run(client: Client, args: &UninstallArgs) -> Result<()> {
+    if let Some(name) = args.controller_deployment() {
+        stop_controller(&client, name).await?;
+    }
+
+    // controllerが停止した後はIngressの削除を妨げないようfinalizerを外す
+    crate::controllers::ingress::remove_finalizers(&client, args.controller_args()).await?;
+
+    let controller_args = args.controller_args().clone().resolve_secret_refs().await?;
+    let controller_id = controller_id(&client, &controller_args).await?;
+    let ctx = Context::new(
+        client.clone(),
+        controller_args,
+        controller_id,
+        Metrics::default(),
+        Readiness::default(),
+        // 一度きりの実行のため、Secretは監視せずに毎回読み込む
+        reflector::store().0,
+    )?;
+    let ctx = &ctx;
+    // 1つの削除に失敗しても残りの削除は続け、失敗したものを最後にまとめて返す
+    let mut failed = Vec::new();
+    // routeはCloudflaredTunnelのaccountで削除し、virtual networkはrouteが残っていると削除できない
+    uninstall_objects::<CloudflareDNSRecord, _, _>(
+        &client,
+        &dns_record::finalizer_name(),
+        &mut failed,
+        move |record| async move { ctx.cleanup_dns_record(&record).await.map(|_| ()) },
+    )
+    .await?;
+    uninstall_objects::<CloudflaredTunnelRoute, _, _>(
+        &client,
+        &tunnel_route::finalizer_name(),
+        &mut failed,
+        move |route| async move { ctx.cleanup_tunnel_route(&route).await.map(|_| ()) },
+    )
+    .await?;
+    uninstall_objects::<CloudflaredTunnel, _, _>(
+        &client,
+        &finalizer_name(),
+        &mut failed,
+        move |cfdt| async move { ctx.delete_tunnel(Arc::new(cfdt)).await },
+    )
+    .await?;
+    uninstall_objects::<CloudflaredVirtualNetwork, _, _>(
+        &client,
+        &virtual_network::finalizer_name(),
+        &mut failed,
+        move |vnet| async move { ctx.cleanup_virtual_network(&vnet).await.map(|_| ()) },
+    )
+    .await?;
+
+    // CloudflaredTunnelに紐付かないtunnelも削除する
+    let account_id = ctx.args.cloudflare_account_id().to_string();
+    let mut account = ctx.default_account();
+    let zones = ctx.list_zones(&mut account).await?;
+    for tunnel in ctx
+        .cloudflare_api
+        .list_tunnels(
+            account_id.clone(),
+            ctx.args.cloudflare_tunnel_prefix().to_string(),
+        )
+        .await?
+    {
+        // 他のcontrollerや以前のversionが作成したtunnelは残す
+        if owner_of(&tunnel, &ctx.controller_id) != TunnelOwner::Controller {
+            info!("Skip tunnel {} not created by this controller", tunnel.name);
+            continue;
+        }
+        info!("Uninstall orphaned tunnel {}", tunnel.name);
+        if let Err(error) = uninstall_tunnel(ctx, &account, &zones, &tunnel).await {
+            warn!("Uninstall of tunnel {} failed: {error}", tunnel.name);
+            failed.push(format!("tunnel {}: {error}", tunnel.name));
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(Error::uninstall_incomplete(failed));
+    }
+    info!("Uninstall completed");
+    Ok(())
+}
+
+/// Clean up every object of the kind, then remove its finalizer and delete it. The objects
+/// that fail are added to `failed` and keep their finalizer
+async fn uninstall_objects<K, F, Fut>(
+    client: &Client,
+    finalizer_name: &str,
+    failed: &mut Vec<String>,
+    cleanup: F,
+) -> Result<()>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + Debug
+        + DeserializeOwned,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    for obj in Api::<K>::all(client.clone())
+        .list(&ListParams::default())
+        .await?
+        .items
+    {
+        let key = format!(
+            "{} {}/{}",
+            K::kind(&()),
+            obj.namespace().unwrap_or_default(),
+            obj.name_any()
+        );
+        info!("Uninstall {key}");
+        if let Err(error) = uninstall_object(client, finalizer_name, obj, &cleanup).await {
+            warn!("Uninstall of {key} failed: {error}");
+            failed.push(format!("{key}: {error}"));
+        }
+    }
+    Ok(())
+}
+
+async fn uninstall_object<K, F, Fut>(
+    client: &Client,
+    finalizer_name: &str,
+    obj: K,
+    cleanup: &F,
+) -> Result<()>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + Debug
+        + DeserializeOwned,
+    F: Fn(K) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let ns = obj.namespace().unwrap_or_default();
+    let name = obj.name_any();
+    cleanup(obj.clone()).await?;
+
+    // controllerは停止しているため、finalizerを外してから削除する
+    let api = Api::<K>::namespaced(client.clone(), &ns);
+    let finalizers = obj
+        .finalizers()
+        .iter()
+        .filter(|f| *f != finalizer_name)
+        .cloned()
+        .collect::<Vec<_>>();
+    api.patch(
+        &name,
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "metadata": { "finalizers": finalizers } })),
+    )
+    .await?;
+    // 所有するworkload・secretの削除を待つ
+    api.delete(&name, &DeleteParams::foreground()).await?;
+    if let Some(uid) = obj.uid() {
+        await_condition(api, &name, conditions::is_deleted(&uid)).await?;
+    }
+    Ok(())
+}
+
+/// Delete a tunnel left without a CloudflaredTunnel together with its CNAME records
+async fn uninstall_tunnel(
+    ctx: &Context,
+    account: &CloudflareAccount,
+    zones: &[Zone],
+    tunnel: &Tunnel,
+) -> Result<()> {
+    let account_id = ctx.args.cloudflare_account_id().to_string();
+    let tunnel_id = tunnel
+        .id
+        .as_hyphenated()
+        .encode_lower(&mut Uuid::encode_buffer())
+        .to_string();
+    for zone in zones.iter() {
+        ctx.delete_tunnel_cnames(account.zone_api(&zone.id), &zone.id, &tunnel_id)
+            .await?;
+    }
+    if !tunnel.connections.is_empty() {
+        ctx.cloudflare_api
+            .cleanup_tunnel_connections(account_id.clone(), tunnel_id.clone())
+            .await?;
+    }
+    ctx.cloudflare_api
+        .delete_tunnel(account_id, tunnel_id, ctx.args.tunnel_delete_cascade())
+        .await?;
+    Ok(())
+}
+
+/// Scale the controller down to zero and wait for its pods to stop
+async fn stop_controller(client: &Client, name: &str) -> Result<()> {
+    info!("Stop controller deployment {name}");
+    let api = Api::<Deployment>::default_namespaced(client.clone());
+    api.patch_scale(
+        name,
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "spec": { "replicas": 0 } })),
+    )
+    .await?;
+    await_condition(api, name, |d: Option<&Deployment>| {
+        d.map_or(true, |d| {
+            d.status
+                .as_ref()
+                .and_then(|s| s.replicas)
+                .unwrap_or_default()
+                == 0
+        })
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt as _;
+use kube::{
+    api::{ObjectMeta, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::EventType,
+        finalizer::{finalizer, Event as FinalizerEvent},
+        watcher::Config,
+        Controller,
+    },
+    Api, CustomResource, Resource as _, ResourceExt as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    cf_api::{VirtualNetwork, VirtualNetworkSettings},
+    customresource::CloudflaredTunnelCredentialsSecretRef,
+    Context, PATCH_PARAMS_APPLY_NAME,
+};
+use crate::{resync::ResyncTrigger, Error, Result};
+
+#[derive(CustomResource, Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[kube(
+    // Required properties
+    group = "chalharu.top",
+    version = "v1alpha1",
+    kind = "CloudflaredVirtualNetwork",
+    // Optional properties
+    singular = "cloudflaredvirtualnetwork",
+    plural = "cloudflaredvirtualnetworks",
+    shortname = "cfdvnet",
+    status = "CloudflaredVirtualNetworkStatus",
+    namespaced,
+)]
+pub struct CloudflaredVirtualNetworkSpec {
+    /// Name of the virtual network, `{namespace}/{name}` of the CloudflaredVirtualNetwork by
+    /// default
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    /// Whether the virtual network is the default one of the account, false by default
+    pub is_default: Option<bool>,
+    /// Cloudflare credentials of the virtual network, instead of `--cloudflare-token` and
+    /// `--cloudflare-account-id`
+    pub cloudflare_credentials_secret_ref: Option<CloudflaredTunnelCredentialsSecretRef>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CloudflaredVirtualNetworkStatus {
+    pub virtual_network_id: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Reconcile the CloudflaredVirtualNetworks until the controller is shut down
+pub(super) async fn run_virtual_network_controller(ctx: Arc<Context>, resync: ResyncTrigger) {
+    info!("Starting controller for CloudflaredVirtualNetwork");
+
+    let api = Api::<CloudflaredVirtualNetwork>::all(ctx.client.clone());
+    Controller::new(api, Config::default().any_semantic())
+        .reconcile_all_on(ctx.subscribe_resync(&resync))
+        .shutdown_on_signal()
+        .run(reconcile, error_policy, ctx)
+        .filter_map(|x| async move { std::result::Result::ok(x) })
+        .for_each(|_| futures::future::ready(()))
+        .await;
+
+    info!("controller for CloudflaredVirtualNetwork shutdown");
+}
+
+async fn reconcile(vnet: Arc<CloudflaredVirtualNetwork>, ctx: Arc<Context>) -> Result<Action> {
+    if !ctx.is_own_shard(&*vnet) {
+        return Ok(Action::await_change());
+    }
+    let ns = virtual_network_namespace(&vnet)?;
+    let api = Api::<CloudflaredVirtualNetwork>::namespaced(ctx.client.clone(), &ns);
+    finalizer(&api, &finalizer_name(), vnet, |e| {
+        let ctx = ctx.clone();
+        async move {
+            match e {
+                FinalizerEvent::Apply(vnet) => ctx.apply_virtual_network(&vnet).await,
+                FinalizerEvent::Cleanup(vnet) => ctx.cleanup_virtual_network(&vnet).await,
+            }
+        }
+    })
+    .await
+    .map_err(|e| Error::from(Box::new(e)))
+}
+
+fn error_policy(vnet: Arc<CloudflaredVirtualNetwork>, error: &Error, ctx: Arc<Context>) -> Action {
+    warn!("reconcile of CloudflaredVirtualNetwork failed: {error:?}");
+    // error_policyは同期関数のため、eventの送信は別タスクで行う
+    let reason = error.reason();
+    let note = error.to_string();
+    tokio::spawn(async move {
+        ctx.publish_object_event(&*vnet, EventType::Warning, reason, "Reconcile", Some(note))
+            .await
+    });
+    Action::requeue(Duration::from_secs(60))
+}
+
+pub(super) fn finalizer_name() -> String {
+    format!("{}/virtual-network-finalizer", PATCH_PARAMS_APPLY_NAME)
+}
+
+fn virtual_network_namespace(vnet: &CloudflaredVirtualNetwork) -> Result<String> {
+    vnet.namespace().ok_or_else(|| {
+        Error::missing_namespace(CloudflaredVirtualNetwork::kind(&()), vnet.name_any())
+    })
+}
+
+/// Settings of the virtual network, named and commented after the object unless given
+fn virtual_network_settings(
+    vnet: &CloudflaredVirtualNetwork,
+    namespace: &str,
+) -> VirtualNetworkSettings {
+    let default_name = format!("{namespace}/{}", vnet.name_any());
+    VirtualNetworkSettings {
+        name: vnet
+            .spec
+            .name
+            .clone()
+            .unwrap_or_else(|| default_name.clone()),
+        comment: vnet.spec.comment.clone().unwrap_or(default_name),
+        is_default_network: vnet.spec.is_default.unwrap_or(false),
+    }
+}
+
+fn is_up_to_date(network: &VirtualNetwork, settings: &VirtualNetworkSettings) -> bool {
+    network.name == settings.name
+        && network.comment.as_deref() == Some(settings.comment.as_str())
+        && network.is_default_network == settings.is_default_network
+}
+
+impl Context {
+    /// Create, rename or update the virtual network of the CloudflaredVirtualNetwork
+    async fn apply_virtual_network(&self, vnet: &CloudflaredVirtualNetwork) -> Result<Action> {
+        let namespace = virtual_network_namespace(vnet)?;
+        let account = self
+            .account_of_secret(
+                &namespace,
+                vnet.spec.cloudflare_credentials_secret_ref.as_ref(),
+            )
+            .await?;
+        let settings = virtual_network_settings(vnet, &namespace);
+
+        // Cloudflare側で削除された場合は作り直す
+        let current = match vnet
+            .status
+            .as_ref()
+            .and_then(|s| s.virtual_network_id.clone())
+        {
+            Some(id) => {
+                account
+                    .api
+                    .get_virtual_network_opt(account.account_id.clone(), id)
+                    .await?
+            }
+            None => None,
+        };
+        let applied = match current {
+            Some(current) if is_up_to_date(&current, &settings) => current,
+            Some(current) => {
+                account
+                    .api
+                    .update_virtual_network(account.account_id.clone(), current.id, &settings)
+                    .await?
+            }
+            None => {
+                let created = account
+                    .api
+                    .create_virtual_network(account.account_id.clone(), &settings)
+                    .await?;
+                self.publish_object_event(
+                    vnet,
+                    EventType::Normal,
+                    "VirtualNetworkCreated",
+                    "CreateVirtualNetwork",
+                    Some(format!(
+                        "Created virtual network {} ({})",
+                        created.name, created.id
+                    )),
+                )
+                .await;
+                created
+            }
+        };
+
+        let status = CloudflaredVirtualNetworkStatus {
+            virtual_network_id: Some(applied.id),
+            name: Some(applied.name),
+        };
+        if vnet.status.as_ref() != Some(&status) {
+            Api::<CloudflaredVirtualNetwork>::namespaced(self.client.clone(), &namespace)
+                .patch_status(
+                    &vnet.name_any(),
+                    &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+                    &Patch::Apply(CloudflaredVirtualNetwork {
+                        metadata: ObjectMeta::default(),
+                        spec: CloudflaredVirtualNetworkSpec::default(),
+                        status: Some(status),
+                    }),
+                )
+                .await?;
+        }
+        Ok(Action::requeue(Duration::from_secs(60 * 60)))
+    }
+
+    /// Delete the virtual network, refused by Cloudflare while it is the default one or routed
+    pub(super) async fn cleanup_virtual_network(
+        &self,
+        vnet: &CloudflaredVirtualNetwork,
+    ) -> Result<Action> {
+        let Some(id) = vnet
+            .status
+            .as_ref()
+            .and_then(|s| s.virtual_network_id.clone())
+        else {
+            return Ok(Action::await_change());
+        };
+        let namespace = virtual_network_namespace(vnet)?;
+        let account = self
+            .account_of_secret(
+                &namespace,
+                vnet.spec.cloudflare_credentials_secret_ref.as_ref(),
+            )
+            .await?;
+        if account
+            .api
+            .get_virtual_network_opt(account.account_id.clone(), id.clone())
+            .await?
+            .is_some()
+        {
+            account
+                .api
+                .delete_virtual_network(account.account_id.clone(), id)
+                .await?;
+        }
+        Ok(Action::await_change())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_default_to_object_name() {
+        let vnet = CloudflaredVirtualNetwork {
+            metadata: ObjectMeta {
+                name: Some("cluster".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            spec: CloudflaredVirtualNetworkSpec::default(),
+            status: None,
+        };
+        let settings = virtual_network_settings(&vnet, "default");
+        assert_eq!(
+            VirtualNetworkSettings {
+                name: "default/cluster".to_string(),
+                comment: "default/cluster".to_string(),
+                is_default_network: false,
+            },
+            settings
+        );
+
+        let network = VirtualNetwork {
+            id: "00000000-0000-0000-0000-000000000008".to_string(),
+            name: "default/cluster".to_string(),
+            comment: Some("default/cluster".to_string()),
+            is_default_network: false,
+        };
+        assert!(is_up_to_date(&network, &settings));
+        assert!(!is_up_to_date(
+            &network,
+            &VirtualNetworkSettings {
+                name: "cluster-b".to_string(),
+                ..settings
+            }
+        ));
+    }
+}
@@ -1,21 +1,43 @@
+mod admission;
+mod annotations;
+mod backend_resolver;
+mod caches;
+mod class_params;
+mod path;
+mod rule_order;
+mod service_ports;
+mod sharding;
+
+pub use self::admission::{review_ingress, validate_ingress};
+pub use self::backend_resolver::{BackendResolvers, ResourceBackendResolver};
+pub use self::class_params::{CloudflaredIngressClassParams, CloudflaredIngressClassParamsSpec};
+
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Debug,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
     sync::{Arc, Mutex},
     time::Duration,
 };
 
 use futures::StreamExt as _;
-use k8s_openapi::api::{
-    core::v1::Service,
-    networking::v1::{HTTPIngressPath, HTTPIngressRuleValue, Ingress, IngressClass},
+use k8s_openapi::{
+    api::{
+        core::v1::Service,
+        discovery::v1::EndpointSlice,
+        networking::v1::{
+            HTTPIngressPath, HTTPIngressRuleValue, Ingress, IngressBackend, IngressClass,
+            IngressClassSpec, IngressLoadBalancerIngress, IngressLoadBalancerStatus, IngressStatus,
+        },
+    },
+    NamespaceResourceScope,
 };
 use kube::{
-    api::{ListParams, ObjectMeta, PartialObjectMeta, PartialObjectMetaExt, Patch, PatchParams},
+    api::{DeleteParams, ListParams, ObjectMeta, Patch, PatchParams},
     runtime::{
         controller::Action,
-        metadata_watcher,
-        reflector::{self, ObjectRef},
+        events::{Event, EventType, Recorder, Reporter},
+        reflector::ObjectRef,
         watcher::{watcher, Config},
         Controller, WatchStreamExt as _,
     },
@@ -25,26 +47,63 @@ use serde::de::DeserializeOwned;
 use tracing::{info, warn};
 
 use crate::{
-    cli::ControllerArgs,
-    controllers::cloudflared::{
-        CloudflaredTunnelAccess, CloudflaredTunnelIngress, CloudflaredTunnelOriginRequest,
-    },
+    cli::{tunnel_cname, ControllerArgs},
+    controllers::cloudflared::CloudflaredTunnelIngress,
+    resync::ResyncTrigger,
     Error, Result,
 };
 
+use self::{
+    caches::{CacheWriters, Caches},
+    class_params::get_class_params,
+    service_ports::{index_services, is_backend_service, service_of_slice, ServiceIndex},
+};
 use super::cloudflared::{CloudflaredTunnel, CloudflaredTunnelSpec};
 
 const PATCH_PARAMS_APPLY_NAME: &str = "cloudflared-ingress.chalharu.top";
+const EVENT_REPORTER_CONTROLLER: &str = "cloudflared-ingress-controller";
+/// IngressClass the CloudflaredTunnel is generated for
+const INGRESS_CLASS_LABEL: &str = "cloudflared-ingress.chalharu.top/ingress-class";
+/// Finalizer holding a deleted Ingress until its rules are removed from the CloudflaredTunnel
+const INGRESS_FINALIZER: &str = "cloudflared-ingress.chalharu.top/finalizer";
+/// Annotation marking the IngressClass of the Ingresses without `spec.ingressClassName`
+const DEFAULT_CLASS_ANNOTATION: &str = "ingressclass.kubernetes.io/is-default-class";
+/// Label marking the CloudflaredTunnels generated by the ingress controller
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+/// Annotation allowing the ingress controller to take over a CloudflaredTunnel it did not create
+const ADOPT_ANNOTATION: &str = "cloudflared-ingress.chalharu.top/adopt";
+/// Shard index of the CloudflaredTunnel shared by the Ingresses of an IngressClass
+const SHARD_LABEL: &str = "cloudflared-ingress.chalharu.top/shard";
 
 /// Initialize the controller and shared state (given the crd is installed)
-pub async fn run_controllers(args: ControllerArgs) -> Result<()> {
+pub async fn run_controllers(args: ControllerArgs, resync: ResyncTrigger) -> Result<()> {
+    run_controllers_with_resolvers(args, resync, BackendResolvers::default()).await
+}
+
+/// Initialize the controller with resolvers translating the resource backends of Ingresses
+pub async fn run_controllers_with_resolvers(
+    args: ControllerArgs,
+    resync: ResyncTrigger,
+    backend_resolvers: BackendResolvers,
+) -> Result<()> {
     let client = Client::try_default().await?;
+    // IngressClassが存在しないとreconcileが始まらないため、起動時に作成する
+    apply_ingress_class(&client, &args).await?;
+    let reporter = Reporter {
+        controller: EVENT_REPORTER_CONTROLLER.to_string(),
+        instance: std::env::var("HOSTNAME").ok(),
+    };
+    let (caches, writers) = caches::caches(scoped_apis::<Ingress>(&client, &args).len());
     let context = Arc::new(Context {
         client: client.clone(),
         args,
         target_ingressclass: Arc::new(Mutex::new(HashMap::new())),
+        recorder: Recorder::new(client.clone(), reporter),
+        backend_resolvers: Arc::new(backend_resolvers),
+        caches,
+        tls_notices: Arc::default(),
     });
-    run_controller(client, context).await;
+    run_controller(client, context, writers, resync).await;
 
     // tokio::join!(
     //     run_controller::<Ingress>(client.clone(), context.clone()),
@@ -53,6 +112,49 @@ pub async fn run_controllers(args: ControllerArgs) -> Result<()> {
     Ok(())
 }
 
+/// Create the IngressClass given by `--create-ingress-class`, or bring it back in line with
+/// the arguments
+async fn apply_ingress_class(client: &Client, args: &ControllerArgs) -> Result<()> {
+    let Some(name) = args.create_ingress_class() else {
+        return Ok(());
+    };
+    let annotations = args
+        .default_ingress_class()
+        .then(|| BTreeMap::from([(DEFAULT_CLASS_ANNOTATION.to_string(), "true".to_string())]));
+    let ingress_class = IngressClass {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            annotations,
+            labels: Some(BTreeMap::from([(
+                MANAGED_BY_LABEL.to_string(),
+                EVENT_REPORTER_CONTROLLER.to_string(),
+            )])),
+            ..Default::default()
+        },
+        spec: Some(IngressClassSpec {
+            controller: args.ingress_controller().map(str::to_string),
+            ..Default::default()
+        }),
+    };
+    // 他のfield managerが設定した値は奪わず、競合した場合は報告のみとする
+    match Api::<IngressClass>::all(client.clone())
+        .patch(
+            name,
+            &PatchParams::apply(PATCH_PARAMS_APPLY_NAME),
+            &Patch::Apply(ingress_class),
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 409 => {
+            warn!("IngressClass {name} is not brought in line, its fields are managed by another manager: {e}");
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// IngressClasses served by the controller, listed from the API server
 async fn get_ingress_classes(client: &Client, args: &ControllerArgs) -> Result<Vec<IngressClass>> {
     let ingress_class_api = Api::<IngressClass>::all(client.clone());
     let ingress_class = if let Some(ingress_class) = args.ingress_class() {
@@ -60,11 +162,7 @@ async fn get_ingress_classes(client: &Client, args: &ControllerArgs) -> Result<V
             .get(ingress_class)
             .await
             .ok()
-            .filter(|ic| {
-                ic.spec.as_ref().map_or(false, |s| {
-                    s.controller.as_ref().map_or(false, |c| c == ingress_class)
-                })
-            })
+            .filter(|ic| is_served(ic, args))
             .into_iter()
             .collect()
     } else {
@@ -73,27 +171,79 @@ async fn get_ingress_classes(client: &Client, args: &ControllerArgs) -> Result<V
             .await?
             .items
             .into_iter()
-            .filter(|ic| {
-                ic.spec
-                    .as_ref()
-                    .and_then(|s| s.controller.as_ref())
-                    .map_or(false, |c| c == args.ingress_controller())
-            })
+            .filter(|ic| is_served(ic, args))
             .collect()
     };
     Ok(ingress_class)
 }
 
-async fn get_ingresses(
-    client: &Client,
-    ingress_class: &str,
-    include_default: bool,
-) -> Result<Vec<Ingress>> {
-    let ingress_api = Api::<Ingress>::all(client.clone());
-    let ingresses = ingress_api
-        .list(&ListParams::default())
-        .await?
-        .items
+/// Whether the IngressClass is given by `--ingress-class`, if any, and its controller is one
+/// of `--ingress-controller`
+fn is_served(ic: &IngressClass, args: &ControllerArgs) -> bool {
+    // --ingress-classのIngressClassもspec.controllerは--ingress-controllerと比較する
+    args.ingress_class()
+        .map_or(true, |name| ic.metadata.name.as_ref() == Some(name))
+        && ic
+            .spec
+            .as_ref()
+            .and_then(|s| s.controller.as_ref())
+            .map_or(false, |c| args.ingress_controllers().contains(c))
+}
+
+/// Whether the IngressClass is the default of the Ingresses without `spec.ingressClassName`
+fn is_default_class(ic: &IngressClass) -> bool {
+    ic.annotations()
+        .get(DEFAULT_CLASS_ANNOTATION)
+        .map_or(false, |x| x.to_lowercase() == "true")
+}
+
+/// Apis of the namespaces given by `--watch-namespaces`, or of all namespaces
+fn scoped_apis<K>(client: &Client, args: &ControllerArgs) -> Vec<Api<K>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+{
+    if args.watch_namespaces().is_empty() {
+        vec![Api::all(client.clone())]
+    } else {
+        args.watch_namespaces()
+            .iter()
+            .map(|ns| Api::namespaced(client.clone(), ns))
+            .collect()
+    }
+}
+
+/// Field selector excluding the namespaces given by `--deny-namespaces`
+fn deny_namespaces_selector(args: &ControllerArgs) -> Option<String> {
+    (!args.deny_namespaces().is_empty()).then(|| {
+        args.deny_namespaces()
+            .iter()
+            .map(|ns| format!("metadata.namespace!={ns}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    })
+}
+
+async fn list_scoped<K>(client: &Client, args: &ControllerArgs) -> Result<Vec<K>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + Debug,
+{
+    let mut params = ListParams::default();
+    if let Some(selector) = deny_namespaces_selector(args) {
+        params = params.fields(&selector);
+    }
+    let mut items = Vec::new();
+    for api in scoped_apis::<K>(client, args) {
+        items.extend(api.list(&params).await?.items);
+    }
+    Ok(items)
+}
+
+fn get_ingresses(caches: &Caches, ingress_class: &str, include_default: bool) -> Vec<Ingress> {
+    caches
+        .ingresses()
         .into_iter()
         .filter(|ing| {
             ing.spec
@@ -101,22 +251,15 @@ async fn get_ingresses(
                 .and_then(|s| s.ingress_class_name.as_ref())
                 .map_or(include_default, |c| c == ingress_class)
         })
-        .collect::<Vec<_>>();
-    Ok(ingresses)
+        .collect()
 }
 
-async fn get_services(client: &Client) -> Result<Vec<Service>> {
-    let service_api = Api::<Service>::all(client.clone());
-    let services = service_api
-        .list(&ListParams::default())
-        .await?
-        .items
-        .into_iter()
-        .collect::<Vec<_>>();
-    Ok(services)
-}
+/// `namespace/name` of the Ingress serving each hostname and path of an IngressClass
+type RuleOwners = HashMap<(String, Option<String>), String>;
 
-type PartialIngressClass = PartialObjectMeta<IngressClass>;
+fn rule_key(rule: &CloudflaredTunnelIngress) -> (String, Option<String>) {
+    (rule.hostname.to_lowercase(), rule.path.clone())
+}
 
 // Context for our reconciler
 #[derive(Clone)]
@@ -124,36 +267,150 @@ struct Context {
     /// Kubernetes client
     client: Client,
     args: ControllerArgs,
-    target_ingressclass: Arc<Mutex<HashMap<Option<String>, ObjectRef<PartialIngressClass>>>>,
+    target_ingressclass: Arc<Mutex<HashMap<Option<String>, ObjectRef<IngressClass>>>>,
+    /// Event recorder for Ingress
+    recorder: Recorder,
+    backend_resolvers: Arc<BackendResolvers>,
+    caches: Caches,
+    /// Last `spec.tls` notice published per Ingress uid, published again only once it changes
+    tls_notices: Arc<Mutex<HashMap<String, String>>>,
 }
 
-async fn run_controller(client: Client, context: Arc<Context>) {
+async fn run_controller(
+    client: Client,
+    context: Arc<Context>,
+    writers: CacheWriters,
+    resync: ResyncTrigger,
+) {
     info!("Starting controller for Ingress");
 
     let api_ingressclass = Api::<IngressClass>::all(client.clone());
-    let api_ingress = Api::<Ingress>::all(client);
-    let (reader_ingressclass, writer_ingressclass) = reflector::store();
+    // IngressClass毎にtunnel namespaceを変更できるため、全namespaceを監視する
+    let api_cfdt = Api::<CloudflaredTunnel>::all(client.clone());
+    let api_class_params = Api::<CloudflaredIngressClassParams>::all(client.clone());
 
-    // controller main stream from metadata_watcher
-    let stream_ingressclass = metadata_watcher(api_ingressclass, Config::default())
+    // spec.controllerを参照するため、metadataだけでなくIngressClass全体を監視する
+    let stream_ingressclass = watcher(api_ingressclass, Config::default())
         .default_backoff()
-        .reflect(writer_ingressclass)
+        .reflect(writers.ingress_classes)
         .applied_objects();
 
-    let stream_ingress = watcher(api_ingress, Config::default()).touched_objects();
+    // --watch-namespacesが指定された場合はnamespace毎にIngressを監視する
+    let mut config_ingress = Config::default();
+    if let Some(selector) = deny_namespaces_selector(&context.args) {
+        config_ingress = config_ingress.fields(&selector);
+    }
+    let stream_ingress = futures::stream::select_all(
+        scoped_apis::<Ingress>(&client, &context.args)
+            .into_iter()
+            .zip(writers.ingresses)
+            .map(|(api, writer)| {
+                watcher(api, config_ingress.clone())
+                    .default_backoff()
+                    .reflect(writer)
+                    .touched_objects()
+                    .boxed()
+            }),
+    );
+
+    // tunnel_idが確定した時点でIngressのstatusを更新するため、CloudflaredTunnelを監視する
+    let stream_cfdt = watcher(api_cfdt, Config::default())
+        .default_backoff()
+        .touched_objects()
+        .predicate_filter(tunnel_id_hash);
+
+    let stream_class_params = watcher(api_class_params, Config::default())
+        .default_backoff()
+        .touched_objects();
+
+    // 名前付きportを再解決するため、portが変更されたServiceとEndpointSliceを監視し、
+    // それらをbackendに持つIngressのIngressClassのみ再調整する
+    let stream_services = futures::stream::select_all(
+        scoped_apis::<Service>(&client, &context.args)
+            .into_iter()
+            .zip(writers.services)
+            .map(|(api, writer)| {
+                watcher(api, config_ingress.clone())
+                    .default_backoff()
+                    .reflect(writer)
+                    .touched_objects()
+                    .predicate_filter(|s: &Service| {
+                        ports_hash(&s.spec.as_ref().map(|s| (&s.cluster_ip, &s.ports)))
+                    })
+                    .boxed()
+            }),
+    );
+    let stream_endpoint_slices = futures::stream::select_all(
+        scoped_apis::<EndpointSlice>(&client, &context.args)
+            .into_iter()
+            .zip(writers.endpoint_slices)
+            .map(|(api, writer)| {
+                watcher(api, config_ingress.clone())
+                    .default_backoff()
+                    .reflect(writer)
+                    .touched_objects()
+                    .predicate_filter(|s: &EndpointSlice| ports_hash(&s.ports))
+                    .boxed()
+            }),
+    );
 
     let target_ingressclass = context.target_ingressclass.clone();
-    Controller::for_stream(stream_ingressclass, reader_ingressclass)
+    let class_params_targets = context.target_ingressclass.clone();
+    let service_targets = context.target_ingressclass.clone();
+    let service_caches = context.caches.clone();
+    let endpoint_slice_targets = context.target_ingressclass.clone();
+    let endpoint_slice_caches = context.caches.clone();
+    Controller::for_stream(stream_ingressclass, context.caches.ingress_classes.clone())
         .watches_stream(stream_ingress, move |i| {
-            let target_ingressclass = target_ingressclass.clone();
-            i.spec.and_then(|is| {
-                target_ingressclass
-                    .lock()
-                    .unwrap()
-                    .get(&is.ingress_class_name)
-                    .cloned()
-            })
+            let targets = target_ingressclass.lock().unwrap();
+            let class = i.spec.as_ref().map(|is| &is.ingress_class_name);
+            match class.and_then(|c| targets.get(c)) {
+                Some(target) => vec![target.clone()],
+                // 対象外のIngressClassに変更されたIngressもfinalizerを外すために再調整する
+                None if has_finalizer(&i) => targets.values().cloned().collect(),
+                None => Vec::new(),
+            }
+        })
+        .watches_stream(stream_cfdt, |cfdt| {
+            cfdt.owner_references()
+                .iter()
+                .find(|o| o.kind == IngressClass::kind(&()))
+                .map(|o| ObjectRef::new(&o.name))
+                .or_else(|| {
+                    cfdt.labels()
+                        .get(INGRESS_CLASS_LABEL)
+                        .map(|ic| ObjectRef::new(ic))
+                })
+        })
+        // parametersの参照元は分からないため、全てのIngressClassを再調整する
+        .watches_stream(stream_class_params, move |_| {
+            class_params_targets
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect::<HashSet<_>>()
         })
+        .watches_stream(stream_services, move |s| {
+            classes_of_service(
+                &service_caches,
+                &service_targets.lock().unwrap(),
+                s.namespace(),
+                &s.name_any(),
+            )
+        })
+        .watches_stream(stream_endpoint_slices, move |s| {
+            match service_of_slice(&s) {
+                Some(service) => classes_of_service(
+                    &endpoint_slice_caches,
+                    &endpoint_slice_targets.lock().unwrap(),
+                    s.namespace(),
+                    service,
+                ),
+                None => HashSet::new(),
+            }
+        })
+        .reconcile_all_on(resync.subscribe())
         .shutdown_on_signal()
         .run(reconcile, error_policy, context)
         .for_each(|_| futures::future::ready(()))
@@ -162,17 +419,118 @@ async fn run_controller(client: Client, context: Arc<Context>) {
     info!("controller for Ingress shutdown");
 }
 
-async fn reconcile<K>(res: Arc<PartialObjectMeta<K>>, ctx: Arc<Context>) -> Result<Action>
-where
-    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug,
-{
-    let kind = K::kind(&()).to_string();
-    let name = res.name_any();
-    if let Some(ns) = res.namespace() {
-        info!("Reconciling {kind} \"{name}\" in {ns}");
+/// IngressClasses of the Ingresses in the namespace with a backend in the Service
+fn classes_of_service(
+    caches: &Caches,
+    targets: &HashMap<Option<String>, ObjectRef<IngressClass>>,
+    namespace: Option<String>,
+    service: &str,
+) -> HashSet<ObjectRef<IngressClass>> {
+    caches
+        .ingresses()
+        .iter()
+        .filter(|i| i.namespace() == namespace && is_backend_service(i, service))
+        .filter_map(|i| {
+            i.spec
+                .as_ref()
+                .and_then(|s| targets.get(&s.ingress_class_name))
+                .cloned()
+        })
+        .collect()
+}
+
+fn has_finalizer(i: &Ingress) -> bool {
+    i.finalizers().iter().any(|f| f == INGRESS_FINALIZER)
+}
+
+fn is_deleting(i: &Ingress) -> bool {
+    i.metadata.deletion_timestamp.is_some()
+}
+
+async fn patch_finalizers(client: &Client, i: &Ingress, patch: serde_json::Value) -> Result<()> {
+    let patch: json_patch::Patch = serde_json::from_value(patch)?;
+    Api::<Ingress>::namespaced(client.clone(), &i.namespace().unwrap_or_default())
+        .patch(
+            &i.name_any(),
+            &PatchParams::default(),
+            &Patch::<()>::Json(patch),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn add_finalizer(client: &Client, i: &Ingress) -> Result<()> {
+    // 他のcontrollerが同時に付けたfinalizerを上書きしないよう、現在の一覧をtestしてから追加する
+    let patch = if i.finalizers().is_empty() {
+        serde_json::json!([
+            { "op": "test", "path": "/metadata/finalizers", "value": i.metadata.finalizers },
+            { "op": "add", "path": "/metadata/finalizers", "value": [INGRESS_FINALIZER] },
+        ])
     } else {
-        info!("Reconciling {kind} \"{name}\"");
+        serde_json::json!([
+            { "op": "test", "path": "/metadata/finalizers", "value": i.finalizers() },
+            { "op": "add", "path": "/metadata/finalizers/-", "value": INGRESS_FINALIZER },
+        ])
+    };
+    patch_finalizers(client, i, patch).await
+}
+
+async fn remove_finalizer(client: &Client, i: &Ingress) -> Result<()> {
+    let Some(index) = i.finalizers().iter().position(|f| f == INGRESS_FINALIZER) else {
+        return Ok(());
+    };
+    // 位置がずれていた場合に別のfinalizerを外さないよう、削除する要素をtestする
+    let path = format!("/metadata/finalizers/{index}");
+    patch_finalizers(
+        client,
+        i,
+        serde_json::json!([
+            { "op": "test", "path": path, "value": INGRESS_FINALIZER },
+            { "op": "remove", "path": path },
+        ]),
+    )
+    .await
+}
+
+/// Remove the finalizer from every Ingress, as the controller will not remove it any more
+pub async fn remove_finalizers(client: &Client, args: &ControllerArgs) -> Result<()> {
+    for i in list_scoped::<Ingress>(client, args)
+        .await?
+        .iter()
+        .filter(|i| has_finalizer(i))
+    {
+        info!(
+            "Remove finalizer of Ingress {}/{}",
+            i.namespace().unwrap_or_default(),
+            i.name_any()
+        );
+        remove_finalizer(client, i).await?;
     }
+    Ok(())
+}
+
+fn tunnel_id_hash(cfdt: &CloudflaredTunnel) -> Option<u64> {
+    cfdt.status
+        .as_ref()
+        .and_then(|s| s.tunnel_id.as_ref())
+        .map(|id| {
+            let mut hasher = DefaultHasher::new();
+            id.hash(&mut hasher);
+            hasher.finish()
+        })
+}
+
+/// Hash of the ports of a Service or EndpointSlice, which have no `Hash` implementation
+fn ports_hash<T: serde::Serialize>(ports: &T) -> Option<u64> {
+    let ports = serde_json::to_string(ports).ok()?;
+    let mut hasher = DefaultHasher::new();
+    ports.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+async fn reconcile(ic: Arc<IngressClass>, ctx: Arc<Context>) -> Result<Action> {
+    info!("Reconciling IngressClass \"{}\"", ic.name_any());
+    ctx.caches.wait_until_ready().await?;
     ctx.reconcile().await?;
     Ok(Action::requeue(Duration::from_secs(60 * 60)))
 }
@@ -183,8 +541,91 @@ fn error_policy<K>(_: Arc<K>, error: &Error, _ctx: Arc<Context>) -> Action {
 }
 
 impl Context {
+    async fn publish_rejection(&self, ingress: &Ingress, error: &Error) {
+        self.publish_event(
+            ingress,
+            EventType::Warning,
+            error.reason(),
+            error.to_string(),
+        )
+        .await;
+    }
+
+    /// Tell that `spec.tls` is not used for the edge certificates, which Cloudflare manages,
+    /// unless the origin certificate is verified with `tls-origin-verify`
+    async fn publish_tls_notice(&self, ingress: &Ingress) {
+        let uid = ingress.uid().unwrap_or_default();
+        let hosts = tls_hosts(ingress);
+        if hosts.is_empty()
+            || annotations::tls_origin_verify(ingress.annotations()).unwrap_or_default()
+        {
+            self.tls_notices.lock().unwrap().remove(&uid);
+            return;
+        }
+        let mut hosts = hosts.into_iter().collect::<Vec<_>>();
+        hosts.sort();
+        let note = format!(
+            "spec.tls is ignored, TLS of {} is terminated at the Cloudflare edge with Cloudflare certificates",
+            hosts.join(", ")
+        );
+        // 再調整の度に同じEventを記録しないよう、内容が変わった場合のみ記録する
+        if self
+            .tls_notices
+            .lock()
+            .unwrap()
+            .insert(uid, note.clone())
+            .as_ref()
+            == Some(&note)
+        {
+            return;
+        }
+        self.publish_event(
+            ingress,
+            EventType::Normal,
+            "EdgeTlsManagedByCloudflare",
+            note,
+        )
+        .await;
+    }
+
+    async fn publish_event<K: Resource<DynamicType = ()>>(
+        &self,
+        obj: &K,
+        type_: EventType,
+        reason: &str,
+        note: String,
+    ) {
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note: Some(note),
+            action: "Reconcile".to_string(),
+            secondary: None,
+        };
+        if let Err(e) = self.recorder.publish(&event, &obj.object_ref(&())).await {
+            warn!("failed to publish event {reason}: {e:?}");
+        }
+    }
+
     async fn reconcile(&self) -> Result<()> {
-        let ingress_class = get_ingress_classes(&self.client, &self.args).await?;
+        // 削除されたIngressの記録は残さない
+        let uids = self
+            .caches
+            .ingresses()
+            .iter()
+            .filter_map(|i| i.uid())
+            .collect::<HashSet<_>>();
+        self.tls_notices
+            .lock()
+            .unwrap()
+            .retain(|uid, _| uids.contains(uid));
+        apply_ingress_class(&self.client, &self.args).await?;
+        let ingress_class = self
+            .caches
+            .ingress_classes()
+            .into_iter()
+            .filter(|ic| is_served(ic, &self.args))
+            .collect::<Vec<_>>();
 
         let mut current_ic: HashSet<_> = self
             .target_ingressclass
@@ -195,16 +636,10 @@ impl Context {
             .collect();
 
         for ic in ingress_class.iter() {
-            let is_default_class = ic
-                .meta()
-                .annotations
-                .as_ref()
-                .and_then(|a| a.get("ingressclass.kubernetes.io/is-default-class"))
-                .map_or(false, |x| x.to_lowercase() == "true");
+            let is_default_class = is_default_class(ic);
             let name = ic.name_any();
 
-            let obj_ref =
-                reflector::Lookup::to_object_ref(&ic.metadata.clone().into_request_partial(), ());
+            let obj_ref = ObjectRef::from_obj(ic);
             if is_default_class {
                 current_ic.remove(&None);
                 self.target_ingressclass
@@ -223,16 +658,40 @@ impl Context {
             self.target_ingressclass.lock().unwrap().remove(&ic);
         }
 
+        // 対象外のIngressClassに変更された後に削除されたIngressはそのままfinalizerを外す
+        // IngressClass毎の調整が失敗しても削除を妨げないよう、先に外す
+        let targets = self.target_ingressclass.lock().unwrap().clone();
+        for i in self
+            .caches
+            .ingresses()
+            .iter()
+            .filter(|i| is_deleting(i) && has_finalizer(i))
+            .filter(|i| {
+                !targets.contains_key(&i.spec.as_ref().and_then(|s| s.ingress_class_name.clone()))
+            })
+        {
+            remove_finalizer(&self.client, i).await?;
+        }
+
+        // 振り分け先の判定には全てのIngressClassを用い、調整は自身のshardのもののみ行う
         for ic in ingress_class {
-            let is_default_class = ic
-                .meta()
-                .annotations
-                .as_ref()
-                .and_then(|a| a.get("ingressclass.kubernetes.io/is-default-class"))
-                .map_or(false, |x| x.to_lowercase() == "true");
+            if !self.args.is_own_shard(&ic.name_any()) {
+                continue;
+            }
+            let is_default_class = is_default_class(&ic);
 
-            self.reconcile_for_ingressclass(ic, is_default_class)
-                .await?;
+            // 利用者のCloudflaredTunnelと名前が衝突したIngressClassのみ調整を中断し、他のIngressClassは継続する
+            if let Err(error) = self
+                .reconcile_for_ingressclass(ic.clone(), is_default_class)
+                .await
+            {
+                if !error.is_cloudflared_tunnel_not_owned() {
+                    return Err(error);
+                }
+                warn!("IngressClass {} skipped: {error}", ic.name_any());
+                self.publish_event(&ic, EventType::Warning, error.reason(), error.to_string())
+                    .await;
+            }
         }
         Ok(())
     }
@@ -242,197 +701,925 @@ impl Context {
         ic: IngressClass,
         is_default_class: bool,
     ) -> Result<()> {
-        const SERVERSSCHEME_ANNOTATION: &str =
-            "cloudflared-ingress.ingress.kubernetes.io/service.serversscheme";
-        const ACCESS_AUD_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/service.aud";
-        const ACCESS_TEAM_ANNOTATION: &str =
-            "cloudflared-ingress.ingress.kubernetes.io/service.team";
-
-        let ingresses = get_ingresses(&self.client, &ic.name_any(), is_default_class).await?;
+        let ingresses = get_ingresses(&self.caches, &ic.name_any(), is_default_class);
         let name = ic.name_any();
-        let owner_ref = ic.controller_owner_ref(&());
 
-        let mut cfdt_ingress = Vec::new();
+        // 削除中のIngressはruleから除外してfinalizerを外す
+        // 以降の処理が失敗し続けてもIngressの削除を妨げないよう、最初に外す
+        // この調整でCloudflaredTunnelを更新できなくても、再調整時に削除済みのIngressは含まれない
+        let (deleting, ingresses): (Vec<_>, Vec<_>) = ingresses.into_iter().partition(is_deleting);
+        for i in deleting.iter().filter(|i| has_finalizer(i)) {
+            info!(
+                "Remove rules of deleted Ingress {}/{}",
+                i.namespace().unwrap_or_default(),
+                i.name_any()
+            );
+            remove_finalizer(&self.client, i).await?;
+        }
+        for i in ingresses.iter().filter(|i| !has_finalizer(i)) {
+            add_finalizer(&self.client, i).await?;
+        }
+        let params = get_class_params(&self.client, &ic)
+            .await?
+            .unwrap_or_default();
 
-        let cfdt_api = Api::<CloudflaredTunnel>::namespaced(
-            self.client.clone(),
-            self.args.cloudflare_tunnel_namespace(),
-        );
-        let services: HashMap<_, _> = get_services(&self.client)
+        let tunnel_namespace = params
+            .tunnel_namespace
+            .clone()
+            .unwrap_or_else(|| self.args.cloudflare_tunnel_namespace().to_string());
+        let services = index_services(&self.caches.services(), &self.caches.endpoint_slices());
+
+        // 同一のhostnameとpathを複数のIngressが持つ場合は最も古いIngressを優先する
+        let owners = self.rule_owners(&ingresses, &services);
+
+        // dedicated-tunnelが指定されたIngressはIngress毎にCloudflaredTunnelを作成する
+        let mut shared = Vec::new();
+        let mut dedicated = Vec::new();
+        for i in ingresses.into_iter() {
+            match annotations::dedicated_tunnel(i.annotations()) {
+                Ok(true) => dedicated.push(i),
+                Ok(false) => shared.push(i),
+                Err(error) => {
+                    warn!("Ingress rejected: {error}");
+                    self.publish_rejection(&i, &error).await;
+                }
+            }
+        }
+
+        let labels = BTreeMap::from([(INGRESS_CLASS_LABEL.to_string(), name.clone())]);
+        let mut applied = self
+            .apply_shared_tunnels(&ic, &tunnel_namespace, shared, &services, &owners, &params)
             .await?
             .into_iter()
-            .map(|s| {
-                let svc_name = format!("{}.{}.svc", s.name_any(), s.namespace().unwrap());
-                let ports: HashMap<_, _> = s
-                    .spec
-                    .iter()
-                    .flat_map(|s| {
-                        s.ports.iter().flat_map(|p| {
-                            p.iter()
-                                .flat_map(|p| p.name.as_ref().map(|n| (n.clone(), p.port)))
-                        })
-                    })
-                    .collect();
-                (svc_name, ports)
+            .map(|cfdt_name| (tunnel_namespace.clone(), cfdt_name))
+            .collect::<HashSet<_>>();
+        // Ingressをownerとするため、CloudflaredTunnelはIngressと同じnamespaceに作成する
+        for i in dedicated {
+            let namespace = i.namespace().unwrap();
+            let cfdt_name = format!("{}-{}", name, i.name_any());
+            let metadata = ObjectMeta {
+                name: Some(cfdt_name.clone()),
+                namespace: Some(namespace.clone()),
+                owner_references: Some(i.controller_owner_ref(&()).into_iter().collect()),
+                labels: Some(labels.clone()),
+                ..Default::default()
+            };
+            self.apply_tunnel(metadata, vec![i], &services, &owners, &params)
+                .await?;
+            applied.insert((namespace, cfdt_name));
+        }
+
+        // tunnel namespaceの変更やdedicated-tunnelの解除で不要になったCloudflaredTunnelを削除する
+        let ic_uid = ic.uid();
+        for old in Api::<CloudflaredTunnel>::all(self.client.clone())
+            .list(&ListParams::default())
+            .await?
+            .items
+            .into_iter()
+            .filter(|c| !applied.contains(&(c.namespace().unwrap_or_default(), c.name_any())))
+            .filter(|c| {
+                c.labels().get(INGRESS_CLASS_LABEL) == Some(&name)
+                    || c.owner_references()
+                        .iter()
+                        .any(|o| Some(&o.uid) == ic_uid.as_ref())
             })
-            .collect();
+        {
+            info!(
+                "Delete CloudflaredTunnel {}/{} no longer used by {name}",
+                old.namespace().unwrap_or_default(),
+                old.name_any()
+            );
+            Api::<CloudflaredTunnel>::namespaced(
+                self.client.clone(),
+                &old.namespace().unwrap_or_default(),
+            )
+            .delete(&old.name_any(), &DeleteParams::background())
+            .await?;
+        }
+        Ok(())
+    }
 
-        for i in ingresses.into_iter() {
-            let scheme = i
-                .annotations()
-                .get(SERVERSSCHEME_ANNOTATION)
-                .map(String::as_str)
-                .unwrap_or("http")
-                .to_lowercase();
-
-            let aud_tags = i
-                .annotations()
-                .get(ACCESS_AUD_ANNOTATION)
-                .map(String::as_str)
-                .map(|s| {
-                    s.split(',')
-                        .map(str::trim)
-                        .map(str::to_string)
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
+    /// Apply the CloudflaredTunnels shared by the Ingresses of the IngressClass, split into
+    /// shards of at most `--max-rules-per-tunnel` rules, and return their names
+    async fn apply_shared_tunnels(
+        &self,
+        ic: &IngressClass,
+        tunnel_namespace: &str,
+        ingresses: Vec<Ingress>,
+        services: &ServiceIndex,
+        owners: &RuleOwners,
+        params: &CloudflaredIngressClassParamsSpec,
+    ) -> Result<Vec<String>> {
+        let name = ic.name_any();
+        let (mut accepted, default_ingress_service) =
+            self.convert_ingresses(ingresses, services, owners).await;
+        self.reject_conflicting_source_ranges(&mut accepted).await;
+        // service tokenのSecretはtunnel namespaceに作成されるため、Ingressのnamespaceで区別する
+        for (i, rules) in accepted.iter_mut() {
+            let ns = i.namespace().unwrap_or_default();
+            for secret in rules
+                .iter_mut()
+                .filter_map(|rule| rule.access_application.as_mut())
+                .filter_map(|application| application.service_token_secret.as_mut())
+            {
+                *secret = format!("{ns}-{secret}");
+            }
+        }
+
+        // hostnameの割り当てを維持するため、既存のshardが持つhostnameを取得する
+        let previous = Api::<CloudflaredTunnel>::namespaced(self.client.clone(), tunnel_namespace)
+            .list(&ListParams::default().labels(&format!("{INGRESS_CLASS_LABEL}={name}")))
+            .await?
+            .items
+            .into_iter()
+            .filter_map(|c| {
+                let shard = c.labels().get(SHARD_LABEL)?.parse::<usize>().ok()?;
+                Some(
+                    c.spec
+                        .ingress
+                        .into_iter()
+                        .flatten()
+                        .map(move |i| (i.hostname, shard)),
+                )
+            })
+            .flatten()
+            .collect::<HashMap<_, _>>();
+        let ordered = rule_order::ordered_rules(&accepted);
+        let mut rule_counts = BTreeMap::<String, usize>::new();
+        for rule in ordered.iter() {
+            *rule_counts.entry(rule.hostname.clone()).or_default() += 1;
+        }
+        let assigned =
+            sharding::assign_shards(&rule_counts, &previous, self.args.max_rules_per_tunnel());
 
-            let team_name = i.annotations().get(ACCESS_TEAM_ANNOTATION).cloned();
+        // 最初のshardはruleが無くても作成する
+        let mut shard_rules = BTreeMap::from([(0, Vec::new())]);
+        for rule in ordered {
+            shard_rules
+                .entry(assigned[&rule.hostname])
+                .or_insert_with(Vec::new)
+                .push(rule);
+        }
 
-            let ns = i.namespace().unwrap();
+        let mut shard_tunnel_ids = HashMap::new();
+        let mut applied = Vec::new();
+        for (shard, rules) in shard_rules {
+            let cfdt_name = sharding::shard_name(&name, shard);
+            let metadata = ObjectMeta {
+                name: Some(cfdt_name.clone()),
+                namespace: Some(tunnel_namespace.to_string()),
+                owner_references: Some(ic.controller_owner_ref(&()).into_iter().collect()),
+                labels: Some(BTreeMap::from([
+                    (INGRESS_CLASS_LABEL.to_string(), name.clone()),
+                    (SHARD_LABEL.to_string(), shard.to_string()),
+                ])),
+                ..Default::default()
+            };
+            if let Some(tunnel_id) = self
+                .apply_cfdt(metadata, rules, default_ingress_service.clone(), params)
+                .await?
+            {
+                shard_tunnel_ids.insert(shard, tunnel_id);
+            }
+            applied.push(cfdt_name);
+        }
 
-            let Some(spec) = i.spec else {
-                continue;
+        // Ingressのhostnameを持つ全てのshardのtunnelが作成済みの場合にADDRESSを設定する
+        for (i, rules) in accepted {
+            let tunnel_ids = rules
+                .iter()
+                .map(|rule| shard_tunnel_ids.get(&assigned[&rule.hostname]))
+                .collect::<Option<BTreeSet<_>>>();
+            let tunnel_ids = match tunnel_ids {
+                Some(tunnel_ids) if !tunnel_ids.is_empty() => tunnel_ids,
+                // ruleが無い場合は最初のshardのtunnelを用いる
+                Some(_) => match shard_tunnel_ids.get(&0) {
+                    Some(tunnel_id) => BTreeSet::from([tunnel_id]),
+                    None => continue,
+                },
+                None => continue,
             };
+            let load_balancer = load_balancer(tunnel_ids, self.args.tunnel_cname_domain());
+            self.patch_ingress_load_balancer(&i, &load_balancer).await?;
+        }
+        Ok(applied)
+    }
 
-            let default_backend =
-                spec.default_backend
-                    .as_ref()
-                    .map(|backend| HTTPIngressRuleValue {
-                        paths: vec![HTTPIngressPath {
-                            backend: backend.clone(),
-                            path: None,
-                            path_type: "ImplementationSpecific".to_string(),
-                        }],
-                    });
-
-            let origin_request = team_name
-                .map(|t| CloudflaredTunnelOriginRequest {
-                    access: Some(CloudflaredTunnelAccess {
-                        required: true,
-                        team_name: t.to_string(),
-                        aud_tag: aud_tags,
-                    }),
-                    no_tls_verify: Some(true),
-                    ..Default::default()
-                })
-                .or(Some(CloudflaredTunnelOriginRequest {
-                    no_tls_verify: Some(true),
-                    ..Default::default()
-                }));
-
-            for r in spec.rules.iter().flat_map(|r| r.iter()) {
-                for p in r
-                    .http
-                    .as_ref()
-                    .or(default_backend.as_ref())
-                    .ok_or_else(Error::illegal_document)?
-                    .paths
-                    .iter()
-                {
-                    if p.backend.resource.is_some() {
-                        return Err(Error::illegal_document());
-                    }
-                    let Some(ref service) = p.backend.service else {
-                        return Err(Error::illegal_document());
-                    };
-                    let svc_name = format!("{}.{}.svc", service.name, ns);
-                    let port = service
-                        .port
-                        .as_ref()
-                        .and_then(|p| {
-                            p.number.or_else(|| {
-                                p.name.as_ref().and_then(|p_name| {
-                                    services
-                                        .get(&svc_name)
-                                        .and_then(|svc| svc.get(p_name).cloned())
-                                })
-                            })
-                        })
-                        .filter(|&x| {
-                            !(x == 80 && scheme == "http" || x == 443 && scheme == "https")
-                        });
-                    let cfdt_service = if let Some(port) = port {
-                        format!("{}://{}:{}", scheme, svc_name, port)
-                    } else {
-                        format!("{}://{}", scheme, svc_name)
-                    };
+    /// Apply the CloudflaredTunnel serving the Ingresses and set their load balancer status
+    async fn apply_tunnel(
+        &self,
+        metadata: ObjectMeta,
+        ingresses: Vec<Ingress>,
+        services: &ServiceIndex,
+        owners: &RuleOwners,
+        params: &CloudflaredIngressClassParamsSpec,
+    ) -> Result<()> {
+        let (mut accepted, default_ingress_service) =
+            self.convert_ingresses(ingresses, services, owners).await;
+        self.reject_conflicting_source_ranges(&mut accepted).await;
+        let rules = rule_order::ordered_rules(&accepted);
+
+        // tunnelが作成済みであれば、IngressのADDRESSとしてtunnelのhostnameを設定する
+        if let Some(tunnel_id) = self
+            .apply_cfdt(metadata, rules, default_ingress_service, params)
+            .await?
+        {
+            let load_balancer = load_balancer([&tunnel_id], self.args.tunnel_cname_domain());
+            for (i, _) in accepted {
+                self.patch_ingress_load_balancer(&i, &load_balancer).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ingress serving each hostname and path, the oldest one when several Ingresses declare it
+    fn rule_owners(&self, ingresses: &[Ingress], services: &ServiceIndex) -> RuleOwners {
+        let mut sorted = ingresses.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|i| (i.creation_timestamp(), i.namespace(), i.name_any()));
+        let mut owners = RuleOwners::new();
+        for i in sorted {
+            // 変換できないIngressは後でEventを記録するためここでは無視する
+            let Ok((rules, _)) = convert_ingress(
+                i,
+                services,
+                &self.backend_resolvers,
+                self.args.default_hostname_template(),
+            ) else {
+                continue;
+            };
+            let ingress_name = format!("{}/{}", i.namespace().unwrap_or_default(), i.name_any());
+            for rule in rules {
+                owners
+                    .entry(rule_key(&rule))
+                    .or_insert_with(|| ingress_name.clone());
+            }
+        }
+        owners
+    }
 
-                    let path = match p.path_type.as_str() {
-                        "Exact" => Some(format!(
-                            "^{}$",
-                            p.path
-                                .as_ref()
-                                .map(|x| regex_escape(x.to_string()))
-                                .unwrap_or_else(|| "/".to_string())
-                        )),
-                        "Prefix" | "ImplementationSpecific" => p
-                            .path
-                            .as_ref()
-                            .filter(|x| x.as_str() != "/")
-                            .map(|x| format!("^{}", regex_escape(x.to_string()))),
-                        _ => return Err(Error::illegal_document()),
+    /// Convert the Ingresses into tunnel rules together with the catch-all service of their
+    /// defaultBackend, skipping the Ingresses that cannot be converted and the rules already
+    /// served by an older Ingress
+    async fn convert_ingresses(
+        &self,
+        ingresses: Vec<Ingress>,
+        services: &ServiceIndex,
+        owners: &RuleOwners,
+    ) -> (
+        Vec<(Ingress, Vec<CloudflaredTunnelIngress>)>,
+        Option<String>,
+    ) {
+        let mut accepted_ingresses = Vec::new();
+        let mut default_backends = Vec::new();
+        for i in ingresses.into_iter() {
+            // 変換できないIngressはEventを記録して除外し、他のIngressの処理は継続する
+            let converted = convert_ingress(
+                &i,
+                services,
+                &self.backend_resolvers,
+                self.args.default_hostname_template(),
+            )
+            .and_then(|(rules, mut skipped)| {
+                let default_backend =
+                    match convert_default_backend(&i, services, &self.backend_resolvers) {
+                        Err(error) if error.is_missing_backend() => {
+                            skipped.push(error);
+                            None
+                        }
+                        default_backend => default_backend?,
                     };
+                Ok((rules, skipped, default_backend))
+            });
+            match converted {
+                Ok((rules, skipped, default_backend)) => {
+                    let ingress_name =
+                        format!("{}/{}", i.namespace().unwrap_or_default(), i.name_any());
+                    for error in skipped {
+                        warn!("Ingress path skipped: {error}");
+                        self.publish_rejection(&i, &error).await;
+                    }
+                    self.publish_tls_notice(&i).await;
+                    let mut accepted_rules = Vec::new();
+                    for rule in rules {
+                        match owners.get(&rule_key(&rule)) {
+                            Some(winner) if winner != &ingress_name => {
+                                let error = Error::ingress_rule_collision(
+                                    &ingress_name,
+                                    format!(
+                                        "{}{}",
+                                        rule.hostname,
+                                        rule.path.as_deref().unwrap_or_default()
+                                    ),
+                                    winner,
+                                );
+                                warn!("Ingress rule rejected: {error}");
+                                self.publish_rejection(&i, &error).await;
+                            }
+                            _ => accepted_rules.push(rule),
+                        }
+                    }
+                    let rules = accepted_rules;
+                    if let Some(default_backend) = default_backend {
+                        default_backends.push((ingress_name, default_backend, i.clone()));
+                    }
+                    accepted_ingresses.push((i, rules));
+                }
+                Err(error) => {
+                    warn!("Ingress rejected: {error}");
+                    self.publish_rejection(&i, &error).await;
+                }
+            }
+        }
+        // 複数のIngressにdefaultBackendがある場合は、namespace/nameの順で最初のものを使用する
+        default_backends.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        let mut default_backends = default_backends.into_iter();
+        let default_ingress_service = default_backends
+            .next()
+            .map(|(winner, service, _)| (winner, service));
+        if let Some((ref winner, ref winner_service)) = default_ingress_service {
+            for (ingress_name, service, i) in default_backends {
+                if &service != winner_service {
+                    let error = Error::default_backend_collision(
+                        ingress_name,
+                        service,
+                        winner,
+                        winner_service,
+                    );
+                    warn!("Ingress defaultBackend ignored: {error}");
+                    self.publish_rejection(&i, &error).await;
+                }
+            }
+        }
+        (
+            accepted_ingresses,
+            default_ingress_service.map(|(_, service)| service),
+        )
+    }
 
-                    cfdt_ingress.push(CloudflaredTunnelIngress {
-                        // Hostなしは最終的にCNAMEが振れないことからエラーとする
-                        hostname: r.host.clone().ok_or_else(Error::illegal_document)?,
-                        service: cfdt_service,
-                        path,
-                        origin_request: origin_request.clone(),
-                    });
+    /// Drop the rules of an Ingress listing other source ranges for a hostname than an older
+    /// Ingress of the tunnel, as the WAF rule applies to the whole hostname
+    async fn reject_conflicting_source_ranges(
+        &self,
+        accepted: &mut [(Ingress, Vec<CloudflaredTunnelIngress>)],
+    ) {
+        let mut order = (0..accepted.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&n| {
+            let i = &accepted[n].0;
+            (i.creation_timestamp(), i.namespace(), i.name_any())
+        });
+        let mut source_ranges = HashMap::<String, Option<Vec<String>>>::new();
+        for n in order {
+            let (i, rules) = &mut accepted[n];
+            let mut rejected = BTreeSet::new();
+            rules.retain(|rule| {
+                let ranges = rule.source_ranges.clone().map(|mut ranges| {
+                    ranges.sort();
+                    ranges.dedup();
+                    ranges
+                });
+                match source_ranges.get(&rule.hostname) {
+                    Some(other) if other != &ranges => {
+                        rejected.insert(rule.hostname.clone());
+                        false
+                    }
+                    Some(_) => true,
+                    None => {
+                        source_ranges.insert(rule.hostname.clone(), ranges);
+                        true
+                    }
                 }
+            });
+            for hostname in rejected {
+                let error = Error::conflicting_source_ranges(hostname);
+                warn!("Ingress rule rejected: {error}");
+                self.publish_rejection(i, &error).await;
             }
         }
+    }
+
+    /// Apply a CloudflaredTunnel and return its tunnel id once the tunnel is created
+    async fn apply_cfdt(
+        &self,
+        metadata: ObjectMeta,
+        cfdt_ingress: Vec<CloudflaredTunnelIngress>,
+        default_ingress_service: Option<String>,
+        params: &CloudflaredIngressClassParamsSpec,
+    ) -> Result<Option<String>> {
+        let name = metadata.name.clone().unwrap_or_default();
+        let cfdt_api = Api::<CloudflaredTunnel>::namespaced(
+            self.client.clone(),
+            metadata.namespace.as_deref().unwrap_or_default(),
+        );
+        // 利用者が作成した同名のCloudflaredTunnelは上書きしない
+        if let Some(existing) = cfdt_api.get_opt(&name).await? {
+            if !is_owned_cfdt(&existing, &metadata) {
+                return Err(Error::cloudflared_tunnel_not_owned(
+                    format!("{}/{name}", existing.namespace().unwrap_or_default()),
+                    ADOPT_ANNOTATION,
+                ));
+            }
+        }
+        let mut metadata = metadata;
+        metadata.labels.get_or_insert_with(BTreeMap::new).insert(
+            MANAGED_BY_LABEL.to_string(),
+            EVENT_REPORTER_CONTROLLER.to_string(),
+        );
         let cfd = CloudflaredTunnel {
-            metadata: ObjectMeta {
-                name: Some(name.clone()),
-                owner_references: Some(owner_ref.into_iter().collect()),
-                ..Default::default()
-            },
+            metadata,
             spec: CloudflaredTunnelSpec {
                 ingress: Some(cfdt_ingress),
-                default_ingress_service: "http_status:404".to_string(),
+                default_ingress_service: default_ingress_service
+                    .unwrap_or_else(|| "http_status:404".to_string()),
+                origin_request: params.origin_request.clone(),
+                image: params.image.clone(),
+                resources: params.resources.clone(),
+                tunnel_prefix: params.tunnel_prefix.clone(),
                 ..Default::default()
             },
             status: None,
         };
 
-        cfdt_api
+        let cfdt = cfdt_api
             .patch(
                 name.as_str(),
                 &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
                 &Patch::Apply(cfd),
             )
             .await?;
+        Ok(cfdt.status.and_then(|s| s.tunnel_id))
+    }
+
+    async fn patch_ingress_load_balancer(
+        &self,
+        ingress: &Ingress,
+        load_balancer: &IngressLoadBalancerStatus,
+    ) -> Result<()> {
+        if ingress
+            .status
+            .as_ref()
+            .and_then(|s| s.load_balancer.as_ref())
+            == Some(load_balancer)
+        {
+            return Ok(());
+        }
+        let ns = ingress
+            .namespace()
+            .ok_or_else(|| Error::missing_namespace(Ingress::kind(&()), ingress.name_any()))?;
+        let api = Api::<Ingress>::namespaced(self.client.clone(), &ns);
+        api.patch_status(
+            &ingress.name_any(),
+            &PatchParams::apply(PATCH_PARAMS_APPLY_NAME).force(),
+            &Patch::Apply(Ingress {
+                status: Some(IngressStatus {
+                    load_balancer: Some(load_balancer.clone()),
+                }),
+                ..Default::default()
+            }),
+        )
+        .await?;
         Ok(())
     }
 }
 
-fn regex_escape(s: String) -> String {
-    s.replace("\\", "\\\\")
-        .replace("*", "\\*")
-        .replace("+", "\\+")
-        .replace("?", "\\?")
-        .replace("{", "\\{")
-        .replace("}", "\\}")
-        .replace("(", "\\(")
-        .replace(")", "\\)")
-        .replace("[", "\\[")
-        .replace("]", "\\]")
-        .replace("^", "\\^")
-        .replace("$", "\\$")
-        .replace("-", "\\-")
-        .replace("|", "\\|")
-        .replace(".", "\\.")
+/// Whether the existing CloudflaredTunnel was generated by the ingress controller, marked
+/// to be adopted, or owned by the object it is generated for. The last one covers the
+/// CloudflaredTunnels generated before the ownership label was introduced.
+fn is_owned_cfdt(existing: &CloudflaredTunnel, metadata: &ObjectMeta) -> bool {
+    existing.labels().get(MANAGED_BY_LABEL).map(String::as_str) == Some(EVENT_REPORTER_CONTROLLER)
+        || existing
+            .annotations()
+            .get(ADOPT_ANNOTATION)
+            .is_some_and(|x| x.to_lowercase() == "true")
+        || existing.owner_references().iter().any(|o| {
+            metadata
+                .owner_references
+                .iter()
+                .flatten()
+                .any(|owner| owner.uid == o.uid)
+        })
+}
+
+fn convert_ingress(
+    i: &Ingress,
+    services: &ServiceIndex,
+    resolvers: &BackendResolvers,
+    default_hostname_template: Option<&str>,
+) -> Result<(Vec<CloudflaredTunnelIngress>, Vec<Error>)> {
+    let scheme = annotations::servers_scheme(i.annotations());
+    let builtin_services = annotations::builtin_services(i.annotations())?;
+    let origin_request = Some(annotations::origin_request(i.annotations())?);
+    let path_overrides = annotations::path_overrides(i.annotations())?;
+    let allow_existing_dns = annotations::allow_existing_dns(i.annotations())?;
+    let tls_origin_verify = annotations::tls_origin_verify(i.annotations())?;
+    let source_ranges = annotations::whitelist_source_range(i.annotations())?;
+    let proxied = annotations::dns_proxied(i.annotations())?;
+    let zone_id = annotations::zone_id(i.annotations())?;
+    let health_check = annotations::health_check(i.annotations())?;
+    let access_application = annotations::access_application(i.annotations())?;
+
+    let ns = i.namespace().unwrap();
+    let ingress_name = format!("{}/{}", ns, i.name_any());
+    // proxyされないhostnameにはWAFのruleが適用されないため、制限が無視されないよう拒否する
+    if source_ranges.is_some() && proxied == Some(false) {
+        return Err(Error::source_ranges_not_proxied(&ingress_name));
+    }
+
+    let Some(ref spec) = i.spec else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+
+    let default_backend = spec
+        .default_backend
+        .as_ref()
+        .map(|backend| HTTPIngressRuleValue {
+            paths: vec![HTTPIngressPath {
+                backend: backend.clone(),
+                path: None,
+                path_type: "ImplementationSpecific".to_string(),
+            }],
+        });
+
+    let default_hostname = default_hostname_template
+        .map(|template| render_hostname_template(template, &i.name_any(), &ns));
+
+    let tls_hosts = tls_hosts(i);
+
+    let mut cfdt_ingress = Vec::new();
+    let mut skipped = Vec::new();
+    for r in spec.rules.iter().flat_map(|r| r.iter()) {
+        // Hostなしはtemplateが無ければCNAMEが振れないことからエラーとする
+        let Some(hostname) = r.host.as_ref().or(default_hostname.as_ref()) else {
+            return Err(Error::missing_host(&ingress_name));
+        };
+        let Some(http) = r.http.as_ref().or(default_backend.as_ref()) else {
+            return Err(Error::missing_backend_service(&ingress_name, hostname));
+        };
+        for p in http.paths.iter() {
+            // path単位の設定がある場合はIngress全体の設定より優先する
+            let (scheme, origin_request, path_regex) =
+                match path_overrides.get(p.path.as_deref().unwrap_or("/")) {
+                    Some(a) => (
+                        annotations::servers_scheme(a),
+                        Some(annotations::origin_request(a)?),
+                        annotations::path_regex(a)?,
+                    ),
+                    None => (scheme.clone(), origin_request.clone(), None),
+                };
+
+            // cloudflared内蔵のserviceが指定されたpathはbackendを参照しない
+            let cfdt_service =
+                if let Some(builtin) = builtin_services.get(p.path.as_deref().unwrap_or("/")) {
+                    builtin.clone()
+                } else {
+                    match backend_service_url(
+                        &p.backend,
+                        &ns,
+                        &scheme,
+                        services,
+                        resolvers,
+                        &ingress_name,
+                        hostname,
+                    ) {
+                        Ok(service) => service,
+                        // 存在しないServiceを参照するpathは除外し、他のpathは継続して提供する
+                        Err(error) if error.is_missing_backend() => {
+                            skipped.push(error);
+                            continue;
+                        }
+                        Err(error) => return Err(error),
+                    }
+                };
+
+            // spec.tlsのhostはoriginの証明書をhostnameで検証する
+            let origin_request = match origin_request {
+                Some(mut o)
+                    if tls_origin_verify
+                        && scheme == "https"
+                        && tls_hosts.contains(&hostname.to_lowercase()) =>
+                {
+                    o.origin_server_name.get_or_insert_with(|| hostname.clone());
+                    o.no_tls_verify = Some(false);
+                    Some(o)
+                }
+                o => o,
+            };
+
+            // pathRegexが指定された場合はpathTypeに依らずそのまま用いる
+            let path = match p.path_type.as_str() {
+                _ if path_regex.is_some() => path_regex,
+                "Exact" => Some(path::exact_path_regex(p.path.as_deref())),
+                "Prefix" | "ImplementationSpecific" => path::prefix_path_regex(p.path.as_deref()),
+                path_type => return Err(Error::unsupported_path_type(&ingress_name, path_type)),
+            };
+
+            let rule = CloudflaredTunnelIngress {
+                hostname: hostname.clone(),
+                service: cfdt_service,
+                path,
+                origin_request,
+                worker_script: None,
+                allow_existing_dns,
+                additional_hostnames: None,
+                zone_id: zone_id.clone(),
+                proxied,
+                source_ranges: source_ranges.clone(),
+                health_check: health_check.clone(),
+                access_application: access_application.clone(),
+            };
+            // TCPのserviceはpathで振り分けられないため、pathを持たない規則とする
+            cfdt_ingress.push(if rule.is_tcp() {
+                CloudflaredTunnelIngress { path: None, ..rule }
+            } else {
+                rule
+            });
+        }
+    }
+    Ok((cfdt_ingress, skipped))
+}
+
+/// Hosts of `spec.tls` of the Ingress in lower case
+fn tls_hosts(i: &Ingress) -> HashSet<String> {
+    i.spec
+        .iter()
+        .flat_map(|s| s.tls.iter().flatten())
+        .flat_map(|tls| tls.hosts.iter().flatten())
+        .map(|host| host.to_lowercase())
+        .collect()
+}
+
+/// Load balancer status pointing at the hostnames of the tunnels
+fn load_balancer<'a>(
+    tunnel_ids: impl IntoIterator<Item = &'a String>,
+    tunnel_cname_domain: &str,
+) -> IngressLoadBalancerStatus {
+    IngressLoadBalancerStatus {
+        ingress: Some(
+            tunnel_ids
+                .into_iter()
+                .map(|tunnel_id| IngressLoadBalancerIngress {
+                    hostname: Some(tunnel_cname(tunnel_id, tunnel_cname_domain)),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Catch-all service of the tunnel from `spec.defaultBackend` of the Ingress
+fn convert_default_backend(
+    i: &Ingress,
+    services: &ServiceIndex,
+    resolvers: &BackendResolvers,
+) -> Result<Option<String>> {
+    let Some(backend) = i.spec.as_ref().and_then(|s| s.default_backend.as_ref()) else {
+        return Ok(None);
+    };
+    let ns = i.namespace().unwrap();
+    let ingress_name = format!("{}/{}", ns, i.name_any());
+    let scheme = annotations::servers_scheme(i.annotations());
+    backend_service_url(
+        backend,
+        &ns,
+        &scheme,
+        services,
+        resolvers,
+        &ingress_name,
+        "defaultBackend",
+    )
+    .map(Some)
+}
+
+/// cloudflared service URL of an Ingress backend
+fn backend_service_url(
+    backend: &IngressBackend,
+    ns: &str,
+    scheme: &str,
+    services: &ServiceIndex,
+    resolvers: &BackendResolvers,
+    ingress_name: &str,
+    hostname: &str,
+) -> Result<String> {
+    if let Some(ref resource) = backend.resource {
+        return resolvers.resolve(ns, resource, scheme, ingress_name, hostname);
+    }
+    let Some(ref service) = backend.service else {
+        return Err(Error::missing_backend_service(ingress_name, hostname));
+    };
+    let svc_name = format!("{}.{}.svc", service.name, ns);
+    let Some(ports) = services.get(&svc_name) else {
+        return Err(Error::backend_service_not_found(
+            ingress_name,
+            hostname,
+            format!("{}/{}", ns, service.name),
+        ));
+    };
+    let port = match service.port.as_ref() {
+        Some(p) => match (p.number, p.name.as_ref()) {
+            (Some(number), _) => Some(number),
+            (None, Some(p_name)) => Some(ports.resolve(p_name).ok_or_else(|| {
+                Error::backend_service_port_not_found(
+                    ingress_name,
+                    hostname,
+                    format!("{}/{}", ns, service.name),
+                    p_name,
+                )
+            })?),
+            (None, None) => None,
+        },
+        None => None,
+    }
+    .filter(|&x| !(x == 80 && scheme == "http" || x == 443 && scheme == "https"));
+    if let Some(port) = port {
+        Ok(format!("{}://{}:{}", scheme, svc_name, port))
+    } else {
+        Ok(format!("{}://{}", scheme, svc_name))
+    }
+}
+
+/// Expand `{name}` and `{namespace}` of the Ingress in the hostname template
+fn render_hostname_template(template: &str, name: &str, namespace: &str) -> String {
+    template
+        .replace("{name}", name)
+        .replace("{namespace}", namespace)
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::{
+        api::{
+            core::v1::{ServicePort, ServiceSpec},
+            networking::v1::{IngressRule, IngressServiceBackend, IngressSpec, ServiceBackendPort},
+        },
+        apimachinery::pkg::apis::meta::v1::OwnerReference,
+    };
+
+    use super::*;
+
+    fn services() -> ServiceIndex {
+        let service = Service {
+            metadata: ObjectMeta {
+                name: Some("web".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            spec: Some(ServiceSpec {
+                ports: Some(vec![ServicePort {
+                    name: Some("http".to_string()),
+                    port: 8080,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            status: None,
+        };
+        index_services(&[service], &[])
+    }
+
+    fn hostless_ingress() -> Ingress {
+        Ingress {
+            metadata: ObjectMeta {
+                name: Some("web".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            spec: Some(IngressSpec {
+                rules: Some(vec![IngressRule {
+                    host: None,
+                    http: Some(HTTPIngressRuleValue {
+                        paths: vec![HTTPIngressPath {
+                            backend: IngressBackend {
+                                service: Some(IngressServiceBackend {
+                                    name: "web".to_string(),
+                                    port: Some(ServiceBackendPort {
+                                        number: Some(8080),
+                                        ..Default::default()
+                                    }),
+                                }),
+                                ..Default::default()
+                            },
+                            path: Some("/".to_string()),
+                            path_type: "Prefix".to_string(),
+                        }],
+                    }),
+                }]),
+                ..Default::default()
+            }),
+            status: None,
+        }
+    }
+
+    #[test]
+    fn hostname_template_expands_name_and_namespace() {
+        assert_eq!(
+            "web-default.example.com",
+            render_hostname_template("{name}-{namespace}.example.com", "web", "default")
+        );
+        assert_eq!(
+            "static.example.com",
+            render_hostname_template("static.example.com", "web", "default")
+        );
+    }
+
+    #[test]
+    fn hostless_rule_uses_hostname_template() {
+        let (rules, skipped) = convert_ingress(
+            &hostless_ingress(),
+            &services(),
+            &BackendResolvers::default(),
+            Some("{name}.{namespace}.example.com"),
+        )
+        .unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(1, rules.len());
+        assert_eq!("web.default.example.com", rules[0].hostname);
+        assert_eq!("http://web.default.svc:8080", rules[0].service);
+    }
+
+    #[test]
+    fn hostless_rule_without_template_is_rejected() {
+        let error = convert_ingress(
+            &hostless_ingress(),
+            &services(),
+            &BackendResolvers::default(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!("MissingHost", error.reason());
+    }
+
+    fn owner_ref(uid: &str) -> OwnerReference {
+        OwnerReference {
+            api_version: "networking.k8s.io/v1".to_string(),
+            kind: "IngressClass".to_string(),
+            name: "cloudflared".to_string(),
+            uid: uid.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn cfdt(metadata: ObjectMeta) -> CloudflaredTunnel {
+        CloudflaredTunnel {
+            metadata,
+            spec: Default::default(),
+            status: None,
+        }
+    }
+
+    fn generated_for(uid: &str) -> ObjectMeta {
+        ObjectMeta {
+            owner_references: Some(vec![owner_ref(uid)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn managed_cfdt_is_owned() {
+        let existing = cfdt(ObjectMeta {
+            labels: Some(BTreeMap::from([(
+                MANAGED_BY_LABEL.to_string(),
+                EVENT_REPORTER_CONTROLLER.to_string(),
+            )])),
+            ..Default::default()
+        });
+        assert!(is_owned_cfdt(&existing, &generated_for("uid")));
+    }
+
+    #[test]
+    fn adopted_cfdt_is_owned() {
+        let existing = cfdt(ObjectMeta {
+            annotations: Some(BTreeMap::from([(
+                ADOPT_ANNOTATION.to_string(),
+                "True".to_string(),
+            )])),
+            ..Default::default()
+        });
+        assert!(is_owned_cfdt(&existing, &generated_for("uid")));
+    }
+
+    #[test]
+    fn cfdt_of_same_owner_is_owned() {
+        let existing = cfdt(generated_for("uid"));
+        assert!(is_owned_cfdt(&existing, &generated_for("uid")));
+    }
+
+    #[test]
+    fn user_cfdt_is_not_owned() {
+        let existing = cfdt(ObjectMeta {
+            labels: Some(BTreeMap::from([(
+                MANAGED_BY_LABEL.to_string(),
+                "helm".to_string(),
+            )])),
+            annotations: Some(BTreeMap::from([(
+                ADOPT_ANNOTATION.to_string(),
+                "false".to_string(),
+            )])),
+            owner_references: Some(vec![owner_ref("other")]),
+            ..Default::default()
+        });
+        assert!(!is_owned_cfdt(&existing, &generated_for("uid")));
+        assert!(!is_owned_cfdt(
+            &cfdt(ObjectMeta::default()),
+            &generated_for("uid")
+        ));
+    }
 }
@@ -0,0 +1,203 @@
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::{
+    core::{
+        admission::{AdmissionRequest, AdmissionResponse, AdmissionReview},
+        DynamicObject,
+    },
+    Client,
+};
+use tracing::warn;
+
+use crate::{cli::ControllerArgs, Result};
+
+use super::{
+    convert_default_backend, convert_ingress, get_ingress_classes, is_default_class,
+    service_ports::ServiceIndex, BackendResolvers,
+};
+
+/// Review an Ingress for the validating admission webhook, denying the Ingresses of the
+/// IngressClasses of this controller that would be rejected by the controller
+pub async fn review_ingress(
+    client: &Client,
+    args: &ControllerArgs,
+    resolvers: &BackendResolvers,
+    review: AdmissionReview<Ingress>,
+) -> AdmissionReview<DynamicObject> {
+    let request: AdmissionRequest<Ingress> = match review.try_into() {
+        Ok(request) => request,
+        Err(e) => return AdmissionResponse::invalid(e.to_string()).into_review(),
+    };
+    let response = AdmissionResponse::from(&request);
+    let Some(mut ingress) = request.object else {
+        // DELETEはobjectを持たない
+        return response.into_review();
+    };
+    // CREATEのobjectにはnamespaceが未設定の場合がある
+    if ingress.metadata.namespace.is_none() {
+        ingress.metadata.namespace = request.namespace.clone();
+    }
+    match is_target(client, args, &ingress).await {
+        Ok(true) => {}
+        Ok(false) => return response.into_review(),
+        Err(e) => {
+            // IngressClassが取得できない場合はcontrollerの判断に委ねる
+            warn!("failed to get IngressClasses for admission review: {e}");
+            return response.into_review();
+        }
+    }
+    match validate_ingress(&ingress, args, resolvers) {
+        Ok(()) => response.into_review(),
+        Err(e) => response.deny(e.to_string()).into_review(),
+    }
+}
+
+/// Whether the Ingress belongs to one of the IngressClasses of this controller
+async fn is_target(client: &Client, args: &ControllerArgs, ingress: &Ingress) -> Result<bool> {
+    let namespace = ingress.metadata.namespace.as_deref().unwrap_or_default();
+    let watched = args.watch_namespaces().is_empty()
+        || args.watch_namespaces().iter().any(|ns| ns == namespace);
+    if !watched || args.deny_namespaces().iter().any(|ns| ns == namespace) {
+        return Ok(false);
+    }
+    let class_name = ingress
+        .spec
+        .as_ref()
+        .and_then(|s| s.ingress_class_name.as_deref());
+    let classes = get_ingress_classes(client, args).await?;
+    Ok(classes.iter().any(|ic| match class_name {
+        Some(name) => ic.metadata.name.as_deref() == Some(name),
+        None => is_default_class(ic),
+    }))
+}
+
+/// Reject the Ingresses the controller cannot convert into tunnel rules. The backend
+/// Services are not checked, as they may be created after the Ingress.
+pub fn validate_ingress(
+    ingress: &Ingress,
+    args: &ControllerArgs,
+    resolvers: &BackendResolvers,
+) -> Result<()> {
+    let services = ServiceIndex::new();
+    convert_ingress(
+        ingress,
+        &services,
+        resolvers,
+        args.default_hostname_template(),
+    )?;
+    match convert_default_backend(ingress, &services, resolvers) {
+        Err(error) if !error.is_missing_backend() => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser as _;
+    use k8s_openapi::api::{
+        core::v1::TypedLocalObjectReference,
+        networking::v1::{
+            HTTPIngressPath, HTTPIngressRuleValue, IngressBackend, IngressRule,
+            IngressServiceBackend, IngressSpec, ServiceBackendPort,
+        },
+    };
+    use kube::api::ObjectMeta;
+
+    use super::*;
+    use crate::cli::{Cli, Commands};
+
+    fn args(extra: &[&str]) -> ControllerArgs {
+        let cli = Cli::try_parse_from(
+            [
+                "cloudflared-ingress-rs",
+                "run",
+                "--cloudflare-token",
+                "token",
+                "--cloudflare-account-id",
+                "0123456789abcdef0123456789abcdef",
+            ]
+            .iter()
+            .chain(extra),
+        )
+        .unwrap();
+        let Commands::Run(args) = cli.commands() else {
+            unreachable!();
+        };
+        args.clone()
+    }
+
+    fn service_backend() -> IngressBackend {
+        IngressBackend {
+            service: Some(IngressServiceBackend {
+                name: "web".to_string(),
+                port: Some(ServiceBackendPort {
+                    number: Some(80),
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn ingress(host: Option<&str>, path_type: &str, backend: IngressBackend) -> Ingress {
+        Ingress {
+            metadata: ObjectMeta {
+                name: Some("web".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            spec: Some(IngressSpec {
+                rules: Some(vec![IngressRule {
+                    host: host.map(str::to_string),
+                    http: Some(HTTPIngressRuleValue {
+                        paths: vec![HTTPIngressPath {
+                            backend,
+                            path: Some("/".to_string()),
+                            path_type: path_type.to_string(),
+                        }],
+                    }),
+                }]),
+                ..Default::default()
+            }),
+            status: None,
+        }
+    }
+
+    #[test]
+    fn missing_service_is_allowed() {
+        let i = ingress(Some("www.example.com"), "Prefix", service_backend());
+        assert!(validate_ingress(&i, &args(&[]), &BackendResolvers::default()).is_ok());
+    }
+
+    #[test]
+    fn unsupported_features_are_denied() {
+        let resource = IngressBackend {
+            resource: Some(TypedLocalObjectReference {
+                api_group: Some("example.com".to_string()),
+                kind: "Bucket".to_string(),
+                name: "assets".to_string(),
+            }),
+            ..Default::default()
+        };
+        for (i, reason) in [
+            (
+                ingress(Some("www.example.com"), "Prefix", resource),
+                "ResourceBackendUnsupported",
+            ),
+            (ingress(None, "Prefix", service_backend()), "MissingHost"),
+            (
+                ingress(Some("www.example.com"), "Regex", service_backend()),
+                "UnsupportedPathType",
+            ),
+        ] {
+            let error = validate_ingress(&i, &args(&[]), &BackendResolvers::default()).unwrap_err();
+            assert_eq!(reason, error.reason());
+        }
+    }
+
+    #[test]
+    fn missing_host_is_allowed_with_template() {
+        let i = ingress(None, "Prefix", service_backend());
+        let args = args(&["--default-hostname-template", "{name}.apps.example.com"]);
+        assert!(validate_ingress(&i, &args, &BackendResolvers::default()).is_ok());
+    }
+}
@@ -0,0 +1,684 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    str::FromStr,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    controllers::cloudflared::{
+        CloudflaredTunnelAccess, CloudflaredTunnelAccessApplication, CloudflaredTunnelExistingDns,
+        CloudflaredTunnelHealthCheck, CloudflaredTunnelOriginRequest,
+    },
+    Error, Result,
+};
+
+const SERVERSSCHEME_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/service.serversscheme";
+const ACCESS_AUD_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/service.aud";
+const ACCESS_TEAM_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/service.team";
+const BUILTIN_SERVICE_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/service.builtin";
+const PATH_OVERRIDES_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/path-overrides";
+const DEDICATED_TUNNEL_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/dedicated-tunnel";
+const ALLOW_EXISTING_DNS_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/allow-existing-dns";
+const TLS_ORIGIN_VERIFY_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/tls-origin-verify";
+const ZONE_ID_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/zone-id";
+const DNS_PROXIED_ANNOTATION: &str = "cloudflared-ingress.ingress.kubernetes.io/dns-proxied";
+const ACCESS_ALLOWED_EMAILS_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-allowed-emails";
+const ACCESS_ALLOWED_EMAIL_DOMAINS_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-allowed-email-domains";
+const ACCESS_ALLOWED_GROUPS_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-allowed-groups";
+const ACCESS_IDENTITY_PROVIDERS_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-identity-providers";
+const ACCESS_SESSION_DURATION_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-session-duration";
+const ACCESS_SERVICE_TOKEN_SECRET_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/access-service-token-secret";
+const HEALTH_CHECK_PATH_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/health-check-path";
+const WHITELIST_SOURCE_RANGE_ANNOTATION: &str =
+    "cloudflared-ingress.ingress.kubernetes.io/whitelist-source-range";
+// ingress-nginxから移行したIngressのannotationもそのまま受け付ける
+const NGINX_WHITELIST_SOURCE_RANGE_ANNOTATION: &str =
+    "nginx.ingress.kubernetes.io/whitelist-source-range";
+// path-overridesのpathRegexはpath単位のannotationとして扱う
+const PATH_REGEX_KEY: &str = "cloudflared-ingress.ingress.kubernetes.io/path-regex";
+
+// originRequestの各項目はcloudflaredの設定名をそのまま用いる
+const ORIGIN_PREFIX: &str = "cloudflared-ingress.ingress.kubernetes.io/origin.";
+
+pub(super) fn servers_scheme(annotations: &BTreeMap<String, String>) -> String {
+    annotations
+        .get(SERVERSSCHEME_ANNOTATION)
+        .map(String::as_str)
+        .unwrap_or("http")
+        .to_lowercase()
+}
+
+pub(super) fn builtin_services(
+    annotations: &BTreeMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    annotations
+        .get(BUILTIN_SERVICE_ANNOTATION)
+        .map(String::as_str)
+        .map(parse_builtin_services)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Whether the Ingress gets its own CloudflaredTunnel instead of the one of its IngressClass
+pub(super) fn dedicated_tunnel(annotations: &BTreeMap<String, String>) -> Result<bool> {
+    annotations
+        .get(DEDICATED_TUNNEL_ANNOTATION)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| Error::invalid_annotation(DEDICATED_TUNNEL_ANNOTATION))
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Whether the origin certificate of the `spec.tls` hosts is verified against the hostname
+pub(super) fn tls_origin_verify(annotations: &BTreeMap<String, String>) -> Result<bool> {
+    annotations
+        .get(TLS_ORIGIN_VERIFY_ANNOTATION)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| Error::invalid_annotation(TLS_ORIGIN_VERIFY_ANNOTATION))
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Cloudflare zone id of the hosts, skipping the lookup of the zone by hostname
+pub(super) fn zone_id(annotations: &BTreeMap<String, String>) -> Result<Option<String>> {
+    annotations
+        .get(ZONE_ID_ANNOTATION)
+        .map(|value| {
+            let value = value.trim();
+            (value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit()))
+                .then(|| value.to_lowercase())
+                .ok_or_else(|| Error::invalid_annotation(ZONE_ID_ANNOTATION))
+        })
+        .transpose()
+}
+
+/// Whether the CNAME records of the hosts are proxied, `None` keeps the default of the tunnel
+pub(super) fn dns_proxied(annotations: &BTreeMap<String, String>) -> Result<Option<bool>> {
+    annotations
+        .get(DNS_PROXIED_ANNOTATION)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| Error::invalid_annotation(DNS_PROXIED_ANNOTATION))
+        })
+        .transpose()
+}
+
+/// Cloudflare health check of the hosts, requesting the path of the annotation
+pub(super) fn health_check(
+    annotations: &BTreeMap<String, String>,
+) -> Result<Option<CloudflaredTunnelHealthCheck>> {
+    annotations
+        .get(HEALTH_CHECK_PATH_ANNOTATION)
+        .map(|value| {
+            let value = value.trim();
+            value
+                .starts_with('/')
+                .then(|| CloudflaredTunnelHealthCheck {
+                    path: Some(value.to_string()),
+                })
+                .ok_or_else(|| Error::invalid_annotation(HEALTH_CHECK_PATH_ANNOTATION))
+        })
+        .transpose()
+}
+
+/// Cloudflare Access application of the hosts, created when any of the allowed emails, email
+/// domains, groups or a service token is given
+pub(super) fn access_application(
+    annotations: &BTreeMap<String, String>,
+) -> Result<Option<CloudflaredTunnelAccessApplication>> {
+    let list = |key| {
+        annotations.get(key).map(|value: &String| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+    };
+    let application = CloudflaredTunnelAccessApplication {
+        emails: list(ACCESS_ALLOWED_EMAILS_ANNOTATION),
+        email_domains: list(ACCESS_ALLOWED_EMAIL_DOMAINS_ANNOTATION),
+        groups: list(ACCESS_ALLOWED_GROUPS_ANNOTATION),
+        identity_providers: list(ACCESS_IDENTITY_PROVIDERS_ANNOTATION),
+        session_duration: annotations
+            .get(ACCESS_SESSION_DURATION_ANNOTATION)
+            .map(|value| value.trim().to_string()),
+        service_token_secret: annotations
+            .get(ACCESS_SERVICE_TOKEN_SECRET_ANNOTATION)
+            .map(|value| value.trim().to_string()),
+    };
+    if application
+        .service_token_secret
+        .as_deref()
+        .is_some_and(str::is_empty)
+    {
+        return Err(Error::invalid_annotation(
+            ACCESS_SERVICE_TOKEN_SECRET_ANNOTATION,
+        ));
+    }
+    let allowed = [
+        &application.emails,
+        &application.email_domains,
+        &application.groups,
+    ];
+    if allowed.iter().all(|x| x.is_none()) && application.service_token_secret.is_none() {
+        // 許可対象が無い場合、他のaccess annotationは意味を持たない
+        return match (
+            &application.identity_providers,
+            &application.session_duration,
+        ) {
+            (None, None) => Ok(None),
+            (Some(_), _) => Err(Error::invalid_annotation(
+                ACCESS_IDENTITY_PROVIDERS_ANNOTATION,
+            )),
+            (_, Some(_)) => Err(Error::invalid_annotation(
+                ACCESS_SESSION_DURATION_ANNOTATION,
+            )),
+        };
+    }
+    // 空の指定は誰もsign inできないapplicationとなるため誤りとする
+    if allowed
+        .iter()
+        .all(|x| x.as_ref().map_or(true, Vec::is_empty))
+        && application.service_token_secret.is_none()
+    {
+        return Err(Error::invalid_annotation(ACCESS_ALLOWED_EMAILS_ANNOTATION));
+    }
+    Ok(Some(application))
+}
+
+/// Client CIDRs allowed to reach the hosts of the Ingress, from the `whitelist-source-range`
+/// annotation or the one of ingress-nginx
+pub(super) fn whitelist_source_range(
+    annotations: &BTreeMap<String, String>,
+) -> Result<Option<Vec<String>>> {
+    let Some((key, value)) = [
+        WHITELIST_SOURCE_RANGE_ANNOTATION,
+        NGINX_WHITELIST_SOURCE_RANGE_ANNOTATION,
+    ]
+    .into_iter()
+    .find_map(|key| annotations.get(key).map(|value| (key, value))) else {
+        return Ok(None);
+    };
+    let ranges = value
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|range| {
+            is_cidr(range)
+                .then(|| range.to_string())
+                .ok_or_else(|| Error::invalid_annotation(key))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // 空の指定は全てのclientを拒否してしまうため誤りとする
+    if ranges.is_empty() {
+        return Err(Error::invalid_annotation(key));
+    }
+    Ok(Some(ranges))
+}
+
+/// Whether the value is an IP address or a CIDR
+fn is_cidr(value: &str) -> bool {
+    let (addr, prefix) = value.split_once('/').unwrap_or((value, ""));
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        return false;
+    };
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    prefix.is_empty() || prefix.parse::<u8>().is_ok_and(|p| p <= max_prefix)
+}
+
+/// Handling of A/AAAA records already existing for the hostnames, `replace` or `keep`
+pub(super) fn allow_existing_dns(
+    annotations: &BTreeMap<String, String>,
+) -> Result<Option<CloudflaredTunnelExistingDns>> {
+    annotations
+        .get(ALLOW_EXISTING_DNS_ANNOTATION)
+        .map(|value| match value.trim().to_lowercase().as_str() {
+            "replace" => Ok(CloudflaredTunnelExistingDns::Replace),
+            "keep" => Ok(CloudflaredTunnelExistingDns::Keep),
+            _ => Err(Error::invalid_annotation(ALLOW_EXISTING_DNS_ANNOTATION)),
+        })
+        .transpose()
+}
+
+/// Per-path settings of the `path-overrides` annotation
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PathOverride {
+    servers_scheme: Option<String>,
+    /// Raw cloudflared path regex used instead of the one generated from the path
+    path_regex: Option<String>,
+    #[serde(default)]
+    origin: BTreeMap<String, serde_json::Value>,
+}
+
+/// Parse the `path-overrides` annotation, a JSON object keyed by path, into the
+/// annotations that apply to each overridden path
+pub(super) fn path_overrides(
+    annotations: &BTreeMap<String, String>,
+) -> Result<HashMap<String, BTreeMap<String, String>>> {
+    let Some(value) = annotations.get(PATH_OVERRIDES_ANNOTATION) else {
+        return Ok(HashMap::new());
+    };
+    let overrides = serde_json::from_str::<BTreeMap<String, PathOverride>>(value)
+        .map_err(|_| Error::invalid_annotation(PATH_OVERRIDES_ANNOTATION))?;
+    Ok(overrides
+        .into_iter()
+        .map(|(path, path_override)| {
+            // Ingress全体のannotationにpath単位の設定を上書きする
+            let mut merged = annotations.clone();
+            merged.remove(PATH_REGEX_KEY);
+            if let Some(scheme) = path_override.servers_scheme {
+                merged.insert(SERVERSSCHEME_ANNOTATION.to_string(), scheme);
+            }
+            if let Some(path_regex) = path_override.path_regex {
+                merged.insert(PATH_REGEX_KEY.to_string(), path_regex);
+            }
+            for (name, value) in path_override.origin {
+                let value = match value {
+                    serde_json::Value::String(s) => s,
+                    v => v.to_string(),
+                };
+                merged.insert(format!("{ORIGIN_PREFIX}{name}"), value);
+            }
+            (path, merged)
+        })
+        .collect())
+}
+
+/// Raw path regex given by `pathRegex` of the `path-overrides` annotation, checked to be a
+/// valid RE2 style regex as cloudflared uses Go regexp
+pub(super) fn path_regex(annotations: &BTreeMap<String, String>) -> Result<Option<String>> {
+    annotations
+        .get(PATH_REGEX_KEY)
+        .map(|value| {
+            regex::Regex::new(value)
+                .map(|_| value.clone())
+                .map_err(|_| Error::invalid_annotation(PATH_OVERRIDES_ANNOTATION))
+        })
+        .transpose()
+}
+
+/// Build the originRequest from the `service.team`, `service.aud` and `origin.*` annotations
+pub(super) fn origin_request(
+    annotations: &BTreeMap<String, String>,
+) -> Result<CloudflaredTunnelOriginRequest> {
+    let access = annotations
+        .get(ACCESS_TEAM_ANNOTATION)
+        .map(|team_name| CloudflaredTunnelAccess {
+            required: true,
+            team_name: team_name.to_string(),
+            aud_tag: annotations
+                .get(ACCESS_AUD_ANNOTATION)
+                .map(|s| {
+                    s.split(',')
+                        .map(str::trim)
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+        });
+
+    Ok(CloudflaredTunnelOriginRequest {
+        origin_server_name: origin(annotations, "originServerName")?,
+        ca_pool: origin(annotations, "caPool")?,
+        // 指定が無い場合は従来通りTLS検証を行わない
+        no_tls_verify: Some(origin(annotations, "noTLSVerify")?.unwrap_or(true)),
+        tls_timeout: origin(annotations, "tlsTimeout")?,
+        http2_origin: origin(annotations, "http2Origin")?,
+        http_host_header: origin(annotations, "httpHostHeader")?,
+        disable_chunked_encoding: origin(annotations, "disableChunkedEncoding")?,
+        connect_timeout: origin(annotations, "connectTimeout")?,
+        no_happy_eyeballs: origin(annotations, "noHappyEyeballs")?,
+        proxy_type: origin(annotations, "proxyType")?,
+        proxy_address: origin(annotations, "proxyAddress")?,
+        proxy_port: origin(annotations, "proxyPort")?,
+        keep_alive_timeout: origin(annotations, "keepAliveTimeout")?,
+        keep_alive_connections: origin(annotations, "keepAliveConnections")?,
+        tcp_keep_alive: origin(annotations, "tcpKeepAlive")?,
+        access,
+    })
+}
+
+fn origin<T: FromStr>(annotations: &BTreeMap<String, String>, name: &str) -> Result<Option<T>> {
+    let key = format!("{ORIGIN_PREFIX}{name}");
+    annotations
+        .get(&key)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| Error::invalid_annotation(&key))
+        })
+        .transpose()
+}
+
+/// Parse `<path>=<service>[,<path>=<service>...]` where service is one of cloudflared's
+/// built-in services (`http_status:<code>` or `hello_world`)
+fn parse_builtin_services(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|entry| {
+            let (path, service) = entry
+                .split_once('=')
+                .ok_or_else(|| Error::invalid_annotation(BUILTIN_SERVICE_ANNOTATION))?;
+            let service = service.trim();
+            let valid = service == "hello_world"
+                || service.strip_prefix("http_status:").is_some_and(|code| {
+                    code.len() == 3 && code.parse::<u16>().is_ok_and(|c| (100..600).contains(&c))
+                });
+            if !valid {
+                return Err(Error::invalid_annotation(BUILTIN_SERVICE_ANNOTATION));
+            }
+            Ok((path.trim().to_string(), service.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn annotations(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn origin_request_defaults_to_no_tls_verify() {
+        let origin_request = origin_request(&BTreeMap::new()).unwrap();
+        assert_eq!(Some(true), origin_request.no_tls_verify);
+        assert_eq!(None, origin_request.access);
+    }
+
+    #[test]
+    fn origin_request_from_annotations() {
+        let origin_request = origin_request(&annotations(&[
+            (
+                "cloudflared-ingress.ingress.kubernetes.io/origin.noTLSVerify",
+                "false",
+            ),
+            (
+                "cloudflared-ingress.ingress.kubernetes.io/origin.httpHostHeader",
+                "example.com",
+            ),
+            (
+                "cloudflared-ingress.ingress.kubernetes.io/origin.keepAliveConnections",
+                "10",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(Some(false), origin_request.no_tls_verify);
+        assert_eq!(
+            Some("example.com"),
+            origin_request.http_host_header.as_deref()
+        );
+        assert_eq!(Some(10), origin_request.keep_alive_connections);
+    }
+
+    #[test]
+    fn path_overrides_replace_ingress_annotations() {
+        let overrides = path_overrides(&annotations(&[
+            (
+                "cloudflared-ingress.ingress.kubernetes.io/origin.httpHostHeader",
+                "example.com",
+            ),
+            (
+                "cloudflared-ingress.ingress.kubernetes.io/path-overrides",
+                r#"{"/api":{"serversScheme":"https","origin":{"httpHostHeader":"api.example.com","noTLSVerify":false}}}"#,
+            ),
+        ]))
+        .unwrap();
+        let api = overrides.get("/api").unwrap();
+        assert_eq!("https", servers_scheme(api));
+        let origin_request = origin_request(api).unwrap();
+        assert_eq!(
+            Some("api.example.com"),
+            origin_request.http_host_header.as_deref()
+        );
+        assert_eq!(Some(false), origin_request.no_tls_verify);
+        assert!(!overrides.contains_key("/"));
+    }
+
+    #[test]
+    fn path_overrides_path_regex() {
+        let overrides = path_overrides(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/path-overrides",
+            r#"{"/api":{"pathRegex":"^/api/v[0-9]+/"},"/static":{"pathRegex":"^/static/("}}"#,
+        )]))
+        .unwrap();
+        assert_eq!(
+            Some("^/api/v[0-9]+/"),
+            path_regex(overrides.get("/api").unwrap())
+                .unwrap()
+                .as_deref()
+        );
+        assert!(path_regex(overrides.get("/static").unwrap()).is_err());
+        assert_eq!(None, path_regex(&BTreeMap::new()).unwrap());
+    }
+
+    #[test]
+    fn origin_request_rejects_invalid_value() {
+        let error = origin_request(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/origin.http2Origin",
+            "yes",
+        )]))
+        .unwrap_err();
+        assert_eq!("InvalidAnnotation", error.reason());
+    }
+
+    #[test]
+    fn dedicated_tunnel_annotation() {
+        assert!(!dedicated_tunnel(&BTreeMap::new()).unwrap());
+        assert!(dedicated_tunnel(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/dedicated-tunnel",
+            "true",
+        )]))
+        .unwrap());
+        assert!(dedicated_tunnel(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/dedicated-tunnel",
+            "on",
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn tls_origin_verify_annotation() {
+        assert!(!tls_origin_verify(&BTreeMap::new()).unwrap());
+        assert!(tls_origin_verify(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/tls-origin-verify",
+            "true",
+        )]))
+        .unwrap());
+        assert!(tls_origin_verify(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/tls-origin-verify",
+            "yes",
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn zone_id_annotation() {
+        assert_eq!(None, zone_id(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some("0123456789abcdef0123456789abcdef".to_string()),
+            zone_id(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/zone-id",
+                "0123456789ABCDEF0123456789ABCDEF",
+            )]))
+            .unwrap()
+        );
+        assert!(zone_id(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/zone-id",
+            "example.com",
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn dns_proxied_annotation() {
+        assert_eq!(None, dns_proxied(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some(false),
+            dns_proxied(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/dns-proxied",
+                "false",
+            )]))
+            .unwrap()
+        );
+        assert!(dns_proxied(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/dns-proxied",
+            "grey",
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn health_check_annotation() {
+        assert_eq!(None, health_check(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some(CloudflaredTunnelHealthCheck {
+                path: Some("/healthz".to_string()),
+            }),
+            health_check(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/health-check-path",
+                "/healthz",
+            )]))
+            .unwrap()
+        );
+        assert!(health_check(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/health-check-path",
+            "healthz",
+        )]))
+        .is_err());
+    }
+
+    #[test]
+    fn access_application_annotation() {
+        assert_eq!(None, access_application(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some(CloudflaredTunnelAccessApplication {
+                emails: Some(vec![
+                    "alice@example.com".to_string(),
+                    "bob@example.com".to_string()
+                ]),
+                session_duration: Some("12h".to_string()),
+                ..Default::default()
+            }),
+            access_application(&annotations(&[
+                (
+                    "cloudflared-ingress.ingress.kubernetes.io/access-allowed-emails",
+                    "alice@example.com, bob@example.com",
+                ),
+                (
+                    "cloudflared-ingress.ingress.kubernetes.io/access-session-duration",
+                    "12h",
+                ),
+            ]))
+            .unwrap()
+        );
+        assert!(access_application(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/access-identity-providers",
+            "00000000-0000-0000-0000-000000000001",
+        )]))
+        .is_err());
+        assert!(access_application(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/access-allowed-groups",
+            " ",
+        )]))
+        .is_err());
+        assert_eq!(
+            Some(CloudflaredTunnelAccessApplication {
+                service_token_secret: Some("api-client".to_string()),
+                ..Default::default()
+            }),
+            access_application(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/access-service-token-secret",
+                "api-client",
+            )]))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn whitelist_source_range_annotation() {
+        assert_eq!(None, whitelist_source_range(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some(vec!["10.0.0.0/8".to_string(), "2001:db8::1".to_string()]),
+            whitelist_source_range(&annotations(&[(
+                "nginx.ingress.kubernetes.io/whitelist-source-range",
+                "10.0.0.0/8, 2001:db8::1",
+            )]))
+            .unwrap()
+        );
+        // 独自のannotationを優先する
+        assert_eq!(
+            Some(vec!["192.0.2.0/24".to_string()]),
+            whitelist_source_range(&annotations(&[
+                (
+                    "cloudflared-ingress.ingress.kubernetes.io/whitelist-source-range",
+                    "192.0.2.0/24",
+                ),
+                (
+                    "nginx.ingress.kubernetes.io/whitelist-source-range",
+                    "10.0.0.0/8",
+                ),
+            ]))
+            .unwrap()
+        );
+        for value in ["10.0.0.0/33", "example.com", " , "] {
+            assert!(whitelist_source_range(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/whitelist-source-range",
+                value,
+            )]))
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn allow_existing_dns_annotation() {
+        assert_eq!(None, allow_existing_dns(&BTreeMap::new()).unwrap());
+        assert_eq!(
+            Some(CloudflaredTunnelExistingDns::Replace),
+            allow_existing_dns(&annotations(&[(
+                "cloudflared-ingress.ingress.kubernetes.io/allow-existing-dns",
+                "Replace",
+            )]))
+            .unwrap()
+        );
+        assert!(allow_existing_dns(&annotations(&[(
+            "cloudflared-ingress.ingress.kubernetes.io/allow-existing-dns",
+            "delete",
+        )]))
+        .is_err());
+    }
+}
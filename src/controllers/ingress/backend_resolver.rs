@@ -0,0 +1,124 @@
+use k8s_openapi::api::core::v1::TypedLocalObjectReference;
+
+use crate::{Error, Result};
+
+/// Translates Ingress resource backends of a kind into cloudflared service URLs
+pub trait ResourceBackendResolver: Send + Sync {
+    /// apiGroup of the handled resources, `None` for the core group
+    fn api_group(&self) -> Option<&str>;
+
+    fn kind(&self) -> &str;
+
+    /// cloudflared service URL of the resource in the namespace of the Ingress
+    fn resolve(
+        &self,
+        namespace: &str,
+        resource: &TypedLocalObjectReference,
+        scheme: &str,
+    ) -> Result<String>;
+}
+
+/// Resolvers of the resource backends, a resource backend of a kind without resolver is rejected
+#[derive(Default)]
+pub struct BackendResolvers {
+    resolvers: Vec<Box<dyn ResourceBackendResolver>>,
+}
+
+impl BackendResolvers {
+    pub fn register(mut self, resolver: impl ResourceBackendResolver + 'static) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    pub(super) fn resolve(
+        &self,
+        namespace: &str,
+        resource: &TypedLocalObjectReference,
+        scheme: &str,
+        ingress_name: &str,
+        hostname: &str,
+    ) -> Result<String> {
+        // apiGroupは空文字列でもcore groupを表す
+        let api_group = resource.api_group.as_deref().filter(|g| !g.is_empty());
+        self.resolvers
+            .iter()
+            .find(|r| r.api_group() == api_group && r.kind() == resource.kind)
+            .ok_or_else(|| Error::resource_backend_unsupported(ingress_name, hostname))?
+            .resolve(namespace, resource, scheme)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct GatewayResolver;
+
+    impl ResourceBackendResolver for GatewayResolver {
+        fn api_group(&self) -> Option<&str> {
+            Some("gateway.networking.k8s.io")
+        }
+
+        fn kind(&self) -> &str {
+            "Gateway"
+        }
+
+        fn resolve(
+            &self,
+            namespace: &str,
+            resource: &TypedLocalObjectReference,
+            scheme: &str,
+        ) -> Result<String> {
+            Ok(format!(
+                "{scheme}://{}-gateway.{namespace}.svc",
+                resource.name
+            ))
+        }
+    }
+
+    fn reference(api_group: Option<&str>, kind: &str) -> TypedLocalObjectReference {
+        TypedLocalObjectReference {
+            api_group: api_group.map(str::to_string),
+            kind: kind.to_string(),
+            name: "public".to_string(),
+        }
+    }
+
+    #[test]
+    fn registered_kind_is_resolved() {
+        let resolvers = BackendResolvers::default().register(GatewayResolver);
+        assert_eq!(
+            "http://public-gateway.default.svc",
+            resolvers
+                .resolve(
+                    "default",
+                    &reference(Some("gateway.networking.k8s.io"), "Gateway"),
+                    "http",
+                    "default/web",
+                    "www.example.com",
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_kind_is_rejected() {
+        let resolvers = BackendResolvers::default().register(GatewayResolver);
+        for resource in [
+            reference(Some("example.com"), "Gateway"),
+            reference(Some("gateway.networking.k8s.io"), "HTTPRoute"),
+            reference(None, "Gateway"),
+        ] {
+            let error = resolvers
+                .resolve(
+                    "default",
+                    &resource,
+                    "http",
+                    "default/web",
+                    "www.example.com",
+                )
+                .unwrap_err();
+            assert_eq!("ResourceBackendUnsupported", error.reason());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use k8s_openapi::api::{
+    core::v1::Service,
+    discovery::v1::EndpointSlice,
+    networking::v1::{Ingress, IngressClass},
+};
+use kube::runtime::reflector::{self, store::Writer, Store};
+
+use crate::Result;
+
+/// Reflector stores of the objects the ingress controller watches, read on reconcile instead
+/// of listing the objects from the API server. The namespaced objects have a store per
+/// namespace given by `--watch-namespaces`.
+#[derive(Clone)]
+pub(super) struct Caches {
+    pub(super) ingress_classes: Store<IngressClass>,
+    ingresses: Vec<Store<Ingress>>,
+    services: Vec<Store<Service>>,
+    endpoint_slices: Vec<Store<EndpointSlice>>,
+}
+
+/// Writers of the stores of [`Caches`], fed by the watch streams of the controller
+pub(super) struct CacheWriters {
+    pub(super) ingress_classes: Writer<IngressClass>,
+    pub(super) ingresses: Vec<Writer<Ingress>>,
+    pub(super) services: Vec<Writer<Service>>,
+    pub(super) endpoint_slices: Vec<Writer<EndpointSlice>>,
+}
+
+pub(super) fn caches(scopes: usize) -> (Caches, CacheWriters) {
+    let (ingress_classes, ingress_class_writer) = reflector::store();
+    let (ingresses, ingress_writers) = (0..scopes).map(|_| reflector::store()).unzip();
+    let (services, service_writers) = (0..scopes).map(|_| reflector::store()).unzip();
+    let (endpoint_slices, endpoint_slice_writers) = (0..scopes).map(|_| reflector::store()).unzip();
+    (
+        Caches {
+            ingress_classes,
+            ingresses,
+            services,
+            endpoint_slices,
+        },
+        CacheWriters {
+            ingress_classes: ingress_class_writer,
+            ingresses: ingress_writers,
+            services: service_writers,
+            endpoint_slices: endpoint_slice_writers,
+        },
+    )
+}
+
+impl Caches {
+    /// Wait until every store has received the initial list of its objects
+    pub(super) async fn wait_until_ready(&self) -> Result<()> {
+        self.ingress_classes.wait_until_ready().await?;
+        for store in self.ingresses.iter() {
+            store.wait_until_ready().await?;
+        }
+        for store in self.services.iter() {
+            store.wait_until_ready().await?;
+        }
+        for store in self.endpoint_slices.iter() {
+            store.wait_until_ready().await?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn ingress_classes(&self) -> Vec<IngressClass> {
+        state(std::slice::from_ref(&self.ingress_classes))
+    }
+
+    pub(super) fn ingresses(&self) -> Vec<Ingress> {
+        state(&self.ingresses)
+    }
+
+    pub(super) fn services(&self) -> Vec<Service> {
+        state(&self.services)
+    }
+
+    pub(super) fn endpoint_slices(&self) -> Vec<EndpointSlice> {
+        state(&self.endpoint_slices)
+    }
+}
+
+fn state<K>(stores: &[Store<K>]) -> Vec<K>
+where
+    K: reflector::Lookup + Clone + 'static,
+    K::DynamicType: Eq + std::hash::Hash + Clone,
+{
+    stores
+        .iter()
+        .flat_map(|s| s.state())
+        .map(|o| K::clone(&o))
+        .collect()
+}
@@ -0,0 +1,57 @@
+use k8s_openapi::api::networking::v1::IngressClass;
+use kube::{Api, Client, CustomResource, Resource as _};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controllers::cloudflared::{CloudflaredTunnelOriginRequest, CloudflaredTunnelResources},
+    Error, Result,
+};
+
+/// Per-IngressClass settings, referenced by `IngressClass.spec.parameters`
+#[derive(CustomResource, Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[kube(
+    // Required properties
+    group = "chalharu.top",
+    version = "v1alpha1",
+    kind = "CloudflaredIngressClassParams",
+    // Optional properties
+    singular = "cloudflaredingressclassparams",
+    plural = "cloudflaredingressclassparams",
+    shortname = "cfdicp",
+)]
+pub struct CloudflaredIngressClassParamsSpec {
+    /// Namespace of the CloudflaredTunnel, defaults to `--cloudflare-tunnel-namespace`
+    pub tunnel_namespace: Option<String>,
+    /// Cloudflare tunnel name prefix, defaults to `--cloudflare-tunnel-prefix`. An empty prefix
+    /// would match every tunnel of the account
+    #[schemars(length(min = 1))]
+    pub tunnel_prefix: Option<String>,
+    pub origin_request: Option<CloudflaredTunnelOriginRequest>,
+    pub image: Option<String>,
+    pub resources: Option<CloudflaredTunnelResources>,
+}
+
+/// Parameters referenced by the IngressClass, if it references a CloudflaredIngressClassParams
+pub(super) async fn get_class_params(
+    client: &Client,
+    ic: &IngressClass,
+) -> Result<Option<CloudflaredIngressClassParamsSpec>> {
+    let Some(parameters) = ic.spec.as_ref().and_then(|s| s.parameters.as_ref()) else {
+        return Ok(None);
+    };
+    // 他のcontrollerやAPIのparametersは無視する
+    if parameters.api_group.as_deref() != Some(CloudflaredIngressClassParams::group(&()).as_ref())
+        || parameters.kind != CloudflaredIngressClassParams::kind(&())
+    {
+        return Ok(None);
+    }
+    let api = Api::<CloudflaredIngressClassParams>::all(client.clone());
+    let params = api.get_opt(&parameters.name).await?.ok_or_else(|| {
+        Error::object_not_found(
+            CloudflaredIngressClassParams::kind(&()),
+            parameters.name.clone(),
+        )
+    })?;
+    Ok(Some(params.spec))
+}
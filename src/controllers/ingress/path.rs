@@ -0,0 +1,76 @@
+/// cloudflared path regex of an `Exact` Ingress path, matching the path only as is
+pub(super) fn exact_path_regex(path: Option<&str>) -> String {
+    format!("^{}$", regex::escape(path.unwrap_or("/")))
+}
+
+/// cloudflared path regex of a `Prefix` Ingress path, matching it element by element so that
+/// `/foo` matches `/foo` and `/foo/bar` but not `/foobar`. `None` is returned for `/`, which
+/// matches every path.
+pub(super) fn prefix_path_regex(path: Option<&str>) -> Option<String> {
+    // 末尾の`/`は無視する
+    let path = path?.trim_end_matches('/');
+    if path.is_empty() {
+        return None;
+    }
+    Some(format!("^{}(/|$)", regex::escape(path)))
+}
+
+#[cfg(test)]
+mod test {
+    use regex::Regex;
+
+    use super::*;
+
+    fn matches(regex: &str, path: &str) -> bool {
+        Regex::new(regex).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn exact_matches_only_the_path() {
+        let regex = exact_path_regex(Some("/foo"));
+        assert!(matches(&regex, "/foo"));
+        assert!(!matches(&regex, "/foo/"));
+        assert!(!matches(&regex, "/foobar"));
+        assert!(!matches(&regex, "/bar/foo"));
+    }
+
+    #[test]
+    fn exact_escapes_regex_characters() {
+        let regex = exact_path_regex(Some("/a.b+c(d)/[e]$"));
+        assert!(matches(&regex, "/a.b+c(d)/[e]$"));
+        assert!(!matches(&regex, "/aXb+c(d)/[e]$"));
+        assert!(!matches(&regex, "/a.bbc(d)/[e]$"));
+    }
+
+    #[test]
+    fn prefix_matches_path_elements() {
+        let regex = prefix_path_regex(Some("/foo")).unwrap();
+        assert!(matches(&regex, "/foo"));
+        assert!(matches(&regex, "/foo/"));
+        assert!(matches(&regex, "/foo/bar"));
+        assert!(!matches(&regex, "/foobar"));
+        assert!(!matches(&regex, "/bar/foo"));
+    }
+
+    #[test]
+    fn prefix_ignores_trailing_slash() {
+        let regex = prefix_path_regex(Some("/foo/bar/")).unwrap();
+        assert!(matches(&regex, "/foo/bar"));
+        assert!(matches(&regex, "/foo/bar/baz"));
+        assert!(!matches(&regex, "/foo/barbaz"));
+        assert!(!matches(&regex, "/foo"));
+    }
+
+    #[test]
+    fn prefix_root_matches_everything() {
+        assert_eq!(None, prefix_path_regex(Some("/")));
+        assert_eq!(None, prefix_path_regex(None));
+    }
+
+    #[test]
+    fn prefix_escapes_regex_characters() {
+        let regex = prefix_path_regex(Some("/v1.0")).unwrap();
+        assert!(matches(&regex, "/v1.0/items"));
+        assert!(!matches(&regex, "/v1x0/items"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{cmp::Reverse, collections::HashSet};
+
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::ResourceExt as _;
+
+use crate::controllers::cloudflared::CloudflaredTunnelIngress;
+
+/// Rules of the Ingresses in a stable order independent of the order the Ingresses were
+/// listed in. cloudflared uses the first matching rule, so the rules are ordered by hostname
+/// with wildcard hostnames last, then by path from the most specific one, then by the age of
+/// their Ingress. Only the first of the rules for the same hostname and path is kept.
+pub(super) fn ordered_rules(
+    accepted: &[(Ingress, Vec<CloudflaredTunnelIngress>)],
+) -> Vec<CloudflaredTunnelIngress> {
+    let mut rules = accepted
+        .iter()
+        .flat_map(|(i, rules)| {
+            let age = (i.creation_timestamp(), i.namespace(), i.name_any());
+            rules.iter().map(move |rule| (age.clone(), rule))
+        })
+        .collect::<Vec<_>>();
+    rules.sort_by_cached_key(|(age, rule)| {
+        (
+            rule.hostname.starts_with('*'),
+            rule.hostname.to_lowercase(),
+            Reverse(path_specificity(rule.path.as_deref())),
+            age.clone(),
+        )
+    });
+    let mut seen = HashSet::new();
+    rules
+        .into_iter()
+        .filter(|(_, rule)| seen.insert((rule.hostname.to_lowercase(), rule.path.clone())))
+        .map(|(_, rule)| rule.clone())
+        .collect()
+}
+
+/// Specificity of a path regex: the length of the Ingress path it was built from, an exact
+/// path before a prefix of the same path, and any path before the rule matching every path
+fn path_specificity(path: Option<&str>) -> (bool, usize, bool) {
+    let Some(path) = path else {
+        return (false, 0, false);
+    };
+    let path = path.strip_prefix('^').unwrap_or(path);
+    let (path, exact) = match path.strip_suffix("(/|$)") {
+        Some(prefix) => (prefix, false),
+        None => match path.strip_suffix('$') {
+            Some(exact) => (exact, true),
+            None => (path, false),
+        },
+    };
+    (true, unescaped_len(path), exact)
+}
+
+/// Length of a path escaped by `regex::escape`
+fn unescaped_len(path: &str) -> usize {
+    let mut chars = path.chars();
+    let mut len = 0;
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        }
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kube::api::ObjectMeta;
+
+    use super::*;
+
+    fn ingress(name: &str, created_secs: i64) -> Ingress {
+        Ingress {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("default".to_string()),
+                creation_timestamp: Some(Time(
+                    k8s_openapi::chrono::DateTime::from_timestamp(created_secs, 0).unwrap(),
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn rule(hostname: &str, path: Option<&str>, service: &str) -> CloudflaredTunnelIngress {
+        CloudflaredTunnelIngress {
+            hostname: hostname.to_string(),
+            path: path.map(str::to_string),
+            service: service.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn keys(rules: &[CloudflaredTunnelIngress]) -> Vec<(&str, Option<&str>, &str)> {
+        rules
+            .iter()
+            .map(|r| (r.hostname.as_str(), r.path.as_deref(), r.service.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn order_does_not_depend_on_listing() {
+        let a = (
+            ingress("a", 100),
+            vec![
+                rule("www.example.com", None, "http://a"),
+                rule("*.example.com", None, "http://wildcard"),
+            ],
+        );
+        let b = (
+            ingress("b", 200),
+            vec![
+                rule("www.example.com", Some("^/api(/|$)"), "http://b"),
+                rule("api.example.com", Some("^/$"), "http://b"),
+            ],
+        );
+        let forward = ordered_rules(&[a.clone(), b.clone()]);
+        assert_eq!(forward, ordered_rules(&[b, a]));
+        assert_eq!(
+            vec![
+                ("api.example.com", Some("^/$"), "http://b"),
+                ("www.example.com", Some("^/api(/|$)"), "http://b"),
+                ("www.example.com", None, "http://a"),
+                ("*.example.com", None, "http://wildcard"),
+            ],
+            keys(&forward)
+        );
+    }
+
+    #[test]
+    fn exact_path_before_prefix() {
+        let rules = ordered_rules(&[(
+            ingress("a", 100),
+            vec![
+                rule("www.example.com", Some("^/a(/|$)"), "http://prefix"),
+                rule("www.example.com", Some("^/a/b$"), "http://exact"),
+                rule("www.example.com", Some("^/a/b(/|$)"), "http://prefix-b"),
+                rule("www.example.com", Some("^/a\\.bc$"), "http://exact-dot-bc"),
+                rule("www.example.com", Some("^/a/bc(/|$)"), "http://prefix-bc"),
+            ],
+        )]);
+        assert_eq!(
+            vec![
+                ("www.example.com", Some("^/a\\.bc$"), "http://exact-dot-bc"),
+                ("www.example.com", Some("^/a/bc(/|$)"), "http://prefix-bc"),
+                ("www.example.com", Some("^/a/b$"), "http://exact"),
+                ("www.example.com", Some("^/a/b(/|$)"), "http://prefix-b"),
+                ("www.example.com", Some("^/a(/|$)"), "http://prefix"),
+            ],
+            keys(&rules)
+        );
+    }
+
+    #[test]
+    fn duplicates_keep_the_oldest() {
+        let rules = ordered_rules(&[
+            (
+                ingress("new", 200),
+                vec![rule("www.example.com", None, "http://new")],
+            ),
+            (
+                ingress("old", 100),
+                vec![
+                    rule("www.example.com", None, "http://old"),
+                    rule("www.example.com", None, "http://old-again"),
+                ],
+            ),
+        ]);
+        assert_eq!(vec![("www.example.com", None, "http://old")], keys(&rules));
+    }
+}
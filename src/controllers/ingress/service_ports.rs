@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use k8s_openapi::{
+    api::{core::v1::Service, discovery::v1::EndpointSlice, networking::v1::Ingress},
+    apimachinery::pkg::util::intstr::IntOrString,
+};
+use kube::ResourceExt as _;
+
+/// Label of an EndpointSlice naming the Service it belongs to
+const SERVICE_NAME_LABEL: &str = "kubernetes.io/service-name";
+
+/// Ports of a Service by name, used to resolve the named ports of Ingress backends
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct ServicePorts {
+    /// Service ports by their name
+    ports: HashMap<String, i32>,
+    /// Service ports by the name of their targetPort
+    target_ports: HashMap<String, i32>,
+    /// Pod ports of the EndpointSlices by name, reached directly through a headless Service
+    endpoint_ports: HashMap<String, i32>,
+    headless: bool,
+}
+
+/// Ports of each Service keyed by `{name}.{namespace}.svc`
+pub(super) type ServiceIndex = HashMap<String, ServicePorts>;
+
+impl ServicePorts {
+    /// Port number of a named backend port. A headless Service resolves to the pods, so
+    /// the pod port of its EndpointSlices is preferred. Otherwise the Service port of that
+    /// name is used, falling back to the Service port whose targetPort has that name.
+    pub(super) fn resolve(&self, name: &str) -> Option<i32> {
+        let endpoint_port = self
+            .headless
+            .then(|| self.endpoint_ports.get(name))
+            .flatten();
+        endpoint_port
+            .or_else(|| self.ports.get(name))
+            .or_else(|| self.target_ports.get(name))
+            .copied()
+    }
+}
+
+/// Index the ports of the Services together with the EndpointSlices backing them
+pub(super) fn index_services(
+    services: &[Service],
+    endpoint_slices: &[EndpointSlice],
+) -> ServiceIndex {
+    let mut index = services
+        .iter()
+        .map(|s| {
+            let svc_name = format!("{}.{}.svc", s.name_any(), s.namespace().unwrap_or_default());
+            let mut ports = ServicePorts {
+                headless: s
+                    .spec
+                    .as_ref()
+                    .and_then(|s| s.cluster_ip.as_deref())
+                    .is_some_and(|ip| ip == "None"),
+                ..Default::default()
+            };
+            for p in s.spec.iter().flat_map(|s| s.ports.iter().flatten()) {
+                if let Some(ref name) = p.name {
+                    ports.ports.insert(name.clone(), p.port);
+                }
+                if let Some(IntOrString::String(ref target)) = p.target_port {
+                    ports.target_ports.entry(target.clone()).or_insert(p.port);
+                }
+            }
+            (svc_name, ports)
+        })
+        .collect::<ServiceIndex>();
+    for slice in endpoint_slices {
+        let Some(service) = service_of_slice(slice) else {
+            continue;
+        };
+        let svc_name = format!("{}.{}.svc", service, slice.namespace().unwrap_or_default());
+        let Some(ports) = index.get_mut(&svc_name) else {
+            continue;
+        };
+        for p in slice.ports.iter().flatten() {
+            if let (Some(name), Some(port)) = (p.name.as_ref(), p.port) {
+                ports.endpoint_ports.entry(name.clone()).or_insert(port);
+            }
+        }
+    }
+    index
+}
+
+/// Name of the Service an EndpointSlice belongs to
+pub(super) fn service_of_slice(slice: &EndpointSlice) -> Option<&str> {
+    slice.labels().get(SERVICE_NAME_LABEL).map(String::as_str)
+}
+
+/// Whether a backend of the Ingress, the default one included, is the Service of the name in the
+/// namespace of the Ingress
+pub(super) fn is_backend_service(ingress: &Ingress, service: &str) -> bool {
+    ingress
+        .spec
+        .iter()
+        .flat_map(|s| {
+            s.default_backend
+                .iter()
+                .chain(s.rules.iter().flatten().flat_map(|r| {
+                    r.http
+                        .iter()
+                        .flat_map(|h| h.paths.iter().map(|p| &p.backend))
+                }))
+        })
+        .any(|b| b.service.as_ref().is_some_and(|s| s.name == service))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::api::{
+        core::v1::{ServicePort, ServiceSpec},
+        discovery::v1::EndpointPort,
+    };
+    use kube::api::ObjectMeta;
+
+    use super::*;
+
+    fn service(cluster_ip: &str, ports: Vec<ServicePort>) -> Service {
+        Service {
+            metadata: ObjectMeta {
+                name: Some("web".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            spec: Some(ServiceSpec {
+                cluster_ip: Some(cluster_ip.to_string()),
+                ports: Some(ports),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn endpoint_slice(ports: Vec<EndpointPort>) -> EndpointSlice {
+        EndpointSlice {
+            metadata: ObjectMeta {
+                name: Some("web-abcde".to_string()),
+                namespace: Some("default".to_string()),
+                labels: Some(BTreeMap::from([(
+                    SERVICE_NAME_LABEL.to_string(),
+                    "web".to_string(),
+                )])),
+                ..Default::default()
+            },
+            address_type: "IPv4".to_string(),
+            endpoints: Vec::new(),
+            ports: Some(ports),
+        }
+    }
+
+    fn service_port(name: Option<&str>, port: i32, target_port: Option<&str>) -> ServicePort {
+        ServicePort {
+            name: name.map(str::to_string),
+            port,
+            target_port: target_port.map(|t| IntOrString::String(t.to_string())),
+            ..Default::default()
+        }
+    }
+
+    fn endpoint_port(name: &str, port: i32) -> EndpointPort {
+        EndpointPort {
+            name: Some(name.to_string()),
+            port: Some(port),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn service_port_name_is_preferred() {
+        let index = index_services(
+            &[service(
+                "10.0.0.1",
+                vec![
+                    service_port(Some("http"), 80, Some("web")),
+                    service_port(Some("web"), 8080, None),
+                ],
+            )],
+            &[endpoint_slice(vec![endpoint_port("http", 3000)])],
+        );
+        let ports = &index["web.default.svc"];
+        assert_eq!(Some(80), ports.resolve("http"));
+        assert_eq!(Some(8080), ports.resolve("web"));
+    }
+
+    #[test]
+    fn falls_back_to_target_port_name() {
+        let index = index_services(
+            &[service(
+                "10.0.0.1",
+                vec![service_port(None, 80, Some("web"))],
+            )],
+            &[],
+        );
+        assert_eq!(Some(80), index["web.default.svc"].resolve("web"));
+        assert_eq!(None, index["web.default.svc"].resolve("metrics"));
+    }
+
+    #[test]
+    fn headless_service_resolves_to_endpoint_port() {
+        let index = index_services(
+            &[service(
+                "None",
+                vec![service_port(Some("http"), 80, Some("web"))],
+            )],
+            &[endpoint_slice(vec![endpoint_port("http", 3000)])],
+        );
+        assert_eq!(Some(3000), index["web.default.svc"].resolve("http"));
+    }
+
+    #[test]
+    fn backend_services_of_ingress() {
+        let ingress: Ingress = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": {
+                "defaultBackend": { "service": { "name": "fallback", "port": { "number": 80 } } },
+                "rules": [{
+                    "host": "web.example.com",
+                    "http": { "paths": [{
+                        "path": "/",
+                        "pathType": "Prefix",
+                        "backend": { "service": { "name": "web", "port": { "name": "http" } } },
+                    }] },
+                }],
+            },
+        }))
+        .unwrap();
+        assert!(is_backend_service(&ingress, "web"));
+        assert!(is_backend_service(&ingress, "fallback"));
+        assert!(!is_backend_service(&ingress, "api"));
+        assert_eq!(Some("web"), service_of_slice(&endpoint_slice(Vec::new())));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Assign each hostname to a shard so that no shard exceeds `limit` rules.
+///
+/// Hostnames keep the shard of `previous` while it has room, new hostnames fill the
+/// first shard with room, and a hostname with more rules than `limit` gets a shard of
+/// its own. All rules of a hostname stay in the same shard.
+pub(super) fn assign_shards(
+    rule_counts: &BTreeMap<String, usize>,
+    previous: &HashMap<String, usize>,
+    limit: Option<usize>,
+) -> BTreeMap<String, usize> {
+    let Some(limit) = limit else {
+        return rule_counts.keys().map(|h| (h.clone(), 0)).collect();
+    };
+    let mut loads = Vec::<usize>::new();
+    let mut assigned = BTreeMap::new();
+    let fits = |loads: &Vec<usize>, shard: usize, count: usize| {
+        let load = loads.get(shard).copied().unwrap_or_default();
+        load == 0 || load + count <= limit
+    };
+
+    // 以前のshardに収まるhostnameは移動させない
+    for (hostname, &count) in rule_counts.iter() {
+        let Some(&shard) = previous.get(hostname) else {
+            continue;
+        };
+        if fits(&loads, shard, count) {
+            if loads.len() <= shard {
+                loads.resize(shard + 1, 0);
+            }
+            loads[shard] += count;
+            assigned.insert(hostname.clone(), shard);
+        }
+    }
+
+    for (hostname, &count) in rule_counts.iter() {
+        if assigned.contains_key(hostname) {
+            continue;
+        }
+        let shard = (0..loads.len())
+            .find(|&shard| fits(&loads, shard, count))
+            .unwrap_or(loads.len());
+        if loads.len() <= shard {
+            loads.resize(shard + 1, 0);
+        }
+        loads[shard] += count;
+        assigned.insert(hostname.clone(), shard);
+    }
+    assigned
+}
+
+/// Name of the CloudflaredTunnel of a shard, the first one keeps the IngressClass name
+pub(super) fn shard_name(name: &str, shard: usize) -> String {
+    if shard == 0 {
+        name.to_string()
+    } else {
+        format!("{name}-shard-{shard}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn counts(entries: &[(&str, usize)]) -> BTreeMap<String, usize> {
+        entries.iter().map(|(h, c)| (h.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn without_limit_everything_is_in_first_shard() {
+        let assigned = assign_shards(
+            &counts(&[("a.example.com", 10), ("b.example.com", 10)]),
+            &HashMap::new(),
+            None,
+        );
+        assert!(assigned.values().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn split_when_limit_is_exceeded() {
+        let assigned = assign_shards(
+            &counts(&[
+                ("a.example.com", 2),
+                ("b.example.com", 2),
+                ("c.example.com", 1),
+            ]),
+            &HashMap::new(),
+            Some(3),
+        );
+        assert_eq!(Some(&0), assigned.get("a.example.com"));
+        assert_eq!(Some(&1), assigned.get("b.example.com"));
+        assert_eq!(Some(&0), assigned.get("c.example.com"));
+    }
+
+    #[test]
+    fn oversized_hostname_gets_own_shard() {
+        let assigned = assign_shards(
+            &counts(&[("a.example.com", 5), ("b.example.com", 1)]),
+            &HashMap::new(),
+            Some(3),
+        );
+        assert_eq!(Some(&0), assigned.get("a.example.com"));
+        assert_eq!(Some(&1), assigned.get("b.example.com"));
+    }
+
+    #[test]
+    fn previous_assignment_is_kept() {
+        let previous = HashMap::from([
+            ("b.example.com".to_string(), 0),
+            ("c.example.com".to_string(), 1),
+        ]);
+        // aが追加されてもb, cは移動しない
+        let assigned = assign_shards(
+            &counts(&[
+                ("a.example.com", 2),
+                ("b.example.com", 2),
+                ("c.example.com", 2),
+            ]),
+            &previous,
+            Some(3),
+        );
+        assert_eq!(Some(&0), assigned.get("b.example.com"));
+        assert_eq!(Some(&1), assigned.get("c.example.com"));
+        assert_eq!(Some(&2), assigned.get("a.example.com"));
+    }
+
+    #[test]
+    fn shard_names() {
+        assert_eq!("cloudflared", shard_name("cloudflared", 0));
+        assert_eq!("cloudflared-shard-2", shard_name("cloudflared", 2));
+    }
+}
@@ -43,8 +43,273 @@ pub enum ControllerError {
         backtrace: Backtrace,
     },
 
-    #[snafu(display("IllegalDocument"))]
-    IllegalDocument {
+    #[snafu(display("No Cloudflare zone matches hostname {hostname}"))]
+    HostnameZoneNotFound {
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Zone {zone} of hostname {hostname} is not allowed by --zone-allowlist/--zone-denylist"
+    ))]
+    ZoneNotAllowed {
+        hostname: String,
+        zone: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("DNS record for {hostname} is not managed by this tunnel"))]
+    ConflictingDnsRecord {
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "TTL {ttl} of the DNS record {name} is neither 1 for automatic nor between 60 and 86400"
+    ))]
+    InvalidDnsRecordTtl {
+        name: String,
+        ttl: u32,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("{content} is not a valid content of the DNS record {name}"))]
+    InvalidDnsRecordContent {
+        name: String,
+        content: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Different worker scripts are specified for {hostname}"))]
+    ConflictingWorkerScript {
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Source ranges of {name} are not enforced, as WAF rules only apply to proxied hostnames"
+    ))]
+    SourceRangesNotProxied {
+        name: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Different source ranges are specified for {hostname}"))]
+    ConflictingSourceRanges {
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Different access applications are specified for {hostname}"))]
+    ConflictingAccessApplication {
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("{kind} {name} has no namespace"))]
+    MissingNamespace {
+        kind: String,
+        name: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("{kind} {name} is not found"))]
+    ObjectNotFound {
+        kind: String,
+        name: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Ingress {ingress} has a rule without host"))]
+    MissingHost {
+        ingress: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Ingress {ingress} has no backend service for {hostname}"))]
+    MissingBackendService {
+        ingress: String,
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Ingress {ingress} refers to Service {service} for {hostname}, which does not exist"
+    ))]
+    BackendServiceNotFound {
+        ingress: String,
+        hostname: String,
+        service: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Ingress {ingress} refers to port {port} of Service {service} for {hostname}, which does not exist"
+    ))]
+    BackendServicePortNotFound {
+        ingress: String,
+        hostname: String,
+        service: String,
+        port: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Ingress {ingress} uses a resource backend for {hostname}, whose kind has no resolver"
+    ))]
+    ResourceBackendUnsupported {
+        ingress: String,
+        hostname: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Ingress {ingress} uses unsupported pathType {path_type}"))]
+    UnsupportedPathType {
+        ingress: String,
+        path_type: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Ingress {ingress} defaultBackend {service} is ignored, the tunnel uses {winner_service} of Ingress {winner}"
+    ))]
+    DefaultBackendCollision {
+        ingress: String,
+        service: String,
+        winner: String,
+        winner_service: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Ingress {ingress} rule for {rule} is already served by Ingress {winner}"))]
+    IngressRuleCollision {
+        ingress: String,
+        rule: String,
+        winner: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "CloudflaredTunnel {name} is not managed by the ingress controller, annotate it with {annotation}=true to adopt it"
+    ))]
+    CloudflaredTunnelNotOwned {
+        name: String,
+        annotation: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Reconcile of CloudflaredTunnels {} failed", names.join(", ")))]
+    CloudflaredTunnelsFailed {
+        names: Vec<String>,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Annotation {key} has an invalid value"))]
+    InvalidAnnotation {
+        key: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret {secret} has no key {key}"))]
+    SecretKeyMissing {
+        secret: String,
+        key: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret {secret} exists and is not owned by CloudflaredTunnel {owner}"))]
+    SecretNotOwned {
+        secret: String,
+        owner: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "spec.secret_rotation_period {period} of CloudflaredTunnel {name} is shorter than {minimum} seconds"
+    ))]
+    SecretRotationPeriodTooShort {
+        name: String,
+        period: u64,
+        minimum: u64,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "spec.secret_rotation_period of CloudflaredTunnel {name} is not supported with spec.external_connector"
+    ))]
+    SecretRotationUnsupported {
+        name: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret {secret} is too short ({len} bytes)"))]
+    SecretTooShort {
+        secret: String,
+        len: usize,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret reference {value} is not in the form namespace/name#key"))]
+    InvalidSecretRef {
+        value: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "Secret manager reference {value} is not in the form aws:<id>, gcp:<name> or azure:<url>"
+    ))]
+    InvalidSecretManagerRef {
+        value: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret manager {provider} is not enabled in this build"))]
+    SecretManagerUnsupported {
+        provider: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Cloudflare API token is {status}"))]
+    CloudflareTokenInactive {
+        status: String,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Secret manager {provider} error: {message}"))]
+    SecretManagerError {
+        provider: String,
+        message: String,
         #[snafu(backtrace)]
         backtrace: Backtrace,
     },
@@ -113,6 +378,38 @@ pub enum ControllerError {
         backtrace: Backtrace,
     },
 
+    #[snafu(display("Wait error: {source}"))]
+    WaitError {
+        #[snafu(source)]
+        source: kube::runtime::wait::Error,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Timeout: {source}"))]
+    TimeoutError {
+        #[snafu(source)]
+        source: tokio::time::error::Elapsed,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("Uninstall left {} behind: {}", failed.len(), failed.join("; ")))]
+    UninstallIncomplete {
+        failed: Vec<String>,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
+    #[cfg(feature = "fips")]
+    #[snafu(display("FIPS rand error: {source}"))]
+    FipsRandError {
+        #[snafu(source)]
+        source: aws_lc_rs::error::Unspecified,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
+
     #[snafu(display("Convert from int error: {source}"))]
     TryFromIntError {
         #[snafu(source)]
@@ -120,6 +417,14 @@ pub enum ControllerError {
         #[snafu(backtrace)]
         backtrace: Backtrace,
     },
+
+    #[snafu(display("Reflector store error: {source}"))]
+    StoreWriterDropped {
+        #[snafu(source)]
+        source: kube::runtime::reflector::store::WriterDropped,
+        #[snafu(backtrace)]
+        backtrace: Backtrace,
+    },
 }
 
 impl From<serde_json::Error> for ControllerError {
@@ -194,21 +499,397 @@ impl From<rand::Error> for ControllerError {
     }
 }
 
+impl From<kube::runtime::wait::Error> for ControllerError {
+    fn from(value: kube::runtime::wait::Error) -> Self {
+        WaitSnafu.into_error(value)
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for ControllerError {
+    fn from(value: tokio::time::error::Elapsed) -> Self {
+        TimeoutSnafu.into_error(value)
+    }
+}
+
+#[cfg(feature = "fips")]
+impl From<aws_lc_rs::error::Unspecified> for ControllerError {
+    fn from(value: aws_lc_rs::error::Unspecified) -> Self {
+        FipsRandSnafu.into_error(value)
+    }
+}
+
 impl From<TryFromIntError> for ControllerError {
     fn from(value: TryFromIntError) -> Self {
         TryFromIntSnafu.into_error(value)
     }
 }
 
+impl From<kube::runtime::reflector::store::WriterDropped> for ControllerError {
+    fn from(value: kube::runtime::reflector::store::WriterDropped) -> Self {
+        StoreWriterDroppedSnafu.into_error(value)
+    }
+}
+
 impl ControllerError {
-    pub fn illegal_document() -> Self {
-        IllegalDocumentSnafu.build()
+    pub fn hostname_zone_not_found(hostname: impl Into<String>) -> Self {
+        HostnameZoneNotFoundSnafu {
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn zone_not_allowed(hostname: impl Into<String>, zone: impl Into<String>) -> Self {
+        ZoneNotAllowedSnafu {
+            hostname: hostname.into(),
+            zone: zone.into(),
+        }
+        .build()
+    }
+
+    pub fn conflicting_dns_record(hostname: impl Into<String>) -> Self {
+        ConflictingDnsRecordSnafu {
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn invalid_dns_record_ttl(name: impl Into<String>, ttl: u32) -> Self {
+        InvalidDnsRecordTtlSnafu {
+            name: name.into(),
+            ttl,
+        }
+        .build()
+    }
+
+    pub fn invalid_dns_record_content(name: impl Into<String>, content: impl Into<String>) -> Self {
+        InvalidDnsRecordContentSnafu {
+            name: name.into(),
+            content: content.into(),
+        }
+        .build()
+    }
+
+    pub fn conflicting_worker_script(hostname: impl Into<String>) -> Self {
+        ConflictingWorkerScriptSnafu {
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn source_ranges_not_proxied(name: impl Into<String>) -> Self {
+        SourceRangesNotProxiedSnafu { name: name.into() }.build()
+    }
+
+    pub fn conflicting_source_ranges(hostname: impl Into<String>) -> Self {
+        ConflictingSourceRangesSnafu {
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn conflicting_access_application(hostname: impl Into<String>) -> Self {
+        ConflictingAccessApplicationSnafu {
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn missing_namespace(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        MissingNamespaceSnafu {
+            kind: kind.into(),
+            name: name.into(),
+        }
+        .build()
+    }
+
+    pub fn object_not_found(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        ObjectNotFoundSnafu {
+            kind: kind.into(),
+            name: name.into(),
+        }
+        .build()
+    }
+
+    pub fn missing_host(ingress: impl Into<String>) -> Self {
+        MissingHostSnafu {
+            ingress: ingress.into(),
+        }
+        .build()
+    }
+
+    pub fn missing_backend_service(
+        ingress: impl Into<String>,
+        hostname: impl Into<String>,
+    ) -> Self {
+        MissingBackendServiceSnafu {
+            ingress: ingress.into(),
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn backend_service_not_found(
+        ingress: impl Into<String>,
+        hostname: impl Into<String>,
+        service: impl Into<String>,
+    ) -> Self {
+        BackendServiceNotFoundSnafu {
+            ingress: ingress.into(),
+            hostname: hostname.into(),
+            service: service.into(),
+        }
+        .build()
+    }
+
+    pub fn backend_service_port_not_found(
+        ingress: impl Into<String>,
+        hostname: impl Into<String>,
+        service: impl Into<String>,
+        port: impl Into<String>,
+    ) -> Self {
+        BackendServicePortNotFoundSnafu {
+            ingress: ingress.into(),
+            hostname: hostname.into(),
+            service: service.into(),
+            port: port.into(),
+        }
+        .build()
+    }
+
+    pub fn resource_backend_unsupported(
+        ingress: impl Into<String>,
+        hostname: impl Into<String>,
+    ) -> Self {
+        ResourceBackendUnsupportedSnafu {
+            ingress: ingress.into(),
+            hostname: hostname.into(),
+        }
+        .build()
+    }
+
+    pub fn unsupported_path_type(ingress: impl Into<String>, path_type: impl Into<String>) -> Self {
+        UnsupportedPathTypeSnafu {
+            ingress: ingress.into(),
+            path_type: path_type.into(),
+        }
+        .build()
+    }
+
+    pub fn default_backend_collision(
+        ingress: impl Into<String>,
+        service: impl Into<String>,
+        winner: impl Into<String>,
+        winner_service: impl Into<String>,
+    ) -> Self {
+        DefaultBackendCollisionSnafu {
+            ingress: ingress.into(),
+            service: service.into(),
+            winner: winner.into(),
+            winner_service: winner_service.into(),
+        }
+        .build()
+    }
+
+    pub fn ingress_rule_collision(
+        ingress: impl Into<String>,
+        rule: impl Into<String>,
+        winner: impl Into<String>,
+    ) -> Self {
+        IngressRuleCollisionSnafu {
+            ingress: ingress.into(),
+            rule: rule.into(),
+            winner: winner.into(),
+        }
+        .build()
+    }
+
+    pub fn cloudflared_tunnel_not_owned(
+        name: impl Into<String>,
+        annotation: impl Into<String>,
+    ) -> Self {
+        CloudflaredTunnelNotOwnedSnafu {
+            name: name.into(),
+            annotation: annotation.into(),
+        }
+        .build()
+    }
+
+    pub fn cloudflared_tunnels_failed(names: Vec<String>) -> Self {
+        CloudflaredTunnelsFailedSnafu { names }.build()
+    }
+
+    pub fn invalid_annotation(key: impl Into<String>) -> Self {
+        InvalidAnnotationSnafu { key: key.into() }.build()
+    }
+
+    pub fn secret_key_missing(secret: impl Into<String>, key: impl Into<String>) -> Self {
+        SecretKeyMissingSnafu {
+            secret: secret.into(),
+            key: key.into(),
+        }
+        .build()
+    }
+
+    pub fn secret_not_owned(secret: impl Into<String>, owner: impl Into<String>) -> Self {
+        SecretNotOwnedSnafu {
+            secret: secret.into(),
+            owner: owner.into(),
+        }
+        .build()
+    }
+
+    pub fn secret_rotation_period_too_short(
+        name: impl Into<String>,
+        period: u64,
+        minimum: u64,
+    ) -> Self {
+        SecretRotationPeriodTooShortSnafu {
+            name: name.into(),
+            period,
+            minimum,
+        }
+        .build()
+    }
+
+    pub fn secret_rotation_unsupported(name: impl Into<String>) -> Self {
+        SecretRotationUnsupportedSnafu { name: name.into() }.build()
+    }
+
+    pub fn secret_too_short(secret: impl Into<String>, len: usize) -> Self {
+        SecretTooShortSnafu {
+            secret: secret.into(),
+            len,
+        }
+        .build()
+    }
+
+    pub fn invalid_secret_ref(value: impl Into<String>) -> Self {
+        InvalidSecretRefSnafu {
+            value: value.into(),
+        }
+        .build()
+    }
+
+    pub fn invalid_secret_manager_ref(value: impl Into<String>) -> Self {
+        InvalidSecretManagerRefSnafu {
+            value: value.into(),
+        }
+        .build()
+    }
+
+    pub fn secret_manager_unsupported(provider: impl Into<String>) -> Self {
+        SecretManagerUnsupportedSnafu {
+            provider: provider.into(),
+        }
+        .build()
+    }
+
+    pub fn cloudflare_token_inactive(status: impl Into<String>) -> Self {
+        CloudflareTokenInactiveSnafu {
+            status: status.into(),
+        }
+        .build()
+    }
+
+    pub fn secret_manager(provider: impl Into<String>, message: impl std::fmt::Display) -> Self {
+        SecretManagerSnafu {
+            provider: provider.into(),
+            message: message.to_string(),
+        }
+        .build()
+    }
+
+    pub fn uninstall_incomplete(failed: Vec<String>) -> Self {
+        UninstallIncompleteSnafu { failed }.build()
+    }
+
+    /// Short CamelCase reason for Events and conditions
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::HostnameZoneNotFound { .. } => "HostnameZoneNotFound",
+            Self::ZoneNotAllowed { .. } => "ZoneNotAllowed",
+            Self::ConflictingDnsRecord { .. } => "ConflictingDnsRecord",
+            Self::InvalidDnsRecordTtl { .. } => "InvalidDnsRecordTtl",
+            Self::InvalidDnsRecordContent { .. } => "InvalidDnsRecordContent",
+            Self::ConflictingWorkerScript { .. } => "ConflictingWorkerScript",
+            Self::SourceRangesNotProxied { .. } => "SourceRangesNotProxied",
+            Self::ConflictingSourceRanges { .. } => "ConflictingSourceRanges",
+            Self::ConflictingAccessApplication { .. } => "ConflictingAccessApplication",
+            Self::MissingNamespace { .. } => "MissingNamespace",
+            Self::ObjectNotFound { .. } => "ObjectNotFound",
+            Self::MissingHost { .. } => "MissingHost",
+            Self::MissingBackendService { .. } => "MissingBackendService",
+            Self::BackendServiceNotFound { .. } => "BackendServiceNotFound",
+            Self::BackendServicePortNotFound { .. } => "BackendServicePortNotFound",
+            Self::ResourceBackendUnsupported { .. } => "ResourceBackendUnsupported",
+            Self::UnsupportedPathType { .. } => "UnsupportedPathType",
+            Self::DefaultBackendCollision { .. } => "DefaultBackendCollision",
+            Self::IngressRuleCollision { .. } => "IngressRuleCollision",
+            Self::CloudflaredTunnelNotOwned { .. } => "CloudflaredTunnelNotOwned",
+            Self::InvalidAnnotation { .. } => "InvalidAnnotation",
+            Self::SecretKeyMissing { .. } => "SecretKeyMissing",
+            Self::SecretNotOwned { .. } => "SecretNotOwned",
+            Self::SecretRotationPeriodTooShort { .. } => "SecretRotationPeriodTooShort",
+            Self::SecretRotationUnsupported { .. } => "SecretRotationUnsupported",
+            Self::SecretTooShort { .. } => "SecretTooShort",
+            Self::InvalidSecretRef { .. } => "InvalidSecretRef",
+            Self::InvalidSecretManagerRef { .. } => "InvalidSecretManagerRef",
+            Self::SecretManagerUnsupported { .. } => "SecretManagerUnsupported",
+            Self::SecretManagerError { .. } => "SecretManagerError",
+            Self::CloudflareTokenInactive { .. } => "ApiTokenInactive",
+            _ if self.is_insufficient_token_permissions() => "InsufficientTokenPermissions",
+            _ => "ReconcileFailed",
+        }
     }
 }
 
 pub type Result<T, E = ControllerError> = std::result::Result<T, E>;
 
 impl ControllerError {
+    /// Whether an Ingress backend refers to a Service or Service port that does not exist
+    pub fn is_missing_backend(&self) -> bool {
+        matches!(
+            self,
+            Self::BackendServiceNotFound { .. } | Self::BackendServicePortNotFound { .. }
+        )
+    }
+
+    /// Whether a CloudflaredTunnel to be applied was created by someone else
+    pub fn is_cloudflared_tunnel_not_owned(&self) -> bool {
+        matches!(self, Self::CloudflaredTunnelNotOwned { .. })
+    }
+
+    /// Whether a Kubernetes object the request needs does not exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Self::KubeError { source: kube::Error::Api(e), .. } if e.code == 404
+        )
+    }
+
+    /// Whether Cloudflare rejected a request because the token lacks a permission
+    pub fn is_insufficient_token_permissions(&self) -> bool {
+        use cloudflare::framework::response::ApiFailure;
+        matches!(
+            self,
+            Self::CloudflareApiFailure { source, .. }
+                if matches!(**source, ApiFailure::Error(status, _) if status.as_u16() == 403)
+        )
+    }
+
+    /// Whether Cloudflare rejected the token itself or its access to the account, which
+    /// retrying does not fix
+    pub fn is_rejected_credentials(&self) -> bool {
+        use cloudflare::framework::response::ApiFailure;
+        match self {
+            Self::CloudflareTokenInactive { .. } => true,
+            Self::CloudflareApiFailure { source, .. } => {
+                matches!(**source, ApiFailure::Error(status, _) if matches!(status.as_u16(), 400 | 401 | 403))
+            }
+            _ => false,
+        }
+    }
+
     pub fn metric_label(&self) -> String {
         format!("{self:?}").to_lowercase()
     }
@@ -0,0 +1,13 @@
+pub mod cli;
+pub mod client;
+pub mod controllers;
+pub mod error;
+pub mod metrics;
+pub mod readiness;
+pub mod resync;
+pub mod secret_manager;
+pub mod secret_ref;
+pub mod zone_accounts;
+pub mod zone_resolver;
+
+pub use crate::error::{ControllerError as Error, Result};
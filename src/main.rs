@@ -1,29 +1,118 @@
-mod cli;
-mod controllers;
-mod error;
+use std::{fs::File, io::BufReader, sync::Arc};
 
-use actix_web::{get, middleware, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    get, http::header, middleware, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
 use clap::Parser as _;
-use cli::{Cli, Commands};
-use kube::CustomResourceExt as _;
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::{core::admission::AdmissionReview, Client, CustomResourceExt as _};
+use tracing::error;
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
-pub use crate::error::{ControllerError as Error, Result};
+use cloudflared_ingress_rs::{
+    cli::{Cli, Commands, ControllerArgs, InvalidArgs, EXIT_INVALID_CREDENTIALS},
+    controllers::{self, ingress::BackendResolvers},
+    metrics::Metrics,
+    readiness::Readiness,
+    resync::ResyncTrigger,
+    Error, Result,
+};
 
 #[get("/health")]
 async fn health(_: HttpRequest) -> impl Responder {
     HttpResponse::Ok().json("healthy")
 }
 
+#[get("/readyz")]
+async fn readyz(_: HttpRequest, state: web::Data<Readiness>) -> impl Responder {
+    match state.status() {
+        Ok(()) => HttpResponse::Ok().json("ready"),
+        Err(reason) => HttpResponse::ServiceUnavailable().json(reason),
+    }
+}
+
+struct AdminState {
+    token: Option<String>,
+    resync: ResyncTrigger,
+}
+
+#[post("/admin/resync")]
+async fn admin_resync(req: HttpRequest, state: web::Data<AdminState>) -> impl Responder {
+    // tokenが設定されていない場合はエンドポイント自体を無効化する
+    let Some(token) = state.token.as_deref() else {
+        return HttpResponse::NotFound().finish();
+    };
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map_or(false, |v| constant_time_eq(v.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return HttpResponse::Unauthorized().finish();
+    }
+    state.resync.trigger();
+    HttpResponse::Accepted().json("resync triggered")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[get("/metrics")]
+async fn metrics(_: HttpRequest, state: web::Data<Metrics>) -> impl Responder {
+    match state.encode() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+            .body(body),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+struct WebhookState {
+    client: Client,
+    args: ControllerArgs,
+    resolvers: BackendResolvers,
+}
+
+#[post("/validate-ingress")]
+async fn validate_ingress(
+    review: web::Json<AdmissionReview<Ingress>>,
+    state: web::Data<WebhookState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(
+        controllers::ingress::review_ingress(
+            &state.client,
+            &state.args,
+            &state.resolvers,
+            review.into_inner(),
+        )
+        .await,
+    )
+}
+
 #[get("/")]
 async fn index(_req: HttpRequest) -> impl Responder {
     HttpResponse::Ok()
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let args = Cli::parse();
 
+    // 環境変数の変更は他のスレッドと競合しないよう、tokioのruntimeを起動する前に行う
+    match args.commands() {
+        Commands::Run(args) => args.export_http_client_env(),
+        Commands::Uninstall(args) => args.controller_args().export_http_client_env(),
+        _ => {}
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(args))
+}
+
+async fn run(args: Cli) -> Result<()> {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
@@ -37,28 +126,122 @@ async fn main() -> Result<()> {
                 std::io::stdout(),
                 &controllers::cloudflared::CloudflaredTunnel::crd(),
             )?;
+            println!("---");
+            serde_yaml::to_writer(
+                std::io::stdout(),
+                &controllers::ingress::CloudflaredIngressClassParams::crd(),
+            )?;
+            println!("---");
+            serde_yaml::to_writer(
+                std::io::stdout(),
+                &controllers::cloudflared::CloudflaredTunnelRoute::crd(),
+            )?;
+            println!("---");
+            serde_yaml::to_writer(
+                std::io::stdout(),
+                &controllers::cloudflared::CloudflaredVirtualNetwork::crd(),
+            )?;
+            println!("---");
+            serde_yaml::to_writer(
+                std::io::stdout(),
+                &controllers::cloudflared::CloudflareDNSRecord::crd(),
+            )?;
         }
         Commands::Run(args) => {
+            exit_on_invalid_args(args);
+            let args = args.clone().resolve_secret_refs().await?;
+            verify_credentials(&args).await;
+            let resync = ResyncTrigger::default();
+            let metrics = Metrics::default();
+            let readiness = Readiness::default();
             // Both runtimes implements graceful shutdown, so poll until both are done
             tokio::join!(
-                controllers::ingress::run_controllers(args.clone()),
-                controllers::cloudflared::run_controller(args.clone()),
-                run_server()
+                controllers::ingress::run_controllers(args.clone(), resync.clone()),
+                controllers::cloudflared::run_controller(
+                    args.clone(),
+                    resync.clone(),
+                    metrics.clone(),
+                    readiness.clone()
+                ),
+                run_server(
+                    args.admin_token().map(str::to_string),
+                    resync,
+                    metrics,
+                    readiness
+                ),
+                run_webhook_server(args.clone())
             )
             .1?;
         }
+        Commands::Uninstall(args) => {
+            exit_on_invalid_args(args.controller_args());
+            controllers::cloudflared::uninstall(args).await?;
+        }
+        Commands::RenderConfig(args) => {
+            let cfdt: controllers::cloudflared::CloudflaredTunnel =
+                serde_yaml::from_reader(std::fs::File::open(args.file())?)?;
+            print!("{}", controllers::cloudflared::render_config(&cfdt)?);
+        }
     }
 
     Ok(())
 }
 
-async fn run_server() -> Result<(), std::io::Error> {
+/// Exit with the code of the invalid arguments before any controller starts
+fn exit_on_invalid_args(args: &ControllerArgs) {
+    if let Err(e) = args.validate() {
+        exit_with(&e);
+    }
+}
+
+fn exit_with(e: &InvalidArgs) -> ! {
+    eprintln!("{e}");
+    std::process::exit(e.exit_code.into());
+}
+
+/// Exit when Cloudflare rejects the credentials. Other failures, e.g. of the network, are
+/// left to the periodic verification, which keeps the controller unready until it succeeds
+async fn verify_credentials(args: &ControllerArgs) {
+    match controllers::cloudflared::verify_credentials(args).await {
+        Ok(()) => {}
+        Err(e) if e.is_rejected_credentials() => exit_with(&InvalidArgs {
+            exit_code: EXIT_INVALID_CREDENTIALS,
+            message: format!("Cloudflare rejected the credentials: {e}"),
+            remediation: "check that the token is active and has the Zone:Read, DNS:Edit and Cloudflare Tunnel:Edit permissions for the account".to_string(),
+        }),
+        Err(e) => error!("Cloudflare credentials could not be verified: {e}"),
+    }
+}
+
+async fn run_server(
+    admin_token: Option<String>,
+    resync: ResyncTrigger,
+    metrics_state: Metrics,
+    readiness: Readiness,
+) -> Result<(), std::io::Error> {
+    let state = web::Data::new(AdminState {
+        token: admin_token,
+        resync,
+    });
+    let metrics_state = web::Data::new(metrics_state);
+    let readiness = web::Data::new(readiness);
+
     // Start web server
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(middleware::Logger::default().exclude("/health"))
+            .app_data(state.clone())
+            .app_data(metrics_state.clone())
+            .app_data(readiness.clone())
+            .wrap(
+                middleware::Logger::default()
+                    .exclude("/health")
+                    .exclude("/readyz"),
+            )
             .service(index)
             .service(health)
+            .service(readyz)
+            .service(metrics)
+            .service(admin_resync)
     })
     .bind("0.0.0.0:8080")?
     .workers(2)
@@ -66,3 +249,58 @@ async fn run_server() -> Result<(), std::io::Error> {
 
     server.run().await
 }
+
+/// Serve the validating admission webhook over TLS when its certificate is given
+async fn run_webhook_server(args: ControllerArgs) {
+    let Some((cert_file, key_file)) = args.webhook_tls_files() else {
+        return;
+    };
+    let tls_config = match load_tls_config(cert_file, key_file) {
+        Ok(tls_config) => tls_config,
+        Err(e) => {
+            error!("admission webhook disabled, failed to load the certificate: {e}");
+            return;
+        }
+    };
+    let result = async {
+        let state = web::Data::new(WebhookState {
+            client: Client::try_default().await?,
+            args: args.clone(),
+            resolvers: BackendResolvers::default(),
+        });
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                // AdmissionReviewはobjectとoldObjectの両方を含む
+                .app_data(web::JsonConfig::default().limit(4 * 1024 * 1024))
+                .wrap(middleware::Logger::default())
+                .service(validate_ingress)
+        })
+        .bind_rustls_0_23(("0.0.0.0", args.webhook_port()), tls_config)?
+        .workers(2)
+        .shutdown_timeout(5);
+        server.run().await?;
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(e) = result {
+        error!("admission webhook server failed: {e}");
+    }
+}
+
+fn load_tls_config(
+    cert_file: &std::path::Path,
+    key_file: &std::path::Path,
+) -> std::io::Result<rustls::ServerConfig> {
+    let invalid_data = |e: Box<dyn std::error::Error + Send + Sync>| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    };
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_file)?))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_file)?))?
+        .ok_or_else(|| invalid_data("no private key found".into()))?;
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| invalid_data(e.into()))
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ColoLabels {
+    pub namespace: String,
+    pub tunnel: String,
+    /// Value of the workload label on the cloudflared pods
+    pub workload: String,
+    pub colo: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CleanupLabels {
+    /// Cleanup step of the finalizer, e.g. `dns_records` or `tunnel`
+    pub step: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CleanupResultLabels {
+    pub step: String,
+    /// `success` or `failure`
+    pub result: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CloudflareApiLabels {
+    pub method: String,
+    /// Path of the endpoint with the IDs replaced by `{id}`
+    pub endpoint: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CloudflareApiResultLabels {
+    pub method: String,
+    pub endpoint: String,
+    /// `success` or `failure`
+    pub result: String,
+    /// HTTP status of a failure, `2xx` for a success and `none` when no response was received
+    pub status: String,
+}
+
+/// Prometheus metrics of the controllers
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+    tunnel_colo_connections: Family<ColoLabels, Gauge>,
+    // tunnelごとに出力中のlabelを保持し、消えたcoloのgaugeを削除する
+    tunnel_colo_labels: Arc<Mutex<HashMap<(String, String), Vec<ColoLabels>>>>,
+    dns_list_requests: Counter,
+    dns_list_in_flight: Gauge,
+    dns_list_duration: Histogram,
+    dns_list_zones_skipped: Counter,
+    finalizer_cleanup_attempts: Family<CleanupLabels, Counter>,
+    finalizer_cleanup_results: Family<CleanupResultLabels, Counter>,
+    finalizer_cleanup_duration: Family<CleanupLabels, Histogram, fn() -> Histogram>,
+    api_token_expiry: Gauge,
+    api_token_permission_errors: Counter,
+    orphan_tunnels: Gauge,
+    cloudflare_api_requests: Family<CloudflareApiResultLabels, Counter>,
+    cloudflare_api_duration: Family<CloudflareApiLabels, Histogram, fn() -> Histogram>,
+    cloudflare_api_retries: Family<CloudflareApiLabels, Counter>,
+    cloudflare_api_rate_limit_wait: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::default();
+        let tunnel_colo_connections = Family::<ColoLabels, Gauge>::default();
+        registry.register(
+            "cloudflared_tunnel_colo_connections",
+            "Active tunnel connections per Cloudflare colo",
+            tunnel_colo_connections.clone(),
+        );
+        let dns_list_requests = Counter::default();
+        registry.register(
+            "cloudflared_ingress_dns_list_requests",
+            "DNS record list requests sent to Cloudflare",
+            dns_list_requests.clone(),
+        );
+        let dns_list_in_flight = Gauge::default();
+        registry.register(
+            "cloudflared_ingress_dns_list_in_flight",
+            "DNS record list requests in flight",
+            dns_list_in_flight.clone(),
+        );
+        let dns_list_duration = Histogram::new(exponential_buckets(0.05, 2.0, 10));
+        registry.register(
+            "cloudflared_ingress_dns_list_duration_seconds",
+            "Duration of the DNS record list requests including the wait for a free slot",
+            dns_list_duration.clone(),
+        );
+        let dns_list_zones_skipped = Counter::default();
+        registry.register(
+            "cloudflared_ingress_dns_list_zones_skipped",
+            "Zones not listed because they hold no record of the tunnel",
+            dns_list_zones_skipped.clone(),
+        );
+        let finalizer_cleanup_attempts = Family::<CleanupLabels, Counter>::default();
+        registry.register(
+            "cloudflared_ingress_finalizer_cleanup_attempts",
+            "Finalizer cleanup steps attempted",
+            finalizer_cleanup_attempts.clone(),
+        );
+        let finalizer_cleanup_results = Family::<CleanupResultLabels, Counter>::default();
+        registry.register(
+            "cloudflared_ingress_finalizer_cleanup_results",
+            "Finalizer cleanup steps completed, by result",
+            finalizer_cleanup_results.clone(),
+        );
+        let finalizer_cleanup_duration =
+            Family::<CleanupLabels, Histogram, fn() -> Histogram>::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.1, 2.0, 10))
+            });
+        registry.register(
+            "cloudflared_ingress_finalizer_cleanup_duration_seconds",
+            "Duration of the finalizer cleanup steps",
+            finalizer_cleanup_duration.clone(),
+        );
+        let api_token_expiry = Gauge::default();
+        registry.register(
+            "cloudflared_ingress_api_token_expiry_timestamp_seconds",
+            "Expiry of the Cloudflare API token as a Unix timestamp, 0 when it does not expire",
+            api_token_expiry.clone(),
+        );
+        let api_token_permission_errors = Counter::default();
+        registry.register(
+            "cloudflared_ingress_api_token_permission_errors",
+            "Cloudflare API requests rejected for insufficient token permissions",
+            api_token_permission_errors.clone(),
+        );
+        let orphan_tunnels = Gauge::default();
+        registry.register(
+            "cloudflared_ingress_orphan_tunnels",
+            "Tunnels matching a managed prefix without a CloudflaredTunnel, found by the last reconcile",
+            orphan_tunnels.clone(),
+        );
+        let cloudflare_api_requests = Family::<CloudflareApiResultLabels, Counter>::default();
+        registry.register(
+            "cloudflared_ingress_cloudflare_api_requests",
+            "Cloudflare API requests sent, by endpoint and result, each retry counted",
+            cloudflare_api_requests.clone(),
+        );
+        let cloudflare_api_duration =
+            Family::<CloudflareApiLabels, Histogram, fn() -> Histogram>::new_with_constructor(
+                || Histogram::new(exponential_buckets(0.05, 2.0, 10)),
+            );
+        registry.register(
+            "cloudflared_ingress_cloudflare_api_duration_seconds",
+            "Duration of the Cloudflare API requests, each retry observed",
+            cloudflare_api_duration.clone(),
+        );
+        let cloudflare_api_retries = Family::<CloudflareApiLabels, Counter>::default();
+        registry.register(
+            "cloudflared_ingress_cloudflare_api_retries",
+            "Cloudflare API requests retried after a transient failure",
+            cloudflare_api_retries.clone(),
+        );
+        let cloudflare_api_rate_limit_wait = Histogram::new(exponential_buckets(0.01, 2.0, 12));
+        registry.register(
+            "cloudflared_ingress_cloudflare_api_rate_limit_wait_seconds",
+            "Wait of the Cloudflare API requests for the client side rate limit",
+            cloudflare_api_rate_limit_wait.clone(),
+        );
+        Self {
+            registry: Arc::new(registry),
+            tunnel_colo_connections,
+            tunnel_colo_labels: Arc::new(Mutex::new(HashMap::new())),
+            dns_list_requests,
+            dns_list_in_flight,
+            dns_list_duration,
+            dns_list_zones_skipped,
+            finalizer_cleanup_attempts,
+            finalizer_cleanup_results,
+            finalizer_cleanup_duration,
+            api_token_expiry,
+            api_token_permission_errors,
+            orphan_tunnels,
+            cloudflare_api_requests,
+            cloudflare_api_duration,
+            cloudflare_api_retries,
+            cloudflare_api_rate_limit_wait,
+        }
+    }
+}
+
+impl Metrics {
+    /// Replace the per-colo connection gauges of a tunnel
+    pub fn set_tunnel_colo_connections(
+        &self,
+        namespace: &str,
+        tunnel: &str,
+        workload: &str,
+        connections: BTreeMap<String, i64>,
+    ) {
+        let labels = connections
+            .into_iter()
+            .map(|(colo, count)| {
+                let labels = ColoLabels {
+                    namespace: namespace.to_string(),
+                    tunnel: tunnel.to_string(),
+                    workload: workload.to_string(),
+                    colo,
+                };
+                self.tunnel_colo_connections
+                    .get_or_create(&labels)
+                    .set(count);
+                labels
+            })
+            .collect::<Vec<_>>();
+        let stale = self
+            .tunnel_colo_labels
+            .lock()
+            .unwrap()
+            .insert((namespace.to_string(), tunnel.to_string()), labels.clone())
+            .unwrap_or_default();
+        for l in stale.iter().filter(|l| !labels.contains(l)) {
+            self.tunnel_colo_connections.remove(l);
+        }
+    }
+
+    pub fn remove_tunnel(&self, namespace: &str, tunnel: &str) {
+        let stale = self
+            .tunnel_colo_labels
+            .lock()
+            .unwrap()
+            .remove(&(namespace.to_string(), tunnel.to_string()))
+            .unwrap_or_default();
+        for l in stale.iter() {
+            self.tunnel_colo_connections.remove(l);
+        }
+    }
+
+    /// Count a DNS record list request waiting for or holding a slot
+    pub fn dns_list_started(&self) {
+        self.dns_list_requests.inc();
+        self.dns_list_in_flight.inc();
+    }
+
+    pub fn dns_list_finished(&self, elapsed: Duration) {
+        self.dns_list_in_flight.dec();
+        self.dns_list_duration.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn add_dns_list_zones_skipped(&self, zones: usize) {
+        self.dns_list_zones_skipped.inc_by(zones as u64);
+    }
+
+    pub fn finalizer_cleanup_started(&self, step: &str) {
+        self.finalizer_cleanup_attempts
+            .get_or_create(&CleanupLabels {
+                step: step.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn finalizer_cleanup_finished(&self, step: &str, succeeded: bool, elapsed: Duration) {
+        self.finalizer_cleanup_duration
+            .get_or_create(&CleanupLabels {
+                step: step.to_string(),
+            })
+            .observe(elapsed.as_secs_f64());
+        self.finalizer_cleanup_results
+            .get_or_create(&CleanupResultLabels {
+                step: step.to_string(),
+                result: if succeeded { "success" } else { "failure" }.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn set_api_token_expiry(&self, expires_at: Option<i64>) {
+        self.api_token_expiry.set(expires_at.unwrap_or_default());
+    }
+
+    pub fn inc_api_token_permission_errors(&self) {
+        self.api_token_permission_errors.inc();
+    }
+
+    pub fn set_orphan_tunnels(&self, count: usize) {
+        self.orphan_tunnels.set(count as i64);
+    }
+
+    /// Count a Cloudflare API request, `status` is `None` when no response was received
+    pub fn cloudflare_api_request_finished(
+        &self,
+        method: &str,
+        endpoint: &str,
+        result: Result<(), Option<u16>>,
+        elapsed: Duration,
+    ) {
+        self.cloudflare_api_duration
+            .get_or_create(&CloudflareApiLabels {
+                method: method.to_string(),
+                endpoint: endpoint.to_string(),
+            })
+            .observe(elapsed.as_secs_f64());
+        let (result, status) = match result {
+            Ok(()) => ("success", "2xx".to_string()),
+            Err(Some(status)) => ("failure", status.to_string()),
+            Err(None) => ("failure", "none".to_string()),
+        };
+        self.cloudflare_api_requests
+            .get_or_create(&CloudflareApiResultLabels {
+                method: method.to_string(),
+                endpoint: endpoint.to_string(),
+                result: result.to_string(),
+                status,
+            })
+            .inc();
+    }
+
+    pub fn inc_cloudflare_api_retries(&self, method: &str, endpoint: &str) {
+        self.cloudflare_api_retries
+            .get_or_create(&CloudflareApiLabels {
+                method: method.to_string(),
+                endpoint: endpoint.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn observe_cloudflare_api_rate_limit_wait(&self, elapsed: Duration) {
+        self.cloudflare_api_rate_limit_wait
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Encode the metrics in the OpenMetrics text format
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry)?;
+        Ok(buffer)
+    }
+}
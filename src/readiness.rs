@@ -0,0 +1,32 @@
+use std::sync::{Arc, Mutex};
+
+/// Readiness reported by `/readyz`, not ready until the Cloudflare credentials are verified
+#[derive(Clone)]
+pub struct Readiness {
+    state: Arc<Mutex<Result<(), String>>>,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(Err(
+                "Cloudflare credentials not verified yet".to_string()
+            ))),
+        }
+    }
+}
+
+impl Readiness {
+    pub fn set_ready(&self) {
+        *self.state.lock().unwrap() = Ok(());
+    }
+
+    pub fn set_not_ready(&self, reason: impl Into<String>) {
+        *self.state.lock().unwrap() = Err(reason.into());
+    }
+
+    /// `Err` holds the reason of not being ready
+    pub fn status(&self) -> Result<(), String> {
+        self.state.lock().unwrap().clone()
+    }
+}
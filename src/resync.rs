@@ -0,0 +1,26 @@
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Broadcasts forced full resync requests to the controllers
+#[derive(Clone, Default)]
+pub struct ResyncTrigger {
+    senders: Arc<Mutex<Vec<UnboundedSender<()>>>>,
+}
+
+impl ResyncTrigger {
+    /// Stream of resync requests, suitable for `Controller::reconcile_all_on`
+    pub fn subscribe(&self) -> UnboundedReceiver<()> {
+        let (tx, rx) = mpsc::unbounded();
+        self.senders.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn trigger(&self) {
+        // 受信側が終了しているものは取り除く
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.unbounded_send(()).is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(feature = "aws-secrets-manager")]
+mod aws;
+#[cfg(feature = "azure-key-vault")]
+mod azure;
+#[cfg(feature = "gcp-secret-manager")]
+mod gcp;
+
+use std::{fmt, str::FromStr};
+
+use crate::{Error, Result};
+
+/// Secret of a cloud secret manager read with the workload identity of the pod, written as
+/// `aws:<secret id>`, `gcp:projects/<project>/secrets/<secret>/versions/<version>` or
+/// `azure:https://<vault>.vault.azure.net/secrets/<secret>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretManagerRef {
+    Aws { secret_id: String },
+    Gcp { name: String },
+    Azure { url: String },
+}
+
+impl SecretManagerRef {
+    fn provider(&self) -> &'static str {
+        match self {
+            Self::Aws { .. } => "aws",
+            Self::Gcp { .. } => "gcp",
+            Self::Azure { .. } => "azure",
+        }
+    }
+
+    /// Read the secret as a UTF-8 string, with surrounding whitespace removed
+    pub async fn read(&self) -> Result<String> {
+        let value = match self {
+            #[cfg(feature = "aws-secrets-manager")]
+            Self::Aws { secret_id } => aws::read(secret_id).await?,
+            #[cfg(feature = "gcp-secret-manager")]
+            Self::Gcp { name } => gcp::read(name).await?,
+            #[cfg(feature = "azure-key-vault")]
+            Self::Azure { url } => azure::read(url).await?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::secret_manager_unsupported(self.provider())),
+        };
+        Ok(value.trim().to_string())
+    }
+}
+
+impl FromStr for SecretManagerRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (provider, path) = s
+            .split_once(':')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| Error::invalid_secret_manager_ref(s))?;
+        match provider {
+            "aws" => Ok(Self::Aws {
+                secret_id: path.to_string(),
+            }),
+            "gcp" if path.starts_with("projects/") && path.contains("/secrets/") => Ok(Self::Gcp {
+                name: path.to_string(),
+            }),
+            "azure" if path.starts_with("https://") && path.contains("/secrets/") => {
+                Ok(Self::Azure {
+                    url: path.to_string(),
+                })
+            }
+            _ => Err(Error::invalid_secret_manager_ref(s)),
+        }
+    }
+}
+
+impl fmt::Display for SecretManagerRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Aws { secret_id } => write!(f, "aws:{secret_id}"),
+            Self::Gcp { name } => write!(f, "gcp:{name}"),
+            Self::Azure { url } => write!(f, "azure:{url}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for s in [
+            "aws:arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:cloudflare",
+            "gcp:projects/example/secrets/cloudflare/versions/latest",
+            "azure:https://example.vault.azure.net/secrets/cloudflare",
+        ] {
+            assert_eq!(s, s.parse::<SecretManagerRef>().unwrap().to_string());
+        }
+        assert_eq!(
+            SecretManagerRef::Aws {
+                secret_id: "cloudflare".to_string()
+            },
+            "aws:cloudflare".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "cloudflare",
+            "aws:",
+            "gcp:cloudflare",
+            "azure:example.vault.azure.net/secrets/cloudflare",
+            "vault:secret/cloudflare",
+        ] {
+            assert!(s.parse::<SecretManagerRef>().is_err(), "{s}");
+        }
+    }
+}
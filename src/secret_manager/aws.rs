@@ -0,0 +1,19 @@
+use aws_sdk_secretsmanager::error::DisplayErrorContext;
+
+use crate::{Error, Result};
+
+/// Read a secret of AWS Secrets Manager with the credentials of the default provider chain,
+/// e.g. IAM Roles for Service Accounts or EKS Pod Identity
+pub(super) async fn read(secret_id: &str) -> Result<String> {
+    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let output = aws_sdk_secretsmanager::Client::new(&config)
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(|e| Error::secret_manager("aws", DisplayErrorContext(e)))?;
+    output
+        .secret_string()
+        .map(str::to_string)
+        .ok_or_else(|| Error::secret_manager("aws", format!("{secret_id} has no string value")))
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+const DEFAULT_AUTHORITY_HOST: &str = "https://login.microsoftonline.com/";
+const KEY_VAULT_SCOPE: &str = "https://vault.azure.net/.default";
+const KEY_VAULT_API_VERSION: &str = "7.4";
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct SecretBundle {
+    value: String,
+}
+
+fn env(name: &str) -> Result<String> {
+    std::env::var(name).map_err(|_| Error::secret_manager("azure", format!("{name} is not set")))
+}
+
+/// Read a secret of Azure Key Vault with Microsoft Entra Workload ID, exchanging the projected
+/// service account token given by the `AZURE_*` environment variables for an access token
+pub(super) async fn read(url: &str) -> Result<String> {
+    let error = |e: reqwest::Error| Error::secret_manager("azure", e);
+    let authority_host =
+        std::env::var("AZURE_AUTHORITY_HOST").unwrap_or_else(|_| DEFAULT_AUTHORITY_HOST.into());
+    let tenant_id = env("AZURE_TENANT_ID")?;
+    let client_id = env("AZURE_CLIENT_ID")?;
+    let assertion = std::fs::read_to_string(env("AZURE_FEDERATED_TOKEN_FILE")?)?;
+
+    let client = reqwest::Client::new();
+    let token = client
+        .post(format!(
+            "{}/{tenant_id}/oauth2/v2.0/token",
+            authority_host.trim_end_matches('/')
+        ))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("scope", KEY_VAULT_SCOPE),
+            (
+                "client_assertion_type",
+                "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+            ),
+            ("client_assertion", assertion.trim()),
+        ])
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(error)?
+        .json::<AccessToken>()
+        .await
+        .map_err(error)?;
+    let secret = client
+        .get(format!("{url}?api-version={KEY_VAULT_API_VERSION}"))
+        .bearer_auth(token.access_token)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(error)?
+        .json::<SecretBundle>()
+        .await
+        .map_err(error)?;
+    Ok(secret.value)
+}
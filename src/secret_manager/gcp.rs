@@ -0,0 +1,55 @@
+use base64::Engine as _;
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+// GKE Workload Identityではmetadata serverがKubernetes ServiceAccountに紐づくtokenを返す
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct AccessSecretVersionResponse {
+    payload: SecretPayload,
+}
+
+#[derive(Deserialize)]
+struct SecretPayload {
+    data: String,
+}
+
+/// Read a secret version of GCP Secret Manager with the token of the metadata server
+pub(super) async fn read(name: &str) -> Result<String> {
+    let error = |e: reqwest::Error| Error::secret_manager("gcp", e);
+    let client = reqwest::Client::new();
+    let token = client
+        .get(METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(error)?
+        .json::<AccessToken>()
+        .await
+        .map_err(error)?;
+    let response = client
+        .get(format!(
+            "https://secretmanager.googleapis.com/v1/{name}:access"
+        ))
+        .bearer_auth(token.access_token)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(error)?
+        .json::<AccessSecretVersionResponse>()
+        .await
+        .map_err(error)?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(response.payload.data)
+        .map_err(|e| Error::secret_manager("gcp", e))?;
+    Ok(String::from_utf8(data)?)
+}
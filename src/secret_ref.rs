@@ -0,0 +1,131 @@
+use std::{fmt, str::FromStr};
+
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client};
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// Reference to a key of a Secret, written as `namespace/name#key`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SecretKeyRef {
+    namespace: String,
+    name: String,
+    key: String,
+}
+
+impl SecretKeyRef {
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the value of the key as a UTF-8 string, with surrounding whitespace removed
+    pub async fn read(&self, client: &Client) -> Result<String> {
+        let api = Api::<Secret>::namespaced(client.clone(), &self.namespace);
+        self.value_of(&api.get(&self.name).await?)
+    }
+
+    /// Value of the key in the referenced Secret, e.g. received from a watch
+    pub fn value_of(&self, secret: &Secret) -> Result<String> {
+        let value = secret
+            .data
+            .as_ref()
+            .and_then(|data| data.get(&self.key))
+            .ok_or_else(|| Error::secret_key_missing(self.to_string(), &self.key))?;
+        Ok(std::str::from_utf8(&value.0)?.trim().to_string())
+    }
+}
+
+impl FromStr for SecretKeyRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (path, key) = s
+            .split_once('#')
+            .ok_or_else(|| Error::invalid_secret_ref(s))?;
+        let (namespace, name) = path
+            .split_once('/')
+            .ok_or_else(|| Error::invalid_secret_ref(s))?;
+        if [namespace, name, key].iter().any(|x| x.is_empty()) {
+            return Err(Error::invalid_secret_ref(s));
+        }
+        Ok(Self {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            key: key.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for SecretKeyRef {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for SecretKeyRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}#{}", self.namespace, self.name, self.key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let secret_ref = "cloudflared/credential#ACCOUNT_ID"
+            .parse::<SecretKeyRef>()
+            .unwrap();
+        assert_eq!("cloudflared", secret_ref.namespace);
+        assert_eq!("credential", secret_ref.name);
+        assert_eq!("ACCOUNT_ID", secret_ref.key);
+        assert_eq!("cloudflared/credential#ACCOUNT_ID", secret_ref.to_string());
+    }
+
+    #[test]
+    fn value_of_secret() {
+        let secret_ref = "cloudflared/credential#token"
+            .parse::<SecretKeyRef>()
+            .unwrap();
+        let secret = Secret {
+            data: Some(
+                [(
+                    "token".to_string(),
+                    k8s_openapi::ByteString(b"rotated\n".to_vec()),
+                )]
+                .into(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!("rotated", secret_ref.value_of(&secret).unwrap());
+        assert_eq!(
+            "SecretKeyMissing",
+            secret_ref
+                .value_of(&Secret::default())
+                .unwrap_err()
+                .reason()
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in [
+            "credential#key",
+            "ns/credential",
+            "ns/#key",
+            "/name#key",
+            "ns/name#",
+        ] {
+            assert!(s.parse::<SecretKeyRef>().is_err(), "{s}");
+        }
+    }
+}
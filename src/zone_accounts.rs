@@ -0,0 +1,85 @@
+use std::{collections::HashSet, path::Path};
+
+use serde::Deserialize;
+
+use crate::secret_ref::SecretKeyRef;
+
+/// Zones of other Cloudflare accounts than the one of `--cloudflare-token`, each managed with
+/// the token of its account
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ZoneAccounts {
+    pub accounts: Vec<ZoneAccount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ZoneAccount {
+    /// Secret key holding the API token, as `namespace/name#key`
+    pub token_secret_ref: SecretKeyRef,
+    /// Names of the zones managed with the token
+    pub zones: Vec<String>,
+}
+
+impl ZoneAccounts {
+    /// Read the mapping from a YAML file, rejecting a zone mapped to more than one account
+    pub fn from_file(path: &Path) -> std::result::Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        let zone_accounts = serde_yaml::from_reader::<_, Self>(file).map_err(|e| e.to_string())?;
+        let mut zones = HashSet::new();
+        for zone in zone_accounts.accounts.iter().flat_map(|a| a.zones.iter()) {
+            if !zones.insert(zone.to_ascii_lowercase()) {
+                return Err(format!("zone {zone} is mapped to more than one account"));
+            }
+        }
+        Ok(zone_accounts)
+    }
+
+    /// Account the zone is mapped to
+    pub fn account_of(&self, zone_name: &str) -> Option<&ZoneAccount> {
+        self.accounts
+            .iter()
+            .find(|a| a.zones.iter().any(|z| z.eq_ignore_ascii_case(zone_name)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write as _;
+
+    use super::*;
+
+    fn write_file(contents: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("zone-accounts-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::File::create(&path)
+            .and_then(|mut f| f.write_all(contents.as_bytes()))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn read_mapping() {
+        let path = write_file(
+            "accounts:\n- token_secret_ref: cloudflare/team-a#token\n  zones: [example.net]\n",
+        );
+        let zone_accounts = ZoneAccounts::from_file(&path).unwrap();
+        assert_eq!(
+            "cloudflare/team-a#token",
+            zone_accounts
+                .account_of("Example.NET")
+                .unwrap()
+                .token_secret_ref
+                .to_string()
+        );
+        assert!(zone_accounts.account_of("example.com").is_none());
+    }
+
+    #[test]
+    fn zone_mapped_twice_is_rejected() {
+        let path = write_file(concat!(
+            "accounts:\n",
+            "- token_secret_ref: cloudflare/team-a#token\n  zones: [example.net]\n",
+            "- token_secret_ref: cloudflare/team-b#token\n  zones: [example.net]\n",
+        ));
+        assert!(ZoneAccounts::from_file(&path).is_err());
+    }
+}
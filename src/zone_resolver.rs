@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use url::Host;
+
+/// Resolves the zone of a hostname by the longest matching zone name
+///
+/// Names are compared label by label after IDNA (punycode) and case normalization, so
+/// `www.example.com` never matches the zone `ample.com`. A leading `*.` of a wildcard hostname
+/// is ignored.
+#[derive(Debug, Clone)]
+pub struct ZoneResolver<T> {
+    root: Node<T>,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: Option<T>,
+    children: HashMap<String, Node<T>>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Default for ZoneResolver<T> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<T> ZoneResolver<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a zone, names that are not valid domains are ignored
+    pub fn insert(&mut self, zone_name: &str, value: T) {
+        let Some(zone_name) = normalize(zone_name) else {
+            return;
+        };
+        let node = zone_name.rsplit('.').fold(&mut self.root, |node, label| {
+            node.children.entry(label.to_string()).or_default()
+        });
+        node.value = Some(value);
+    }
+
+    /// Zone of the hostname, the most specific one when zones are nested
+    pub fn resolve(&self, hostname: &str) -> Option<&T> {
+        let hostname = normalize(hostname.strip_prefix("*.").unwrap_or(hostname))?;
+        let mut node = &self.root;
+        let mut found = None;
+        for label in hostname.rsplit('.') {
+            let Some(child) = node.children.get(label) else {
+                break;
+            };
+            node = child;
+            found = node.value.as_ref().or(found);
+        }
+        found
+    }
+}
+
+impl<S: AsRef<str>, T> FromIterator<(S, T)> for ZoneResolver<T> {
+    fn from_iter<I: IntoIterator<Item = (S, T)>>(iter: I) -> Self {
+        let mut resolver = Self::new();
+        for (zone_name, value) in iter {
+            resolver.insert(zone_name.as_ref(), value);
+        }
+        resolver
+    }
+}
+
+/// ASCII lowercase form of a domain name without the trailing dot
+fn normalize(name: &str) -> Option<String> {
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+    match Host::parse(name).ok()? {
+        Host::Domain(domain) => Some(domain),
+        // IPアドレスはzoneに属さない
+        Host::Ipv4(_) | Host::Ipv6(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolver() -> ZoneResolver<&'static str> {
+        [
+            ("example.com", "example"),
+            ("sub.example.com", "sub"),
+            ("example.co.jp", "jp"),
+            ("bücher.example", "idn"),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn apex() {
+        let resolver = resolver();
+        assert_eq!(Some(&"example"), resolver.resolve("example.com"));
+        assert_eq!(Some(&"sub"), resolver.resolve("sub.example.com"));
+    }
+
+    #[test]
+    fn subdomain() {
+        let resolver = resolver();
+        assert_eq!(Some(&"example"), resolver.resolve("www.example.com"));
+        assert_eq!(Some(&"example"), resolver.resolve("a.b.example.com"));
+        assert_eq!(Some(&"jp"), resolver.resolve("www.example.co.jp"));
+    }
+
+    #[test]
+    fn nested_zone() {
+        let resolver = resolver();
+        assert_eq!(Some(&"sub"), resolver.resolve("www.sub.example.com"));
+        assert_eq!(Some(&"example"), resolver.resolve("www.other.example.com"));
+    }
+
+    #[test]
+    fn not_found() {
+        let resolver = resolver();
+        assert_eq!(None, resolver.resolve("example.net"));
+        assert_eq!(None, resolver.resolve("notexample.com"));
+        assert_eq!(None, resolver.resolve("com"));
+        assert_eq!(None, resolver.resolve("co.jp"));
+        assert_eq!(None, resolver.resolve(""));
+        assert_eq!(None, resolver.resolve("192.0.2.1"));
+        assert_eq!(None, resolver.resolve("[2001:db8::1]"));
+    }
+
+    #[test]
+    fn case_and_trailing_dot() {
+        let resolver = resolver();
+        assert_eq!(Some(&"example"), resolver.resolve("WWW.Example.COM"));
+        assert_eq!(Some(&"example"), resolver.resolve("www.example.com."));
+    }
+
+    #[test]
+    fn idn() {
+        let resolver = resolver();
+        assert_eq!(Some(&"idn"), resolver.resolve("www.bücher.example"));
+        assert_eq!(Some(&"idn"), resolver.resolve("www.xn--bcher-kva.example"));
+        assert_eq!(Some(&"idn"), resolver.resolve("WWW.BÜCHER.EXAMPLE"));
+
+        let resolver = [("xn--bcher-kva.example", "idn")]
+            .into_iter()
+            .collect::<ZoneResolver<_>>();
+        assert_eq!(Some(&"idn"), resolver.resolve("bücher.example"));
+    }
+
+    #[test]
+    fn wildcard() {
+        let resolver = resolver();
+        assert_eq!(Some(&"example"), resolver.resolve("*.example.com"));
+        assert_eq!(Some(&"sub"), resolver.resolve("*.sub.example.com"));
+        assert_eq!(Some(&"example"), resolver.resolve("*.www.example.com"));
+        assert_eq!(None, resolver.resolve("*.com"));
+    }
+}